@@ -0,0 +1,270 @@
+use crate::generate_module_commands::{Command, ModuleCommandDef, handlers_macro_name, type_name};
+use convert_case::{Case, Casing};
+use proc_macro::TokenStream;
+use proc_macro2::Span;
+use quote::{ToTokens, quote, quote_spanned};
+use syn::{
+    Error, FnArg, ImplItem, ImplItemFn, ItemImpl, Pat, Path, Result, Token,
+    parse::{Parse, ParseStream},
+    spanned::Spanned,
+};
+
+// Input of `__command_handlers!`: the firmware-common root and the command definitions, as
+// handed over by the hidden macro of `def_module_commands!`, then the path given to
+// `#[command_handlers]` and the handlers
+pub struct HandlersInput {
+    root: Path,
+    module: ModuleCommandDef,
+    commands_path: Path,
+    handlers: ItemImpl,
+}
+
+impl Parse for HandlersInput {
+    fn parse(input: ParseStream) -> Result<Self> {
+        let root = input.parse()?;
+        input.parse::<Token![;]>()?;
+        let module = input.parse()?;
+        let commands_path = input.parse()?;
+        input.parse::<Token![;]>()?;
+        let handlers = input.parse()?;
+        Ok(HandlersInput {
+            root,
+            module,
+            commands_path,
+            handlers,
+        })
+    }
+}
+
+// Path of the module the command enum is defined in, with a trailing `::`, empty for an
+// enum named without a path
+fn commands_module(path: &Path) -> proc_macro2::TokenStream {
+    let leading_colon = &path.leading_colon;
+    let segments = path.segments.iter().take(path.segments.len() - 1);
+    quote! { #leading_colon #(#segments::)* }
+}
+
+// Expands the attribute into a call of the hidden macro generated for the enum, which calls
+// `__command_handlers!` back with the command definitions
+pub fn command_handlers(commands_path: Path, item: TokenStream) -> TokenStream {
+    if let Err(error) = syn::parse::<ItemImpl>(item.clone()).and_then(|item| match &item.trait_ {
+        Some((_, path, _)) => Err(Error::new(
+            path.span(),
+            "`#[command_handlers]` goes on an inherent `impl` block of handler methods",
+        )),
+        None => Ok(()),
+    }) {
+        return error.to_compile_error().into();
+    }
+
+    let Some(enum_name) = commands_path.segments.last().map(|segment| &segment.ident) else {
+        return Error::new(Span::call_site(), "expected the command enum")
+            .to_compile_error()
+            .into();
+    };
+    let module = commands_module(&commands_path);
+    let macro_name = handlers_macro_name(enum_name);
+    let item = proc_macro2::TokenStream::from(item);
+
+    TokenStream::from(quote! {
+        #module #macro_name! { #commands_path; #item }
+    })
+}
+
+// `fn set_output(&mut self, state: bool)`, as expected for a command
+fn expected_signature(cmd: &Command) -> String {
+    let method = cmd.name.to_string().to_case(Case::Snake);
+    let args = cmd
+        .args
+        .iter()
+        .map(|arg| format!(", {}: {}", arg.name, type_name(&arg.ty.to_token_stream())))
+        .collect::<String>();
+    let response = type_name(&cmd.response_type());
+    let output = if response == "()" {
+        String::new()
+    } else {
+        format!(" -> {response}")
+    };
+    format!("fn {method}(&mut self{args}){output}")
+}
+
+// Match arm calling the handler of a command, or why the handler does not fit the command
+fn handler_arm(
+    enum_path: &proc_macro2::TokenStream,
+    cmd: &Command,
+    handler: &ImplItemFn,
+) -> Result<proc_macro2::TokenStream> {
+    let sig = &handler.sig;
+    if sig.asyncness.is_some() || !sig.generics.params.is_empty() {
+        return Err(Error::new(
+            sig.ident.span(),
+            "command handlers cannot be `async` or generic",
+        ));
+    }
+
+    match sig.receiver() {
+        Some(receiver) if receiver.reference.is_some() => {}
+        _ => {
+            return Err(Error::new(
+                sig.ident.span(),
+                format!(
+                    "`{}` must take `&mut self` or `&self`: `{}`",
+                    sig.ident,
+                    expected_signature(cmd)
+                ),
+            ));
+        }
+    }
+
+    let params = sig
+        .inputs
+        .iter()
+        .filter_map(|input| match input {
+            FnArg::Typed(param) => Some(param),
+            FnArg::Receiver(_) => None,
+        })
+        .collect::<Vec<_>>();
+    if params.len() != cmd.args.len() {
+        return Err(Error::new(
+            sig.inputs.span(),
+            format!(
+                "`{}` must take the arguments of `{}`: `{}`",
+                sig.ident,
+                cmd.name,
+                expected_signature(cmd)
+            ),
+        ));
+    }
+
+    // Arguments are matched by name, so that two arguments of the same type cannot be swapped
+    let mut bindings = Vec::new();
+    for (param, arg) in params.iter().zip(&cmd.args) {
+        match &*param.pat {
+            Pat::Ident(pat) if pat.ident == arg.name => bindings.push(&pat.ident),
+            pat => {
+                return Err(Error::new(
+                    pat.span(),
+                    format!("expected argument `{}` of `{}`", arg.name, cmd.name),
+                ));
+            }
+        }
+    }
+
+    let variant = &cmd.name;
+    let method = &sig.ident;
+    let response_type = cmd.response_type();
+    let pattern = if bindings.is_empty() {
+        quote! { #enum_path::#variant }
+    } else {
+        quote! { #enum_path::#variant { #(#bindings),* } }
+    };
+    // Located at the handler, so that a response of the wrong type is reported there
+    let span = Span::call_site().located_at(sig.output.span());
+    let call = quote_spanned! {span=>
+        let response: #response_type = self.#method(#(#bindings),*);
+    };
+
+    Ok(quote! {
+        #pattern => {
+            #call
+            Ok(Box::new(response) as Box<dyn std::any::Any>)
+        }
+    })
+}
+
+// Implements `CommandHandlers` for the type of the handlers, checking that there is exactly
+// one handler per command
+pub fn generate_command_handlers(input: HandlersInput) -> TokenStream {
+    let HandlersInput {
+        root,
+        module,
+        commands_path,
+        handlers,
+    } = input;
+    let enum_name = &module.enum_name;
+    let commands_module = commands_module(&commands_path);
+    let enum_path = quote! { #commands_module #enum_name };
+
+    let mut errors: Option<Error> = None;
+    let mut push_error = |error: Error| match &mut errors {
+        Some(errors) => errors.combine(error),
+        None => errors = Some(error),
+    };
+
+    let mut arms = Vec::new();
+    let mut handled = Vec::new();
+    for handler in handlers.items.iter().filter_map(|item| match item {
+        ImplItem::Fn(handler) => Some(handler),
+        _ => None,
+    }) {
+        let method = handler.sig.ident.to_string();
+        let Some(cmd) = module
+            .commands
+            .iter()
+            .find(|cmd| cmd.name.to_string().to_case(Case::Snake) == method)
+        else {
+            let example = module.commands.first().map_or(String::new(), |cmd| {
+                format!(
+                    ", e.g. `{}` for `{}`",
+                    cmd.name.to_string().to_case(Case::Snake),
+                    cmd.name
+                )
+            });
+            push_error(Error::new(
+                handler.sig.ident.span(),
+                format!(
+                    "`{method}` does not match any command of `{enum_name}`, handlers are named after their command in snake case{example}"
+                ),
+            ));
+            continue;
+        };
+
+        handled.push(&cmd.name);
+        match handler_arm(&enum_path, cmd, handler) {
+            Ok(arm) => arms.push(arm),
+            Err(error) => push_error(error),
+        }
+    }
+
+    for cmd in &module.commands {
+        if !handled.contains(&&cmd.name) {
+            push_error(Error::new(
+                commands_path.span(),
+                format!(
+                    "missing handler for `{}::{}`: `{}`",
+                    enum_name,
+                    cmd.name,
+                    expected_signature(cmd)
+                ),
+            ));
+        }
+    }
+
+    // The handlers are kept as they are, errors or not, so that their own code still gets
+    // checked. On errors the dispatch is left out, but the trait is still implemented so
+    // that `process_command` callers do not add to the errors.
+    let (errors, dispatch) = match errors {
+        Some(errors) => (Some(errors.to_compile_error()), quote! { unreachable!() }),
+        None => (None, quote! { match command { #(#arms)* } }),
+    };
+
+    let self_ty = &handlers.self_ty;
+    let (impl_generics, _, where_clause) = handlers.generics.split_for_impl();
+
+    TokenStream::from(quote! {
+        #handlers
+
+        #errors
+
+        impl #impl_generics #root::modules::module::CommandHandlers for #self_ty #where_clause {
+            type Commands = #enum_path;
+
+            fn process_command(
+                &mut self,
+                command: Self::Commands,
+            ) -> #root::modules::module::ModuleCommandExecutionResponse {
+                #dispatch
+            }
+        }
+    })
+}
@@ -1,7 +1,7 @@
 use syn::parse::{Parse, ParseStream};
 use syn::punctuated::Punctuated;
 use syn::token::{Brace, Colon};
-use syn::{Expr, Ident, Path, Token, Type};
+use syn::{Expr, Ident, Path, Token};
 
 /// Represents a key-value pair inside `{}` for struct-like enum variants.
 pub struct KeyValuePair {
@@ -11,7 +11,12 @@ pub struct KeyValuePair {
 
 impl Parse for KeyValuePair {
     fn parse(input: ParseStream) -> syn::Result<Self> {
-        let key = input.parse()?;
+        let key: Ident = input.parse()?;
+        // `{ limit }` is short for `{ limit: limit }`, as in struct expressions
+        if !input.peek(Token![:]) {
+            let value = syn::parse_quote!(#key);
+            return Ok(KeyValuePair { key, value });
+        }
         let _colon: Token![:] = input.parse()?;
         let value = input.parse()?;
 
@@ -22,7 +27,7 @@ impl Parse for KeyValuePair {
 /// Parses input for the `execute_command!` macro
 pub struct CommandInput {
     pub module: Expr,              // The module instance (e.g., dummy_module)
-    pub module_command_type: Type, // The explicit module type (e.g., DummyModule)
+    pub module_command_type: Path, // The command enum type (e.g., BatteryCommand)
     pub command_enum: Path,        // Full path (e.g., BatteryCommand::GetVoltage)
     pub command_variant: Ident,    // Extracted variant (e.g., GetVoltage)
     pub args: Option<Punctuated<KeyValuePair, Token![,]>>, //  Key-value arguments
@@ -33,7 +38,7 @@ impl Parse for CommandInput {
         let module: Expr = input.parse()?; // Parse module
         input.parse::<Colon>()?; // Expect colon (`:`)
 
-        let module_command_type: Type = input.parse()?; // Parse module type
+        let module_command_type: Path = input.parse()?; // Parse command enum type
         input.parse::<Token![,]>()?; // Expect comma
 
         let command_enum: Path = input.parse()?; // Parse full path (e.g., BatteryCommand::GetVoltage)
@@ -66,3 +71,46 @@ impl Parse for CommandInput {
         })
     }
 }
+
+/// Parses input for the `submit_command!` macro: an optional `crate = path;`, the manager,
+/// then the command as for `execute_command!` with the module ID in place of the module, then
+/// an optional `queue_timeout = expr`
+pub struct SubmitInput {
+    pub root: proc_macro2::TokenStream,
+    pub manager: Expr,
+    pub command: CommandInput,
+    pub queue_timeout: Option<Expr>,
+}
+
+impl Parse for SubmitInput {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let root = crate::parse_root(input)?;
+        let manager: Expr = input.parse()?;
+        input.parse::<Token![,]>()?;
+        let command: CommandInput = input.parse()?;
+
+        let mut queue_timeout = None;
+        if input.peek(Token![,]) {
+            input.parse::<Token![,]>()?;
+            if !input.is_empty() {
+                let key: Ident = input.parse()?;
+                if key != "queue_timeout" {
+                    return Err(syn::Error::new(
+                        key.span(),
+                        "expected `queue_timeout = ...`",
+                    ));
+                }
+                input.parse::<Token![=]>()?;
+                queue_timeout = Some(input.parse()?);
+                let _ = input.parse::<Option<Token![,]>>()?;
+            }
+        }
+
+        Ok(SubmitInput {
+            root,
+            manager,
+            command,
+            queue_timeout,
+        })
+    }
+}
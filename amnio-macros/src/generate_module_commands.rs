@@ -1,51 +1,162 @@
 use convert_case::{Case, Casing};
 use proc_macro::TokenStream;
-use quote::{format_ident, quote};
+use quote::{ToTokens, format_ident, quote};
+use std::collections::HashMap;
 use syn::{
-    Ident, Result, Token, Type, parenthesized,
+    Attribute, Error, Expr, ExprRange, Ident, LitInt, LitStr, RangeLimits, Result, Token, Type,
+    parenthesized,
     parse::{Parse, ParseStream},
     punctuated::Punctuated,
 };
 
+// Represents a single argument of a command
+pub(crate) struct Arg {
+    // Doc comments
+    attrs: Vec<Attribute>,
+    pub(crate) name: Ident,
+    pub(crate) ty: Type,
+    // Accepted values, from `#[range(min..=max)]`
+    range: Option<(Expr, Expr)>,
+}
+
+// A condition under which a command is refused, from `#[interlock(...)]`
+struct Interlock {
+    // Alarm of the module for `no_alarm("temperature")`, none for `no_critical_alarm`
+    condition: Option<LitStr>,
+    // Whether the interlock applies, from the arguments of the command
+    when: Option<Expr>,
+}
+
 // Represents a single command within a module
-struct Command {
-    name: Ident,
-    args: Vec<(Ident, Type)>,
+pub(crate) struct Command {
+    attrs: Vec<Attribute>,
+    pub(crate) name: Ident,
+    pub(crate) args: Vec<Arg>,
     return_type: Option<Type>,
+    id: u8,
+    // Handled by the host, never sent over the bus
+    local: bool,
+    // `SafetyClass` variant, from `#[safety(...)]`
+    safety: Option<Ident>,
+    interlocks: Vec<Interlock>,
 }
 
 // Represents a single module definition inside the macro
-struct ModuleCommandDef {
-    enum_name: Ident,
-    commands: Vec<Command>,
+pub(crate) struct ModuleCommandDef {
+    pub(crate) enum_name: Ident,
+    pub(crate) commands: Vec<Command>,
+    // Name of the mock module to generate, from `#[mock(Name)]`
+    mock: Option<Ident>,
 }
 
 // Represents multiple module definitions within the macro
 pub struct ModuleCommandsDefList {
+    root: proc_macro2::TokenStream,
     modules: Punctuated<ModuleCommandDef, Token![,]>,
 }
 
+impl Parse for Arg {
+    fn parse(input: ParseStream) -> Result<Self> {
+        let mut attrs = input.call(Attribute::parse_outer)?;
+        let mut range = None;
+        for attr in attrs.iter().filter(|attr| attr.path().is_ident("range")) {
+            let ExprRange {
+                start: Some(start),
+                limits: RangeLimits::Closed(_),
+                end: Some(end),
+                ..
+            } = attr.parse_args::<ExprRange>()?
+            else {
+                return Err(Error::new_spanned(attr, "expected `#[range(min..=max)]`"));
+            };
+            range = Some((*start, *end));
+        }
+        attrs.retain(|attr| !attr.path().is_ident("range"));
+
+        let name = input.parse()?;
+        input.parse::<Token![:]>()?;
+        let ty = input.parse()?;
+        Ok(Arg {
+            attrs,
+            name,
+            ty,
+            range,
+        })
+    }
+}
+
+impl Parse for Interlock {
+    fn parse(input: ParseStream) -> Result<Self> {
+        let kind: Ident = input.parse()?;
+        let condition = match kind.to_string().as_str() {
+            "no_alarm" => {
+                let condition;
+                parenthesized!(condition in input);
+                Some(condition.parse()?)
+            }
+            "no_critical_alarm" => None,
+            _ => {
+                return Err(Error::new(
+                    kind.span(),
+                    "expected `no_alarm(\"condition\")` or `no_critical_alarm`",
+                ));
+            }
+        };
+
+        let when = if input.peek(Token![,]) {
+            input.parse::<Token![,]>()?;
+            let when: Ident = input.parse()?;
+            if when != "when" {
+                return Err(Error::new(when.span(), "expected `when = <condition>`"));
+            }
+            input.parse::<Token![=]>()?;
+            Some(input.parse()?)
+        } else {
+            None
+        };
+
+        Ok(Interlock { condition, when })
+    }
+}
+
 impl Parse for Command {
     fn parse(input: ParseStream) -> Result<Self> {
+        let mut attrs = input.call(Attribute::parse_outer)?;
+        let local = attrs.iter().any(|attr| attr.path().is_ident("local"));
+
+        let mut safety = None;
+        let mut interlocks = Vec::new();
+        for attr in &attrs {
+            if attr.path().is_ident("safety") {
+                let class: Ident = attr.parse_args()?;
+                safety = Some(match class.to_string().as_str() {
+                    "normal" => format_ident!("Normal", span = class.span()),
+                    "critical" => format_ident!("Critical", span = class.span()),
+                    _ => {
+                        return Err(Error::new(
+                            class.span(),
+                            "expected `#[safety(normal)]` or `#[safety(critical)]`",
+                        ));
+                    }
+                });
+            } else if attr.path().is_ident("interlock") {
+                interlocks.push(attr.parse_args()?);
+            }
+        }
+        attrs.retain(|attr| {
+            !["local", "safety", "interlock"]
+                .iter()
+                .any(|name| attr.path().is_ident(name))
+        });
+
         let cmd_name: Ident = input.parse()?;
 
         let args = if input.peek(syn::token::Paren) {
             let args_content;
             parenthesized!(args_content in input);
-            let args_punct: Punctuated<(Ident, Token![:], Type), Token![,]> = args_content
-                .parse_terminated(
-                    |p| {
-                        let name: Ident = p.parse()?;
-                        let _: Token![:] = p.parse()?;
-                        let ty: Type = p.parse()?;
-                        Ok((name, Token![:](p.span()), ty))
-                    },
-                    Token![,],
-                )?;
-            args_punct
-                .into_iter()
-                .map(|(name, _, ty)| (name, ty))
-                .collect()
+            let args_punct: Punctuated<Arg, Token![,]> =
+                args_content.parse_terminated(Arg::parse, Token![,])?;
+            args_punct.into_iter().collect()
         } else {
             Vec::new()
         };
@@ -57,93 +168,753 @@ impl Parse for Command {
             None
         };
 
+        if !input.peek(Token![=]) {
+            return Err(input.error(format!(
+                "expected `= <command id>` after `{cmd_name}`, every command needs a stable ID"
+            )));
+        }
+        input.parse::<Token![=]>()?;
+        let id = input.parse::<LitInt>()?.base10_parse::<u8>()?;
+
         input.parse::<Token![;]>()?;
 
         Ok(Command {
+            attrs,
             name: cmd_name,
             args,
             return_type,
+            id,
+            local,
+            safety,
+            interlocks,
         })
     }
 }
 
 impl Parse for ModuleCommandDef {
     fn parse(input: ParseStream) -> Result<Self> {
+        let mut mock = None;
+        for attr in input.call(Attribute::parse_outer)? {
+            if !attr.path().is_ident("mock") {
+                return Err(Error::new_spanned(attr, "expected `#[mock(Name)]`"));
+            }
+            mock = Some(attr.parse_args()?);
+        }
+
         let enum_name: Ident = input.parse()?;
         let content;
         syn::braced!(content in input);
 
-        let mut commands = Vec::new();
+        let mut commands: Vec<Command> = Vec::new();
+        let mut ids = HashMap::new();
         while !content.is_empty() {
-            commands.push(content.parse()?);
+            let command: Command = content.parse()?;
+            if let Some(other) = ids.insert(command.id, command.name.clone()) {
+                return Err(Error::new(
+                    command.name.span(),
+                    format!(
+                        "command ID {:#04x} of `{}` is already used by `{}`",
+                        command.id, command.name, other
+                    ),
+                ));
+            }
+            commands.push(command);
         }
 
         Ok(ModuleCommandDef {
             enum_name,
             commands,
+            mock,
         })
     }
 }
 
 impl Parse for ModuleCommandsDefList {
     fn parse(input: ParseStream) -> Result<Self> {
+        let root = crate::parse_root(input)?;
         let modules = Punctuated::parse_terminated(input)?;
-        Ok(ModuleCommandsDefList { modules })
+        Ok(ModuleCommandsDefList { root, modules })
+    }
+}
+
+impl Command {
+    // Pattern matching the variant, binding its arguments by name
+    fn pattern(&self) -> proc_macro2::TokenStream {
+        let name = &self.name;
+        if self.args.is_empty() {
+            quote! { Self::#name }
+        } else {
+            let args = self.args.iter().map(|arg| &arg.name);
+            quote! { Self::#name { #(#args),* } }
+        }
+    }
+
+    // Pattern matching the variant, ignoring its arguments
+    fn pattern_any(&self) -> proc_macro2::TokenStream {
+        let name = &self.name;
+        quote! { Self::#name { .. } }
+    }
+
+    pub(crate) fn response_type(&self) -> proc_macro2::TokenStream {
+        self.return_type
+            .as_ref()
+            .map_or(quote! { () }, |ty| quote! { #ty })
+    }
+
+    fn doc(&self) -> String {
+        doc_string(&self.attrs)
+    }
+
+    fn safety_class(&self) -> Ident {
+        self.safety
+            .clone()
+            .unwrap_or_else(|| format_ident!("Normal"))
+    }
+}
+
+// Doc comment lines, joined
+fn doc_string(attrs: &[Attribute]) -> String {
+    attrs
+        .iter()
+        .filter(|attr| attr.path().is_ident("doc"))
+        .filter_map(|attr| match &attr.meta {
+            syn::Meta::NameValue(syn::MetaNameValue {
+                value:
+                    syn::Expr::Lit(syn::ExprLit {
+                        lit: syn::Lit::Str(doc),
+                        ..
+                    }),
+                ..
+            }) => Some(doc.value()),
+            _ => None,
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+// Type as written in the definition, without the spaces `quote` puts between tokens
+pub(crate) fn type_name(ty: &proc_macro2::TokenStream) -> String {
+    ty.to_token_stream().to_string().replace(' ', "")
+}
+
+// Description of the commands of a module for the protocol schema
+fn schema_impl(
+    module: &ModuleCommandDef,
+    root: &proc_macro2::TokenStream,
+) -> proc_macro2::TokenStream {
+    let enum_name = &module.enum_name;
+    let enum_name_str = enum_name.to_string();
+    let schema = quote! { #root::comms::schema };
+
+    let wire_type = |local: bool, ty: &proc_macro2::TokenStream| {
+        if local {
+            quote! { None }
+        } else {
+            quote! { Some(<#ty as #root::comms::codec::WireCodec>::wire_type()) }
+        }
+    };
+
+    let commands = module.commands.iter().map(|cmd| {
+        let name = cmd.name.to_string();
+        let id = cmd.id;
+        let local = cmd.local;
+        let doc = cmd.doc();
+
+        let args = cmd.args.iter().map(|arg| {
+            let name = arg.name.to_string();
+            let ty = arg.ty.to_token_stream();
+            let rust_type = type_name(&ty);
+            let wire = wire_type(local, &ty);
+            quote! {
+                #schema::ValueSchema {
+                    name: Some(#name),
+                    rust_type: #rust_type,
+                    wire: #wire,
+                }
+            }
+        });
+
+        let response_type = cmd.response_type();
+        let rust_type = type_name(&response_type);
+        let response_wire = wire_type(local, &response_type);
+
+        quote! {
+            #schema::CommandSchema {
+                name: #name,
+                id: #id,
+                local: #local,
+                doc: #doc,
+                args: vec![#(#args),*],
+                response: #schema::ValueSchema {
+                    name: None,
+                    rust_type: #rust_type,
+                    wire: #response_wire,
+                },
+            }
+        }
+    });
+
+    quote! {
+        impl #enum_name {
+            /// Commands, IDs and payload layouts, for the protocol schema
+            pub fn schema() -> #schema::ModuleSchema {
+                #schema::ModuleSchema {
+                    name: #enum_name_str,
+                    commands: vec![#(#commands),*],
+                }
+            }
+        }
+    }
+}
+
+// Runtime description of the commands of a module, for UIs building their own forms
+fn descriptor_impl(
+    module: &ModuleCommandDef,
+    root: &proc_macro2::TokenStream,
+) -> proc_macro2::TokenStream {
+    let enum_name = &module.enum_name;
+    let descriptor = quote! { #root::modules::descriptor };
+    let interlock = quote! { #root::modules::interlock };
+
+    let range = |arg: &Arg| match &arg.range {
+        Some((start, end)) => quote! { Some(((#start) as f64, (#end) as f64)) },
+        None => quote! { None },
+    };
+
+    let descriptors = module.commands.iter().map(|cmd| {
+        let name = cmd.name.to_string();
+        let id = cmd.id;
+        let local = cmd.local;
+        let doc = cmd.doc();
+
+        let args = cmd.args.iter().map(|arg| {
+            let name = arg.name.to_string();
+            let doc = doc_string(&arg.attrs);
+            let ty = &arg.ty;
+            let rust_type = type_name(&ty.to_token_stream());
+            let range = range(arg);
+            quote! {
+                #descriptor::ArgDescriptor {
+                    name: #name,
+                    doc: #doc,
+                    rust_type: #rust_type,
+                    kind: <#ty as #descriptor::CommandValue>::kind(),
+                    range: #range,
+                }
+            }
+        });
+
+        let response_type = cmd.response_type();
+        let response_type_name = type_name(&response_type);
+        let safety = cmd.safety_class();
+
+        quote! {
+            #descriptor::CommandDescriptor {
+                name: #name,
+                id: #id,
+                local: #local,
+                doc: #doc,
+                args: vec![#(#args),*],
+                response_type: #response_type_name,
+                response: <#response_type as #descriptor::CommandValue>::kind(),
+                safety: #interlock::SafetyClass::#safety,
+            }
+        }
+    });
+
+    let names = module.commands.iter().map(|cmd| {
+        let pattern = cmd.pattern_any();
+        let name = cmd.name.to_string();
+        quote! { #pattern => #name }
+    });
+
+    let safety = module.commands.iter().map(|cmd| {
+        let safety = cmd.safety_class();
+        if cmd.interlocks.is_empty() {
+            let pattern = cmd.pattern_any();
+            return quote! {
+                #pattern => #interlock::CommandSafety {
+                    class: #interlock::SafetyClass::#safety,
+                    interlocks: Vec::new(),
+                },
+            };
+        }
+
+        let pattern = cmd.pattern();
+        let interlocks = cmd.interlocks.iter().map(|Interlock { condition, when }| {
+            let interlock = match condition {
+                Some(condition) => quote! { #interlock::Interlock::NoAlarm(#condition) },
+                None => quote! { #interlock::Interlock::NoCriticalAlarm },
+            };
+            match when {
+                Some(when) => quote! { if #when { interlocks.push(#interlock); } },
+                None => quote! { interlocks.push(#interlock); },
+            }
+        });
+        quote! {
+            #pattern => {
+                let mut interlocks = Vec::new();
+                #(#interlocks)*
+                #interlock::CommandSafety {
+                    class: #interlock::SafetyClass::#safety,
+                    interlocks,
+                }
+            }
+        }
+    });
+
+    let constructors = module.commands.iter().map(|cmd| {
+        let name = &cmd.name;
+        let name_str = name.to_string();
+        let count = cmd.args.len();
+        let bindings = cmd.args.iter().map(|arg| &arg.name);
+        let command = if cmd.args.is_empty() {
+            quote! { Self::#name }
+        } else {
+            let args = cmd.args.iter().map(|arg| {
+                let Arg { name, ty, .. } = arg;
+                let name_str = name.to_string();
+                let range = range(arg);
+                quote! { #name: #descriptor::arg_value::<#ty>(#name_str, #name, #range)? }
+            });
+            quote! { Self::#name { #(#args),* } }
+        };
+        quote! {
+            #name_str => {
+                let [#(#bindings),*] = #descriptor::take_args::<#count>(#name_str, args)?;
+                Ok(#command)
+            }
+        }
+    });
+
+    let responses = module.commands.iter().map(|cmd| {
+        let name = cmd.name.to_string();
+        let response_type = cmd.response_type();
+        quote! {
+            #name => response
+                .downcast_ref::<#response_type>()
+                .map(#descriptor::CommandValue::to_value),
+        }
+    });
+
+    quote! {
+        impl #descriptor::CommandSet for #enum_name {
+            fn descriptors() -> Vec<#descriptor::CommandDescriptor> {
+                vec![#(#descriptors),*]
+            }
+
+            fn from_values(
+                command: &str,
+                args: Vec<#descriptor::Value>,
+            ) -> Result<Self, #descriptor::ValueError> {
+                match command {
+                    #(#constructors)*
+                    _ => Err(#descriptor::ValueError::UnknownCommand(command.to_string())),
+                }
+            }
+
+            fn response_value(
+                command: &str,
+                response: &dyn std::any::Any,
+            ) -> Option<#descriptor::Value> {
+                match command {
+                    #(#responses)*
+                    _ => None,
+                }
+            }
+
+            fn command_name(&self) -> &'static str {
+                match self {
+                    #(#names),*
+                }
+            }
+
+            // Arguments not used by any `when` are bound all the same
+            #[allow(unused_variables)]
+            fn safety(&self) -> #interlock::CommandSafety {
+                match self {
+                    #(#safety)*
+                }
+            }
+        }
+    }
+}
+
+// Wrapper of `MockModule` with an `expect_*` method per command, for enums marked
+// `#[mock(Name)]`
+fn mock_module(
+    module: &ModuleCommandDef,
+    root: &proc_macro2::TokenStream,
+) -> Option<proc_macro2::TokenStream> {
+    let name = module.mock.as_ref()?;
+    let enum_name = &module.enum_name;
+    let enum_module = format_ident!("{}", enum_name.to_string().to_case(Case::Snake));
+    let mock = quote! { #root::modules::mock };
+    let module_trait = quote! { #root::modules::module::Module };
+    let error = quote! { #root::modules::module::ModuleCommandExecutionError };
+    let doc = format!("Mock module taking `{enum_name}`, see `MockModule`");
+
+    let expectations = module.commands.iter().map(|cmd| {
+        let command = &cmd.name;
+        let method = format_ident!("expect_{}", command.to_string().to_case(Case::Snake));
+        let doc = format!("Program an answer to `{enum_name}::{command}`");
+        quote! {
+            #[doc = #doc]
+            pub fn #method(&mut self) -> #mock::MockExpectation<'_, #enum_name, #enum_module::#command> {
+                self.0.expect::<#enum_module::#command>()
+            }
+        }
+    });
+
+    Some(quote! {
+        #[doc = #doc]
+        pub struct #name(pub #mock::MockModule<#enum_name>);
+
+        impl #name {
+            pub fn new(
+                identity: #root::modules::identity::ModuleIdentity,
+                kind: #root::modules::module::ModuleKind,
+            ) -> Self {
+                Self(#mock::MockModule::new(identity, kind))
+            }
+
+            #(#expectations)*
+        }
+
+        impl std::ops::Deref for #name {
+            type Target = #mock::MockModule<#enum_name>;
+
+            fn deref(&self) -> &Self::Target {
+                &self.0
+            }
+        }
+
+        impl std::ops::DerefMut for #name {
+            fn deref_mut(&mut self) -> &mut Self::Target {
+                &mut self.0
+            }
+        }
+
+        impl #module_trait for #name {
+            type ModuleCommand = #enum_name;
+            type ModuleStatus = ();
+
+            fn metadata(&self) -> #root::modules::module::ModuleMetadata {
+                #module_trait::metadata(&self.0)
+            }
+
+            fn identify(&mut self) -> Result<#root::modules::identity::ModuleIdentity, #error> {
+                #module_trait::identify(&mut self.0)
+            }
+
+            fn process_command(
+                &mut self,
+                command: #enum_name,
+            ) -> #root::modules::module::ModuleCommandExecutionResponse {
+                #module_trait::process_command(&mut self.0, command)
+            }
+
+            fn status(&self) {}
+
+            fn telemetry(&self) -> #root::modules::telemetry::TelemetryReport {
+                #module_trait::telemetry(&self.0)
+            }
+
+            fn enter_safe_state(&mut self) {
+                #module_trait::enter_safe_state(&mut self.0)
+            }
+
+            fn initialize(
+                &mut self,
+                system_controller: std::sync::Arc<#root::modules::system_controller::SystemController>,
+            ) -> Result<(), #error> {
+                #module_trait::initialize(&mut self.0, system_controller)
+            }
+        }
+    })
+}
+
+// Name of the hidden macro through which `#[command_handlers]` reads the commands of an enum
+pub(crate) fn handlers_macro_name(enum_name: &Ident) -> Ident {
+    format_ident!("__{}_handlers", enum_name.to_string().to_case(Case::Snake))
+}
+
+// Hidden macro handing the command definitions of an enum, along with the handler `impl` block
+// it is called with, to `__command_handlers!`. Attribute macros only see the item they are
+// placed on, this is how `#[command_handlers]` gets to check handlers against the definitions.
+fn handlers_macro(
+    module: &ModuleCommandDef,
+    root: &proc_macro2::TokenStream,
+) -> proc_macro2::TokenStream {
+    let enum_name = &module.enum_name;
+    let macro_name = handlers_macro_name(enum_name);
+
+    let commands = module.commands.iter().map(|cmd| {
+        let name = &cmd.name;
+        let id = cmd.id;
+        let local = cmd.local.then(|| quote! { #[local] });
+        let args = cmd
+            .args
+            .iter()
+            .map(|Arg { name, ty, .. }| quote! { #name: #ty });
+        let response_type = cmd.response_type();
+        quote! { #local #name(#(#args),*) -> #response_type = #id; }
+    });
+
+    quote! {
+        #[doc(hidden)]
+        #[allow(unused_macros)]
+        macro_rules! #macro_name {
+            ($($input:tt)*) => {
+                #root::modules::module::__command_handlers! {
+                    #root;
+                    #enum_name { #(#commands)* }
+                    $($input)*
+                }
+            };
+        }
+
+        #[doc(hidden)]
+        #[allow(unused_imports)]
+        pub(crate) use #macro_name;
+    }
+}
+
+// Command ID, name, and payload encoding and decoding of a command enum
+fn wire_impl(
+    module: &ModuleCommandDef,
+    root: &proc_macro2::TokenStream,
+) -> proc_macro2::TokenStream {
+    let enum_name = &module.enum_name;
+    let codec = quote! { #root::comms::codec };
+
+    let ids = module.commands.iter().map(|cmd| {
+        let pattern = cmd.pattern_any();
+        let id = cmd.id;
+        quote! { #pattern => #id }
+    });
+
+    let names = module.commands.iter().map(|cmd| {
+        let pattern = cmd.pattern_any();
+        let name = cmd.name.to_string();
+        quote! { #pattern => #name }
+    });
+
+    let locals = module.commands.iter().map(|cmd| {
+        let pattern = cmd.pattern_any();
+        let local = cmd.local;
+        quote! { #pattern => #local }
+    });
+
+    let encoders = module.commands.iter().map(|cmd| {
+        if cmd.local {
+            let pattern = cmd.pattern_any();
+            let name = cmd.name.to_string();
+            quote! { #pattern => Err(#codec::CodecError::LocalCommand(#name)), }
+        } else {
+            let pattern = cmd.pattern();
+            let args = cmd.args.iter().map(|arg| &arg.name);
+            quote! {
+                #pattern => {
+                    #( #codec::WireCodec::encode(#args, &mut payload)?; )*
+                    Ok(())
+                }
+            }
+        }
+    });
+
+    let decoders = module.commands.iter().map(|cmd| {
+        let name = &cmd.name;
+        let id = cmd.id;
+        if cmd.local {
+            let name = name.to_string();
+            quote! { #id => Err(#codec::CodecError::LocalCommand(#name)), }
+        } else if cmd.args.is_empty() {
+            quote! { #id => Ok(Self::#name), }
+        } else {
+            let args = cmd.args.iter().map(|Arg { name, ty, .. }| {
+                quote! { #name: <#ty as #codec::WireCodec>::decode(&mut payload)? }
+            });
+            quote! { #id => Ok(Self::#name { #(#args),* }), }
+        }
+    });
+
+    // Without wire arguments, the payload is never written or read
+    let payload_mut = module
+        .commands
+        .iter()
+        .any(|cmd| !cmd.local && !cmd.args.is_empty())
+        .then(|| quote! { mut });
+
+    quote! {
+        impl #enum_name {
+            /// Stable ID of the command on the bus
+            pub fn command_id(&self) -> u8 {
+                match self {
+                    #(#ids),*
+                }
+            }
+
+            pub fn name(&self) -> &'static str {
+                match self {
+                    #(#names),*
+                }
+            }
+
+            /// Whether the command is handled by the host rather than sent over the bus
+            pub fn is_local(&self) -> bool {
+                match self {
+                    #(#locals),*
+                }
+            }
+
+            /// Encode the arguments of the command as a frame payload
+            pub fn encode(&self) -> Result<Vec<u8>, #codec::CodecError> {
+                let #payload_mut payload = Vec::new();
+                let encoded: Result<(), #codec::CodecError> = match self {
+                    #(#encoders)*
+                };
+                encoded?;
+                #codec::check_payload_len(&payload)?;
+                Ok(payload)
+            }
+
+            /// Decode a command from its ID and frame payload
+            pub fn decode(command_id: u8, #payload_mut payload: &[u8]) -> Result<Self, #codec::CodecError> {
+                let command = match command_id {
+                    #(#decoders)*
+                    _ => Err(#codec::CodecError::UnknownCommand(command_id)),
+                }?;
+                match payload.len() {
+                    0 => Ok(command),
+                    trailing => Err(#codec::CodecError::TrailingBytes(trailing)),
+                }
+            }
+
+            /// Encode the command as a complete frame for the module at `address`
+            pub fn to_frame(&self, address: u8) -> Result<Vec<u8>, #codec::CodecError> {
+                let payload = self.encode()?;
+                Ok(#root::comms::i2c_protocol::I2CMessage::new(address, self.command_id(), &payload).to_bytes())
+            }
+
+            /// Decode a complete frame into the address it was sent to and the command
+            pub fn from_frame(bytes: &[u8]) -> Result<(u8, Self), #codec::CodecError> {
+                let message = #root::comms::i2c_protocol::I2CMessage::from_bytes(bytes)?;
+                let command = Self::decode(message.command_id(), message.payload())?;
+                Ok((message.module_address(), command))
+            }
+        }
     }
 }
 
 // Generates the Rust code for the macro
 pub fn generate_module_commands(defs: ModuleCommandsDefList) -> TokenStream {
+    let root = &defs.root;
     let module_code = defs.modules.iter().map(|module| {
         let enum_name = &module.enum_name;
         let module_name = format_ident!("{}", enum_name.to_string().to_case(Case::Snake));
 
         let enum_variants = module.commands.iter().map(|cmd| {
+            let attrs = &cmd.attrs;
             let name = &cmd.name;
             if cmd.args.is_empty() {
-                quote! { #name }
+                quote! { #(#attrs)* #name }
             } else {
-                let args = cmd.args.iter().map(|(name, ty)| quote! { #name: #ty });
-                quote! { #name { #(#args),* } }
+                let args = cmd.args.iter().map(|Arg { attrs, name, ty, .. }| {
+                    quote! { #(#attrs)* #name: #ty }
+                });
+                quote! { #(#attrs)* #name { #(#args),* } }
             }
         });
 
         let struct_defs = module.commands.iter().map(|cmd| {
+            let attrs = &cmd.attrs;
             let name = &cmd.name;
-            let response_type = cmd
-                .return_type
-                .as_ref()
-                .map_or(quote! { () }, |ty| quote! { #ty });
+            let name_str = name.to_string();
+            let id = cmd.id;
+            let response_type = cmd.response_type();
+
+            let wire_impl = (!cmd.local).then(|| {
+                quote! {
+                    impl #root::modules::module::WireCommand for #name {
+                        fn encode_response(response: &Self::Response) -> Result<Vec<u8>, #root::comms::codec::CodecError> {
+                            #root::comms::codec::encode(response)
+                        }
+
+                        fn decode_response(payload: &[u8]) -> Result<Self::Response, #root::comms::codec::CodecError> {
+                            #root::comms::codec::decode(payload)
+                        }
+                    }
+                }
+            });
 
             quote! {
+                #(#attrs)*
                 pub struct #name;
 
-                impl crate::modules::module::ModuleCommand for #name {
+                impl #root::modules::module::ModuleCommand for #name {
                     type Response = #response_type;
 
+                    const ID: u8 = #id;
+                    const NAME: &'static str = #name_str;
+
                     fn as_any(&self) -> &dyn std::any::Any {
                         self
                     }
                 }
+
+                // The identification request is answered by every module, whatever its kind
+                const _: () = assert!(
+                    #id != #root::modules::identity::IDENTIFY_COMMAND_ID,
+                    concat!("`", #name_str, "` uses the command ID reserved for identification")
+                );
+
+                #wire_impl
             }
         });
 
+        let wire_impl = wire_impl(module, root);
+        let schema_impl = schema_impl(module, root);
+        let handlers_macro = handlers_macro(module, root);
+        let descriptor_impl = descriptor_impl(module, root);
+        let mock_module = mock_module(module, root);
+
         quote! {
             #[derive(Debug)]
             pub enum #enum_name {
                 #(#enum_variants),*
             }
 
+            #wire_impl
+
+            #schema_impl
+
+            #descriptor_impl
+
+            #handlers_macro
+
+            #mock_module
+
             pub mod #module_name {
-                use crate::modules::module::ModuleCommand;
+                // Argument and response types resolve as they do for the enum
+                #[allow(unused_imports)]
+                use super::*;
+                use #root::modules::module::ModuleCommand;
 
                 #(#struct_defs)*
             }
         }
     });
 
+    let enum_names = defs.modules.iter().map(|module| &module.enum_name);
+
     let expanded = quote! {
         #(#module_code)*
+
+        /// Schema of every module defined here, see `ProtocolSchema`
+        pub fn command_schemas() -> Vec<#root::comms::schema::ModuleSchema> {
+            vec![#(#enum_names::schema()),*]
+        }
     };
 
     TokenStream::from(expanded)
@@ -1,83 +1,236 @@
 use convert_case::{Case, Casing};
-use execute_command::CommandInput;
+use execute_command::{CommandInput, KeyValuePair, SubmitInput};
 use proc_macro::TokenStream;
-use syn::parse_macro_input;
+use syn::{Path, Token, parse::ParseStream, parse_macro_input, punctuated::Punctuated};
+mod command_handlers;
 mod execute_command;
 mod generate_module_commands;
 use quote::{format_ident, quote};
 
+// Path of `stratum-firmware-common` in generated code. The crate names itself the same way
+// (`extern crate self as stratum_firmware_common`), so this works inside of it as well.
+fn default_root() -> proc_macro2::TokenStream {
+    quote! { ::stratum_firmware_common }
+}
+
+// Optional `crate = path;`, for crates that depend on firmware-common under another name or
+// through a re-export
+fn parse_root(input: ParseStream) -> syn::Result<proc_macro2::TokenStream> {
+    if !(input.peek(Token![crate]) && input.peek2(Token![=])) {
+        return Ok(default_root());
+    }
+    input.parse::<Token![crate]>()?;
+    input.parse::<Token![=]>()?;
+    let root: Path = input.parse()?;
+    input.parse::<Token![;]>()?;
+    Ok(quote! { #root })
+}
+
+// `execute_command!` input, after the optional `crate = path;`
+fn parse_execute_input(
+    input: ParseStream,
+) -> syn::Result<(proc_macro2::TokenStream, CommandInput)> {
+    Ok((parse_root(input)?, input.parse()?))
+}
+
 /// A macro to define module commands and their corresponding structures.
 ///
 /// This macro generates an enum representing all possible commands for a given module,
 /// along with structs implementing the `ModuleCommand` trait for each command.
 ///
+/// Every command is assigned an explicit, stable command ID, which is what identifies it in
+/// an `I2CMessage`. IDs must be unique within a module. Commands marked `#[local]` are handled
+/// by the host and never sent over the bus; they still get an ID but no wire encoding.
+///
 /// # Example
 /// ```
 /// def_module_commands! {
 ///     my_module {
-///         Foo(arg1: u32, arg2: String) -> bool;
-///         Bar(arg: i32) -> ();
-///         Baz() -> ();
+///         /// Shown in LVScope and the protocol schema
+///         Foo(#[range(1..=100)] arg1: u32, arg2: String) -> bool = 0x10;
+///         Bar(arg: i32) -> () = 0x11;
+///         #[local] Baz() -> () = 0x20;
 ///     }
 /// }
 /// ```
 ///
 /// This expands to:
 /// ```rust
-/// pub mod my_module {
-///     use crate::modules::module::ModuleCommand;
-///
-///     /// Enum representing all commands for the module.
-///     #[derive(Debug)]
-///     pub enum MyModule {
-///         Foo { arg1: u32, arg2: String },
-///         Bar { arg: i32 },
-///         Baz { },
-///     }
+/// /// Enum representing all commands for the module.
+/// #[derive(Debug)]
+/// pub enum MyModule {
+///     Foo { arg1: u32, arg2: String },
+///     Bar { arg: i32 },
+///     Baz,
+/// }
+///
+/// impl MyModule {
+///     pub fn command_id(&self) -> u8 { /* 0x10, 0x11 or 0x20 */ }
+///     pub fn name(&self) -> &'static str { /* "Foo", "Bar" or "Baz" */ }
+///     pub fn is_local(&self) -> bool { /* true for Baz */ }
 ///
+///     /// Arguments as a frame payload, through `WireCodec`
+///     pub fn encode(&self) -> Result<Vec<u8>, CodecError> { /* ... */ }
+///     pub fn decode(command_id: u8, payload: &[u8]) -> Result<Self, CodecError> { /* ... */ }
+///
+///     /// Complete `I2CMessage` frame for the module at `address`
+///     pub fn to_frame(&self, address: u8) -> Result<Vec<u8>, CodecError> { /* ... */ }
+///     pub fn from_frame(bytes: &[u8]) -> Result<(u8, Self), CodecError> { /* ... */ }
+///
+///     /// Commands, IDs, argument and response layouts, for the protocol schema
+///     pub fn schema() -> ModuleSchema { /* ... */ }
+/// }
+///
+/// impl CommandSet for MyModule {
+///     /// Names, docs, argument kinds, units and ranges, response kinds
+///     fn descriptors() -> Vec<CommandDescriptor> { /* ... */ }
+///     /// `Foo { arg1, arg2 }` from `"Foo"` and the values of `arg1` and `arg2`
+///     fn from_values(command: &str, args: Vec<Value>) -> Result<Self, ValueError> { /* ... */ }
+///     fn response_value(command: &str, response: &dyn Any) -> Option<Value> { /* ... */ }
+/// }
+///
+/// pub mod my_module {
 ///     /// Struct representing the `Foo` command.
 ///     pub struct Foo;
 ///
 ///     impl ModuleCommand for Foo {
 ///         type Response = bool;
 ///
+///         const ID: u8 = 0x10;
+///         const NAME: &'static str = "Foo";
+///
 ///         fn as_any(&self) -> &dyn std::any::Any {
 ///             self
 ///         }
 ///     }
 ///
-///     /// Struct representing the `Bar` command.
-///     pub struct Bar;
+///     /// Only for commands sent over the bus
+///     impl WireCommand for Foo {
+///         fn encode_response(response: &bool) -> Result<Vec<u8>, CodecError> { /* ... */ }
+///         fn decode_response(payload: &[u8]) -> Result<bool, CodecError> { /* ... */ }
+///     }
 ///
-///     impl ModuleCommand for Bar {
-///         type Response = ();
+///     // ... and likewise for `Bar` and `Baz`
+/// }
+/// ```
 ///
-///         fn as_any(&self) -> &dyn std::any::Any {
-///             self
-///         }
-///     }
+/// Alongside, `command_schemas()` returns the schema of every module of the invocation, from
+/// which `ProtocolSchema` exports the JSON schema and C header used by module firmware.
+///
+/// This ensures that each command in the module has a corresponding struct that implements
+/// the `ModuleCommand` trait, defining the associated response type for each command.
+/// If a command has no arguments, it is treated as a unit struct variant instead of an empty struct.
+/// Argument and response types of commands that are not `#[local]` must implement `WireCodec`.
 ///
-///     /// Struct representing the `Baz` command (unit struct).
-///     pub struct Baz;
+/// # Descriptors
+/// Through `CommandSet`, UIs can list the commands of any module, build a form for their
+/// arguments and show the typed response, without knowing the command enum. Doc comments on
+/// commands and arguments become their descriptions, and `#[range(min..=max)]` on a numeric
+/// argument bounds the values accepted by `from_values` (quantities in the display unit of
+/// their kind). Argument and response types must implement `CommandValue`.
 ///
-///     impl ModuleCommand for Baz {
-///         type Response = ();
+/// # Safety classes and interlocks
+/// `CommandSet::safety` tells `ModuleManager` whether it may dispatch a command:
+/// - **`#[safety(critical)]`** marks commands that can defeat a protection; they are only
+///   dispatched with a confirmation token (`ModuleManager::request_confirmation`).
+/// - **`#[interlock(no_alarm("temperature"))]`** refuses the command while the module has
+///   that alarm raised, **`#[interlock(no_critical_alarm)]`** while any critical alarm is.
+///   With `when = <expr>`, the interlock only applies when the arguments, bound by reference,
+///   say so:
+/// ```ignore
+/// #[interlock(no_alarm("temperature"), when = *state)]
+/// SetOutput(state: bool) -> () = 0x10;
+/// ```
 ///
-///         fn as_any(&self) -> &dyn std::any::Any {
-///             self
-///         }
+/// # Mock modules
+/// Marking an enum `#[mock(Name)]` generates `Name`, a `Module` taking the enum built on
+/// `MockModule`, with one `expect_*` method per command to program canned responses and
+/// injected errors. It records the commands it receives, for `ModuleManager` and safety logic
+/// tests:
+/// ```ignore
+/// def_module_commands! {
+///     #[mock(MockPort)]
+///     PortModuleCommands {
+///         SetOutput(state: bool) -> () = 0x10;
 ///     }
 /// }
+///
+/// let mut port = MockPort::new(identity, ModuleKind::OutputPort);
+/// port.expect_set_output().times(1).returning(());
 /// ```
 ///
-/// This ensures that each command in the module has a corresponding struct that implements
-/// the `ModuleCommand` trait, defining the associated response type for each command.
-/// If a command has no arguments, it is treated as a unit struct variant instead of an empty struct.
+/// # Outside of firmware-common
+/// The generated code refers to `ModuleCommand`, `WireCodec` and the other types it needs
+/// through `::stratum_firmware_common`, so that module crates other than firmware-common can
+/// define their commands too. A crate depending on firmware-common under another name gives
+/// the path to use first:
+/// ```ignore
+/// def_module_commands! {
+///     crate = firmware::common;
+///
+///     SolderingUnitCommands {
+///         SetTemperature(target: ThermodynamicTemperature) -> () = 0x10;
+///     }
+/// }
+/// ```
 #[proc_macro]
 pub fn def_module_commands(input: TokenStream) -> TokenStream {
     let input = parse_macro_input!(input as generate_module_commands::ModuleCommandsDefList);
     generate_module_commands::generate_module_commands(input)
 }
+
+/// An attribute implementing `CommandHandlers` from one method per command.
+///
+/// Placed on an inherent `impl` block, it maps every method to the command of the same name in
+/// snake case (`set_output` handles `SetOutput`), and generates a `process_command` matching
+/// the command enum and calling the handler with the arguments of the command.
+///
+/// ## **Usage**
+///
+/// ```ignore
+/// #[command_handlers(PortModuleCommands)]
+/// impl DummyPortModule {
+///     fn set_output(&mut self, state: bool) {
+///         self.output_enabled = state;
+///     }
+///
+///     fn get_demand(&self) -> ElectricCurrent {
+///         self.demand
+///     }
+///
+///     // ... one method per command
+/// }
+///
+/// impl Module for DummyPortModule {
+///     fn process_command(&mut self, command: PortModuleCommands) -> ModuleCommandExecutionResponse {
+///         CommandHandlers::process_command(self, command)
+///     }
+/// }
+/// ```
+///
+/// ## **Checks**
+/// - **Every command has a handler**, and every method of the block handles a command.
+/// - **Arguments** are those of the command, in order and by name.
+/// - **Argument and response types** are those of `def_module_commands!`; mismatches are
+///   reported by the compiler on the handler.
+///
+/// The enum is named the way it is reachable from the handlers (`PortModuleCommands` if it is
+/// defined or imported there, `commands::PortModuleCommands` otherwise). The commands must be
+/// defined in the same crate as the handlers.
+#[proc_macro_attribute]
+pub fn command_handlers(attr: TokenStream, item: TokenStream) -> TokenStream {
+    let commands_path = parse_macro_input!(attr as syn::Path);
+    command_handlers::command_handlers(commands_path, item)
+}
+
+/// Called back with the command definitions by the hidden macro `def_module_commands!`
+/// generates for every enum, see `command_handlers`
+#[doc(hidden)]
+#[proc_macro]
+pub fn __command_handlers(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as command_handlers::HandlersInput);
+    command_handlers::generate_command_handlers(input)
+}
 /// A procedural macro for executing module commands while resolving the correct response type at compile time.
 ///
 /// This macro generates the correct function calls and type resolution for a given module command, ensuring that:
@@ -94,7 +247,7 @@ pub fn def_module_commands(input: TokenStream) -> TokenStream {
 /// ## **Expands To**
 /// ```rust
 /// {
-///     type CommandStruct = stratum_firmware_common::modules::commands::battery_command::SetOutput;
+///     type CommandStruct = battery_command::SetOutput;
 ///     type ResponseType = <CommandStruct as ModuleCommand>::Response;
 ///
 ///     let command = BatteryCommand::SetOutput { state: false };
@@ -109,44 +262,38 @@ pub fn def_module_commands(input: TokenStream) -> TokenStream {
 /// - **`module`** → The instance of the module (e.g., `dummy_module`).
 /// - **`module_command_type`** → The command enum type (e.g., `BatteryCommand`).
 /// - **`command_variant`** → The fully qualified command with arguments (e.g., `BatteryCommand::SetOutput { state: false }`).
+/// - **`crate = path;`** → Optional, first: how firmware-common is named, as for `def_module_commands!`.
 ///
 /// ## **How It Works**
 /// 1. **Converts the command module to `snake_case`** (e.g., `BatteryCommand` → `battery_command`).
-/// 2. **Derives the fully qualified struct path** (e.g., `commands::battery_command::SetOutput`).
+/// 2. **Derives the struct path next to the enum** (e.g., `commands::BatteryCommand` → `commands::battery_command::SetOutput`).
+///    Named without a path, the command module must be in scope as well.
 /// 3. **Constructs the correct command execution logic with its arguments**.
 /// 4. **Processes the command using `process_command()`**.
 /// 5. **Downcasts the result to the correct response type**.
 #[proc_macro]
 pub fn execute_command(input: TokenStream) -> TokenStream {
-    let CommandInput {
-        module,
-        module_command_type,
-        command_enum,
-        command_variant,
-        args,
-    } = parse_macro_input!(input as CommandInput);
-    let module_command_snake = quote!(#module_command_type)
-        .to_string()
-        .to_case(Case::Snake);
-    let mod_module_commands = format_ident!("{}", module_command_snake);
-
-    let command_creation = if let Some(args) = args {
-        let args_named = args.iter().map(|kv| {
-            let key = &kv.key;
-            let value = &kv.value;
-            quote! { #key: #value }
-        });
-        quote! { #command_enum { #(#args_named),* } }
-    } else {
-        // Handles unit variants
-        quote! { #command_enum }
+    let (
+        root,
+        CommandInput {
+            module,
+            module_command_type,
+            command_enum,
+            command_variant,
+            args,
+        },
+    ) = parse_macro_input!(input with parse_execute_input);
+    let command_struct = match command_struct_path(&module_command_type, &command_variant) {
+        Ok(path) => path,
+        Err(err) => return err.to_compile_error().into(),
     };
+    let command_creation = command_creation(&command_enum, args.as_ref());
 
     let expanded = quote! {
         {
-            use crate::modules::module::{Module, ModuleCommand, ModuleCommandExecutionError};
+            use #root::modules::module::{Module, ModuleCommand, ModuleCommandExecutionError};
 
-            type CommandEnum = crate::modules::commands::#mod_module_commands::#command_variant;
+            type CommandEnum = #command_struct;
             type ResponseType = <CommandEnum as ModuleCommand>::Response;
 
             let _: &dyn Module<ModuleCommand = #module_command_type, ModuleStatus = _> =
@@ -154,7 +301,8 @@ pub fn execute_command(input: TokenStream) -> TokenStream {
 
             let command = #command_creation;
 
-            let result: Result<Box<dyn std::any::Any>, ModuleCommandExecutionError> = #module.process_command(command);
+            // Fully qualified, as modules also have the `process_command` of `CommandHandlers`
+            let result: Result<Box<dyn std::any::Any>, ModuleCommandExecutionError> = Module::process_command(#module, command);
             let final_result: Result<ResponseType, ModuleCommandExecutionError> = result.and_then(|boxed| {
                 boxed
                     .downcast::<ResponseType>()
@@ -168,3 +316,121 @@ pub fn execute_command(input: TokenStream) -> TokenStream {
 
     TokenStream::from(expanded)
 }
+
+// The command structs live next to the enum, in the module named after it:
+// `commands::BatteryCommand` and `SetOutput` give `commands::battery_command::SetOutput`
+fn command_struct_path(
+    module_command_type: &Path,
+    command_variant: &syn::Ident,
+) -> syn::Result<proc_macro2::TokenStream> {
+    let Some(enum_name) = module_command_type
+        .segments
+        .last()
+        .map(|segment| &segment.ident)
+    else {
+        return Err(syn::Error::new_spanned(
+            module_command_type,
+            "expected the command enum",
+        ));
+    };
+    let leading_colon = &module_command_type.leading_colon;
+    let enum_module = module_command_type
+        .segments
+        .iter()
+        .take(module_command_type.segments.len() - 1);
+    let mod_module_commands = format_ident!("{}", enum_name.to_string().to_case(Case::Snake));
+
+    Ok(quote! { #leading_colon #(#enum_module::)* #mod_module_commands::#command_variant })
+}
+
+fn command_creation(
+    command_enum: &Path,
+    args: Option<&Punctuated<KeyValuePair, Token![,]>>,
+) -> proc_macro2::TokenStream {
+    if let Some(args) = args {
+        let args_named = args.iter().map(|kv| {
+            let key = &kv.key;
+            let value = &kv.value;
+            quote! { #key: #value }
+        });
+        quote! { #command_enum { #(#args_named),* } }
+    } else {
+        // Handles unit variants
+        quote! { #command_enum }
+    }
+}
+
+/// Queue a command for a module of a `ModuleManager`, the way `execute_command!` runs it on a
+/// module directly.
+///
+/// Evaluates to a `CommandHandle` of the command's response type, completed when the manager
+/// gets to the command in `process_queue`. With `queue_timeout`, the command is dropped with
+/// `QueueTimeout` if it is still queued by then; a command that has been sent always runs to
+/// completion.
+///
+/// ## **Usage**
+///
+/// ```ignore
+/// let handle = submit_command!(modules, port_id: PortModuleCommands, PortModuleCommands::SetOutput { state: true });
+/// let handle = submit_command!(
+///     modules,
+///     port_id: PortModuleCommands,
+///     PortModuleCommands::SetCurrentLimit { limit },
+///     queue_timeout = Duration::from_millis(500)
+/// );
+/// ```
+///
+/// ## **Expands To**
+/// ```ignore
+/// {
+///     let handle: ::stratum_firmware_common::modules::command_queue::CommandHandle<
+///         <port_module_commands::SetOutput as ::stratum_firmware_common::modules::module::ModuleCommand>::Response,
+///     > = modules.submit::<port_module_commands::SetOutput, PortModuleCommands>(
+///         port_id,
+///         PortModuleCommands::SetOutput { state: true },
+///         None,
+///     );
+///     handle
+/// }
+/// ```
+///
+/// As with `def_module_commands!`, a leading `crate = path;` names firmware-common for crates
+/// that depend on it under another name.
+#[proc_macro]
+pub fn submit_command(input: TokenStream) -> TokenStream {
+    let SubmitInput {
+        root,
+        manager,
+        command:
+            CommandInput {
+                module,
+                module_command_type,
+                command_enum,
+                command_variant,
+                args,
+            },
+        queue_timeout,
+    } = parse_macro_input!(input as SubmitInput);
+    let command_struct = match command_struct_path(&module_command_type, &command_variant) {
+        Ok(path) => path,
+        Err(err) => return err.to_compile_error().into(),
+    };
+    let command_creation = command_creation(&command_enum, args.as_ref());
+    let queue_timeout = match queue_timeout {
+        Some(timeout) => quote! { Some(#timeout) },
+        None => quote! { None },
+    };
+
+    TokenStream::from(quote! {
+        {
+            let handle: #root::modules::command_queue::CommandHandle<
+                <#command_struct as #root::modules::module::ModuleCommand>::Response,
+            > = #manager.submit::<#command_struct, #module_command_type>(
+                #module,
+                #command_creation,
+                #queue_timeout,
+            );
+            handle
+        }
+    })
+}
@@ -0,0 +1,28 @@
+//! Writes the module bus protocol schema as JSON and as a C header for module firmware.
+//!
+//! ```sh
+//! cargo run --example export_protocol [output directory]
+//! ```
+//!
+//! Defaults to the `protocol` directory of this crate. Run again after changing a command
+//! definition and commit the result.
+
+use std::path::PathBuf;
+use stratum_firmware_common::comms::schema::ProtocolSchema;
+
+fn main() -> std::io::Result<()> {
+    let out_dir = std::env::args()
+        .nth(1)
+        .map(PathBuf::from)
+        .unwrap_or_else(|| PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("protocol"));
+
+    let schema = ProtocolSchema::current();
+    schema.write_to(&out_dir)?;
+
+    println!(
+        "Wrote protocol schema {:08X} to {}",
+        schema.checksum(),
+        out_dir.display()
+    );
+    Ok(())
+}
@@ -0,0 +1,155 @@
+//! A module driver living outside of firmware-common, the way a soldering unit driver crate
+//! would: its own commands, handlers and wire format, on top of the module traits.
+//!
+//! ```sh
+//! cargo run --example soldering_unit
+//! ```
+
+use std::sync::Arc;
+use stratum_firmware_common::modules::{
+    descriptor::Value,
+    identity::{HardwareUid, ModuleIdentity},
+    module::{
+        command_handlers, def_module_commands, execute_command, CommandHandlers, Module,
+        ModuleCommandExecutionError, ModuleCommandExecutionResponse, ModuleKind, ModuleMetadata,
+    },
+    module_manager::ModuleManager,
+    system_controller::{ModuleEvent, SystemController},
+};
+use uom::si::{f64::ThermodynamicTemperature, thermodynamic_temperature::degree_celsius};
+
+def_module_commands! {
+    // Commands that can be sent to the soldering unit
+    SolderingUnitCommands {
+        /// Tip temperature to regulate to, `None` to turn the heater off
+        #[interlock(no_alarm("tip_sensor"), when = target.is_some())]
+        SetTarget(#[range(100.0..=450.0)] target: Option<ThermodynamicTemperature>) -> () = 0x10;
+        GetTipTemperature() -> ThermodynamicTemperature = 0x11;
+        #[local] GetTarget() -> Option<ThermodynamicTemperature> = 0x20;
+    }
+}
+
+struct SolderingUnit {
+    identity: ModuleIdentity,
+    target: Option<ThermodynamicTemperature>,
+    tip: ThermodynamicTemperature,
+}
+
+#[command_handlers(SolderingUnitCommands)]
+impl SolderingUnit {
+    fn set_target(&mut self, target: Option<ThermodynamicTemperature>) {
+        self.target = target;
+    }
+
+    fn get_tip_temperature(&self) -> ThermodynamicTemperature {
+        self.tip
+    }
+
+    fn get_target(&self) -> Option<ThermodynamicTemperature> {
+        self.target
+    }
+}
+
+impl Module for SolderingUnit {
+    type ModuleCommand = SolderingUnitCommands;
+    type ModuleStatus = Option<ThermodynamicTemperature>;
+
+    fn metadata(&self) -> ModuleMetadata {
+        ModuleMetadata {
+            id: self.identity.module_id(),
+            name: "Soldering Unit".to_string(),
+            module_kind: ModuleKind::SolderingUnit,
+            version: "0.1.0".to_string(),
+        }
+    }
+
+    fn identify(&mut self) -> Result<ModuleIdentity, ModuleCommandExecutionError> {
+        Ok(self.identity)
+    }
+
+    fn process_command(&mut self, command: Self::ModuleCommand) -> ModuleCommandExecutionResponse {
+        CommandHandlers::process_command(self, command)
+    }
+
+    fn status(&self) -> Self::ModuleStatus {
+        self.target
+    }
+
+    fn initialize(
+        &mut self,
+        _system_controller: Arc<SystemController>,
+    ) -> Result<(), ModuleCommandExecutionError> {
+        Ok(())
+    }
+}
+
+fn main() -> Result<(), ModuleCommandExecutionError> {
+    let uid = HardwareUid::simulated(ModuleKind::SolderingUnit, 1);
+    let mut unit = SolderingUnit {
+        identity: ModuleIdentity::new(uid, 3),
+        target: None,
+        tip: ThermodynamicTemperature::new::<degree_celsius>(24.0),
+    };
+
+    // Through the bus encoding, as the host would send it
+    let command = SolderingUnitCommands::SetTarget {
+        target: Some(ThermodynamicTemperature::new::<degree_celsius>(320.0)),
+    };
+    let frame = command.to_frame(unit.identity.address())?;
+    let (address, command) = SolderingUnitCommands::from_frame(&frame)?;
+    println!(
+        "{:#04x} <- {} ({} bytes)",
+        address,
+        command.name(),
+        frame.len()
+    );
+    Module::process_command(&mut unit, command)?;
+
+    let target =
+        execute_command!(&mut unit: SolderingUnitCommands, SolderingUnitCommands::GetTarget)?;
+    let tip = execute_command!(&mut unit: SolderingUnitCommands, SolderingUnitCommands::GetTipTemperature)?;
+    println!(
+        "target {:?} °C, tip {} °C",
+        target.map(|target| target.get::<degree_celsius>()),
+        tip.get::<degree_celsius>()
+    );
+
+    // As a UI knowing nothing of the soldering unit would: from the descriptors, with values
+    let controller = SystemController::new();
+    let mut modules = ModuleManager::new();
+    let id = modules
+        .register_module(unit, controller.clone())
+        .map_err(|err| ModuleCommandExecutionError::HardwareFailure(err.to_string()))?;
+    for command in modules.command_descriptors(id).unwrap_or_default() {
+        let args: Vec<String> = command
+            .args
+            .iter()
+            .map(|arg| format!("{}: {:?} {:?}", arg.name, arg.kind, arg.range))
+            .collect();
+        println!(
+            "{:#04x} {}({}) -> {}",
+            command.id,
+            command.name,
+            args.join(", "),
+            command.response_type
+        );
+    }
+    let target = |celsius| vec![Value::Optional(Some(Box::new(Value::Number(celsius))))];
+    if let Err(err) = modules.send_values(id, "SetTarget", target(500.0), None) {
+        println!("SetTarget: {err}");
+    }
+
+    // Heating with a broken tip sensor is refused by the interlock, turning off is not
+    controller.raise_alarm(
+        id,
+        "tip_sensor",
+        ModuleEvent::Warning("Tip sensor open".into()),
+    );
+    if let Err(err) = modules.send_values(id, "SetTarget", target(350.0), None) {
+        println!("SetTarget: {err}");
+    }
+    modules.send_values(id, "SetTarget", vec![Value::Optional(None)], None)?;
+    let target = modules.send_values(id, "GetTarget", Vec::new(), None)?;
+    println!("GetTarget: {target}");
+    Ok(())
+}
@@ -0,0 +1,94 @@
+// Stratum module bus protocol.
+//
+// Generated from the command definitions in firmware-common with
+// `cargo run --example export_protocol`. Do not edit.
+//
+// Frame: start byte, module address, command ID, payload length, payload, CRC.
+// Payload values are little-endian and packed, one after the other.
+
+#pragma once
+
+#include <stdint.h>
+
+#define STRATUM_PROTOCOL_CHECKSUM 0x6ABD6128u
+#define STRATUM_START_BYTE 0xAC
+#define STRATUM_IDENTIFY_COMMAND_ID 0x01
+#define STRATUM_MAX_PAYLOAD_LEN 255
+
+typedef struct __attribute__((packed)) {
+    int32_t voltage; // mV
+    int32_t temperature; // 0.01 °C
+} cell_reading_t;
+
+// BatteryModuleCommands
+
+// Connect or disconnect the battery output
+#define BATTERY_MODULE_CMD_SET_OUTPUT 0x10
+typedef struct __attribute__((packed)) {
+    uint8_t state; // 0 or 1
+} battery_module_set_output_args_t;
+// Response: none
+
+// Pack voltage
+#define BATTERY_MODULE_CMD_GET_VOLTAGE 0x11
+// Arguments: none
+typedef struct __attribute__((packed)) {
+    int32_t value; // mV
+} battery_module_get_voltage_response_t;
+
+// Voltage and temperature of every cell
+#define BATTERY_MODULE_CMD_GET_CELL_READINGS 0x12
+// Arguments: none
+// Response:
+//   value: uint8_t count, then count x cell_reading_t
+
+// GetConfig (0x20) is handled by the host, modules never receive it
+// SetConfig (0x21) is handled by the host, modules never receive it
+// SetConfigValue (0x22) is handled by the host, modules never receive it
+// ResetConfig (0x23) is handled by the host, modules never receive it
+// LoadThresholdProfile (0x24) is handled by the host, modules never receive it
+// GetBalancingDecision (0x30) is handled by the host, modules never receive it
+// GetBalancingConfig (0x31) is handled by the host, modules never receive it
+// SetBalancingConfig (0x32) is handled by the host, modules never receive it
+
+#define BATTERY_MODULE_CMD_DUMMY 0x7F
+// Arguments: none
+// Response: none
+
+// PortModuleCommands
+
+// Enable or disable the port output
+#define PORT_MODULE_CMD_SET_OUTPUT 0x10
+typedef struct __attribute__((packed)) {
+    uint8_t state; // 0 or 1
+} port_module_set_output_args_t;
+// Response: none
+
+// Limit the current the port may draw
+#define PORT_MODULE_CMD_SET_CURRENT_LIMIT 0x11
+typedef struct __attribute__((packed)) {
+    int32_t limit; // mA
+} port_module_set_current_limit_args_t;
+// Response: none
+
+// Current the connected load asks for
+#define PORT_MODULE_CMD_GET_DEMAND 0x12
+// Arguments: none
+typedef struct __attribute__((packed)) {
+    int32_t value; // mA
+} port_module_get_demand_response_t;
+
+// GetConfig (0x20) is handled by the host, modules never receive it
+// SetConfig (0x21) is handled by the host, modules never receive it
+// SetConfigValue (0x22) is handled by the host, modules never receive it
+// ResetConfig (0x23) is handled by the host, modules never receive it
+
+// ChargerModuleCommands
+
+// SetChargingEnabled (0x10) is handled by the host, modules never receive it
+// GetPhase (0x11) is handled by the host, modules never receive it
+// ResetFault (0x12) is handled by the host, modules never receive it
+// GetConfig (0x20) is handled by the host, modules never receive it
+// SetConfig (0x21) is handled by the host, modules never receive it
+// SetConfigValue (0x22) is handled by the host, modules never receive it
+// ResetConfig (0x23) is handled by the host, modules never receive it
@@ -0,0 +1,36 @@
+use std::{sync::Mutex, time::Duration};
+
+use super::Clock;
+
+/// A clock that only moves when told to. Used for deterministic tests and scripted simulations.
+#[derive(Default)]
+pub struct ManualClock {
+    now: Mutex<Duration>,
+}
+
+impl ManualClock {
+    pub fn new(start: Duration) -> Self {
+        Self {
+            now: Mutex::new(start),
+        }
+    }
+
+    /// Move the clock forward by `delta`.
+    pub fn advance(&self, delta: Duration) {
+        *self.now.lock().unwrap() += delta;
+    }
+
+    /// Jump to an absolute time. Moving backwards is ignored to keep the clock monotonic.
+    pub fn set(&self, time: Duration) {
+        let mut now = self.now.lock().unwrap();
+        if time > *now {
+            *now = time;
+        }
+    }
+}
+
+impl Clock for ManualClock {
+    fn now(&self) -> Duration {
+        *self.now.lock().unwrap()
+    }
+}
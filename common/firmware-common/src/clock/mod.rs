@@ -0,0 +1,60 @@
+use std::{sync::Arc, time::Duration};
+
+mod manual_clock;
+mod real_clock;
+mod scaled_clock;
+
+pub use manual_clock::ManualClock;
+pub use real_clock::RealClock;
+pub use scaled_clock::ScaledClock;
+
+/// A monotonic time source.
+///
+/// Everything in the simulation that needs to know "what time is it" reads it through a
+/// `Clock` instead of calling `Instant::now()` directly. This allows tests to step time by
+/// hand (`ManualClock`) and LVScope to fast-forward a long discharge (`ScaledClock`).
+pub trait Clock: Send + Sync {
+    /// Time elapsed since the clock's epoch (usually its creation).
+    fn now(&self) -> Duration;
+}
+
+pub type SharedClock = Arc<dyn Clock>;
+
+/// A fixed-period deadline driven by a `Clock`, used to schedule periodic work
+/// such as module updates.
+#[derive(Debug, Clone)]
+pub struct Interval {
+    period: Duration,
+    next_due: Duration,
+}
+
+impl Interval {
+    /// Create an interval whose first tick is due immediately.
+    pub fn new(period: Duration, now: Duration) -> Self {
+        Self {
+            period,
+            next_due: now,
+        }
+    }
+
+    pub fn period(&self) -> Duration {
+        self.period
+    }
+
+    /// Returns `true` (and schedules the next tick) if the interval has elapsed.
+    ///
+    /// Missed ticks are not replayed: if the clock jumped several periods ahead,
+    /// the next tick is scheduled one period from `now`.
+    pub fn poll(&mut self, now: Duration) -> bool {
+        if now < self.next_due {
+            return false;
+        }
+
+        self.next_due += self.period;
+        if self.next_due <= now {
+            self.next_due = now + self.period;
+        }
+
+        true
+    }
+}
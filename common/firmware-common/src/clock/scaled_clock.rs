@@ -0,0 +1,60 @@
+use std::{sync::Mutex, time::Duration};
+
+use super::{Clock, SharedClock};
+
+struct ScaledState {
+    speed: f64,
+    source_anchor: Duration,
+    scaled_anchor: Duration,
+}
+
+/// A clock that runs at a multiple of another clock's rate.
+///
+/// A speed of `1.0` follows the source, `60.0` turns one real second into one simulated minute,
+/// and `0.0` pauses time. Changing the speed never makes the clock jump.
+pub struct ScaledClock {
+    source: SharedClock,
+    state: Mutex<ScaledState>,
+}
+
+impl ScaledClock {
+    pub fn new(source: SharedClock, speed: f64) -> Self {
+        Self {
+            state: Mutex::new(ScaledState {
+                speed: speed.max(0.0),
+                source_anchor: source.now(),
+                scaled_anchor: Duration::ZERO,
+            }),
+            source,
+        }
+    }
+
+    pub fn speed(&self) -> f64 {
+        self.state.lock().unwrap().speed
+    }
+
+    pub fn set_speed(&self, speed: f64) {
+        let mut state = self.state.lock().unwrap();
+        let source_now = self.source.now();
+
+        state.scaled_anchor = Self::scaled_at(&state, source_now);
+        state.source_anchor = source_now;
+        state.speed = speed.max(0.0);
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.speed() == 0.0
+    }
+
+    fn scaled_at(state: &ScaledState, source_now: Duration) -> Duration {
+        let elapsed = source_now.saturating_sub(state.source_anchor);
+        state.scaled_anchor + elapsed.mul_f64(state.speed)
+    }
+}
+
+impl Clock for ScaledClock {
+    fn now(&self) -> Duration {
+        let state = self.state.lock().unwrap();
+        Self::scaled_at(&state, self.source.now())
+    }
+}
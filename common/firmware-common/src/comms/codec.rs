@@ -0,0 +1,266 @@
+use thiserror::Error;
+use uom::si::{
+    electric_charge::ampere_hour,
+    electric_current::ampere,
+    electric_potential::volt,
+    energy::watt_hour,
+    f64::{
+        ElectricCharge, ElectricCurrent, ElectricPotential, Energy, Power, ThermodynamicTemperature,
+    },
+    power::watt,
+    thermodynamic_temperature::degree_celsius,
+};
+
+use super::{i2c_protocol::I2CError, schema::WireType};
+
+/// Largest payload an `I2CMessage` can carry
+pub const MAX_PAYLOAD_LEN: usize = u8::MAX as usize;
+
+#[derive(Debug, Error)]
+pub enum CodecError {
+    #[error("Payload ended early")]
+    UnexpectedEnd,
+
+    #[error("{0} unexpected bytes after the payload")]
+    TrailingBytes(usize),
+
+    #[error("Invalid {0} value")]
+    InvalidValue(&'static str),
+
+    #[error("Payload of {0} bytes does not fit in a frame")]
+    PayloadTooLarge(usize),
+
+    #[error("Too many elements to encode ({0})")]
+    TooLong(usize),
+
+    #[error("Unknown command ID {0:#04x}")]
+    UnknownCommand(u8),
+
+    #[error("`{0}` is handled by the host and never sent over the bus")]
+    LocalCommand(&'static str),
+
+    #[error("Invalid frame: {0}")]
+    Frame(#[from] I2CError),
+}
+
+/// Binary representation of a command argument or response on the bus.
+///
+/// Integers and floats are little-endian. Strings and vectors are prefixed with their length
+/// as a single byte, options with a 0/1 tag. Physical quantities are sent as fixed-point
+/// `i32`s in a fixed unit (e.g. millivolts), so that module MCUs never deal with floats.
+pub trait WireCodec: Sized {
+    fn encode(&self, out: &mut Vec<u8>) -> Result<(), CodecError>;
+
+    /// Decode a value from the front of `input`, advancing it past the bytes read
+    fn decode(input: &mut &[u8]) -> Result<Self, CodecError>;
+
+    /// Layout of the encoded value, for the protocol schema
+    fn wire_type() -> WireType;
+}
+
+/// Encode `value` as a whole payload
+pub fn encode<T: WireCodec>(value: &T) -> Result<Vec<u8>, CodecError> {
+    let mut payload = Vec::new();
+    value.encode(&mut payload)?;
+    check_payload_len(&payload)?;
+    Ok(payload)
+}
+
+/// Decode a whole payload, which must hold exactly one `T`
+pub fn decode<T: WireCodec>(mut payload: &[u8]) -> Result<T, CodecError> {
+    let value = T::decode(&mut payload)?;
+    match payload.len() {
+        0 => Ok(value),
+        trailing => Err(CodecError::TrailingBytes(trailing)),
+    }
+}
+
+pub fn check_payload_len(payload: &[u8]) -> Result<(), CodecError> {
+    if payload.len() > MAX_PAYLOAD_LEN {
+        return Err(CodecError::PayloadTooLarge(payload.len()));
+    }
+    Ok(())
+}
+
+fn take<const N: usize>(input: &mut &[u8]) -> Result<[u8; N], CodecError> {
+    let (bytes, rest) = input
+        .split_first_chunk::<N>()
+        .ok_or(CodecError::UnexpectedEnd)?;
+    *input = rest;
+    Ok(*bytes)
+}
+
+fn encode_len(len: usize, out: &mut Vec<u8>) -> Result<(), CodecError> {
+    let len = u8::try_from(len).map_err(|_| CodecError::TooLong(len))?;
+    out.push(len);
+    Ok(())
+}
+
+impl WireCodec for () {
+    fn encode(&self, _out: &mut Vec<u8>) -> Result<(), CodecError> {
+        Ok(())
+    }
+
+    fn decode(_input: &mut &[u8]) -> Result<Self, CodecError> {
+        Ok(())
+    }
+
+    fn wire_type() -> WireType {
+        WireType::Unit
+    }
+}
+
+impl WireCodec for bool {
+    fn encode(&self, out: &mut Vec<u8>) -> Result<(), CodecError> {
+        out.push(*self as u8);
+        Ok(())
+    }
+
+    fn decode(input: &mut &[u8]) -> Result<Self, CodecError> {
+        match take::<1>(input)? {
+            [0] => Ok(false),
+            [1] => Ok(true),
+            _ => Err(CodecError::InvalidValue("bool")),
+        }
+    }
+
+    fn wire_type() -> WireType {
+        WireType::Bool
+    }
+}
+
+macro_rules! little_endian {
+    ($($ty:ty => $wire:ident),* $(,)?) => {
+        $(
+            impl WireCodec for $ty {
+                fn encode(&self, out: &mut Vec<u8>) -> Result<(), CodecError> {
+                    out.extend_from_slice(&self.to_le_bytes());
+                    Ok(())
+                }
+
+                fn decode(input: &mut &[u8]) -> Result<Self, CodecError> {
+                    take(input).map(<$ty>::from_le_bytes)
+                }
+
+                fn wire_type() -> WireType {
+                    WireType::$wire
+                }
+            }
+        )*
+    };
+}
+
+little_endian! {
+    u8 => U8,
+    u16 => U16,
+    u32 => U32,
+    u64 => U64,
+    i8 => I8,
+    i16 => I16,
+    i32 => I32,
+    i64 => I64,
+    f32 => F32,
+    f64 => F64,
+}
+
+impl WireCodec for String {
+    fn encode(&self, out: &mut Vec<u8>) -> Result<(), CodecError> {
+        encode_len(self.len(), out)?;
+        out.extend_from_slice(self.as_bytes());
+        Ok(())
+    }
+
+    fn decode(input: &mut &[u8]) -> Result<Self, CodecError> {
+        let [len] = take::<1>(input)?;
+        let (bytes, rest) = input
+            .split_at_checked(len as usize)
+            .ok_or(CodecError::UnexpectedEnd)?;
+        *input = rest;
+        String::from_utf8(bytes.to_vec()).map_err(|_| CodecError::InvalidValue("string"))
+    }
+
+    fn wire_type() -> WireType {
+        WireType::String
+    }
+}
+
+impl<T: WireCodec> WireCodec for Vec<T> {
+    fn encode(&self, out: &mut Vec<u8>) -> Result<(), CodecError> {
+        encode_len(self.len(), out)?;
+        self.iter().try_for_each(|item| item.encode(out))
+    }
+
+    fn decode(input: &mut &[u8]) -> Result<Self, CodecError> {
+        let [len] = take::<1>(input)?;
+        (0..len).map(|_| T::decode(input)).collect()
+    }
+
+    fn wire_type() -> WireType {
+        WireType::vec(T::wire_type())
+    }
+}
+
+impl<T: WireCodec> WireCodec for Option<T> {
+    fn encode(&self, out: &mut Vec<u8>) -> Result<(), CodecError> {
+        match self {
+            None => {
+                out.push(0);
+                Ok(())
+            }
+            Some(value) => {
+                out.push(1);
+                value.encode(out)
+            }
+        }
+    }
+
+    fn decode(input: &mut &[u8]) -> Result<Self, CodecError> {
+        match take::<1>(input)? {
+            [0] => Ok(None),
+            [1] => T::decode(input).map(Some),
+            _ => Err(CodecError::InvalidValue("option")),
+        }
+    }
+
+    fn wire_type() -> WireType {
+        WireType::option(T::wire_type())
+    }
+}
+
+/// Quantities sent as an `i32` count of `1 / scale` of `unit`, e.g. millivolts. Values out of
+/// range saturate and NaN is sent as 0.
+macro_rules! fixed_point {
+    ($($quantity:ident => $unit:ident, $scale:literal, $symbol:literal, $wire_unit:literal);* $(;)?) => {
+        $(
+            impl WireCodec for $quantity {
+                fn encode(&self, out: &mut Vec<u8>) -> Result<(), CodecError> {
+                    let fixed = (self.get::<$unit>() * $scale).round() as i32;
+                    fixed.encode(out)
+                }
+
+                fn decode(input: &mut &[u8]) -> Result<Self, CodecError> {
+                    let fixed = i32::decode(input)?;
+                    Ok(<$quantity>::new::<$unit>(fixed as f64 / $scale))
+                }
+
+                fn wire_type() -> WireType {
+                    WireType::Fixed {
+                        quantity: stringify!($quantity),
+                        unit: $symbol,
+                        scale: $scale as u32,
+                        wire_unit: $wire_unit,
+                    }
+                }
+            }
+        )*
+    };
+}
+
+fixed_point! {
+    ElectricPotential => volt, 1000.0, "V", "mV";
+    ElectricCurrent => ampere, 1000.0, "A", "mA";
+    Power => watt, 1000.0, "W", "mW";
+    Energy => watt_hour, 1000.0, "Wh", "mWh";
+    ElectricCharge => ampere_hour, 1000.0, "Ah", "mAh";
+    ThermodynamicTemperature => degree_celsius, 100.0, "°C", "0.01 °C";
+}
@@ -29,7 +29,7 @@ pub struct I2CMessage<'a> {
 
 impl<'a> I2CMessage<'a> {
     // Amnio Communication (AC)
-    const START_BYTE: u8 = 0xAC;
+    pub const START_BYTE: u8 = 0xAC;
     const CRC_LEN: usize = 2;
 
     /// Compute CRC-16 checksum (big-endian order)
@@ -61,6 +61,18 @@ impl<'a> I2CMessage<'a> {
         }
     }
 
+    pub fn module_address(&self) -> u8 {
+        self.module_address
+    }
+
+    pub fn command_id(&self) -> u8 {
+        self.command_id
+    }
+
+    pub fn payload(&self) -> &'a [u8] {
+        self.payload
+    }
+
     pub fn to_bytes(&self) -> Vec<u8> {
         let mut data = Vec::with_capacity(4 + self.payload.len() + Self::CRC_LEN);
         data.push(self.start_byte);
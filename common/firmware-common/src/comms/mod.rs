@@ -1 +1,3 @@
+pub mod codec;
 pub mod i2c_protocol;
+pub mod schema;
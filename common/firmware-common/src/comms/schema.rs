@@ -0,0 +1,432 @@
+use crc::{Crc, CRC_32_ISO_HDLC};
+use serde::Serialize;
+use std::{fmt::Write, fs, io, path::Path};
+
+use super::{codec::MAX_PAYLOAD_LEN, i2c_protocol::I2CMessage};
+use crate::modules::{commands, identity::IDENTIFY_COMMAND_ID};
+
+/// How a value is laid out in a frame payload, as produced by `WireCodec`
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum WireType {
+    Unit,
+    Bool,
+    U8,
+    U16,
+    U32,
+    U64,
+    I8,
+    I16,
+    I32,
+    I64,
+    F32,
+    F64,
+    /// Length byte followed by UTF-8
+    String,
+    /// Count byte followed by the items
+    Vec {
+        item: Box<WireType>,
+    },
+    /// 0/1 tag byte followed by the value if present
+    Option {
+        item: Box<WireType>,
+    },
+    /// `i32` count of `1 / scale` of `unit`, e.g. millivolts for volts at a scale of 1000
+    Fixed {
+        quantity: &'static str,
+        unit: &'static str,
+        scale: u32,
+        wire_unit: &'static str,
+    },
+    /// Fields one after the other
+    Struct {
+        name: &'static str,
+        fields: Vec<FieldSchema>,
+    },
+}
+
+impl WireType {
+    pub fn vec(item: WireType) -> Self {
+        Self::Vec {
+            item: Box::new(item),
+        }
+    }
+
+    pub fn option(item: WireType) -> Self {
+        Self::Option {
+            item: Box::new(item),
+        }
+    }
+
+    /// Encoded size, if it does not depend on the value
+    pub fn fixed_size(&self) -> Option<usize> {
+        match self {
+            WireType::Unit => Some(0),
+            WireType::Bool | WireType::U8 | WireType::I8 => Some(1),
+            WireType::U16 | WireType::I16 => Some(2),
+            WireType::U32 | WireType::I32 | WireType::F32 | WireType::Fixed { .. } => Some(4),
+            WireType::U64 | WireType::I64 | WireType::F64 => Some(8),
+            WireType::String | WireType::Vec { .. } | WireType::Option { .. } => None,
+            WireType::Struct { fields, .. } => {
+                fields.iter().map(|field| field.ty.fixed_size()).sum()
+            }
+        }
+    }
+
+    fn c_type(&self) -> Option<String> {
+        Some(
+            match self {
+                WireType::Bool | WireType::U8 => "uint8_t",
+                WireType::U16 => "uint16_t",
+                WireType::U32 => "uint32_t",
+                WireType::U64 => "uint64_t",
+                WireType::I8 => "int8_t",
+                WireType::I16 => "int16_t",
+                WireType::I32 | WireType::Fixed { .. } => "int32_t",
+                WireType::I64 => "int64_t",
+                WireType::F32 => "float",
+                WireType::F64 => "double",
+                WireType::Struct { name, .. } => return Some(format!("{}_t", snake_case(name))),
+                WireType::Unit
+                | WireType::String
+                | WireType::Vec { .. }
+                | WireType::Option { .. } => return None,
+            }
+            .to_string(),
+        )
+    }
+
+    /// Human readable layout, for comments
+    fn describe(&self) -> String {
+        match self {
+            WireType::Unit => "empty".into(),
+            WireType::Bool => "uint8_t (0 or 1)".into(),
+            WireType::String => "uint8_t length, then UTF-8 bytes".into(),
+            WireType::Vec { item } => format!("uint8_t count, then count x {}", item.describe()),
+            WireType::Option { item } => {
+                format!(
+                    "uint8_t present (0 or 1), then {} if present",
+                    item.describe()
+                )
+            }
+            WireType::Fixed { wire_unit, .. } => format!("int32_t in {wire_unit}"),
+            other => other.c_type().unwrap_or_default(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct FieldSchema {
+    pub name: &'static str,
+    #[serde(rename = "type")]
+    pub ty: WireType,
+}
+
+impl FieldSchema {
+    pub fn new(name: &'static str, ty: WireType) -> Self {
+        Self { name, ty }
+    }
+}
+
+/// An argument or response of a command
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ValueSchema {
+    /// Argument name, `None` for responses
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub name: Option<&'static str>,
+    pub rust_type: &'static str,
+    /// Layout on the bus, `None` for local commands
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub wire: Option<WireType>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct CommandSchema {
+    pub name: &'static str,
+    pub id: u8,
+    /// Handled by the host and never sent over the bus
+    pub local: bool,
+    #[serde(skip_serializing_if = "str::is_empty")]
+    pub doc: &'static str,
+    pub args: Vec<ValueSchema>,
+    pub response: ValueSchema,
+}
+
+/// Commands of one module kind, as defined with `def_module_commands!`
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ModuleSchema {
+    pub name: &'static str,
+    pub commands: Vec<CommandSchema>,
+}
+
+/// File the JSON schema is exported to by `ProtocolSchema::write_to`
+pub const JSON_FILE_NAME: &str = "stratum_protocol.json";
+/// File the C header is exported to by `ProtocolSchema::write_to`
+pub const C_HEADER_FILE_NAME: &str = "stratum_protocol.h";
+
+/// Everything module firmware needs to talk to the host: framing and the command table of
+/// every module kind
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ProtocolSchema {
+    pub start_byte: u8,
+    pub identify_command_id: u8,
+    pub max_payload_len: usize,
+    /// CRC-16/IBM-3740 over the frame, big-endian
+    pub crc: &'static str,
+    pub modules: Vec<ModuleSchema>,
+}
+
+impl ProtocolSchema {
+    /// Schema of the commands defined in `modules::commands`
+    pub fn current() -> Self {
+        Self {
+            start_byte: I2CMessage::START_BYTE,
+            identify_command_id: IDENTIFY_COMMAND_ID,
+            max_payload_len: MAX_PAYLOAD_LEN,
+            crc: "CRC-16/IBM-3740",
+            modules: commands::command_schemas(),
+        }
+    }
+
+    pub fn to_json(&self) -> String {
+        // Only plain strings and numbers, which always serialize
+        serde_json::to_string_pretty(self).expect("protocol schema serializes to JSON")
+    }
+
+    /// CRC-32 of the JSON schema, to tell whether module firmware was built against the
+    /// current command table
+    pub fn checksum(&self) -> u32 {
+        Crc::<u32>::new(&CRC_32_ISO_HDLC).checksum(self.to_json().as_bytes())
+    }
+
+    /// Write the JSON schema and the C header into `dir`, as committed under `protocol/`
+    pub fn write_to(&self, dir: &Path) -> io::Result<()> {
+        fs::create_dir_all(dir)?;
+        fs::write(dir.join(JSON_FILE_NAME), self.to_json() + "\n")?;
+        fs::write(dir.join(C_HEADER_FILE_NAME), self.to_c_header())
+    }
+
+    /// C header with the framing constants, command IDs and payload layouts, for module
+    /// firmware
+    pub fn to_c_header(&self) -> String {
+        let mut out = String::new();
+        let mut structs = Vec::new();
+        for module in &self.modules {
+            for command in module.commands.iter().filter(|command| !command.local) {
+                let wire = command.args.iter().chain([&command.response]);
+                for ty in wire.filter_map(|value| value.wire.as_ref()) {
+                    collect_structs(ty, &mut structs);
+                }
+            }
+        }
+
+        // Writing to a `String` cannot fail
+        let _ = self.write_c_header(&mut out, &structs);
+        out
+    }
+
+    fn write_c_header(&self, out: &mut String, structs: &[&WireType]) -> std::fmt::Result {
+        writeln!(out, "// Stratum module bus protocol.")?;
+        writeln!(out, "//")?;
+        writeln!(
+            out,
+            "// Generated from the command definitions in firmware-common with"
+        )?;
+        writeln!(
+            out,
+            "// `cargo run --example export_protocol`. Do not edit."
+        )?;
+        writeln!(out, "//")?;
+        writeln!(
+            out,
+            "// Frame: start byte, module address, command ID, payload length, payload, CRC."
+        )?;
+        writeln!(
+            out,
+            "// Payload values are little-endian and packed, one after the other."
+        )?;
+        writeln!(out)?;
+        writeln!(out, "#pragma once")?;
+        writeln!(out)?;
+        writeln!(out, "#include <stdint.h>")?;
+        writeln!(out)?;
+        writeln!(
+            out,
+            "#define STRATUM_PROTOCOL_CHECKSUM 0x{:08X}u",
+            self.checksum()
+        )?;
+        writeln!(out, "#define STRATUM_START_BYTE 0x{:02X}", self.start_byte)?;
+        writeln!(
+            out,
+            "#define STRATUM_IDENTIFY_COMMAND_ID 0x{:02X}",
+            self.identify_command_id
+        )?;
+        writeln!(
+            out,
+            "#define STRATUM_MAX_PAYLOAD_LEN {}",
+            self.max_payload_len
+        )?;
+
+        for ty in structs {
+            let WireType::Struct { name, fields } = ty else {
+                continue;
+            };
+            writeln!(out)?;
+            write_c_struct(out, &format!("{}_t", snake_case(name)), fields)?;
+        }
+
+        for module in &self.modules {
+            let module_name = snake_case(module.name.trim_end_matches("Commands"));
+            let prefix = module_name.to_uppercase();
+            writeln!(out)?;
+            writeln!(out, "// {}", module.name)?;
+
+            let mut after_local = false;
+            for command in &module.commands {
+                let define = format!("{}_CMD_{}", prefix, snake_case(command.name).to_uppercase());
+                if command.local {
+                    // Listed together, for reference only
+                    if !after_local {
+                        writeln!(out)?;
+                    }
+                    after_local = true;
+                    writeln!(
+                        out,
+                        "// {} (0x{:02X}) is handled by the host, modules never receive it",
+                        command.name, command.id
+                    )?;
+                    continue;
+                }
+                after_local = false;
+                writeln!(out)?;
+
+                for line in command.doc.lines() {
+                    writeln!(out, "//{line}")?;
+                }
+                writeln!(out, "#define {} 0x{:02X}", define, command.id)?;
+
+                let args: Vec<FieldSchema> = command
+                    .args
+                    .iter()
+                    .filter_map(|arg| Some(FieldSchema::new(arg.name?, arg.wire.clone()?)))
+                    .collect();
+                let type_name = format!("{}_{}", module_name, snake_case(command.name));
+                write_c_payload(out, &format!("{type_name}_args_t"), "Arguments", args)?;
+
+                let response = command
+                    .response
+                    .wire
+                    .clone()
+                    .map(|ty| match ty {
+                        WireType::Struct { fields, .. } => fields,
+                        WireType::Unit => Vec::new(),
+                        ty => vec![FieldSchema::new("value", ty)],
+                    })
+                    .unwrap_or_default();
+                write_c_payload(
+                    out,
+                    &format!("{type_name}_response_t"),
+                    "Response",
+                    response,
+                )?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Struct types used on the wire, innermost first
+fn collect_structs<'a>(ty: &'a WireType, structs: &mut Vec<&'a WireType>) {
+    match ty {
+        WireType::Vec { item } | WireType::Option { item } => collect_structs(item, structs),
+        WireType::Struct { fields, .. } => {
+            for field in fields {
+                collect_structs(&field.ty, structs);
+            }
+            if !structs.contains(&ty) {
+                structs.push(ty);
+            }
+        }
+        _ => {}
+    }
+}
+
+fn write_c_struct(out: &mut String, name: &str, fields: &[FieldSchema]) -> std::fmt::Result {
+    writeln!(out, "typedef struct __attribute__((packed)) {{")?;
+    for field in fields {
+        let c_type = field.ty.c_type().unwrap_or_default();
+        match &field.ty {
+            WireType::Fixed { wire_unit, .. } => {
+                writeln!(out, "    {} {}; // {}", c_type, field.name, wire_unit)?
+            }
+            WireType::Bool => writeln!(out, "    {} {}; // 0 or 1", c_type, field.name)?,
+            _ => writeln!(out, "    {} {};", c_type, field.name)?,
+        }
+    }
+    writeln!(out, "}} {name};")
+}
+
+/// A packed struct if the payload has a fixed layout, a comment describing it otherwise
+fn write_c_payload(
+    out: &mut String,
+    name: &str,
+    what: &str,
+    fields: Vec<FieldSchema>,
+) -> std::fmt::Result {
+    if fields.is_empty() {
+        return writeln!(out, "// {what}: none");
+    }
+
+    if fields.iter().all(|field| field.ty.fixed_size().is_some()) {
+        return write_c_struct(out, name, &fields);
+    }
+
+    writeln!(out, "// {what}:")?;
+    for field in fields {
+        writeln!(out, "//   {}: {}", field.name, field.ty.describe())?;
+    }
+    Ok(())
+}
+
+/// `BatteryModuleSetOutput` -> `battery_module_set_output`
+fn snake_case(name: &str) -> String {
+    let mut out = String::with_capacity(name.len() + 4);
+    for (i, c) in name.chars().enumerate() {
+        if c.is_uppercase() {
+            if i > 0 && !out.ends_with('_') {
+                out.push('_');
+            }
+            out.extend(c.to_lowercase());
+        } else {
+            out.push(c);
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    /// The committed `protocol/` files must match the command definitions, otherwise module
+    /// firmware gets built against a stale table. Run the `export_protocol` example to fix.
+    #[test]
+    fn committed_protocol_is_up_to_date() {
+        let committed = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("protocol");
+        let exported =
+            std::env::temp_dir().join(format!("stratum_protocol_{}", std::process::id()));
+        ProtocolSchema::current().write_to(&exported).unwrap();
+
+        for file in [JSON_FILE_NAME, C_HEADER_FILE_NAME] {
+            let expected = fs::read_to_string(exported.join(file)).unwrap();
+            let actual = fs::read_to_string(committed.join(file)).unwrap();
+            assert!(
+                expected == actual,
+                "protocol/{file} is out of date, run `cargo run --example export_protocol`"
+            );
+        }
+        let _ = fs::remove_dir_all(exported);
+    }
+}
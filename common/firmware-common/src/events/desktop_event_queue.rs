@@ -1,12 +1,12 @@
 use crossbeam::channel::{unbounded, Receiver, Sender};
 
-use crate::modules::system_controller::ModuleEvent;
+use crate::modules::system_controller::TimestampedEvent;
 
 use super::EventQueue;
 
 pub struct MpscEventQueue {
-    sender: Sender<ModuleEvent>,
-    receiver: Receiver<ModuleEvent>,
+    sender: Sender<TimestampedEvent>,
+    receiver: Receiver<TimestampedEvent>,
 }
 
 impl MpscEventQueue {
@@ -20,11 +20,11 @@ impl MpscEventQueue {
 }
 
 impl EventQueue for MpscEventQueue {
-    fn send(&self, event: ModuleEvent) {
+    fn send(&self, event: TimestampedEvent) {
         let _ = self.sender.send(event);
     }
 
-    fn receive(&self) -> Option<ModuleEvent> {
+    fn receive(&self) -> Option<TimestampedEvent> {
         self.receiver.try_recv().ok()
     }
 }
@@ -1,4 +1,4 @@
-use crate::modules::system_controller::ModuleEvent;
+use crate::modules::system_controller::{ModuleEvent, TimestampedEvent};
 
 #[cfg(target_arch = "xtensa")] // Runs only on ESP32
 pub struct FreeRtosEventQueue {
@@ -8,21 +8,24 @@ pub struct FreeRtosEventQueue {
 #[cfg(target_arch = "xtensa")]
 impl FreeRtosEventQueue {
     pub fn new() -> Self {
-        let queue = unsafe { xQueueCreate(10, std::mem::size_of::<ModuleEvent>() as u32) };
+        let queue = unsafe { xQueueCreate(10, std::mem::size_of::<TimestampedEvent>() as u32) };
         Self { queue }
     }
 }
 
 #[cfg(target_arch = "xtensa")]
 impl EventQueue for FreeRtosEventQueue {
-    fn send(&self, event: ModuleEvent) {
+    fn send(&self, event: TimestampedEvent) {
         unsafe {
             xQueueSend(self.queue, &event as *const _ as *const c_void, 0);
         }
     }
 
-    fn receive(&self) -> Option<ModuleEvent> {
-        let mut event = ModuleEvent::Info("".to_string());
+    fn receive(&self) -> Option<TimestampedEvent> {
+        let mut event = TimestampedEvent {
+            timestamp: Default::default(),
+            event: ModuleEvent::Info("".to_string()),
+        };
         let result = unsafe {
             xQueueReceive(
                 self.queue,
@@ -1,10 +1,10 @@
 use std::sync::Arc;
 
-use crate::modules::system_controller::ModuleEvent;
+use crate::modules::system_controller::TimestampedEvent;
 
 pub trait EventQueue: Send + Sync {
-    fn send(&self, event: ModuleEvent);
-    fn receive(&self) -> Option<ModuleEvent>;
+    fn send(&self, event: TimestampedEvent);
+    fn receive(&self) -> Option<TimestampedEvent>;
 }
 
 /// Automatically creates the right queue based on platform
@@ -28,7 +28,7 @@ pub use esp32_event_queue::FreeRtosEventQueue;
 
 pub fn start_event_loop(
     queue: Arc<dyn EventQueue>,
-    handle_event: Arc<dyn Fn(ModuleEvent) + Send + Sync>,
+    handle_event: Arc<dyn Fn(TimestampedEvent) + Send + Sync>,
 ) {
     #[cfg(feature = "xtensa")]
     {
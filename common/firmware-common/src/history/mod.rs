@@ -0,0 +1,266 @@
+use log::warn;
+use std::{collections::BTreeMap, mem::size_of, ops::Range, time::Duration};
+
+use crate::modules::telemetry::{TelemetryKey, TelemetryReport};
+
+mod series;
+
+pub use series::{Bucket, Series};
+
+/// One level of detail kept for every series
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Resolution {
+    /// Time covered by one bucket
+    pub bucket: Duration,
+    /// How many buckets are kept, the oldest are dropped first
+    pub capacity: usize,
+}
+
+impl Resolution {
+    pub const fn new(bucket: Duration, capacity: usize) -> Self {
+        Self { bucket, capacity }
+    }
+
+    /// How far back this resolution reaches
+    pub fn span(&self) -> Duration {
+        self.bucket * self.capacity as u32
+    }
+}
+
+/// Sizing of a `TelemetryHistory`
+#[derive(Debug, Clone, PartialEq)]
+pub struct HistoryConfig {
+    /// Levels of detail, finest first
+    pub resolutions: Vec<Resolution>,
+    /// Upper bound on the memory used by the recorded buckets, in bytes. Once reached, series
+    /// that stopped updating are dropped to make room for new ones; if there are none, new
+    /// series are not recorded.
+    pub max_bytes: usize,
+}
+
+impl HistoryConfig {
+    /// An hour at 1 s, a day at 1 min and a week at 15 min, within 64 MiB
+    pub fn desktop() -> Self {
+        Self {
+            resolutions: vec![
+                Resolution::new(Duration::from_secs(1), 3600),
+                Resolution::new(Duration::from_secs(60), 1440),
+                Resolution::new(Duration::from_secs(15 * 60), 672),
+            ],
+            max_bytes: 64 * 1024 * 1024,
+        }
+    }
+
+    /// Ten minutes at 10 s, two hours at 1 min and a day at 15 min, within 128 KiB, to fit
+    /// in the ESP32's internal RAM alongside everything else
+    pub fn embedded() -> Self {
+        Self {
+            resolutions: vec![
+                Resolution::new(Duration::from_secs(10), 60),
+                Resolution::new(Duration::from_secs(60), 120),
+                Resolution::new(Duration::from_secs(15 * 60), 96),
+            ],
+            max_bytes: 128 * 1024,
+        }
+    }
+
+    /// Worst-case memory used by one series once all its resolutions are full, in bytes
+    pub fn bytes_per_series(&self) -> usize {
+        let buckets: usize = self.resolutions.iter().map(|res| res.capacity).sum();
+        buckets * size_of::<Bucket>() + size_of::<Series>()
+    }
+
+    /// How many series fit within `max_bytes`
+    pub fn max_series(&self) -> usize {
+        self.max_bytes / self.bytes_per_series().max(1)
+    }
+}
+
+impl Default for HistoryConfig {
+    fn default() -> Self {
+        Self::desktop()
+    }
+}
+
+/// Identifies a recorded series
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct SeriesKey {
+    pub module_id: u16,
+    pub key: TelemetryKey,
+}
+
+/// Bounded history of every numeric telemetry value, per module and key.
+///
+/// Each sample is folded into min/max/avg buckets at every configured resolution, so that
+/// recent data is kept in detail and older data only as coarse trends. Memory stays within
+/// `HistoryConfig::max_bytes` whatever the number of modules and keys.
+pub struct TelemetryHistory {
+    config: HistoryConfig,
+    max_series: usize,
+    series: BTreeMap<SeriesKey, Series>,
+    /// Series dropped to stay within the memory limit
+    evicted: u64,
+    /// Samples of new series not recorded because the store was full
+    rejected: u64,
+}
+
+impl TelemetryHistory {
+    pub fn new(config: HistoryConfig) -> Self {
+        Self {
+            max_series: config.max_series(),
+            config,
+            series: BTreeMap::new(),
+            evicted: 0,
+            rejected: 0,
+        }
+    }
+
+    pub fn config(&self) -> &HistoryConfig {
+        &self.config
+    }
+
+    /// Record every numeric value of a report; states and other non-numeric values are skipped
+    pub fn record(&mut self, report: &TelemetryReport) {
+        for entry in &report.entries {
+            let value = entry.value.display_value();
+            if value.is_finite() {
+                self.record_value(report.module_id, entry.key.clone(), report.timestamp, value);
+            }
+        }
+    }
+
+    pub fn record_value(
+        &mut self,
+        module_id: u16,
+        key: impl Into<TelemetryKey>,
+        timestamp: Duration,
+        value: f64,
+    ) {
+        let key = SeriesKey {
+            module_id,
+            key: key.into(),
+        };
+
+        if !self.series.contains_key(&key) {
+            if self.series.len() >= self.max_series && !self.evict_stale(timestamp) {
+                if self.rejected == 0 {
+                    warn!(
+                        "Telemetry history is full ({} series), not recording {} of module {}",
+                        self.max_series, key.key, key.module_id
+                    );
+                }
+                self.rejected += 1;
+                return;
+            }
+            self.series
+                .insert(key.clone(), Series::new(&self.config.resolutions));
+        }
+
+        if let Some(series) = self.series.get_mut(&key) {
+            series.push(timestamp, value);
+        }
+    }
+
+    /// Drop the least recently updated series if it has not been updated for as long as the
+    /// finest resolution reaches back, e.g. because its module was removed
+    fn evict_stale(&mut self, now: Duration) -> bool {
+        let stale_after = self
+            .config
+            .resolutions
+            .first()
+            .map_or(Duration::ZERO, Resolution::span);
+
+        let Some(oldest) = self
+            .series
+            .iter()
+            .min_by_key(|(_, series)| series.last_timestamp())
+            .filter(|(_, series)| now.saturating_sub(series.last_timestamp()) > stale_after)
+            .map(|(key, _)| key.clone())
+        else {
+            return false;
+        };
+
+        self.series.remove(&oldest);
+        self.evicted += 1;
+        true
+    }
+
+    /// Buckets of a series overlapping `range`, oldest first.
+    ///
+    /// Uses the finest resolution that still covers the start of the range, or the coarsest
+    /// one if none does.
+    pub fn query(&self, module_id: u16, key: &str, range: Range<Duration>) -> Vec<Bucket> {
+        let Some(series) = self.series(module_id, key) else {
+            return Vec::new();
+        };
+
+        let level = (0..self.config.resolutions.len())
+            .find(|level| series.covers(*level, range.start))
+            .unwrap_or(self.config.resolutions.len().saturating_sub(1));
+
+        series.range(level, range)
+    }
+
+    /// Buckets of a series overlapping `range` at one resolution (index into
+    /// `HistoryConfig::resolutions`), oldest first
+    pub fn query_at(
+        &self,
+        module_id: u16,
+        key: &str,
+        level: usize,
+        range: Range<Duration>,
+    ) -> Vec<Bucket> {
+        self.series(module_id, key)
+            .map(|series| series.range(level, range))
+            .unwrap_or_default()
+    }
+
+    pub fn series(&self, module_id: u16, key: &str) -> Option<&Series> {
+        self.series.get(&SeriesKey {
+            module_id,
+            key: key.to_string().into(),
+        })
+    }
+
+    /// Keys recorded for a module
+    pub fn keys(&self, module_id: u16) -> impl Iterator<Item = &TelemetryKey> {
+        self.series
+            .keys()
+            .filter(move |series| series.module_id == module_id)
+            .map(|series| &series.key)
+    }
+
+    /// Forget everything recorded for a module, e.g. once it is removed
+    pub fn remove_module(&mut self, module_id: u16) {
+        self.series.retain(|key, _| key.module_id != module_id);
+    }
+
+    pub fn clear(&mut self) {
+        self.series.clear();
+    }
+
+    pub fn series_count(&self) -> usize {
+        self.series.len()
+    }
+
+    /// Memory currently used by the recorded buckets, in bytes
+    pub fn memory_usage(&self) -> usize {
+        self.series.values().map(Series::memory_usage).sum()
+    }
+
+    /// How many stale series were dropped to stay within the memory limit
+    pub fn evicted(&self) -> u64 {
+        self.evicted
+    }
+
+    /// How many samples were not recorded because the store was full
+    pub fn rejected(&self) -> u64 {
+        self.rejected
+    }
+}
+
+impl Default for TelemetryHistory {
+    fn default() -> Self {
+        Self::new(HistoryConfig::default())
+    }
+}
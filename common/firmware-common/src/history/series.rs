@@ -0,0 +1,161 @@
+use std::{collections::VecDeque, mem::size_of, ops::Range, time::Duration};
+
+use super::Resolution;
+
+/// Summary of the samples that fell within one time slot.
+///
+/// Values are kept in single precision to halve the footprint on the ESP32; telemetry is
+/// nowhere near accurate enough for that to matter.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Bucket {
+    start_ms: u64,
+    min: f32,
+    max: f32,
+    /// Running average, so that it does not lose precision as the count grows
+    avg: f32,
+    count: u32,
+}
+
+impl Bucket {
+    fn new(start: Duration, value: f64) -> Self {
+        Self {
+            start_ms: start.as_millis() as u64,
+            min: value as f32,
+            max: value as f32,
+            avg: value as f32,
+            count: 1,
+        }
+    }
+
+    fn add(&mut self, value: f64) {
+        let value = value as f32;
+        self.min = self.min.min(value);
+        self.max = self.max.max(value);
+        self.count = self.count.saturating_add(1);
+        self.avg += (value - self.avg) / self.count as f32;
+    }
+
+    pub fn start(&self) -> Duration {
+        Duration::from_millis(self.start_ms)
+    }
+
+    pub fn min(&self) -> f64 {
+        self.min.into()
+    }
+
+    pub fn max(&self) -> f64 {
+        self.max.into()
+    }
+
+    pub fn avg(&self) -> f64 {
+        self.avg.into()
+    }
+
+    /// Number of samples folded into the bucket
+    pub fn count(&self) -> u32 {
+        self.count
+    }
+}
+
+/// Buckets of one resolution
+#[derive(Debug, Clone)]
+struct Level {
+    resolution: Resolution,
+    buckets: VecDeque<Bucket>,
+}
+
+impl Level {
+    fn push(&mut self, timestamp: Duration, value: f64) {
+        let width = self.resolution.bucket.as_millis().max(1);
+        let start = Duration::from_millis((timestamp.as_millis() / width * width) as u64);
+
+        match self.buckets.back_mut() {
+            // Late samples are folded into the newest bucket rather than reordering history
+            Some(last) if last.start() >= start => last.add(value),
+            _ => {
+                if self.buckets.len() >= self.resolution.capacity {
+                    self.buckets.pop_front();
+                }
+                if self.resolution.capacity > 0 {
+                    self.buckets.push_back(Bucket::new(start, value));
+                }
+            }
+        }
+    }
+}
+
+/// History of a single telemetry value, at every configured resolution
+#[derive(Debug, Clone)]
+pub struct Series {
+    levels: Vec<Level>,
+    first_timestamp: Option<Duration>,
+    last_timestamp: Duration,
+}
+
+impl Series {
+    pub fn new(resolutions: &[Resolution]) -> Self {
+        Self {
+            levels: resolutions
+                .iter()
+                .map(|resolution| Level {
+                    resolution: *resolution,
+                    buckets: VecDeque::new(),
+                })
+                .collect(),
+            first_timestamp: None,
+            last_timestamp: Duration::ZERO,
+        }
+    }
+
+    pub fn push(&mut self, timestamp: Duration, value: f64) {
+        self.first_timestamp.get_or_insert(timestamp);
+        self.last_timestamp = self.last_timestamp.max(timestamp);
+        for level in &mut self.levels {
+            level.push(timestamp, value);
+        }
+    }
+
+    /// Time of the newest sample
+    pub fn last_timestamp(&self) -> Duration {
+        self.last_timestamp
+    }
+
+    /// Bucket holding the newest sample at the finest resolution
+    pub fn latest(&self) -> Option<&Bucket> {
+        self.levels.first().and_then(|level| level.buckets.back())
+    }
+
+    /// Whether `level` still holds data from `since` on, give or take a bucket.
+    ///
+    /// Nothing before the first sample was ever recorded, so a series younger than the window
+    /// is covered by every level that has not dropped a bucket yet.
+    pub fn covers(&self, level: usize, since: Duration) -> bool {
+        let since = self.first_timestamp.map_or(since, |first| since.max(first));
+        self.levels.get(level).is_some_and(|level| {
+            level
+                .buckets
+                .front()
+                .is_some_and(|oldest| oldest.start() <= since + level.resolution.bucket)
+        })
+    }
+
+    /// Buckets of `level` overlapping `range`, oldest first
+    pub fn range(&self, level: usize, range: Range<Duration>) -> Vec<Bucket> {
+        let Some(level) = self.levels.get(level) else {
+            return Vec::new();
+        };
+        let width = level.resolution.bucket;
+
+        level
+            .buckets
+            .iter()
+            .filter(|bucket| bucket.start() + width > range.start && bucket.start() < range.end)
+            .copied()
+            .collect()
+    }
+
+    pub(super) fn memory_usage(&self) -> usize {
+        let buckets: usize = self.levels.iter().map(|level| level.buckets.len()).sum();
+        buckets * size_of::<Bucket>() + size_of::<Self>()
+    }
+}
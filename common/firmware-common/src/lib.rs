@@ -1,3 +1,11 @@
+// Code generated by `amnio-macros` names this crate `::stratum_firmware_common`, so that it
+// works the same in module crates depending on it and in here
+extern crate self as stratum_firmware_common;
+
+pub mod clock;
 pub mod comms;
 pub mod events;
+pub mod history;
 pub mod modules;
+pub mod settings;
+pub mod simulation;
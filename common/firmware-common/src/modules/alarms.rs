@@ -0,0 +1,236 @@
+use std::{
+    collections::{BTreeMap, VecDeque},
+    fmt,
+    time::Duration,
+};
+
+use super::telemetry::Severity;
+
+/// How many acknowledged and cleared alarms `AlarmManager::history` keeps
+const HISTORY_LEN: usize = 100;
+
+/// Identifies a distinct alarm condition, e.g. the `"current"` threshold of battery 3
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct AlarmKey {
+    pub module_id: u16,
+    pub condition: String,
+}
+
+impl AlarmKey {
+    pub fn new(module_id: u16, condition: impl Into<String>) -> Self {
+        Self {
+            module_id,
+            condition: condition.into(),
+        }
+    }
+}
+
+impl fmt::Display for AlarmKey {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}/{}", self.module_id, self.condition)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AlarmState {
+    /// The condition is present and nobody has acknowledged it yet
+    Active,
+    /// The condition is still present but has been acknowledged
+    Acknowledged,
+    /// The condition went away before anybody acknowledged it
+    Cleared,
+}
+
+impl fmt::Display for AlarmState {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            AlarmState::Active => write!(f, "Active"),
+            AlarmState::Acknowledged => write!(f, "Acknowledged"),
+            AlarmState::Cleared => write!(f, "Cleared"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Alarm {
+    pub key: AlarmKey,
+    /// Highest severity the condition was raised with
+    pub severity: Severity,
+    /// Latest description of the condition
+    pub message: String,
+    pub state: AlarmState,
+    pub first_seen: Duration,
+    pub last_seen: Duration,
+    /// How many times the condition was raised, repeats included
+    pub occurrences: u32,
+    pub acknowledged_at: Option<Duration>,
+    pub cleared_at: Option<Duration>,
+}
+
+/// Lifecycle of every alarm condition in the system.
+///
+/// Raising a condition that is already raised only refreshes it, so a fault persisting over
+/// many updates makes a single alarm. An alarm stays listed until its condition is gone and
+/// it has been acknowledged, whichever comes last; it then moves to the history.
+#[derive(Debug, Default)]
+pub struct AlarmManager {
+    alarms: BTreeMap<AlarmKey, Alarm>,
+    history: VecDeque<Alarm>,
+}
+
+impl AlarmManager {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Raise `key`, returning whether this is news: a new alarm, one that had cleared, or a
+    /// higher severity than it was raised with. Repeats only update the last seen time.
+    pub fn raise(
+        &mut self,
+        key: AlarmKey,
+        severity: Severity,
+        message: impl Into<String>,
+        now: Duration,
+    ) -> bool {
+        let message = message.into();
+
+        let Some(alarm) = self.alarms.get_mut(&key) else {
+            self.alarms.insert(
+                key.clone(),
+                Alarm {
+                    key,
+                    severity,
+                    message,
+                    state: AlarmState::Active,
+                    first_seen: now,
+                    last_seen: now,
+                    occurrences: 1,
+                    acknowledged_at: None,
+                    cleared_at: None,
+                },
+            );
+            return true;
+        };
+
+        alarm.last_seen = now;
+        alarm.occurrences += 1;
+        alarm.message = message;
+
+        let escalated = severity > alarm.severity;
+        let reraised = alarm.state == AlarmState::Cleared;
+        if escalated || reraised {
+            alarm.severity = alarm.severity.max(severity);
+            alarm.state = AlarmState::Active;
+            alarm.acknowledged_at = None;
+            alarm.cleared_at = None;
+        }
+
+        escalated || reraised
+    }
+
+    /// The condition of `key` went away, returning whether it was raised
+    pub fn clear(&mut self, key: &AlarmKey, now: Duration) -> bool {
+        let Some(alarm) = self.alarms.get_mut(key) else {
+            return false;
+        };
+
+        match alarm.state {
+            AlarmState::Active => {
+                alarm.state = AlarmState::Cleared;
+                alarm.cleared_at = Some(now);
+            }
+            AlarmState::Acknowledged => {
+                alarm.cleared_at = Some(now);
+                self.archive(key);
+            }
+            AlarmState::Cleared => return false,
+        }
+        true
+    }
+
+    /// Clear every alarm of a module, e.g. once it is removed
+    pub fn clear_module(&mut self, module_id: u16, now: Duration) {
+        let keys: Vec<AlarmKey> = self
+            .alarms
+            .keys()
+            .filter(|key| key.module_id == module_id)
+            .cloned()
+            .collect();
+
+        for key in keys {
+            self.clear(&key, now);
+        }
+    }
+
+    /// Acknowledge `key`, returning whether it was waiting for acknowledgement
+    pub fn acknowledge(&mut self, key: &AlarmKey, now: Duration) -> bool {
+        let Some(alarm) = self.alarms.get_mut(key) else {
+            return false;
+        };
+
+        match alarm.state {
+            AlarmState::Active => {
+                alarm.state = AlarmState::Acknowledged;
+                alarm.acknowledged_at = Some(now);
+            }
+            AlarmState::Cleared => {
+                alarm.acknowledged_at = Some(now);
+                self.archive(key);
+            }
+            AlarmState::Acknowledged => return false,
+        }
+        true
+    }
+
+    pub fn acknowledge_all(&mut self, now: Duration) {
+        let keys: Vec<AlarmKey> = self.alarms.keys().cloned().collect();
+        for key in keys {
+            self.acknowledge(&key, now);
+        }
+    }
+
+    fn archive(&mut self, key: &AlarmKey) {
+        if let Some(alarm) = self.alarms.remove(key) {
+            if self.history.len() >= HISTORY_LEN {
+                self.history.pop_front();
+            }
+            self.history.push_back(alarm);
+        }
+    }
+
+    pub fn get(&self, key: &AlarmKey) -> Option<&Alarm> {
+        self.alarms.get(key)
+    }
+
+    /// Listed alarms, most severe first, then oldest first
+    pub fn alarms(&self) -> Vec<Alarm> {
+        let mut alarms: Vec<Alarm> = self.alarms.values().cloned().collect();
+        alarms.sort_by(|a, b| {
+            b.severity
+                .cmp(&a.severity)
+                .then(a.first_seen.cmp(&b.first_seen))
+        });
+        alarms
+    }
+
+    /// Alarms whose condition is still present
+    pub fn active_count(&self) -> usize {
+        self.alarms
+            .values()
+            .filter(|alarm| alarm.state != AlarmState::Cleared)
+            .count()
+    }
+
+    /// Alarms waiting for acknowledgement
+    pub fn unacknowledged_count(&self) -> usize {
+        self.alarms
+            .values()
+            .filter(|alarm| alarm.state != AlarmState::Acknowledged)
+            .count()
+    }
+
+    /// Alarms that were both cleared and acknowledged, oldest first
+    pub fn history(&self) -> impl DoubleEndedIterator<Item = &Alarm> {
+        self.history.iter()
+    }
+}
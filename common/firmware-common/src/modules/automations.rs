@@ -0,0 +1,556 @@
+use log::{error, info, warn};
+use serde::{Deserialize, Serialize};
+use std::{collections::VecDeque, fmt, time::Duration};
+use uom::si::{power::watt, ratio::percent};
+
+use crate::{
+    clock::Interval,
+    settings::{ModuleConfig, SettingsError, SettingsStore},
+};
+
+use super::{
+    command_queue::CommandHandle,
+    commands::{
+        battery_module_commands, charger_module_commands, port_module_commands,
+        BatteryModuleCommands, ChargerModuleCommands, PortModuleCommands,
+    },
+    module::submit_command,
+    module_manager::ModuleManager,
+    system_controller::{ModuleEvent, SystemController},
+    telemetry::TelemetryValue,
+};
+
+/// How often automation triggers are checked by default
+pub const DEFAULT_AUTOMATION_PERIOD: Duration = Duration::from_secs(1);
+
+/// How long the command of an automation may wait in the module's queue
+const QUEUE_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// How often the progress of armed automations is saved. A restart loses at most this much.
+const PROGRESS_SAVE_PERIOD: Duration = Duration::from_secs(60);
+
+/// How many runs `AutomationEngine::history` keeps
+const HISTORY_LEN: usize = 100;
+
+/// Automations are not tied to a module, they are stored under this ID
+const SETTINGS_ID: u16 = 0;
+
+/// What makes an automation run. Progress is counted from when the automation was armed, when
+/// it was added or enabled, and carries over restarts; time the system was off does not count.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum AutomationTrigger {
+    /// Some time after the automation was armed
+    Timer { after_s: f64 },
+    /// Once a module delivered this much energy, as integrated from its `power` telemetry
+    Energy { module_id: u16, energy_wh: f64 },
+    /// Once the `charge` of a battery reaches this level, charging
+    ChargeAbove { module_id: u16, charge_percent: f64 },
+    /// Once the `charge` of a battery falls to this level, discharging
+    ChargeBelow { module_id: u16, charge_percent: f64 },
+}
+
+impl fmt::Display for AutomationTrigger {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            AutomationTrigger::Timer { after_s } => write!(f, "after {after_s:.0} s"),
+            AutomationTrigger::Energy {
+                module_id,
+                energy_wh,
+            } => write!(f, "once module {module_id} delivered {energy_wh:.2} Wh"),
+            AutomationTrigger::ChargeAbove {
+                module_id,
+                charge_percent,
+            } => write!(f, "once battery {module_id} is above {charge_percent:.0} %"),
+            AutomationTrigger::ChargeBelow {
+                module_id,
+                charge_percent,
+            } => write!(f, "once battery {module_id} is below {charge_percent:.0} %"),
+        }
+    }
+}
+
+/// A command an automation sends
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum AutomationAction {
+    SetPortOutput { module_id: u16, state: bool },
+    SetBatteryOutput { module_id: u16, state: bool },
+    SetCharging { module_id: u16, enabled: bool },
+}
+
+impl fmt::Display for AutomationAction {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let on_off = |state: bool| if state { "on" } else { "off" };
+        match self {
+            AutomationAction::SetPortOutput { module_id, state } => {
+                write!(f, "turn port {module_id} {}", on_off(*state))
+            }
+            AutomationAction::SetBatteryOutput { module_id, state } => {
+                write!(f, "turn battery {module_id} {}", on_off(*state))
+            }
+            AutomationAction::SetCharging { module_id, enabled } => {
+                write!(f, "turn charging of {module_id} {}", on_off(*enabled))
+            }
+        }
+    }
+}
+
+/// A user rule such as "turn USB-A off after 2 hours"
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Automation {
+    pub name: String,
+    /// Disabled automations are kept but never run. An automation disables itself once it ran.
+    pub enabled: bool,
+    pub trigger: AutomationTrigger,
+    pub action: AutomationAction,
+}
+
+impl Automation {
+    pub fn new(
+        name: impl Into<String>,
+        trigger: AutomationTrigger,
+        action: AutomationAction,
+    ) -> Self {
+        Self {
+            name: name.into(),
+            enabled: true,
+            trigger,
+            action,
+        }
+    }
+}
+
+/// How far an armed automation got towards its trigger
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AutomationProgress {
+    /// Time spent armed, in seconds
+    pub elapsed_s: f64,
+    /// Energy delivered by the module of an `Energy` trigger so far
+    pub energy_wh: f64,
+}
+
+/// Automations as persisted through the settings store
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AutomationSettings {
+    pub automations: Vec<Automation>,
+    /// Progress of each automation, by index. Missing entries start from scratch.
+    pub progress: Vec<AutomationProgress>,
+}
+
+impl ModuleConfig for AutomationSettings {
+    const NAME: &'static str = "auto";
+    const VERSION: u32 = 1;
+}
+
+/// An automation having sent its command
+#[derive(Debug, Clone, PartialEq)]
+pub struct AutomationRun {
+    pub timestamp: Duration,
+    pub automation: String,
+    pub action: AutomationAction,
+    /// Why the command failed, e.g. refused by a safety hold
+    pub result: Result<(), String>,
+}
+
+impl fmt::Display for AutomationRun {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "[{:.3}s] {}: {}",
+            self.timestamp.as_secs_f64(),
+            self.automation,
+            self.action
+        )?;
+        match &self.result {
+            Ok(()) => Ok(()),
+            Err(err) => write!(f, ", failed: {err}"),
+        }
+    }
+}
+
+// How far an armed automation got, and where the counting left off since the system started
+#[derive(Debug, Default)]
+struct Progress {
+    saved: AutomationProgress,
+    /// System clock time the elapsed time was last counted up to
+    last_tick: Option<Duration>,
+    /// Telemetry timestamp the energy was last integrated up to
+    last_sample: Option<Duration>,
+}
+
+impl Progress {
+    fn resume(saved: AutomationProgress) -> Self {
+        Self {
+            saved,
+            ..Self::default()
+        }
+    }
+}
+
+struct PendingRun {
+    automation: String,
+    action: AutomationAction,
+    handle: CommandHandle<()>,
+}
+
+/// Runs the user's automations, and keeps them in the settings store.
+///
+/// Automations are a convenience, not a protection: their commands are submitted to the module
+/// queues like any other, so the interlocks refuse them while the `SafetyPolicy` holds an
+/// output off or locks charging out, and a refused automation is not retried. Safety rules
+/// belong in the policy, which runs before automations in the main loop.
+pub struct AutomationEngine {
+    settings: SettingsStore,
+    automations: Vec<Automation>,
+    /// Progress of each automation, by index
+    progress: Vec<Progress>,
+    interval: Interval,
+    save_interval: Interval,
+    pending: Vec<PendingRun>,
+    history: VecDeque<AutomationRun>,
+}
+
+impl AutomationEngine {
+    /// An engine without automations, saving the ones added to `settings`
+    pub fn new(settings: SettingsStore) -> Self {
+        Self {
+            settings,
+            automations: Vec::new(),
+            progress: Vec::new(),
+            interval: Interval::new(DEFAULT_AUTOMATION_PERIOD, Duration::ZERO),
+            save_interval: Interval::new(PROGRESS_SAVE_PERIOD, PROGRESS_SAVE_PERIOD),
+            pending: Vec::new(),
+            history: VecDeque::with_capacity(HISTORY_LEN),
+        }
+    }
+
+    /// An engine running the automations stored in `settings`, resuming their progress
+    pub fn load(settings: SettingsStore) -> Result<Self, SettingsError> {
+        let stored: AutomationSettings = settings.load(SETTINGS_ID)?;
+        let mut engine = Self::new(settings);
+        engine.progress = (0..stored.automations.len())
+            .map(|index| Progress::resume(stored.progress.get(index).copied().unwrap_or_default()))
+            .collect();
+        engine.automations = stored.automations;
+        Ok(engine)
+    }
+
+    fn save(&self) -> Result<(), SettingsError> {
+        self.settings.save(
+            SETTINGS_ID,
+            &AutomationSettings {
+                automations: self.automations.clone(),
+                progress: self
+                    .progress
+                    .iter()
+                    .map(|progress| progress.saved)
+                    .collect(),
+            },
+        )
+    }
+
+    pub fn automations(&self) -> &[Automation] {
+        &self.automations
+    }
+
+    /// How far the automation at `index` got towards its trigger
+    pub fn progress(&self, index: usize) -> Option<AutomationProgress> {
+        self.progress.get(index).map(|progress| progress.saved)
+    }
+
+    pub fn add(&mut self, automation: Automation) -> Result<(), SettingsError> {
+        self.automations.push(automation);
+        self.progress.push(Progress::default());
+        self.save()
+    }
+
+    pub fn remove(&mut self, index: usize) -> Result<Option<Automation>, SettingsError> {
+        if index >= self.automations.len() {
+            return Ok(None);
+        }
+
+        self.progress.remove(index);
+        let removed = self.automations.remove(index);
+        self.save()?;
+        Ok(Some(removed))
+    }
+
+    /// Enable or disable an automation. Enabling it arms it again from scratch.
+    pub fn set_enabled(&mut self, index: usize, enabled: bool) -> Result<(), SettingsError> {
+        let Some(automation) = self.automations.get_mut(index) else {
+            return Ok(());
+        };
+
+        automation.enabled = enabled;
+        self.progress[index] = Progress::default();
+        self.save()
+    }
+
+    /// Commands sent by automations, oldest first
+    pub fn history(&self) -> impl DoubleEndedIterator<Item = &AutomationRun> {
+        self.history.iter()
+    }
+
+    /// Record the commands that completed, then run the automations whose trigger fired if
+    /// the automation period has elapsed. Meant to be called from the main loop after
+    /// `SafetyPolicy::tick`.
+    ///
+    /// Progress is saved whenever an automation runs, and every `PROGRESS_SAVE_PERIOD`
+    /// otherwise.
+    pub fn tick(
+        &mut self,
+        modules: &mut ModuleManager,
+        system_controller: &SystemController,
+    ) -> bool {
+        self.collect_results(system_controller);
+
+        let now = system_controller.now();
+        if !self.interval.poll(now) {
+            return false;
+        }
+
+        let mut ran = false;
+        let mut armed = false;
+        for (automation, progress) in self.automations.iter_mut().zip(self.progress.iter_mut()) {
+            if !automation.enabled {
+                continue;
+            }
+
+            armed = true;
+            if let Some(last) = progress.last_tick {
+                progress.saved.elapsed_s += now.saturating_sub(last).as_secs_f64();
+            }
+            progress.last_tick = Some(now);
+
+            let due = match automation.trigger {
+                AutomationTrigger::Timer { after_s } => progress.saved.elapsed_s >= after_s,
+                AutomationTrigger::Energy {
+                    module_id,
+                    energy_wh,
+                } => {
+                    if let Some(report) = modules.telemetry(module_id) {
+                        if let Some(TelemetryValue::Power(power)) =
+                            report.get("power").map(|entry| entry.value)
+                        {
+                            if let Some(last) = progress.last_sample {
+                                let elapsed = report.timestamp.saturating_sub(last);
+                                progress.saved.energy_wh +=
+                                    power.get::<watt>() * elapsed.as_secs_f64() / 3600.0;
+                            }
+                            progress.last_sample = Some(report.timestamp);
+                        }
+                    }
+                    progress.saved.energy_wh >= energy_wh
+                }
+                AutomationTrigger::ChargeAbove {
+                    module_id,
+                    charge_percent,
+                } => charge(modules, module_id).is_some_and(|charge| charge >= charge_percent),
+                AutomationTrigger::ChargeBelow {
+                    module_id,
+                    charge_percent,
+                } => charge(modules, module_id).is_some_and(|charge| charge <= charge_percent),
+            };
+            if !due {
+                continue;
+            }
+
+            info!(
+                "Automation `{}` ({}): {}",
+                automation.name, automation.trigger, automation.action
+            );
+            let handle = match automation.action {
+                AutomationAction::SetPortOutput { module_id, state } => submit_command!(
+                    modules,
+                    module_id: PortModuleCommands,
+                    PortModuleCommands::SetOutput { state },
+                    queue_timeout = QUEUE_TIMEOUT
+                ),
+                AutomationAction::SetBatteryOutput { module_id, state } => submit_command!(
+                    modules,
+                    module_id: BatteryModuleCommands,
+                    BatteryModuleCommands::SetOutput { state },
+                    queue_timeout = QUEUE_TIMEOUT
+                ),
+                AutomationAction::SetCharging { module_id, enabled } => submit_command!(
+                    modules,
+                    module_id: ChargerModuleCommands,
+                    ChargerModuleCommands::SetChargingEnabled { enabled },
+                    queue_timeout = QUEUE_TIMEOUT
+                ),
+            };
+            self.pending.push(PendingRun {
+                automation: automation.name.clone(),
+                action: automation.action,
+                handle,
+            });
+
+            automation.enabled = false;
+            *progress = Progress::default();
+            ran = true;
+        }
+
+        let save_progress = self.save_interval.poll(now) && armed;
+        if ran || save_progress {
+            if let Err(err) = self.save() {
+                error!("Unable to save automations: {}", err);
+            }
+        }
+        true
+    }
+
+    fn collect_results(&mut self, system_controller: &SystemController) {
+        let mut index = 0;
+        while index < self.pending.len() {
+            let Some(result) = self.pending[index].handle.try_take() else {
+                index += 1;
+                continue;
+            };
+            let run = self.pending.swap_remove(index);
+
+            let result = result.map_err(|err| err.to_string());
+            match &result {
+                Ok(()) => system_controller.emit_event(ModuleEvent::Info(format!(
+                    "Automation `{}`: {}",
+                    run.automation, run.action
+                ))),
+                Err(err) => {
+                    warn!("Automation `{}` failed: {}", run.automation, err);
+                    system_controller.emit_event(ModuleEvent::Warning(format!(
+                        "Automation `{}` could not {}: {}",
+                        run.automation, run.action, err
+                    )));
+                }
+            }
+
+            if self.history.len() >= HISTORY_LEN {
+                self.history.pop_front();
+            }
+            self.history.push_back(AutomationRun {
+                timestamp: system_controller.now(),
+                automation: run.automation,
+                action: run.action,
+                result,
+            });
+        }
+    }
+}
+
+/// Charge of a battery in percent, from its telemetry
+fn charge(modules: &ModuleManager, module_id: u16) -> Option<f64> {
+    match modules
+        .telemetry(module_id)?
+        .get("charge")
+        .map(|entry| entry.value)
+    {
+        Some(TelemetryValue::Ratio(charge)) => Some(charge.get::<percent>()),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::{
+        clock::ManualClock,
+        modules::{
+            commands::MockPort,
+            mock::testing::{self, mock_port},
+            system_controller::CriticalEvent,
+        },
+    };
+
+    fn setup() -> (Arc<ManualClock>, Arc<SystemController>, ModuleManager, u16) {
+        let (clock, controller, mut modules) = testing::setup();
+        let mut port = mock_port(1);
+        port.expect_set_output().returning(());
+        let id = modules.register_module(port, controller.clone()).unwrap();
+        (clock, controller, modules, id)
+    }
+
+    #[test]
+    fn interlocked_automation_fails_and_is_not_retried() {
+        let (clock, controller, mut modules, port) = setup();
+        let mut automations = AutomationEngine::new(controller.settings().clone());
+        automations
+            .add(Automation::new(
+                "Port on",
+                AutomationTrigger::Timer { after_s: 0.0 },
+                AutomationAction::SetPortOutput {
+                    module_id: port,
+                    state: true,
+                },
+            ))
+            .unwrap();
+        controller.raise_alarm(
+            port,
+            "current",
+            ModuleEvent::Critical(CriticalEvent::ModuleFailure("shorted".into())),
+        );
+
+        assert!(automations.tick(&mut modules, &controller));
+        modules.process_queue();
+        clock.advance(DEFAULT_AUTOMATION_PERIOD);
+        automations.tick(&mut modules, &controller);
+
+        let runs: Vec<_> = automations.history().collect();
+        assert_eq!(runs.len(), 1);
+        assert!(matches!(&runs[0].result, Err(err) if err.starts_with("Refused")));
+        assert!(!automations.automations()[0].enabled);
+        assert!(modules
+            .get_module::<MockPort>(port)
+            .unwrap()
+            .calls()
+            .is_empty());
+
+        // Clearing the alarm does not run it again
+        controller.clear_alarm(port, "current");
+        clock.advance(DEFAULT_AUTOMATION_PERIOD);
+        automations.tick(&mut modules, &controller);
+        modules.process_queue();
+        assert_eq!(automations.history().count(), 1);
+    }
+
+    #[test]
+    fn timer_progress_survives_a_restart() {
+        let (clock, controller, mut modules, port) = setup();
+        let mut automations = AutomationEngine::new(controller.settings().clone());
+        automations
+            .add(Automation::new(
+                "Port off",
+                AutomationTrigger::Timer { after_s: 300.0 },
+                AutomationAction::SetPortOutput {
+                    module_id: port,
+                    state: false,
+                },
+            ))
+            .unwrap();
+
+        automations.tick(&mut modules, &controller);
+        clock.advance(PROGRESS_SAVE_PERIOD * 3);
+        automations.tick(&mut modules, &controller);
+
+        // Time the system is off does not count
+        clock.advance(Duration::from_secs(3600));
+        let mut automations = AutomationEngine::load(controller.settings().clone()).unwrap();
+        let progress = automations.progress(0).unwrap();
+        assert_eq!(progress.elapsed_s, 180.0);
+
+        automations.tick(&mut modules, &controller);
+        clock.advance(Duration::from_secs(119));
+        automations.tick(&mut modules, &controller);
+        assert!(automations.automations()[0].enabled);
+
+        clock.advance(DEFAULT_AUTOMATION_PERIOD);
+        automations.tick(&mut modules, &controller);
+        modules.process_queue();
+        automations.tick(&mut modules, &controller);
+        assert!(!automations.automations()[0].enabled);
+        assert_eq!(automations.history().next().unwrap().result, Ok(()));
+    }
+}
@@ -0,0 +1,361 @@
+use serde::{Deserialize, Serialize};
+use std::{collections::VecDeque, fmt, time::Duration};
+use uom::si::electric_potential::{millivolt, volt};
+use uom::si::f64::{ElectricPotential, ThermodynamicTemperature};
+use uom::si::thermodynamic_temperature::degree_celsius;
+
+use crate::{
+    comms::{
+        codec::{CodecError, WireCodec},
+        schema::{FieldSchema, WireType},
+    },
+    modules::descriptor::{CommandValue, Value, ValueError, ValueKind},
+    settings::ModuleConfig,
+};
+
+/// How many decisions `BalancingEngine::history` keeps
+const HISTORY_LEN: usize = 100;
+
+/// Tuning of the balancing engine, persisted through the settings store
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct BalancingConfig {
+    pub enabled: bool,
+
+    /// A cell starts balancing once it is this far above the lowest cell, in millivolts
+    pub start_delta_mv: f64,
+    /// A balancing cell stops once it is within this of the lowest cell, in millivolts
+    pub stop_delta_mv: f64,
+
+    /// No balancing while the lowest cell is below this, as voltages spread naturally near
+    /// empty and balancing there only wastes charge, in volts
+    pub min_cell_voltage_v: f64,
+
+    /// A cell hotter than this is never balanced, in °C
+    pub max_cell_temperature_c: f64,
+    /// Balancing stops for the whole pack when any cell is hotter than this, in °C
+    pub pack_thermal_limit_c: f64,
+    /// How far below the pack thermal limit every cell must cool before balancing resumes, in °C
+    pub thermal_hysteresis_c: f64,
+
+    /// Most cells balanced at the same time, to bound the heat dissipated by the balancer
+    pub max_balancing_cells: usize,
+}
+
+impl Default for BalancingConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            start_delta_mv: 30.0,
+            stop_delta_mv: 10.0,
+            min_cell_voltage_v: 3.5,
+            max_cell_temperature_c: 45.0,
+            pack_thermal_limit_c: 50.0,
+            thermal_hysteresis_c: 5.0,
+            max_balancing_cells: 2,
+        }
+    }
+}
+
+impl ModuleConfig for BalancingConfig {
+    const NAME: &'static str = "bal";
+    const VERSION: u32 = 1;
+}
+
+/// What the balancer knows about one cell
+#[derive(Debug, Clone, Copy)]
+pub struct CellReading {
+    pub voltage: ElectricPotential,
+    pub temperature: ThermodynamicTemperature,
+}
+
+impl WireCodec for CellReading {
+    fn encode(&self, out: &mut Vec<u8>) -> Result<(), CodecError> {
+        self.voltage.encode(out)?;
+        self.temperature.encode(out)
+    }
+
+    fn decode(input: &mut &[u8]) -> Result<Self, CodecError> {
+        Ok(Self {
+            voltage: ElectricPotential::decode(input)?,
+            temperature: ThermodynamicTemperature::decode(input)?,
+        })
+    }
+
+    fn wire_type() -> WireType {
+        WireType::Struct {
+            name: "CellReading",
+            fields: vec![
+                FieldSchema::new("voltage", ElectricPotential::wire_type()),
+                FieldSchema::new("temperature", ThermodynamicTemperature::wire_type()),
+            ],
+        }
+    }
+}
+
+impl CommandValue for CellReading {
+    fn kind() -> ValueKind {
+        ValueKind::Opaque("CellReading")
+    }
+
+    fn to_value(&self) -> Value {
+        Value::Text(format!(
+            "{:.3} V, {:.1} °C",
+            self.voltage.get::<volt>(),
+            self.temperature.get::<degree_celsius>()
+        ))
+    }
+
+    fn from_value(_value: Value) -> Result<Self, ValueError> {
+        Err(ValueError::NotAnArgument("CellReading"))
+    }
+}
+
+/// Why the engine decided what it did
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BalancingReason {
+    Disabled,
+    /// All cells are within the thresholds
+    Balanced,
+    /// Some cells are above the start threshold (or still above the stop threshold)
+    Balancing,
+    /// More cells need balancing than `max_balancing_cells`, the highest ones were picked
+    LimitedByMaxCells,
+    /// The lowest cell is below `min_cell_voltage_v`
+    BelowMinimumVoltage,
+    /// A cell is above `pack_thermal_limit_c`, or the pack has not cooled down yet
+    ThermalLimit,
+    /// No readings to decide on
+    NoData,
+}
+
+impl fmt::Display for BalancingReason {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            BalancingReason::Disabled => write!(f, "disabled"),
+            BalancingReason::Balanced => write!(f, "cells balanced"),
+            BalancingReason::Balancing => write!(f, "balancing"),
+            BalancingReason::LimitedByMaxCells => write!(f, "balancing, limited by max cells"),
+            BalancingReason::BelowMinimumVoltage => write!(f, "lowest cell below minimum voltage"),
+            BalancingReason::ThermalLimit => write!(f, "pack thermal limit"),
+            BalancingReason::NoData => write!(f, "no cell data"),
+        }
+    }
+}
+
+/// Output of one evaluation: which cells to balance and why
+#[derive(Debug, Clone, PartialEq)]
+pub struct BalancingDecision {
+    pub timestamp: Duration,
+    /// One flag per cell, `true` for the cells to balance
+    pub balance: Vec<bool>,
+    /// Difference between the highest and the lowest cell
+    pub spread: ElectricPotential,
+    pub reason: BalancingReason,
+}
+
+impl BalancingDecision {
+    pub fn balancing_cells(&self) -> impl Iterator<Item = usize> + '_ {
+        self.balance
+            .iter()
+            .enumerate()
+            .filter(|(_, balance)| **balance)
+            .map(|(cell, _)| cell)
+    }
+
+    /// Whether `other` commands the same cells for the same reason
+    fn same_outcome(&self, other: &BalancingDecision) -> bool {
+        self.balance == other.balance && self.reason == other.reason
+    }
+}
+
+impl fmt::Display for BalancingDecision {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let cells: Vec<String> = self
+            .balancing_cells()
+            .map(|cell| (cell + 1).to_string())
+            .collect();
+
+        write!(
+            f,
+            "Balancing {}: {} (spread {:.0} mV)",
+            if cells.is_empty() {
+                "off".to_string()
+            } else {
+                format!("cells {}", cells.join(", "))
+            },
+            self.reason,
+            self.spread.get::<millivolt>()
+        )
+    }
+}
+
+impl CommandValue for BalancingDecision {
+    fn kind() -> ValueKind {
+        ValueKind::Opaque("BalancingDecision")
+    }
+
+    fn to_value(&self) -> Value {
+        Value::Text(self.to_string())
+    }
+
+    fn from_value(_value: Value) -> Result<Self, ValueError> {
+        Err(ValueError::NotAnArgument("BalancingDecision"))
+    }
+}
+
+/// Decides which cells to balance from per-cell voltages and temperatures.
+///
+/// A cell starts balancing when it is `start_delta_mv` above the lowest cell and keeps
+/// balancing until it is within `stop_delta_mv`, so that cells do not chatter around a single
+/// threshold. Hot cells are skipped, and the whole pack pauses above the thermal limit.
+pub struct BalancingEngine {
+    config: BalancingConfig,
+    current: Option<BalancingDecision>,
+    thermal_paused: bool,
+    history: VecDeque<BalancingDecision>,
+}
+
+impl BalancingEngine {
+    pub fn new(config: BalancingConfig) -> Self {
+        Self {
+            config,
+            current: None,
+            thermal_paused: false,
+            history: VecDeque::with_capacity(HISTORY_LEN),
+        }
+    }
+
+    pub fn config(&self) -> &BalancingConfig {
+        &self.config
+    }
+
+    pub fn set_config(&mut self, config: BalancingConfig) {
+        self.config = config;
+    }
+
+    /// The decision currently in force
+    pub fn decision(&self) -> Option<&BalancingDecision> {
+        self.current.as_ref()
+    }
+
+    /// Decisions that changed the balancing outcome, oldest first
+    pub fn history(&self) -> impl DoubleEndedIterator<Item = &BalancingDecision> {
+        self.history.iter()
+    }
+
+    /// Evaluate fresh cell readings.
+    ///
+    /// Returns the decision if it differs from the previous one, so that callers only log and
+    /// act on changes.
+    pub fn evaluate(&mut self, cells: &[CellReading], now: Duration) -> Option<&BalancingDecision> {
+        let decision = self.decide(cells, now);
+
+        let changed = self
+            .current
+            .as_ref()
+            .is_none_or(|current| !current.same_outcome(&decision));
+
+        if changed {
+            if self.history.len() >= HISTORY_LEN {
+                self.history.pop_front();
+            }
+            self.history.push_back(decision.clone());
+        }
+
+        self.current = Some(decision);
+        changed.then_some(self.current.as_ref()).flatten()
+    }
+
+    fn decide(&mut self, cells: &[CellReading], now: Duration) -> BalancingDecision {
+        let config = &self.config;
+        let voltages: Vec<f64> = cells
+            .iter()
+            .map(|cell| cell.voltage.get::<volt>())
+            .collect();
+        let temperatures: Vec<f64> = cells
+            .iter()
+            .map(|cell| cell.temperature.get::<degree_celsius>())
+            .collect();
+
+        let lowest = voltages.iter().copied().fold(f64::INFINITY, f64::min);
+        let highest = voltages.iter().copied().fold(f64::NEG_INFINITY, f64::max);
+        let hottest = temperatures
+            .iter()
+            .copied()
+            .fold(f64::NEG_INFINITY, f64::max);
+
+        let mut decision = BalancingDecision {
+            timestamp: now,
+            balance: vec![false; cells.len()],
+            spread: ElectricPotential::new::<volt>(if cells.is_empty() {
+                0.0
+            } else {
+                highest - lowest
+            }),
+            reason: BalancingReason::Balanced,
+        };
+
+        // Thermal hysteresis applies whether or not balancing is enabled
+        if hottest > config.pack_thermal_limit_c {
+            self.thermal_paused = true;
+        } else if hottest < config.pack_thermal_limit_c - config.thermal_hysteresis_c {
+            self.thermal_paused = false;
+        }
+
+        if !config.enabled {
+            decision.reason = BalancingReason::Disabled;
+            return decision;
+        }
+        if cells.is_empty() {
+            decision.reason = BalancingReason::NoData;
+            return decision;
+        }
+        if self.thermal_paused {
+            decision.reason = BalancingReason::ThermalLimit;
+            return decision;
+        }
+        if lowest < config.min_cell_voltage_v {
+            decision.reason = BalancingReason::BelowMinimumVoltage;
+            return decision;
+        }
+
+        let was_balancing = |cell: usize| {
+            self.current
+                .as_ref()
+                .is_some_and(|current| current.balance.get(cell) == Some(&true))
+        };
+
+        let mut candidates: Vec<(usize, f64)> = voltages
+            .iter()
+            .enumerate()
+            .map(|(cell, voltage)| (cell, (voltage - lowest) * 1000.0))
+            .filter(|(cell, delta_mv)| {
+                let threshold = if was_balancing(*cell) {
+                    config.stop_delta_mv
+                } else {
+                    config.start_delta_mv
+                };
+                *delta_mv > threshold && temperatures[*cell] <= config.max_cell_temperature_c
+            })
+            .collect();
+
+        if candidates.is_empty() {
+            return decision;
+        }
+
+        // Highest cells first
+        candidates.sort_by(|a, b| b.1.total_cmp(&a.1));
+        decision.reason = if candidates.len() > config.max_balancing_cells {
+            BalancingReason::LimitedByMaxCells
+        } else {
+            BalancingReason::Balancing
+        };
+
+        for (cell, _) in candidates.into_iter().take(config.max_balancing_cells) {
+            decision.balance[cell] = true;
+        }
+
+        decision
+    }
+}
@@ -1,6 +1,17 @@
+use serde::{Deserialize, Serialize};
+use toml::{Table, Value};
 use uom::si::f64::{ElectricCurrent, ElectricPotential, ThermodynamicTemperature};
 
+use crate::{
+    modules::{
+        derating::DeratingCurve,
+        thresholds::{Limit, ThresholdProfile},
+    },
+    settings::{ModuleConfig, SettingsError},
+};
+
 /// Represents the state of a battery module.
+#[derive(Debug, Clone)]
 pub struct BatteryData {
     /// Battery charge level in percentage (0-100)%.
     ///
@@ -22,3 +33,80 @@ pub struct BatteryData {
     /// Determines if the battery is supplying power (`true = enabled`).
     pub output_enabled: bool,
 }
+
+/// User-tunable configuration of a battery module, persisted through the settings store.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct BatteryConfig {
+    /// User-facing name (e.g. "Left pack"), empty if unnamed
+    pub name: String,
+
+    /// Warning and error limits of the pack current (A, either direction), temperature (°C),
+    /// voltage (V) and charge (%)
+    pub thresholds: ThresholdProfile,
+
+    /// Charge level at which the output is switched off to protect the cells, in percent
+    pub output_cutoff_pct: u8,
+
+    /// Offset added to the raw voltage reading, in volts
+    pub voltage_offset_v: f64,
+    /// Gain applied to the raw current reading
+    pub current_gain: f64,
+    /// Fraction of the current warning threshold the pack may supply at a given temperature
+    pub discharge_derating: DeratingCurve,
+}
+
+impl Default for BatteryConfig {
+    fn default() -> Self {
+        Self {
+            name: String::new(),
+            thresholds: ThresholdProfile::default(),
+            output_cutoff_pct: 10,
+            voltage_offset_v: 0.0,
+            current_gain: 1.0,
+            discharge_derating: DeratingCurve::default_discharge(),
+        }
+    }
+}
+
+impl ModuleConfig for BatteryConfig {
+    const NAME: &'static str = "bat";
+    const VERSION: u32 = 2;
+
+    fn migrate(from_version: u32, mut config: Table) -> Result<Table, SettingsError> {
+        match from_version {
+            // Version 1 had fixed limits, which became the threshold profile
+            1 => {
+                let mut profile = ThresholdProfile::li_ion_5s();
+                for (field, key, limit) in [
+                    ("high_current_warning_a", "current", Limit::HighWarning),
+                    ("overcurrent_a", "current", Limit::HighError),
+                    (
+                        "high_temperature_warning_c",
+                        "temperature",
+                        Limit::HighWarning,
+                    ),
+                    ("overheat_c", "temperature", Limit::HighError),
+                    ("undervoltage_v", "voltage", Limit::LowError),
+                    ("overvoltage_v", "voltage", Limit::HighError),
+                    ("low_charge_warning_pct", "charge", Limit::LowWarning),
+                ] {
+                    let value = config.remove(field).and_then(|value| match value {
+                        Value::Float(value) => Some(value),
+                        Value::Integer(value) => Some(value as f64),
+                        _ => None,
+                    });
+
+                    if let (Some(value), Some(threshold)) = (value, profile.thresholds.get_mut(key))
+                    {
+                        *threshold.limit_mut(limit) = Some(value);
+                    }
+                }
+
+                config.insert("thresholds".into(), Value::try_from(profile)?);
+                Ok(config)
+            }
+            _ => Err(SettingsError::MissingMigration(from_version)),
+        }
+    }
+}
@@ -0,0 +1,484 @@
+use serde::{Deserialize, Serialize};
+use std::{fmt, time::Duration};
+use uom::si::electric_current::ampere;
+use uom::si::electric_potential::volt;
+use uom::si::f64::{ElectricCurrent, ElectricPotential, ThermodynamicTemperature};
+use uom::si::thermodynamic_temperature::degree_celsius;
+
+use crate::{
+    modules::{
+        derating::DeratingCurve,
+        descriptor::{CommandValue, Value, ValueError, ValueKind},
+    },
+    settings::ModuleConfig,
+};
+
+/// How long the charge current must stay below the termination current before the charge
+/// is considered complete, so that a load transient does not end it early
+const TERMINATION_DEBOUNCE: Duration = Duration::from_secs(5);
+
+/// How close to the CV voltage the pack must get before switching from CC to CV, in volts
+const CV_ENTRY_MARGIN_V: f64 = 0.05;
+
+/// Charge profile of the pack, persisted through the settings store.
+///
+/// Defaults fit the 5S Li-ion pack of the battery module.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ChargerConfig {
+    /// Below this pack voltage the pack is deeply discharged and pre-charged gently
+    pub precharge_threshold_v: f64,
+    pub precharge_current_a: f64,
+
+    /// Current of the constant current phase
+    pub charge_current_a: f64,
+    /// Voltage of the constant voltage phase
+    pub cv_voltage_v: f64,
+    /// The charge ends once the current drops below this in the CV phase
+    pub termination_current_a: f64,
+    /// A completed charge restarts once the pack drops below this voltage
+    pub recharge_voltage_v: f64,
+    /// Pack voltage that aborts the charge
+    pub overvoltage_v: f64,
+
+    /// Charging is only allowed within this pack temperature window
+    pub min_temperature_c: f64,
+    pub max_temperature_c: f64,
+    /// How far back inside the window the temperature must come before charging resumes
+    pub temperature_hysteresis_c: f64,
+
+    /// Longest allowed pre-charge, in minutes
+    pub precharge_timeout_min: f64,
+    /// Longest allowed charge from start to termination, in minutes
+    pub charge_timeout_min: f64,
+
+    /// Fraction of the pre-charge and charge currents allowed at a given pack temperature
+    pub charge_derating: DeratingCurve,
+}
+
+impl Default for ChargerConfig {
+    fn default() -> Self {
+        Self {
+            precharge_threshold_v: 15.0,
+            precharge_current_a: 0.3,
+            charge_current_a: 2.0,
+            cv_voltage_v: 21.0,
+            termination_current_a: 0.1,
+            recharge_voltage_v: 20.5,
+            overvoltage_v: 21.4,
+            min_temperature_c: 0.0,
+            max_temperature_c: 45.0,
+            temperature_hysteresis_c: 3.0,
+            precharge_timeout_min: 30.0,
+            charge_timeout_min: 600.0,
+            charge_derating: DeratingCurve::default_charge(),
+        }
+    }
+}
+
+impl ModuleConfig for ChargerConfig {
+    const NAME: &'static str = "chg";
+    const VERSION: u32 = 1;
+}
+
+/// Why a charge was aborted. Faults latch until the input is unplugged or the fault is reset.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChargeFault {
+    /// The pack did not leave pre-charge in time, it is likely damaged
+    PreChargeTimeout,
+    /// The charge did not terminate in time
+    ChargeTimeout,
+    /// The pack voltage exceeded `ChargerConfig::overvoltage_v`
+    Overvoltage,
+    /// The charger hardware stopped responding or reported an error
+    Hardware,
+}
+
+impl fmt::Display for ChargeFault {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ChargeFault::PreChargeTimeout => write!(f, "Pre-charge timeout"),
+            ChargeFault::ChargeTimeout => write!(f, "Charge timeout"),
+            ChargeFault::Overvoltage => write!(f, "Pack overvoltage"),
+            ChargeFault::Hardware => write!(f, "Charger hardware failure"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChargePhase {
+    /// Nothing plugged into the input, or charging disabled
+    Idle,
+    /// Low current charge of a deeply discharged pack
+    PreCharge,
+    ConstantCurrent,
+    ConstantVoltage,
+    /// Terminated, waiting for the pack to drop below the recharge voltage
+    Complete,
+    /// Pack temperature outside the charging window
+    TemperaturePaused,
+    Fault(ChargeFault),
+}
+
+impl ChargePhase {
+    pub fn name(&self) -> &'static str {
+        match self {
+            ChargePhase::Idle => "Idle",
+            ChargePhase::PreCharge => "Pre-charge",
+            ChargePhase::ConstantCurrent => "CC",
+            ChargePhase::ConstantVoltage => "CV",
+            ChargePhase::Complete => "Complete",
+            ChargePhase::TemperaturePaused => "Temperature paused",
+            ChargePhase::Fault(_) => "Fault",
+        }
+    }
+
+    /// Whether the pack is being charged in this phase
+    pub fn is_charging(&self) -> bool {
+        matches!(
+            self,
+            ChargePhase::PreCharge | ChargePhase::ConstantCurrent | ChargePhase::ConstantVoltage
+        )
+    }
+}
+
+impl fmt::Display for ChargePhase {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ChargePhase::Fault(fault) => write!(f, "Fault: {fault}"),
+            _ => write!(f, "{}", self.name()),
+        }
+    }
+}
+
+impl CommandValue for ChargePhase {
+    fn kind() -> ValueKind {
+        ValueKind::Opaque("ChargePhase")
+    }
+
+    fn to_value(&self) -> Value {
+        Value::Text(self.to_string())
+    }
+
+    fn from_value(_value: Value) -> Result<Self, ValueError> {
+        Err(ValueError::NotAnArgument("ChargePhase"))
+    }
+}
+
+/// What the charger hardware measures
+#[derive(Debug, Clone, Copy)]
+pub struct ChargeMeasurements {
+    /// Whether a power source is plugged into the input
+    pub input_present: bool,
+    /// Whether a battery pack is connected to charge
+    pub pack_present: bool,
+    pub pack_voltage: ElectricPotential,
+    /// Current into the pack, positive while charging
+    pub pack_current: ElectricCurrent,
+    pub pack_temperature: ThermodynamicTemperature,
+}
+
+/// Limits the charger power stage regulates to; the pack takes whichever is reached first
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ChargeSetpoint {
+    pub voltage_limit: ElectricPotential,
+    pub current_limit: ElectricCurrent,
+}
+
+/// CC/CV charge state machine.
+///
+/// The controller holds no hardware: it is fed measurements with `step` and returns the
+/// setpoint the power stage should regulate to (`None` switches the power stage off), which
+/// lets the same logic drive a real input module and a simulated charger.
+pub struct ChargeController {
+    config: ChargerConfig,
+    enabled: bool,
+    phase: ChargePhase,
+    /// When the current phase was entered
+    phase_started: Duration,
+    /// Time spent charging since the charge started, pauses excluded
+    charge_time: Duration,
+    /// Since when the current has been below the termination current
+    below_termination_since: Option<Duration>,
+    last_step: Option<Duration>,
+    /// Fraction of the nominal current allowed at the last measured pack temperature
+    derating: f64,
+}
+
+impl ChargeController {
+    pub fn new(config: ChargerConfig) -> Self {
+        Self {
+            config,
+            enabled: true,
+            phase: ChargePhase::Idle,
+            phase_started: Duration::ZERO,
+            charge_time: Duration::ZERO,
+            below_termination_since: None,
+            last_step: None,
+            derating: 1.0,
+        }
+    }
+
+    pub fn phase(&self) -> ChargePhase {
+        self.phase
+    }
+
+    /// Time spent in the current phase
+    pub fn phase_duration(&self, now: Duration) -> Duration {
+        now.saturating_sub(self.phase_started)
+    }
+
+    /// Time spent charging since the charge started, pauses excluded
+    pub fn charge_time(&self) -> Duration {
+        self.charge_time
+    }
+
+    pub fn config(&self) -> &ChargerConfig {
+        &self.config
+    }
+
+    pub fn set_config(&mut self, config: ChargerConfig) {
+        self.config = config;
+    }
+
+    /// Fraction of the nominal charge current currently allowed by `charge_derating`
+    pub fn derating(&self) -> f64 {
+        self.derating
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+
+    /// Allow or forbid charging. Disabling stops any charge in progress, but leaves a latched
+    /// fault in place.
+    pub fn set_enabled(&mut self, enabled: bool) {
+        self.enabled = enabled;
+    }
+
+    /// Clear a latched fault; the next `step` qualifies the pack again
+    pub fn reset_fault(&mut self, now: Duration) {
+        if let ChargePhase::Fault(_) = self.phase {
+            self.enter(ChargePhase::Idle, now);
+        }
+    }
+
+    /// Abort the charge, e.g. when the hardware stops responding
+    pub fn fault(&mut self, fault: ChargeFault, now: Duration) {
+        self.enter(ChargePhase::Fault(fault), now);
+    }
+
+    /// Setpoint for the current phase, derated for the pack temperature
+    pub fn setpoint(&self) -> Option<ChargeSetpoint> {
+        let current = match self.phase {
+            ChargePhase::PreCharge => self.config.precharge_current_a,
+            ChargePhase::ConstantCurrent | ChargePhase::ConstantVoltage => {
+                self.config.charge_current_a
+            }
+            _ => return None,
+        };
+
+        Some(ChargeSetpoint {
+            voltage_limit: ElectricPotential::new::<volt>(self.config.cv_voltage_v),
+            current_limit: ElectricCurrent::new::<ampere>(current * self.derating),
+        })
+    }
+
+    /// Advance the state machine with fresh measurements taken at `now`
+    pub fn step(
+        &mut self,
+        measurements: &ChargeMeasurements,
+        now: Duration,
+    ) -> Option<ChargeSetpoint> {
+        let elapsed = self
+            .last_step
+            .map_or(Duration::ZERO, |last| now.saturating_sub(last));
+        self.last_step = Some(now);
+
+        if self.phase.is_charging() {
+            self.charge_time += elapsed;
+        }
+
+        self.derating = self
+            .config
+            .charge_derating
+            .factor_at(measurements.pack_temperature.get::<degree_celsius>());
+
+        let next = self.next_phase(measurements, now);
+        if next != self.phase {
+            self.enter(next, now);
+        }
+
+        self.setpoint()
+    }
+
+    fn next_phase(&mut self, measurements: &ChargeMeasurements, now: Duration) -> ChargePhase {
+        let config = &self.config;
+        let voltage = measurements.pack_voltage.get::<volt>();
+        let current = measurements.pack_current.get::<ampere>();
+        let temperature = measurements.pack_temperature.get::<degree_celsius>();
+
+        // Unplugging the input or the pack ends the charge and clears any fault
+        if !measurements.input_present || !measurements.pack_present {
+            return ChargePhase::Idle;
+        }
+
+        // Disabling charging does not clear a fault, only `reset_fault` does
+        if let ChargePhase::Fault(_) = self.phase {
+            return self.phase;
+        }
+
+        if !self.enabled {
+            return ChargePhase::Idle;
+        }
+
+        if voltage > config.overvoltage_v {
+            return ChargePhase::Fault(ChargeFault::Overvoltage);
+        }
+
+        let in_window = match self.phase {
+            ChargePhase::TemperaturePaused => {
+                temperature >= config.min_temperature_c + config.temperature_hysteresis_c
+                    && temperature <= config.max_temperature_c - config.temperature_hysteresis_c
+            }
+            _ => temperature >= config.min_temperature_c && temperature <= config.max_temperature_c,
+        };
+        if !in_window {
+            return ChargePhase::TemperaturePaused;
+        }
+
+        if self.phase.is_charging()
+            && self.charge_time.as_secs_f64() > config.charge_timeout_min * 60.0
+        {
+            return ChargePhase::Fault(ChargeFault::ChargeTimeout);
+        }
+
+        match self.phase {
+            ChargePhase::Idle | ChargePhase::TemperaturePaused => self.qualify(voltage),
+            ChargePhase::PreCharge => {
+                if voltage >= config.precharge_threshold_v {
+                    ChargePhase::ConstantCurrent
+                } else if self.phase_duration(now).as_secs_f64()
+                    > config.precharge_timeout_min * 60.0
+                {
+                    ChargePhase::Fault(ChargeFault::PreChargeTimeout)
+                } else {
+                    ChargePhase::PreCharge
+                }
+            }
+            ChargePhase::ConstantCurrent => {
+                if voltage >= config.cv_voltage_v - CV_ENTRY_MARGIN_V {
+                    ChargePhase::ConstantVoltage
+                } else {
+                    ChargePhase::ConstantCurrent
+                }
+            }
+            ChargePhase::ConstantVoltage => {
+                // A current held down by derating says nothing about the pack being full
+                let derated_limit = config.charge_current_a * self.derating;
+                if current > config.termination_current_a
+                    || derated_limit <= config.termination_current_a
+                {
+                    self.below_termination_since = None;
+                    return ChargePhase::ConstantVoltage;
+                }
+
+                let since = *self.below_termination_since.get_or_insert(now);
+                if now.saturating_sub(since) >= TERMINATION_DEBOUNCE {
+                    ChargePhase::Complete
+                } else {
+                    ChargePhase::ConstantVoltage
+                }
+            }
+            ChargePhase::Complete => {
+                if voltage < config.recharge_voltage_v {
+                    self.qualify(voltage)
+                } else {
+                    ChargePhase::Complete
+                }
+            }
+            ChargePhase::Fault(_) => self.phase,
+        }
+    }
+
+    /// Pick the phase a charge starts in
+    fn qualify(&self, voltage: f64) -> ChargePhase {
+        if voltage < self.config.precharge_threshold_v {
+            ChargePhase::PreCharge
+        } else {
+            ChargePhase::ConstantCurrent
+        }
+    }
+
+    fn enter(&mut self, phase: ChargePhase, now: Duration) {
+        // A new charge starts whenever charging resumes from anything but a temperature pause
+        if phase.is_charging()
+            && !self.phase.is_charging()
+            && self.phase != ChargePhase::TemperaturePaused
+        {
+            self.charge_time = Duration::ZERO;
+        }
+
+        self.phase = phase;
+        self.phase_started = now;
+        self.below_termination_since = None;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn measurements(pack_voltage_v: f64) -> ChargeMeasurements {
+        ChargeMeasurements {
+            input_present: true,
+            pack_present: true,
+            pack_voltage: ElectricPotential::new::<volt>(pack_voltage_v),
+            pack_current: ElectricCurrent::new::<ampere>(0.0),
+            pack_temperature: ThermodynamicTemperature::new::<degree_celsius>(25.0),
+        }
+    }
+
+    fn overvoltage(controller: &mut ChargeController) {
+        controller.step(&measurements(18.0), Duration::ZERO);
+        assert_eq!(controller.phase(), ChargePhase::ConstantCurrent);
+        controller.step(&measurements(22.0), Duration::from_secs(1));
+        assert_eq!(
+            controller.phase(),
+            ChargePhase::Fault(ChargeFault::Overvoltage)
+        );
+    }
+
+    #[test]
+    fn disabling_keeps_a_fault_latched() {
+        let mut controller = ChargeController::new(ChargerConfig::default());
+        overvoltage(&mut controller);
+
+        controller.set_enabled(false);
+        assert_eq!(
+            controller.step(&measurements(18.0), Duration::from_secs(2)),
+            None
+        );
+        assert_eq!(
+            controller.phase(),
+            ChargePhase::Fault(ChargeFault::Overvoltage)
+        );
+
+        controller.reset_fault(Duration::from_secs(3));
+        controller.step(&measurements(18.0), Duration::from_secs(4));
+        assert_eq!(controller.phase(), ChargePhase::Idle);
+    }
+
+    #[test]
+    fn unplugging_clears_a_fault() {
+        let mut controller = ChargeController::new(ChargerConfig::default());
+        overvoltage(&mut controller);
+
+        let unplugged = ChargeMeasurements {
+            input_present: false,
+            ..measurements(18.0)
+        };
+        controller.step(&unplugged, Duration::from_secs(2));
+        assert_eq!(controller.phase(), ChargePhase::Idle);
+    }
+}
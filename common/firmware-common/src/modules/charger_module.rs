@@ -0,0 +1,397 @@
+use log::error;
+use std::{sync::Arc, time::Duration};
+use uom::si::f64::{Ratio, Time};
+use uom::si::ratio::percent;
+use uom::si::thermodynamic_temperature::degree_celsius;
+use uom::si::time::second;
+
+use crate::{
+    modules::{
+        charger::{
+            ChargeController, ChargeFault, ChargeMeasurements, ChargePhase, ChargeSetpoint,
+            ChargerConfig,
+        },
+        commands::{self, ChargerModuleCommands},
+        derating::{DeratingChange, DeratingMonitor},
+        identity::ModuleIdentity,
+        module::{
+            command_handlers, CommandHandlers, Module, ModuleCommandExecutionError,
+            ModuleCommandExecutionResponse, ModuleKind, ModuleMetadata,
+        },
+        system_controller::{CriticalEvent, ModuleEvent, SystemController},
+        telemetry::{
+            QuantityKind, Severity, TelemetryField, TelemetryReport, TelemetrySchema,
+            TelemetryValue,
+        },
+    },
+    settings::{self, SettingsError},
+    simulation::Simulated,
+};
+
+/// The power stage and sensors of a charge input module.
+///
+/// Implemented over the bus for the real input module, and by
+/// `dummies::dummy_charger::SimulatedChargerHardware` for the simulator.
+pub trait ChargerHardware {
+    fn identify(&mut self) -> Result<ModuleIdentity, ModuleCommandExecutionError>;
+
+    fn measure(&mut self) -> Result<ChargeMeasurements, ModuleCommandExecutionError>;
+
+    /// Regulate to `setpoint`, or switch the power stage off if `None`
+    fn apply(
+        &mut self,
+        setpoint: Option<ChargeSetpoint>,
+    ) -> Result<(), ModuleCommandExecutionError>;
+
+    /// Reset the power stage controller, which switches the power stage off
+    fn reset(&mut self) -> Result<(), ModuleCommandExecutionError> {
+        self.apply(None)
+    }
+
+    fn simulated(&mut self) -> Option<&mut dyn Simulated> {
+        None
+    }
+}
+
+/// Charge input module running a `ChargeController` on top of its hardware
+pub struct ChargerModule<H: ChargerHardware> {
+    id: u16,
+    hardware: H,
+    controller: ChargeController,
+    /// Measurements of the last update, if the hardware answered
+    measurements: Option<ChargeMeasurements>,
+    derating: DeratingMonitor,
+    last_update: Duration,
+    system_controller: Option<Arc<SystemController>>,
+}
+
+impl<H: ChargerHardware> ChargerModule<H> {
+    /// `identity` is what the hardware is expected to report once identified
+    pub fn with_hardware(identity: ModuleIdentity, hardware: H) -> Self {
+        Self {
+            id: identity.module_id(),
+            hardware,
+            controller: ChargeController::new(ChargerConfig::default()),
+            measurements: None,
+            derating: DeratingMonitor::new(),
+            last_update: Duration::ZERO,
+            system_controller: None,
+        }
+    }
+
+    pub fn phase(&self) -> ChargePhase {
+        self.controller.phase()
+    }
+
+    pub fn hardware(&self) -> &H {
+        &self.hardware
+    }
+
+    /// **Persists and applies a new configuration**
+    fn apply_config(&mut self, config: ChargerConfig) -> Result<(), SettingsError> {
+        if let Some(controller) = &self.system_controller {
+            controller.settings().save(self.id, &config)?;
+        }
+
+        self.controller.set_config(config);
+        Ok(())
+    }
+
+    /// **Reports a phase change on the event bus**
+    fn report_transition(&self, from: ChargePhase, to: ChargePhase) {
+        let Some(controller) = &self.system_controller else {
+            return;
+        };
+
+        match from {
+            ChargePhase::Fault(_) => {
+                controller.clear_alarm(self.id, "charge_fault");
+            }
+            ChargePhase::TemperaturePaused => {
+                controller.clear_alarm(self.id, "charge_temperature");
+            }
+            _ => {}
+        }
+
+        match to {
+            ChargePhase::Fault(fault) => controller.raise_alarm(
+                self.id,
+                "charge_fault",
+                ModuleEvent::Critical(CriticalEvent::ModuleFailure(format!(
+                    "Charger {}: {}",
+                    self.id, fault
+                ))),
+            ),
+            ChargePhase::TemperaturePaused => controller.raise_alarm(
+                self.id,
+                "charge_temperature",
+                ModuleEvent::Warning(format!(
+                    "Charger {}: pack temperature outside the charging window, charge paused",
+                    self.id
+                )),
+            ),
+            _ => controller.emit_event(ModuleEvent::Info(format!(
+                "Charger {}: {} -> {}",
+                self.id, from, to
+            ))),
+        }
+    }
+
+    /// **Reports the charge current being derated or restored on the event bus**
+    fn report_derating(&mut self, measurements: &ChargeMeasurements) {
+        // Derating only matters while there is a charge current to derate
+        let factor = if self.controller.phase().is_charging() {
+            self.controller.derating()
+        } else {
+            1.0
+        };
+
+        let Some(change) = self.derating.update(factor) else {
+            return;
+        };
+        let Some(controller) = &self.system_controller else {
+            return;
+        };
+
+        let message = format!(
+            "Charger {}: charge current {} at {:.1} °C",
+            self.id,
+            change,
+            measurements.pack_temperature.get::<degree_celsius>()
+        );
+        controller.emit_event(match change {
+            DeratingChange::Recovered => ModuleEvent::Info(message),
+            _ => ModuleEvent::Warning(message),
+        });
+    }
+}
+
+#[command_handlers(commands::ChargerModuleCommands)]
+impl<H: ChargerHardware> ChargerModule<H> {
+    fn set_charging_enabled(&mut self, enabled: bool) {
+        self.controller.set_enabled(enabled);
+    }
+
+    fn get_phase(&self) -> ChargePhase {
+        self.controller.phase()
+    }
+
+    fn reset_fault(&mut self) {
+        self.controller.reset_fault(self.last_update);
+    }
+
+    fn get_config(&self) -> ChargerConfig {
+        self.controller.config().clone()
+    }
+
+    fn set_config(&mut self, config: ChargerConfig) -> Result<(), SettingsError> {
+        self.apply_config(config)
+    }
+
+    fn set_config_value(&mut self, key: String, value: String) -> Result<(), SettingsError> {
+        settings::with_field(self.controller.config(), &key, &value)
+            .and_then(|config| self.apply_config(config))
+    }
+
+    fn reset_config(&mut self) -> Result<(), SettingsError> {
+        if let Some(controller) = &self.system_controller {
+            controller.settings().reset::<ChargerConfig>(self.id)?;
+        }
+        self.controller.set_config(ChargerConfig::default());
+        Ok(())
+    }
+}
+
+impl<H: ChargerHardware + 'static> Module for ChargerModule<H> {
+    type ModuleCommand = ChargerModuleCommands;
+    type ModuleStatus = ChargePhase;
+
+    fn metadata(&self) -> ModuleMetadata {
+        ModuleMetadata {
+            id: self.id,
+            module_kind: ModuleKind::Charger,
+            name: "Charge Input Module".into(),
+            version: "1".into(),
+        }
+    }
+
+    fn identify(&mut self) -> Result<ModuleIdentity, ModuleCommandExecutionError> {
+        self.hardware.identify()
+    }
+
+    fn process_command(&mut self, command: Self::ModuleCommand) -> ModuleCommandExecutionResponse {
+        CommandHandlers::process_command(self, command)
+    }
+
+    fn status(&self) -> Self::ModuleStatus {
+        self.controller.phase()
+    }
+
+    fn telemetry_schema(&self) -> TelemetrySchema {
+        TelemetrySchema {
+            module_kind: ModuleKind::Charger,
+            fields: vec![
+                TelemetryField::new("phase", "Phase", QuantityKind::State),
+                TelemetryField::new("input_present", "Input", QuantityKind::Boolean),
+                TelemetryField::new("charge_enabled", "Charging Enabled", QuantityKind::Boolean),
+                TelemetryField::new("pack_voltage", "Pack Voltage", QuantityKind::Voltage),
+                TelemetryField::new("pack_current", "Pack Current", QuantityKind::Current),
+                TelemetryField::new(
+                    "pack_temperature",
+                    "Pack Temperature",
+                    QuantityKind::Temperature,
+                ),
+                TelemetryField::new("current_limit", "Current Limit", QuantityKind::Current),
+                TelemetryField::new("charge_derating", "Charge Derating", QuantityKind::Ratio),
+                TelemetryField::new("charge_time", "Charge Time", QuantityKind::Duration),
+            ],
+        }
+    }
+
+    fn telemetry(&self) -> TelemetryReport {
+        let phase = self.controller.phase();
+        let phase_severity = match phase {
+            ChargePhase::Fault(_) => Severity::Critical,
+            ChargePhase::TemperaturePaused => Severity::Warning,
+            _ => Severity::Normal,
+        };
+
+        let mut report = TelemetryReport::new(self.id, self.last_update)
+            .with("phase", TelemetryValue::State(phase.name()), phase_severity)
+            .with(
+                "charge_enabled",
+                TelemetryValue::Boolean(self.controller.is_enabled()),
+                Severity::Normal,
+            );
+
+        if let Some(measurements) = &self.measurements {
+            report.push(
+                "input_present",
+                TelemetryValue::Boolean(measurements.input_present),
+                Severity::Normal,
+            );
+            report.push(
+                "pack_voltage",
+                TelemetryValue::Voltage(measurements.pack_voltage),
+                Severity::Normal,
+            );
+            report.push(
+                "pack_current",
+                TelemetryValue::Current(measurements.pack_current),
+                Severity::Normal,
+            );
+            report.push(
+                "pack_temperature",
+                TelemetryValue::Temperature(measurements.pack_temperature),
+                if phase == ChargePhase::TemperaturePaused {
+                    Severity::Warning
+                } else {
+                    Severity::Normal
+                },
+            );
+        }
+
+        if let Some(setpoint) = self.controller.setpoint() {
+            report.push(
+                "current_limit",
+                TelemetryValue::Current(setpoint.current_limit),
+                Severity::Normal,
+            );
+        }
+
+        report.push(
+            "charge_derating",
+            TelemetryValue::Ratio(Ratio::new::<percent>(self.derating.factor() * 100.0)),
+            if self.derating.is_active() {
+                Severity::Warning
+            } else {
+                Severity::Normal
+            },
+        );
+
+        report.with(
+            "charge_time",
+            TelemetryValue::Duration(Time::new::<second>(
+                self.controller.charge_time().as_secs_f64(),
+            )),
+            Severity::Normal,
+        )
+    }
+
+    fn update(&mut self) {
+        let Some(system_controller) = self.system_controller.clone() else {
+            return;
+        };
+        let now = system_controller.now();
+        self.last_update = now;
+
+        let before = self.controller.phase();
+
+        match self.hardware.measure() {
+            Ok(measurements) => {
+                self.measurements = Some(measurements);
+                let setpoint = self.controller.step(&measurements, now);
+
+                if let Err(err) = self.hardware.apply(setpoint) {
+                    error!("Charger {} did not accept its setpoint: {}", self.id, err);
+                    self.controller.fault(ChargeFault::Hardware, now);
+                }
+
+                self.report_derating(&measurements);
+            }
+            Err(err) => {
+                self.measurements = None;
+                // Only a charge in progress is at risk when the charger stops answering
+                if self.controller.phase().is_charging() {
+                    error!("Charger {} stopped responding: {}", self.id, err);
+                    self.controller.fault(ChargeFault::Hardware, now);
+                }
+            }
+        }
+
+        let after = self.controller.phase();
+        if after != before {
+            if !after.is_charging() {
+                // Best effort, the power stage is also expected to time out on its own
+                let _ = self.hardware.apply(None);
+            }
+            self.report_transition(before, after);
+        }
+    }
+
+    fn reset(&mut self) -> Result<(), ModuleCommandExecutionError> {
+        self.hardware.reset()
+    }
+
+    fn enter_safe_state(&mut self) {
+        self.controller.set_enabled(false);
+        // Best effort, the power stage is also expected to time out on its own
+        let _ = self.hardware.apply(None);
+    }
+
+    fn simulated(&mut self) -> Option<&mut dyn Simulated> {
+        self.hardware.simulated()
+    }
+
+    fn initialize(
+        &mut self,
+        system_controller: Arc<SystemController>,
+    ) -> Result<(), ModuleCommandExecutionError> {
+        self.last_update = system_controller.now();
+
+        let config = system_controller
+            .settings()
+            .load(self.id)
+            .unwrap_or_else(|err| {
+                error!(
+                    "Unable to load charger {} config, using defaults: {}",
+                    self.id, err
+                );
+                ChargerConfig::default()
+            });
+        self.controller.set_config(config);
+
+        self.system_controller = Some(system_controller);
+        Ok(())
+    }
+}
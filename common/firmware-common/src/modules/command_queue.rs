@@ -0,0 +1,312 @@
+use std::{
+    any::Any,
+    cell::RefCell,
+    collections::{BTreeMap, VecDeque},
+    future::Future,
+    marker::PhantomData,
+    pin::Pin,
+    rc::Rc,
+    task::{Context, Poll, Waker},
+    time::Duration,
+};
+
+use super::{
+    interlock::ConfirmationToken,
+    module::{ModuleCommandExecutionError, ModuleCommandExecutionResponse},
+};
+
+/// How many commands can wait for a module before `submit` fails with `QueueFull`
+pub const MAX_QUEUED_COMMANDS: usize = 32;
+
+#[derive(Default)]
+struct Completion {
+    response: Option<ModuleCommandExecutionResponse>,
+    cancelled: bool,
+    waker: Option<Waker>,
+}
+
+impl Completion {
+    fn complete(&mut self, response: ModuleCommandExecutionResponse) {
+        self.response = Some(response);
+        if let Some(waker) = self.waker.take() {
+            waker.wake();
+        }
+    }
+}
+
+/// The response of a submitted command, once the module got to it.
+///
+/// Either polled with `try_take`, or awaited: the handle is a `Future` that resolves when
+/// `ModuleManager::process_queue` has run the command. Commands run synchronously once taken
+/// off the queue, so neither `cancel` nor the queue timeout given to `submit` can stop a
+/// command that has been sent.
+pub struct CommandHandle<R> {
+    completion: Rc<RefCell<Completion>>,
+    response: PhantomData<fn() -> R>,
+}
+
+impl<R: 'static> CommandHandle<R> {
+    /// A handle that is already done, for commands that could not be queued
+    pub(crate) fn failed(error: ModuleCommandExecutionError) -> Self {
+        let handle = Self {
+            completion: Rc::default(),
+            response: PhantomData,
+        };
+        handle.completion.borrow_mut().complete(Err(error));
+        handle
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.completion.borrow().response.is_some()
+    }
+
+    /// The response, if the command has run. Only returns it once.
+    pub fn try_take(&mut self) -> Option<Result<R, ModuleCommandExecutionError>> {
+        let response = self.completion.borrow_mut().response.take()?;
+        Some(response.and_then(|boxed| {
+            boxed
+                .downcast::<R>()
+                .map(|response| *response)
+                .map_err(|_| ModuleCommandExecutionError::DowncastFailure)
+        }))
+    }
+
+    /// Drop the command if it has not been sent yet, completing it with `Cancelled`. A
+    /// command already on the bus runs to completion.
+    pub fn cancel(&self) {
+        self.completion.borrow_mut().cancelled = true;
+    }
+}
+
+impl<R: 'static> Future for CommandHandle<R> {
+    type Output = Result<R, ModuleCommandExecutionError>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = self.get_mut();
+        match this.try_take() {
+            Some(response) => Poll::Ready(response),
+            None => {
+                this.completion.borrow_mut().waker = Some(cx.waker().clone());
+                Poll::Pending
+            }
+        }
+    }
+}
+
+/// A command waiting for its module
+pub(crate) struct QueuedCommand {
+    command: Box<dyn Any>,
+    confirmation: Option<ConfirmationToken>,
+    /// System clock time after which the command is not sent anymore
+    send_by: Option<Duration>,
+    completion: Rc<RefCell<Completion>>,
+}
+
+impl QueuedCommand {
+    /// Whether the command should still be sent at `now`, completing it otherwise
+    pub fn is_live(&self, now: Duration) -> bool {
+        let mut completion = self.completion.borrow_mut();
+        if completion.cancelled {
+            completion.complete(Err(ModuleCommandExecutionError::Cancelled));
+            false
+        } else if self.send_by.is_some_and(|send_by| now > send_by) {
+            completion.complete(Err(ModuleCommandExecutionError::QueueTimeout));
+            false
+        } else {
+            true
+        }
+    }
+
+    /// Send the command with `dispatch` and complete the handle with its response
+    pub fn run(
+        self,
+        dispatch: impl FnOnce(Box<dyn Any>, Option<ConfirmationToken>) -> ModuleCommandExecutionResponse,
+    ) {
+        let response = dispatch(self.command, self.confirmation);
+        self.completion.borrow_mut().complete(response);
+    }
+}
+
+/// Commands submitted to each module, run in order by `ModuleManager::process_queue`
+#[derive(Default)]
+pub(crate) struct CommandQueue {
+    queues: BTreeMap<u16, VecDeque<QueuedCommand>>,
+}
+
+impl CommandQueue {
+    pub fn push<R: 'static>(
+        &mut self,
+        module_id: u16,
+        command: Box<dyn Any>,
+        confirmation: Option<ConfirmationToken>,
+        send_by: Option<Duration>,
+    ) -> CommandHandle<R> {
+        let queue = self.queues.entry(module_id).or_default();
+        if queue.len() >= MAX_QUEUED_COMMANDS {
+            return CommandHandle::failed(ModuleCommandExecutionError::QueueFull(module_id));
+        }
+
+        let completion = Rc::<RefCell<Completion>>::default();
+        queue.push_back(QueuedCommand {
+            command,
+            confirmation,
+            send_by,
+            completion: completion.clone(),
+        });
+        CommandHandle {
+            completion,
+            response: PhantomData,
+        }
+    }
+
+    /// Everything queued so far, module by module in order of their IDs
+    pub fn drain(&mut self) -> Vec<(u16, QueuedCommand)> {
+        self.queues
+            .iter_mut()
+            .flat_map(|(id, queue)| queue.drain(..).map(|queued| (*id, queued)))
+            .collect()
+    }
+
+    pub fn len(&self, module_id: u16) -> usize {
+        self.queues.get(&module_id).map_or(0, VecDeque::len)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+    use uom::si::{electric_current::ampere, f64::ElectricCurrent};
+
+    use super::*;
+    use crate::{
+        clock::ManualClock,
+        modules::{
+            commands::{port_module_commands, MockPort, PortModuleCommands},
+            mock::testing::{self, mock_port},
+            module::submit_command,
+            module_manager::ModuleManager,
+        },
+    };
+
+    fn setup() -> (Arc<ManualClock>, ModuleManager, u16) {
+        let (clock, controller, mut modules) = testing::setup();
+        let mut port = mock_port(1);
+        port.expect_set_output().returning(());
+        port.expect_set_current_limit().returning(());
+        let id = modules.register_module(port, controller).unwrap();
+        (clock, modules, id)
+    }
+
+    fn port(modules: &ModuleManager, id: u16) -> &MockPort {
+        modules.get_module::<MockPort>(id).unwrap()
+    }
+
+    #[test]
+    fn commands_wait_for_the_queue_and_run_in_order() {
+        let (_, mut modules, id) = setup();
+        let mut on = submit_command!(
+            modules,
+            id: PortModuleCommands,
+            PortModuleCommands::SetOutput { state: true }
+        );
+        let mut off = submit_command!(
+            modules,
+            id: PortModuleCommands,
+            PortModuleCommands::SetOutput { state: false }
+        );
+        assert!(on.try_take().is_none());
+        assert!(port(&modules, id).calls().is_empty());
+
+        modules.process_queue();
+        assert!(matches!(on.try_take(), Some(Ok(()))));
+        assert!(matches!(off.try_take(), Some(Ok(()))));
+        assert!(matches!(
+            port(&modules, id).calls(),
+            [
+                PortModuleCommands::SetOutput { state: true },
+                PortModuleCommands::SetOutput { state: false }
+            ]
+        ));
+    }
+
+    #[test]
+    fn cancelled_command_is_not_sent() {
+        let (_, mut modules, id) = setup();
+        let mut cancelled = submit_command!(
+            modules,
+            id: PortModuleCommands,
+            PortModuleCommands::SetOutput { state: true }
+        );
+        cancelled.cancel();
+
+        modules.process_queue();
+        assert!(matches!(
+            cancelled.try_take(),
+            Some(Err(ModuleCommandExecutionError::Cancelled))
+        ));
+        assert!(port(&modules, id).calls().is_empty());
+
+        // Too late once the command has run
+        let mut sent = submit_command!(
+            modules,
+            id: PortModuleCommands,
+            PortModuleCommands::SetOutput { state: true }
+        );
+        modules.process_queue();
+        sent.cancel();
+        assert!(matches!(sent.try_take(), Some(Ok(()))));
+    }
+
+    #[test]
+    fn queue_timeout_only_applies_while_queued() {
+        let (clock, mut modules, id) = setup();
+        let limit = ElectricCurrent::new::<ampere>(2.0);
+        let mut late = submit_command!(
+            modules,
+            id: PortModuleCommands,
+            PortModuleCommands::SetCurrentLimit { limit },
+            queue_timeout = Duration::from_millis(50)
+        );
+        let mut in_time = submit_command!(
+            modules,
+            id: PortModuleCommands,
+            PortModuleCommands::SetOutput { state: true },
+            queue_timeout = Duration::from_millis(500)
+        );
+
+        clock.advance(Duration::from_millis(100));
+        modules.process_queue();
+        assert!(matches!(
+            late.try_take(),
+            Some(Err(ModuleCommandExecutionError::QueueTimeout))
+        ));
+        assert!(matches!(in_time.try_take(), Some(Ok(()))));
+        let port = port(&modules, id);
+        assert_eq!(
+            port.call_count::<port_module_commands::SetCurrentLimit>(),
+            0
+        );
+        assert_eq!(port.call_count::<port_module_commands::SetOutput>(), 1);
+    }
+
+    #[test]
+    fn full_queue_fails_right_away() {
+        let (_, mut modules, id) = setup();
+        let mut handles: Vec<_> = (0..=MAX_QUEUED_COMMANDS)
+            .map(|_| {
+                submit_command!(
+                    modules,
+                    id: PortModuleCommands,
+                    PortModuleCommands::SetOutput { state: true }
+                )
+            })
+            .collect();
+
+        let overflow = handles.last_mut().unwrap().try_take();
+        assert!(matches!(
+            overflow,
+            Some(Err(ModuleCommandExecutionError::QueueFull(_)))
+        ));
+        assert_eq!(modules.queued_commands(id), MAX_QUEUED_COMMANDS);
+    }
+}
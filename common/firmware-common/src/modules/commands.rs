@@ -1,10 +1,93 @@
 use crate::modules::module::def_module_commands;
 
+// Command IDs are part of the bus protocol: never reuse or renumber them, only add new ones.
+// 0x01 is reserved for identification. Commands marked `#[local]` are handled by the host
+// (settings, decisions made from telemetry) and never sent to the module.
+//
+// `#[safety(critical)]` commands can defeat a protection and are only dispatched once
+// confirmed. `#[interlock(...)]` refuses a command while an alarm is raised, optionally only
+// `when` its arguments say so. `#[mock(Name)]` generates a `MockModule` for tests.
 def_module_commands! {
     // Commands that can be sent to the battery module
+    #[mock(MockBattery)]
     BatteryModuleCommands {
-        SetOutput(state: bool) -> ();
-        GetVoltage() -> uom::si::f64::ElectricPotential;
-        Dummy();
+        /// Connect or disconnect the battery output
+        #[interlock(no_alarm("temperature"), when = *state)]
+        #[interlock(no_alarm("current"), when = *state)]
+        #[interlock(no_alarm("safety_hold"), when = *state)]
+        SetOutput(state: bool) -> () = 0x10;
+        /// Pack voltage
+        GetVoltage() -> uom::si::f64::ElectricPotential = 0x11;
+        /// Voltage and temperature of every cell
+        GetCellReadings() -> Vec<crate::modules::balancing::CellReading> = 0x12;
+        #[local] GetConfig() -> crate::modules::battery::BatteryConfig = 0x20;
+        /// Replace the configuration, protection thresholds included
+        #[local] #[safety(critical)]
+        SetConfig(config: crate::modules::battery::BatteryConfig) -> Result<(), crate::settings::SettingsError> = 0x21;
+        /// Change one setting, e.g. `output_cutoff_pct`
+        #[local] #[safety(critical)]
+        SetConfigValue(key: String, value: String) -> Result<(), crate::settings::SettingsError> = 0x22;
+        /// Restore the default configuration
+        #[local] #[safety(critical)]
+        ResetConfig() -> Result<(), crate::settings::SettingsError> = 0x23;
+        /// Apply a named threshold profile, e.g. `li-ion-5s`
+        #[local] #[safety(critical)]
+        LoadThresholdProfile(name: String) -> Result<(), crate::modules::thresholds::ThresholdError> = 0x24;
+        /// Cells to bleed, as last decided from the cell readings
+        #[local] GetBalancingDecision() -> Option<crate::modules::balancing::BalancingDecision> = 0x30;
+        #[local] GetBalancingConfig() -> crate::modules::balancing::BalancingConfig = 0x31;
+        /// Replace the balancing configuration, pack thermal limit included
+        #[local] #[safety(critical)]
+        SetBalancingConfig(config: crate::modules::balancing::BalancingConfig) -> Result<(), crate::settings::SettingsError> = 0x32;
+        Dummy() = 0x7F;
+    },
+
+    // Commands that can be sent to an output port module
+    #[mock(MockPort)]
+    PortModuleCommands {
+        /// Enable or disable the port output
+        #[interlock(no_critical_alarm, when = *state)]
+        #[interlock(no_alarm("safety_hold"), when = *state)]
+        SetOutput(state: bool) -> () = 0x10;
+        /// Limit the current the port may draw
+        SetCurrentLimit(#[range(0.0..=10.0)] limit: uom::si::f64::ElectricCurrent) -> () = 0x11;
+        /// Current the connected load asks for
+        GetDemand() -> uom::si::f64::ElectricCurrent = 0x12;
+        #[local] GetConfig() -> crate::modules::port::PortConfig = 0x20;
+        /// Replace the configuration, including `max_current_a`, the cap the power budget uses
+        #[local] #[safety(critical)]
+        SetConfig(config: crate::modules::port::PortConfig) -> Result<(), crate::settings::SettingsError> = 0x21;
+        /// Change one setting, e.g. `priority`
+        #[local] #[safety(critical)]
+        SetConfigValue(key: String, value: String) -> Result<(), crate::settings::SettingsError> = 0x22;
+        /// Restore the default configuration
+        #[local] #[safety(critical)]
+        ResetConfig() -> Result<(), crate::settings::SettingsError> = 0x23;
+    },
+
+    // Commands that can be sent to the charge input module. The charge state machine runs on
+    // the host, the module itself only takes setpoints through `ChargerHardware`.
+    #[mock(MockCharger)]
+    ChargerModuleCommands {
+        /// Allow or stop charging
+        #[interlock(no_alarm("charge_lockout"), when = *enabled)]
+        #[interlock(no_alarm("charge_fault"), when = *enabled)]
+        #[interlock(no_alarm("safety_hold"), when = *enabled)]
+        #[local] SetChargingEnabled(enabled: bool) -> () = 0x10;
+        /// Current phase of the charge state machine
+        #[local] GetPhase() -> crate::modules::charger::ChargePhase = 0x11;
+        /// Leave the fault phase, once its cause is gone
+        #[local] #[safety(critical)]
+        ResetFault() -> () = 0x12;
+        #[local] GetConfig() -> crate::modules::charger::ChargerConfig = 0x20;
+        /// Replace the configuration, charge limits included
+        #[local] #[safety(critical)]
+        SetConfig(config: crate::modules::charger::ChargerConfig) -> Result<(), crate::settings::SettingsError> = 0x21;
+        /// Change one setting, e.g. `charge_current_a`
+        #[local] #[safety(critical)]
+        SetConfigValue(key: String, value: String) -> Result<(), crate::settings::SettingsError> = 0x22;
+        /// Restore the default configuration
+        #[local] #[safety(critical)]
+        ResetConfig() -> Result<(), crate::settings::SettingsError> = 0x23;
     }
 }
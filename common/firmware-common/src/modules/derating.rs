@@ -0,0 +1,206 @@
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// One point of a derating curve
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct DeratingPoint {
+    /// In °C
+    pub temperature_c: f64,
+    /// Fraction of the nominal current allowed at this temperature (0.0-1.0)
+    pub factor: f64,
+}
+
+impl DeratingPoint {
+    pub const fn new(temperature_c: f64, factor: f64) -> Self {
+        Self {
+            temperature_c,
+            factor,
+        }
+    }
+}
+
+/// Piecewise-linear map from temperature to the fraction of nominal current allowed.
+///
+/// Between two points the factor is interpolated linearly, beyond the first and last points
+/// it stays at their factor. An empty curve never derates.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(from = "Vec<DeratingPoint>", into = "Vec<DeratingPoint>")]
+pub struct DeratingCurve {
+    points: Vec<DeratingPoint>,
+}
+
+impl From<Vec<DeratingPoint>> for DeratingCurve {
+    fn from(points: Vec<DeratingPoint>) -> Self {
+        Self::new(points)
+    }
+}
+
+impl From<DeratingCurve> for Vec<DeratingPoint> {
+    fn from(curve: DeratingCurve) -> Self {
+        curve.points
+    }
+}
+
+impl DeratingCurve {
+    /// Points are sorted by temperature and their factors clamped to 0.0-1.0
+    pub fn new(mut points: Vec<DeratingPoint>) -> Self {
+        points.retain(|point| point.temperature_c.is_finite() && point.factor.is_finite());
+        points.sort_by(|a, b| a.temperature_c.total_cmp(&b.temperature_c));
+        for point in &mut points {
+            point.factor = point.factor.clamp(0.0, 1.0);
+        }
+
+        Self { points }
+    }
+
+    /// A curve allowing full current at any temperature
+    pub fn none() -> Self {
+        Self { points: Vec::new() }
+    }
+
+    pub fn points(&self) -> &[DeratingPoint] {
+        &self.points
+    }
+
+    /// Default discharge curve: full current from 0 °C to 40 °C, nothing below -20 °C or
+    /// above 55 °C
+    pub fn default_discharge() -> Self {
+        Self::new(vec![
+            DeratingPoint::new(-20.0, 0.0),
+            DeratingPoint::new(-10.0, 0.5),
+            DeratingPoint::new(0.0, 1.0),
+            DeratingPoint::new(40.0, 1.0),
+            DeratingPoint::new(50.0, 0.25),
+            DeratingPoint::new(55.0, 0.0),
+        ])
+    }
+
+    /// Default charge curve: cells accept less current when cold, and when warm to keep
+    /// them away from the charge temperature window edges
+    pub fn default_charge() -> Self {
+        Self::new(vec![
+            DeratingPoint::new(0.0, 0.2),
+            DeratingPoint::new(10.0, 1.0),
+            DeratingPoint::new(35.0, 1.0),
+            DeratingPoint::new(45.0, 0.3),
+        ])
+    }
+
+    /// Fraction of nominal current allowed at `temperature_c`
+    pub fn factor_at(&self, temperature_c: f64) -> f64 {
+        let (Some(first), Some(last)) = (self.points.first(), self.points.last()) else {
+            return 1.0;
+        };
+
+        // A broken sensor must not be mistaken for a safe temperature
+        if temperature_c.is_nan() {
+            return 0.0;
+        }
+        if temperature_c <= first.temperature_c {
+            return first.factor;
+        }
+        if temperature_c >= last.temperature_c {
+            return last.factor;
+        }
+
+        self.points
+            .windows(2)
+            .find(|pair| temperature_c <= pair[1].temperature_c)
+            .map(|pair| {
+                let span = pair[1].temperature_c - pair[0].temperature_c;
+                if span <= 0.0 {
+                    return pair[1].factor;
+                }
+
+                let t = (temperature_c - pair[0].temperature_c) / span;
+                pair[0].factor + (pair[1].factor - pair[0].factor) * t
+            })
+            .unwrap_or(last.factor)
+            .clamp(0.0, 1.0)
+    }
+}
+
+impl Default for DeratingCurve {
+    fn default() -> Self {
+        Self::none()
+    }
+}
+
+/// How a derating factor changed, as reported by `DeratingMonitor::update`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DeratingChange {
+    /// Derating started, or its factor moved by more than the reporting step
+    Derating(f64),
+    /// The current is cut off entirely
+    Cutoff,
+    /// Back to full current
+    Recovered,
+}
+
+impl fmt::Display for DeratingChange {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            DeratingChange::Derating(factor) => write!(f, "derated to {:.0}%", factor * 100.0),
+            DeratingChange::Cutoff => write!(f, "cut off"),
+            DeratingChange::Recovered => write!(f, "back to full current"),
+        }
+    }
+}
+
+/// Tracks a derating factor over time so that only meaningful changes are reported
+#[derive(Debug, Clone)]
+pub struct DeratingMonitor {
+    factor: f64,
+    /// Factor of the last reported change
+    reported: f64,
+}
+
+impl DeratingMonitor {
+    /// Factor below which the current counts as derated
+    const ACTIVE_BELOW: f64 = 0.99;
+    /// Smallest further change of the factor reported while derating
+    const REPORT_STEP: f64 = 0.25;
+
+    pub fn new() -> Self {
+        Self {
+            factor: 1.0,
+            reported: 1.0,
+        }
+    }
+
+    pub fn factor(&self) -> f64 {
+        self.factor
+    }
+
+    pub fn is_active(&self) -> bool {
+        self.factor < Self::ACTIVE_BELOW
+    }
+
+    /// Record a new factor, returning the change worth reporting if any
+    pub fn update(&mut self, factor: f64) -> Option<DeratingChange> {
+        self.factor = factor;
+
+        let change = if factor <= 0.0 {
+            (self.reported > 0.0).then_some(DeratingChange::Cutoff)
+        } else if factor >= Self::ACTIVE_BELOW {
+            (self.reported < Self::ACTIVE_BELOW).then_some(DeratingChange::Recovered)
+        } else if self.reported >= Self::ACTIVE_BELOW
+            || (self.reported - factor).abs() >= Self::REPORT_STEP
+        {
+            Some(DeratingChange::Derating(factor))
+        } else {
+            None
+        };
+
+        if change.is_some() {
+            self.reported = factor.clamp(0.0, 1.0);
+        }
+        change
+    }
+}
+
+impl Default for DeratingMonitor {
+    fn default() -> Self {
+        Self::new()
+    }
+}
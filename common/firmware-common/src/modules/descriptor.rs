@@ -0,0 +1,488 @@
+use std::{any::Any, fmt};
+
+use thiserror::Error;
+use uom::si::{
+    electric_charge::milliampere_hour,
+    electric_current::ampere,
+    electric_potential::volt,
+    energy::watt_hour,
+    f64::{
+        ElectricCharge, ElectricCurrent, ElectricPotential, Energy, Power, Ratio,
+        ThermodynamicTemperature, Time,
+    },
+    power::watt,
+    ratio::percent,
+    thermodynamic_temperature::degree_celsius,
+    time::second,
+};
+
+use super::{
+    interlock::{CommandSafety, SafetyClass},
+    telemetry::QuantityKind,
+};
+use crate::settings::ModuleConfig;
+
+/// What an argument or response holds, so that a UI knows how to enter or show it
+#[derive(Debug, Clone, PartialEq)]
+pub enum ValueKind {
+    Unit,
+    Bool,
+    /// Bounds of the integer type, saturated to `i64`
+    Integer {
+        min: i64,
+        max: i64,
+    },
+    Float,
+    /// A physical quantity, as a number in the display unit of its kind
+    Quantity(QuantityKind),
+    Text,
+    Optional(Box<ValueKind>),
+    List(Box<ValueKind>),
+    /// A module configuration, entered and shown as TOML
+    Config(&'static str),
+    /// Shown as text, cannot be entered
+    Opaque(&'static str),
+}
+
+impl ValueKind {
+    /// Unit symbol numbers of this kind are expressed in
+    pub fn unit(&self) -> Option<&'static str> {
+        match self {
+            ValueKind::Quantity(kind) => Some(kind.display_unit()).filter(|unit| !unit.is_empty()),
+            _ => None,
+        }
+    }
+
+    /// Whether a UI can build a value of this kind
+    pub fn is_editable(&self) -> bool {
+        match self {
+            ValueKind::Optional(item) => item.is_editable(),
+            ValueKind::List(_) | ValueKind::Opaque(_) => false,
+            _ => true,
+        }
+    }
+
+    /// Starting point for a form field, within `range` if there is one
+    pub fn default_value(&self, range: Option<(f64, f64)>) -> Value {
+        let low = range.map_or(0.0, |(min, _)| min.max(0.0));
+        match self {
+            ValueKind::Unit => Value::Unit,
+            ValueKind::Bool => Value::Bool(false),
+            ValueKind::Integer { min, max } => Value::Integer((low as i64).clamp(*min, *max)),
+            ValueKind::Float | ValueKind::Quantity(_) => Value::Number(low),
+            ValueKind::Text | ValueKind::Config(_) | ValueKind::Opaque(_) => {
+                Value::Text(String::new())
+            }
+            ValueKind::Optional(_) => Value::Optional(None),
+            ValueKind::List(_) => Value::List(Vec::new()),
+        }
+    }
+
+    /// `value` with the unit of this kind, e.g. `3.300 V`
+    pub fn format(&self, value: &Value) -> String {
+        match (self, value) {
+            (ValueKind::Quantity(_), Value::Number(number)) => match self.unit() {
+                Some(unit) => format!("{number:.3} {unit}"),
+                None => format!("{number:.3}"),
+            },
+            (ValueKind::Optional(item), Value::Optional(Some(value))) => item.format(value),
+            (ValueKind::List(item), Value::List(values)) => {
+                let items: Vec<String> = values.iter().map(|value| item.format(value)).collect();
+                format!("[{}]", items.join(", "))
+            }
+            (_, value) => value.to_string(),
+        }
+    }
+}
+
+/// An argument or response, in a form generic UIs can handle
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    Unit,
+    Bool(bool),
+    Integer(i64),
+    /// Floats, and quantities in the display unit of their kind
+    Number(f64),
+    /// Text, TOML of configurations, and values that can only be shown
+    Text(String),
+    Optional(Option<Box<Value>>),
+    List(Vec<Value>),
+    /// The command ran, but returned an error
+    Error(String),
+}
+
+impl Value {
+    /// The number held, looking through `Optional`
+    pub fn as_number(&self) -> Option<f64> {
+        match self {
+            Value::Integer(number) => Some(*number as f64),
+            Value::Number(number) => Some(*number),
+            Value::Optional(Some(value)) => value.as_number(),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Value::Unit => write!(f, "OK"),
+            Value::Bool(value) => write!(f, "{value}"),
+            Value::Integer(value) => write!(f, "{value}"),
+            Value::Number(value) => write!(f, "{value:.3}"),
+            Value::Text(value) => write!(f, "{value}"),
+            Value::Optional(None) => write!(f, "none"),
+            Value::Optional(Some(value)) => write!(f, "{value}"),
+            Value::List(values) => {
+                let items: Vec<String> = values.iter().map(Value::to_string).collect();
+                write!(f, "[{}]", items.join(", "))
+            }
+            Value::Error(error) => write!(f, "Error: {error}"),
+        }
+    }
+}
+
+#[derive(Debug, Error)]
+pub enum ValueError {
+    #[error("Expected {0}")]
+    Mismatch(&'static str),
+
+    #[error("`{name}` must be between {min} and {max}")]
+    OutOfRange {
+        name: &'static str,
+        min: f64,
+        max: f64,
+    },
+
+    #[error("Invalid {0}: {1}")]
+    Invalid(&'static str, String),
+
+    #[error("{0} cannot be entered")]
+    NotAnArgument(&'static str),
+
+    #[error("Unknown command `{0}`")]
+    UnknownCommand(String),
+
+    #[error("`{command}` takes {expected} arguments, got {found}")]
+    ArgumentCount {
+        command: &'static str,
+        expected: usize,
+        found: usize,
+    },
+}
+
+/// Conversion of a command argument or response from and to a `Value`
+pub trait CommandValue: Sized {
+    fn kind() -> ValueKind;
+
+    fn to_value(&self) -> Value;
+
+    fn from_value(value: Value) -> Result<Self, ValueError>;
+}
+
+/// An argument of a command, as defined in `def_module_commands!`
+#[derive(Debug, Clone, PartialEq)]
+pub struct ArgDescriptor {
+    pub name: &'static str,
+    pub doc: &'static str,
+    pub rust_type: &'static str,
+    pub kind: ValueKind,
+    /// Accepted values, from `#[range(min..=max)]`
+    pub range: Option<(f64, f64)>,
+}
+
+impl ArgDescriptor {
+    pub fn unit(&self) -> Option<&'static str> {
+        self.kind.unit()
+    }
+
+    pub fn default_value(&self) -> Value {
+        self.kind.default_value(self.range)
+    }
+}
+
+/// Everything a UI needs to invoke a command and show its response
+#[derive(Debug, Clone, PartialEq)]
+pub struct CommandDescriptor {
+    pub name: &'static str,
+    pub id: u8,
+    /// Handled by the host and never sent over the bus
+    pub local: bool,
+    pub doc: &'static str,
+    pub args: Vec<ArgDescriptor>,
+    pub response_type: &'static str,
+    pub response: ValueKind,
+    /// Critical commands are only dispatched once confirmed
+    pub safety: SafetyClass,
+}
+
+impl CommandDescriptor {
+    /// Whether a UI can build every argument
+    pub fn is_invocable(&self) -> bool {
+        self.args.iter().all(|arg| arg.kind.is_editable())
+    }
+}
+
+/// A command enum, described for UIs that know nothing of it. Implemented by
+/// `def_module_commands!`.
+pub trait CommandSet: Sized + 'static {
+    fn descriptors() -> Vec<CommandDescriptor>;
+
+    /// Build the command named `command` from the values of its arguments, in order
+    fn from_values(command: &str, args: Vec<Value>) -> Result<Self, ValueError>;
+
+    /// Typed view of a response returned by `process_command` for the command named `command`
+    fn response_value(command: &str, response: &dyn Any) -> Option<Value>;
+
+    fn command_name(&self) -> &'static str;
+
+    /// Safety class of the command, and the interlocks that apply with its arguments
+    fn safety(&self) -> CommandSafety;
+}
+
+/// The arguments of `command`, if there are exactly `N` of them
+pub fn take_args<const N: usize>(
+    command: &'static str,
+    args: Vec<Value>,
+) -> Result<[Value; N], ValueError> {
+    args.try_into()
+        .map_err(|args: Vec<Value>| ValueError::ArgumentCount {
+            command,
+            expected: N,
+            found: args.len(),
+        })
+}
+
+/// Convert the argument `name`, checking it against its `range` first
+pub fn arg_value<T: CommandValue>(
+    name: &'static str,
+    value: Value,
+    range: Option<(f64, f64)>,
+) -> Result<T, ValueError> {
+    if let Some((min, max)) = range {
+        if value
+            .as_number()
+            .is_some_and(|number| !(min..=max).contains(&number))
+        {
+            return Err(ValueError::OutOfRange { name, min, max });
+        }
+    }
+    T::from_value(value)
+}
+
+impl CommandValue for () {
+    fn kind() -> ValueKind {
+        ValueKind::Unit
+    }
+
+    fn to_value(&self) -> Value {
+        Value::Unit
+    }
+
+    fn from_value(_value: Value) -> Result<Self, ValueError> {
+        Ok(())
+    }
+}
+
+impl CommandValue for bool {
+    fn kind() -> ValueKind {
+        ValueKind::Bool
+    }
+
+    fn to_value(&self) -> Value {
+        Value::Bool(*self)
+    }
+
+    fn from_value(value: Value) -> Result<Self, ValueError> {
+        match value {
+            Value::Bool(value) => Ok(value),
+            _ => Err(ValueError::Mismatch("a boolean")),
+        }
+    }
+}
+
+macro_rules! integer_values {
+    ($($ty:ty),* $(,)?) => {
+        $(
+            impl CommandValue for $ty {
+                fn kind() -> ValueKind {
+                    ValueKind::Integer {
+                        min: i64::try_from(<$ty>::MIN).unwrap_or(i64::MIN),
+                        max: i64::try_from(<$ty>::MAX).unwrap_or(i64::MAX),
+                    }
+                }
+
+                fn to_value(&self) -> Value {
+                    Value::Integer(i64::try_from(*self).unwrap_or(i64::MAX))
+                }
+
+                fn from_value(value: Value) -> Result<Self, ValueError> {
+                    match value {
+                        Value::Integer(value) => <$ty>::try_from(value).map_err(|err| {
+                            ValueError::Invalid(stringify!($ty), err.to_string())
+                        }),
+                        _ => Err(ValueError::Mismatch("an integer")),
+                    }
+                }
+            }
+        )*
+    };
+}
+
+integer_values!(u8, u16, u32, u64, i8, i16, i32, i64);
+
+impl CommandValue for f32 {
+    fn kind() -> ValueKind {
+        ValueKind::Float
+    }
+
+    fn to_value(&self) -> Value {
+        Value::Number(f64::from(*self))
+    }
+
+    fn from_value(value: Value) -> Result<Self, ValueError> {
+        f64::from_value(value).map(|value| value as f32)
+    }
+}
+
+impl CommandValue for f64 {
+    fn kind() -> ValueKind {
+        ValueKind::Float
+    }
+
+    fn to_value(&self) -> Value {
+        Value::Number(*self)
+    }
+
+    fn from_value(value: Value) -> Result<Self, ValueError> {
+        match value {
+            Value::Number(value) => Ok(value),
+            Value::Integer(value) => Ok(value as f64),
+            _ => Err(ValueError::Mismatch("a number")),
+        }
+    }
+}
+
+impl CommandValue for String {
+    fn kind() -> ValueKind {
+        ValueKind::Text
+    }
+
+    fn to_value(&self) -> Value {
+        Value::Text(self.clone())
+    }
+
+    fn from_value(value: Value) -> Result<Self, ValueError> {
+        match value {
+            Value::Text(value) => Ok(value),
+            _ => Err(ValueError::Mismatch("text")),
+        }
+    }
+}
+
+impl<T: CommandValue> CommandValue for Option<T> {
+    fn kind() -> ValueKind {
+        ValueKind::Optional(Box::new(T::kind()))
+    }
+
+    fn to_value(&self) -> Value {
+        Value::Optional(self.as_ref().map(|value| Box::new(value.to_value())))
+    }
+
+    fn from_value(value: Value) -> Result<Self, ValueError> {
+        match value {
+            Value::Optional(value) => value.map(|value| T::from_value(*value)).transpose(),
+            _ => Err(ValueError::Mismatch("an optional value")),
+        }
+    }
+}
+
+impl<T: CommandValue> CommandValue for Vec<T> {
+    fn kind() -> ValueKind {
+        ValueKind::List(Box::new(T::kind()))
+    }
+
+    fn to_value(&self) -> Value {
+        Value::List(self.iter().map(CommandValue::to_value).collect())
+    }
+
+    fn from_value(value: Value) -> Result<Self, ValueError> {
+        match value {
+            Value::List(values) => values.into_iter().map(T::from_value).collect(),
+            _ => Err(ValueError::Mismatch("a list")),
+        }
+    }
+}
+
+/// Responses only: errors are shown as `Value::Error`
+impl<T: CommandValue, E: fmt::Display> CommandValue for Result<T, E> {
+    fn kind() -> ValueKind {
+        T::kind()
+    }
+
+    fn to_value(&self) -> Value {
+        match self {
+            Ok(value) => value.to_value(),
+            Err(err) => Value::Error(err.to_string()),
+        }
+    }
+
+    fn from_value(_value: Value) -> Result<Self, ValueError> {
+        Err(ValueError::NotAnArgument("result"))
+    }
+}
+
+/// Configurations go through TOML, like in the settings store
+impl<T: ModuleConfig> CommandValue for T {
+    fn kind() -> ValueKind {
+        ValueKind::Config(T::NAME)
+    }
+
+    fn to_value(&self) -> Value {
+        match toml::to_string_pretty(self) {
+            Ok(toml) => Value::Text(toml),
+            Err(err) => Value::Error(err.to_string()),
+        }
+    }
+
+    fn from_value(value: Value) -> Result<Self, ValueError> {
+        match value {
+            Value::Text(toml) => {
+                toml::from_str(&toml).map_err(|err| ValueError::Invalid(T::NAME, err.to_string()))
+            }
+            _ => Err(ValueError::Mismatch("a configuration")),
+        }
+    }
+}
+
+/// Quantities are exchanged as numbers in the display unit of their `QuantityKind`
+macro_rules! quantity_values {
+    ($($quantity:ident => $kind:ident, $unit:ident);* $(;)?) => {
+        $(
+            impl CommandValue for $quantity {
+                fn kind() -> ValueKind {
+                    ValueKind::Quantity(QuantityKind::$kind)
+                }
+
+                fn to_value(&self) -> Value {
+                    Value::Number(self.get::<$unit>())
+                }
+
+                fn from_value(value: Value) -> Result<Self, ValueError> {
+                    f64::from_value(value).map(<$quantity>::new::<$unit>)
+                }
+            }
+        )*
+    };
+}
+
+quantity_values! {
+    ElectricPotential => Voltage, volt;
+    ElectricCurrent => Current, ampere;
+    ThermodynamicTemperature => Temperature, degree_celsius;
+    Power => Power, watt;
+    Energy => Energy, watt_hour;
+    ElectricCharge => Charge, milliampere_hour;
+    Time => Duration, second;
+    Ratio => Ratio, percent;
+}
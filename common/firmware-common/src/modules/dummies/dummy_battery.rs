@@ -1,150 +1,537 @@
 use crate::{
-    command_match,
+    comms::i2c_protocol::I2CMessage,
     modules::{
-        battery::BatteryData,
-        commands::BatteryModuleCommands,
+        balancing::{BalancingConfig, BalancingDecision, BalancingEngine, CellReading},
+        battery::{BatteryConfig, BatteryData},
+        charger::ChargeSetpoint,
+        commands::{self, BatteryModuleCommands},
+        derating::{DeratingChange, DeratingMonitor},
+        identity::{HardwareUid, ModuleIdentity, IDENTIFY_COMMAND_ID},
         module::{
-            Module, ModuleCommandExecutionError, ModuleCommandExecutionResponse, ModuleKind,
-            ModuleMetadata,
+            command_handlers, CommandHandlers, Module, ModuleCommandExecutionError,
+            ModuleCommandExecutionResponse, ModuleKind, ModuleMetadata,
         },
-        system_controller::{CriticalEvent, SystemController},
+        runtime::RuntimeEstimator,
+        system_controller::{CriticalEvent, LogEntry, ModuleEvent, SystemController},
+        telemetry::{
+            QuantityKind, Severity, TelemetryField, TelemetryReport, TelemetrySchema,
+            TelemetryValue,
+        },
+        thresholds::{Limit, ThresholdError, ThresholdMonitor, ThresholdProfile},
+    },
+    settings::{self, SettingsError},
+    simulation::{
+        faults::{FaultInjector, Sensor},
+        PackState, SimEnvironment, Simulated,
     },
 };
 use anyhow::Result;
-use std::{sync::Arc, time::Instant};
+use log::{error, info};
+use std::{sync::Arc, time::Duration};
+use stratum_ui_common::ui_logging::LogLevel;
+use uom::si::electric_charge::milliampere_hour;
 use uom::si::electric_current::ampere;
-use uom::si::electric_potential::volt;
-use uom::si::f64::{ElectricCurrent, ElectricPotential, ThermodynamicTemperature};
+use uom::si::electric_potential::{millivolt, volt};
+use uom::si::f64::{
+    ElectricCharge, ElectricCurrent, ElectricPotential, Ratio, ThermodynamicTemperature,
+};
+use uom::si::ratio::percent;
 use uom::si::thermodynamic_temperature::degree_celsius;
 
+/// Number of cells in series in the pack
+const CELL_COUNT: usize = 5;
+
+/// Capacity of each cell, in mAh. Cells never match exactly, which is what makes the pack
+/// drift out of balance over cycles.
+const CELL_CAPACITIES_MAH: [f64; CELL_COUNT] = [6000.0, 5880.0, 6090.0, 5950.0, 6030.0];
+
+/// Charge level of each cell when the module is created
+const INITIAL_CELL_CHARGE: [f64; CELL_COUNT] = [0.99, 0.97, 1.0, 0.98, 0.995];
+
+/// Temperature of each cell relative to the pack average, in °C; middle cells run hotter
+const CELL_TEMPERATURE_OFFSETS_C: [f64; CELL_COUNT] = [-0.8, 0.2, 1.0, 0.2, -0.6];
+
+/// Current the active balancer moves out of a balancing cell, in amperes
+const BALANCE_CURRENT_A: f64 = 0.2;
+
+/// Fraction of the charge taken from a balancing cell that reaches the other cells
+const BALANCE_EFFICIENCY: f64 = 0.85;
+
+/// Temperature rise of a cell while it is being balanced, in °C
+const BALANCE_HEATING_C: f64 = 2.0;
+
+/// Temperature rise above ambient per A² of load current once thermally settled, in °C
+const SELF_HEATING_C_PER_A2: f64 = 1.5;
+
+/// Thermal time constant of the pack, in hours
+const THERMAL_TIME_CONSTANT_H: f64 = 10.0 / 60.0;
+
+/// Internal resistance of the pack, in ohms
+const INTERNAL_RESISTANCE_OHM: f64 = 0.1;
+
+/// One simulated cell of the pack
+#[derive(Debug, Clone)]
+struct SimCell {
+    capacity_mah: f64,
+    state_of_charge: f64,
+    temperature_offset_c: f64,
+    balancing: bool,
+}
+
+impl SimCell {
+    /// **Terminal voltage of the cell**, open-circuit voltage linear between 3.0 V and 4.2 V
+    fn voltage(&self, current: f64) -> f64 {
+        3.0 + self.state_of_charge * 1.2 + current * INTERNAL_RESISTANCE_OHM / CELL_COUNT as f64
+    }
+}
+
 pub struct DummyBatteryModule {
     id: u16,
+    identity: ModuleIdentity,
+    /// True physical state of the simulated pack
     data: BatteryData,
-    last_update: Instant,
+    /// What the module's sensors report, i.e. `data` as seen through any injected sensor faults
+    readings: BatteryData,
+    config: BatteryConfig,
+    faults: FaultInjector,
+    /// Charge level as a fraction of capacity (0.0-1.0), kept at full precision so that
+    /// many small updates are not lost to `BatteryData::charge` rounding. Average of the cells.
+    state_of_charge: f64,
+    cells: Vec<SimCell>,
+    /// Per-cell voltages and temperatures as measured on the last update
+    cell_readings: Vec<CellReading>,
+    balancer: BalancingEngine,
+    discharge_derating: DeratingMonitor,
+    /// Warning and error limits from `config.thresholds`, with their hysteresis state
+    thresholds: ThresholdMonitor,
+    runtime: RuntimeEstimator,
+    /// Current drawn from the output by the simulated loads
+    load: ElectricCurrent,
+    /// Limits of the charger connected to the pack, if it is charging
+    charge_supply: Option<ChargeSetpoint>,
+    ambient_temperature: ThermodynamicTemperature,
+    last_update: Duration,
     system_controller: Option<Arc<SystemController>>,
 }
 
 impl DummyBatteryModule {
-    pub fn new(id: u16) -> Self {
+    /// A simulated battery plugged into `slot`, using the slot number as its serial number
+    pub fn new(slot: u8) -> Self {
+        Self::with_identity(ModuleIdentity::new(
+            HardwareUid::simulated(ModuleKind::Battery, slot.into()),
+            slot,
+        ))
+    }
+
+    pub fn with_identity(identity: ModuleIdentity) -> Self {
+        let cells: Vec<SimCell> = (0..CELL_COUNT)
+            .map(|cell| SimCell {
+                capacity_mah: CELL_CAPACITIES_MAH[cell],
+                state_of_charge: INITIAL_CELL_CHARGE[cell],
+                temperature_offset_c: CELL_TEMPERATURE_OFFSETS_C[cell],
+                balancing: false,
+            })
+            .collect();
+        let state_of_charge =
+            cells.iter().map(|cell| cell.state_of_charge).sum::<f64>() / CELL_COUNT as f64;
+
+        let data = BatteryData {
+            charge: (state_of_charge * 100.0).round() as u8,
+            voltage: ElectricPotential::new::<volt>(
+                cells.iter().map(|cell| cell.voltage(0.0)).sum(),
+            ),
+            current: ElectricCurrent::new::<ampere>(0.0),
+            temperature: ThermodynamicTemperature::new::<degree_celsius>(25.0),
+            output_enabled: true,
+        };
+
         Self {
-            data: BatteryData {
-                charge: 100,
-                voltage: ElectricPotential::new::<volt>(21.0),
-                current: ElectricCurrent::new::<ampere>(0.0),
-                temperature: ThermodynamicTemperature::new::<degree_celsius>(25.0),
-                output_enabled: true,
-            },
-            id,
-            last_update: Instant::now(),
+            readings: data.clone(),
+            data,
+            config: BatteryConfig::default(),
+            faults: FaultInjector::default(),
+            id: identity.module_id(),
+            identity,
+            state_of_charge,
+            cells,
+            cell_readings: Vec::new(),
+            balancer: BalancingEngine::new(BalancingConfig::default()),
+            discharge_derating: DeratingMonitor::new(),
+            thresholds: ThresholdMonitor::default(),
+            runtime: RuntimeEstimator::new(),
+            load: ElectricCurrent::new::<ampere>(0.5),
+            charge_supply: None,
+            ambient_temperature: ThermodynamicTemperature::new::<degree_celsius>(25.0),
+            last_update: Duration::ZERO,
             system_controller: None,
         }
     }
 
-    fn is_charging(&self) -> bool {
-        self.data.current.is_sign_positive()
-            && self.data.voltage > ElectricPotential::new::<volt>(18.5)
+    /// **Open-circuit voltage of the pack**, linear between 3.0 V and 4.2 V per cell
+    fn open_circuit_voltage(&self) -> f64 {
+        self.cells.iter().map(|cell| cell.voltage(0.0)).sum()
     }
 
     /// **Helper function to update charge & voltage**
     fn update_charge_and_voltage(&mut self, delta_time: f64) {
         let mut net_current = ElectricCurrent::new::<ampere>(0.0);
 
-        if self.data.output_enabled {
-            net_current += ElectricCurrent::new::<ampere>(-0.5);
+        if let Some(forced) = self.faults.forced_current() {
+            net_current -= forced.abs();
+        } else if self.data.output_enabled {
+            net_current -= self.load;
+        }
+
+        // The charger regulates the terminal voltage: the pack takes whatever current brings
+        // it up to the voltage limit, capped by the current limit
+        if let Some(supply) = &self.charge_supply {
+            let headroom = (supply.voltage_limit.get::<volt>() - self.open_circuit_voltage())
+                / INTERNAL_RESISTANCE_OHM;
+            net_current += ElectricCurrent::new::<ampere>(
+                headroom.clamp(0.0, supply.current_limit.get::<ampere>()),
+            );
         }
 
-        if self.is_charging() && self.data.charge < 100 {
-            net_current += ElectricCurrent::new::<ampere>(1.5);
+        // The pack current flows through every cell in series, while the active balancer
+        // moves charge from the balancing cells to the others
+        let current = net_current.get::<ampere>();
+        let balancing = self.cells.iter().filter(|cell| cell.balancing).count();
+        let moved_mah = BALANCE_CURRENT_A * delta_time * 1000.0;
+        let received_mah = if balancing < CELL_COUNT {
+            moved_mah * balancing as f64 * BALANCE_EFFICIENCY / (CELL_COUNT - balancing) as f64
+        } else {
+            0.0
+        };
+
+        for cell in &mut self.cells {
+            let mut net_mah = current * delta_time * 1000.0;
+            if cell.balancing {
+                net_mah -= moved_mah;
+            } else {
+                net_mah += received_mah;
+            }
+
+            cell.state_of_charge =
+                (cell.state_of_charge + net_mah / cell.capacity_mah).clamp(0.0, 1.0);
         }
 
-        let net_mah = net_current.get::<ampere>() * delta_time * 1000.0;
-        let percent_change = (net_mah / 6000.0) * 100.0;
-        self.data.charge = (self.data.charge as f64 + percent_change).clamp(0.0, 100.0) as u8;
+        self.state_of_charge = self
+            .cells
+            .iter()
+            .map(|cell| cell.state_of_charge)
+            .sum::<f64>()
+            / CELL_COUNT as f64;
+        self.data.charge = (self.state_of_charge * 100.0).round() as u8;
 
-        self.data.voltage =
-            ElectricPotential::new::<volt>(15.0 + (self.data.charge as f64 / 100.0) * 6.0);
+        self.data.voltage = ElectricPotential::new::<volt>(
+            self.cells.iter().map(|cell| cell.voltage(current)).sum(),
+        );
         self.data.current = net_current;
 
-        if self.data.charge < 10 {
+        if self.data.charge < self.config.output_cutoff_pct {
             self.data.output_enabled = false;
         }
     }
 
-    /// **Detects warnings and critical errors**
-    fn detect_warnings_and_errors(&self) -> (Vec<BatteryModuleWarning>, Vec<BatteryModuleError>) {
-        let mut warnings = Vec::new();
-        let mut errors = Vec::new();
+    /// **Helper function to update pack temperature**
+    ///
+    /// First-order model: the pack heats towards ambient plus I²-proportional self-heating.
+    fn update_temperature(&mut self, delta_time: f64) {
+        if let Some(forced) = self.faults.forced_temperature() {
+            self.data.temperature = forced;
+            return;
+        }
 
-        let charge = self.data.charge;
-        let voltage = self.data.voltage.get::<volt>();
         let current = self.data.current.get::<ampere>();
+        let ambient = self.ambient_temperature.get::<degree_celsius>();
+        let target = ambient + SELF_HEATING_C_PER_A2 * current * current;
+
         let temperature = self.data.temperature.get::<degree_celsius>();
+        let blend = 1.0 - (-delta_time / THERMAL_TIME_CONSTANT_H).exp();
+
+        self.data.temperature = ThermodynamicTemperature::new::<degree_celsius>(
+            temperature + (target - temperature) * blend,
+        );
+    }
+
+    /// **Samples the per-cell voltage and temperature sensors**
+    fn read_cells(&self) -> Vec<CellReading> {
+        let current = self.data.current.get::<ampere>();
+        let pack_temperature = self.data.temperature.get::<degree_celsius>();
+
+        self.cells
+            .iter()
+            .map(|cell| {
+                let heating = if cell.balancing {
+                    BALANCE_HEATING_C
+                } else {
+                    0.0
+                };
 
-        if current.abs() > 4.5 {
-            warnings.push(BatteryModuleWarning::HighCurrentDraw);
+                CellReading {
+                    voltage: ElectricPotential::new::<volt>(cell.voltage(current)),
+                    temperature: ThermodynamicTemperature::new::<degree_celsius>(
+                        pack_temperature + cell.temperature_offset_c + heating,
+                    ),
+                }
+            })
+            .collect()
+    }
+
+    /// **Runs the balancing engine on the latest cell readings and logs what it decided**
+    fn update_balancing(&mut self, controller: &SystemController, now: Duration) {
+        self.cell_readings = self.read_cells();
+
+        let Some(decision) = self.balancer.evaluate(&self.cell_readings, now) else {
+            return;
+        };
+
+        for (cell, balance) in self.cells.iter_mut().zip(&decision.balance) {
+            cell.balancing = *balance;
         }
-        if temperature > 40.0 {
-            warnings.push(BatteryModuleWarning::HighTemperature);
+
+        let message = format!("Battery {}: {}", self.id, decision);
+        info!("{message}");
+        controller.log_module_event(
+            self.id,
+            LogEntry {
+                timestamp: now,
+                level: LogLevel::Info,
+                message,
+            },
+        );
+    }
+
+    /// **Derates the discharge limit for the measured pack temperature**
+    fn update_derating(&mut self, controller: &SystemController) {
+        let factor = self
+            .config
+            .discharge_derating
+            .factor_at(self.readings.temperature.get::<degree_celsius>());
+
+        let Some(change) = self.discharge_derating.update(factor) else {
+            return;
+        };
+
+        let message = format!(
+            "Battery {}: discharge current {} at {:.1} °C",
+            self.id,
+            change,
+            self.readings.temperature.get::<degree_celsius>()
+        );
+        controller.emit_event(match change {
+            DeratingChange::Recovered => ModuleEvent::Info(message),
+            _ => ModuleEvent::Warning(message),
+        });
+    }
+
+    pub fn balancer(&self) -> &BalancingEngine {
+        &self.balancer
+    }
+
+    pub fn cell_readings(&self) -> &[CellReading] {
+        &self.cell_readings
+    }
+
+    pub fn thresholds(&self) -> &ThresholdMonitor {
+        &self.thresholds
+    }
+
+    /// **Persists and applies a new balancing configuration**
+    fn apply_balancing_config(&mut self, config: BalancingConfig) -> Result<(), SettingsError> {
+        if let Some(controller) = &self.system_controller {
+            controller.settings().save(self.id, &config)?;
         }
-        if charge < 15 {
-            warnings.push(BatteryModuleWarning::LowBattery);
+
+        self.balancer.set_config(config);
+        Ok(())
+    }
+
+    /// **Samples the sensors, applying any injected sensor faults**
+    fn read_sensors(&mut self) -> BatteryData {
+        let voltage = self
+            .faults
+            .read_sensor(Sensor::Voltage, self.data.voltage.get::<volt>())
+            + self.config.voltage_offset_v;
+        let current = self
+            .faults
+            .read_sensor(Sensor::Current, self.data.current.get::<ampere>())
+            * self.config.current_gain;
+        let temperature = self.faults.read_sensor(
+            Sensor::Temperature,
+            self.data.temperature.get::<degree_celsius>(),
+        );
+
+        BatteryData {
+            voltage: ElectricPotential::new::<volt>(voltage),
+            current: ElectricCurrent::new::<ampere>(current),
+            temperature: ThermodynamicTemperature::new::<degree_celsius>(temperature),
+            ..self.data.clone()
         }
+    }
 
-        if current.abs() > 5.0 {
-            errors.push(BatteryModuleError::Overcurrent);
+    /// **Charge the pack can still deliver**, limited by its emptiest cell
+    fn remaining_capacity_mah(&self) -> f64 {
+        self.cells
+            .iter()
+            .map(|cell| cell.state_of_charge * cell.capacity_mah)
+            .fold(f64::INFINITY, f64::min)
+    }
+
+    /// **Charge of the pack when full**, limited by its smallest cell
+    fn full_capacity_mah(&self) -> f64 {
+        self.cells
+            .iter()
+            .map(|cell| cell.capacity_mah)
+            .fold(f64::INFINITY, f64::min)
+    }
+
+    /// **Most current the pack can supply continuously without raising a warning**
+    fn max_discharge_current(&self) -> ElectricCurrent {
+        if !self.data.output_enabled || self.faults.is_removed() {
+            return ElectricCurrent::new::<ampere>(0.0);
         }
-        if voltage < 15.0 {
-            errors.push(BatteryModuleError::Undervoltage);
+
+        let limit = self
+            .config
+            .thresholds
+            .get("current")
+            .and_then(|threshold| threshold.high_warning.or(threshold.high_error))
+            .unwrap_or(0.0);
+
+        ElectricCurrent::new::<ampere>(limit * self.discharge_derating.factor())
+    }
+
+    /// **Evaluates the readings against the threshold profile and reports what changed**
+    fn update_thresholds(&mut self, controller: &SystemController, now: Duration) {
+        let values = [
+            ("current", self.readings.current.get::<ampere>().abs()),
+            (
+                "temperature",
+                self.readings.temperature.get::<degree_celsius>(),
+            ),
+            ("voltage", self.readings.voltage.get::<volt>()),
+            ("charge", self.readings.charge as f64),
+        ];
+
+        for (key, value) in values {
+            let Some(change) = self.thresholds.update(key, value, now) else {
+                continue;
+            };
+
+            let message = format!("Battery {}: {}", self.id, change);
+            let Some(limit) = change.to else {
+                controller.clear_alarm(self.id, key);
+                controller.emit_event(ModuleEvent::Info(message));
+                continue;
+            };
+
+            let event = match (key, limit) {
+                ("current", Limit::HighError) => {
+                    ModuleEvent::Critical(CriticalEvent::OverCurrent(self.readings.current))
+                }
+                ("voltage", Limit::LowError) => {
+                    ModuleEvent::Critical(CriticalEvent::UnderVoltage(self.readings.voltage))
+                }
+                ("voltage", Limit::HighError) => {
+                    ModuleEvent::Critical(CriticalEvent::OverVoltage(self.readings.voltage))
+                }
+                ("temperature", Limit::HighError) => {
+                    ModuleEvent::Critical(CriticalEvent::OverTemperature(self.readings.temperature))
+                }
+                _ if limit.severity() == Severity::Critical => {
+                    ModuleEvent::Critical(CriticalEvent::ModuleFailure(message))
+                }
+                _ => ModuleEvent::Warning(message),
+            };
+            controller.raise_alarm(self.id, key, event);
         }
-        if temperature > 50.0 {
-            errors.push(BatteryModuleError::Overheating);
+    }
+
+    /// **Warnings and critical errors currently raised by the threshold monitor**
+    fn detect_warnings_and_errors(&self) -> (Vec<BatteryModuleWarning>, Vec<BatteryModuleError>) {
+        let mut warnings = Vec::new();
+        let mut errors = Vec::new();
+
+        for (key, limit) in self.thresholds.active_limits() {
+            match (key, limit) {
+                ("current", Limit::HighWarning) => {
+                    warnings.push(BatteryModuleWarning::HighCurrentDraw)
+                }
+                ("current", Limit::HighError) => errors.push(BatteryModuleError::Overcurrent),
+                ("temperature", Limit::HighWarning) => {
+                    warnings.push(BatteryModuleWarning::HighTemperature)
+                }
+                ("temperature", Limit::HighError) => errors.push(BatteryModuleError::Overheating),
+                ("voltage", Limit::LowError) => errors.push(BatteryModuleError::Undervoltage),
+                ("voltage", Limit::HighError) => errors.push(BatteryModuleError::Overvoltage),
+                ("charge", Limit::LowWarning | Limit::LowError) => {
+                    warnings.push(BatteryModuleWarning::LowBattery)
+                }
+                _ => {}
+            }
         }
 
         (warnings, errors)
     }
 
+    /// **Persists and applies a new configuration**
+    fn apply_config(&mut self, config: BatteryConfig) -> Result<(), SettingsError> {
+        if let Some(controller) = &self.system_controller {
+            controller.settings().save(self.id, &config)?;
+        }
+
+        self.set_threshold_profile(config.thresholds.clone());
+        self.config = config;
+        Ok(())
+    }
+
+    /// **Switches the threshold profile**, clearing the alarms of values it no longer covers
+    fn set_threshold_profile(&mut self, profile: ThresholdProfile) {
+        let dropped = self.thresholds.set_profile(profile);
+        if let Some(controller) = &self.system_controller {
+            for (key, _) in dropped {
+                controller.clear_alarm(self.id, &key);
+            }
+        }
+    }
+
     /// **Refactored update_state function**
     pub fn update_state(&mut self) {
-        let now = Instant::now();
-        let delta_time = now.duration_since(self.last_update).as_secs_f64() / 3600.0;
+        let controller = self.system_controller.clone().unwrap();
+
+        let now = controller.now();
+        let delta_time = now.saturating_sub(self.last_update).as_secs_f64() / 3600.0;
         self.last_update = now;
 
-        self.update_charge_and_voltage(delta_time);
+        // A module that has been pulled out neither changes nor reports anything
+        if self.faults.is_removed() {
+            return;
+        }
 
-        let controller = self.system_controller.clone().unwrap();
+        self.update_charge_and_voltage(delta_time);
+        self.update_temperature(delta_time);
 
-        // 🔥 Trigger Events for Critical Failures
-        let (_, errors) = self.detect_warnings_and_errors();
-        for error in errors {
-            match error {
-                BatteryModuleError::Overcurrent => {
-                    controller.emit_event(crate::modules::system_controller::ModuleEvent::Critical(
-                        CriticalEvent::OverCurrent(self.data.current),
-                    ))
-                }
-                BatteryModuleError::Undervoltage => {
-                    controller.emit_event(crate::modules::system_controller::ModuleEvent::Critical(
-                        CriticalEvent::UnderVoltage(self.data.voltage),
-                    ))
-                }
-                BatteryModuleError::Overheating => {
-                    controller.emit_event(crate::modules::system_controller::ModuleEvent::Critical(
-                        CriticalEvent::OverTemperature(self.data.temperature),
-                    ))
-                }
-                BatteryModuleError::Overvoltage => {
-                    controller.emit_event(
-                        crate::modules::system_controller::ModuleEvent::Critical(
-                            CriticalEvent::OverVoltage(self.data.voltage),
-                        ),
-                    );
-                }
-            }
+        // A hung module no longer samples its sensors or acts on them, although the pack
+        // itself carries on
+        if self.faults.is_hung() {
+            return;
         }
+
+        self.readings = self.read_sensors();
+        self.update_balancing(&controller, now);
+        self.update_derating(&controller);
+        self.runtime.update(
+            self.readings.current,
+            self.remaining_capacity_mah(),
+            self.full_capacity_mah(),
+            now,
+        );
+        self.update_thresholds(&controller, now);
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, PartialEq)]
 pub enum BatteryModuleError {
     Overcurrent,
     Overvoltage,
@@ -152,7 +539,7 @@ pub enum BatteryModuleError {
     Overheating,
 }
 
-#[derive(Debug)]
+#[derive(Debug, PartialEq)]
 pub enum BatteryModuleWarning {
     HighCurrentDraw,
     HighTemperature,
@@ -166,7 +553,95 @@ pub struct BatteryModuleStatus {
     pub temperature: ThermodynamicTemperature,
     pub warnings: Vec<BatteryModuleWarning>,
     pub errors: Vec<BatteryModuleError>,
-    pub last_updated: Instant,
+    pub last_updated: Duration,
+}
+
+impl Simulated for DummyBatteryModule {
+    fn apply_environment(&mut self, environment: &SimEnvironment) {
+        self.load = environment.total_load();
+        self.ambient_temperature = environment.ambient_temperature;
+        self.charge_supply = environment.charge_supply;
+    }
+
+    fn pack_state(&self) -> Option<PackState> {
+        if self.faults.is_removed() {
+            return None;
+        }
+
+        Some(PackState {
+            voltage: self.data.voltage,
+            current: self.data.current,
+            temperature: self.data.temperature,
+        })
+    }
+
+    fn faults(&self) -> &FaultInjector {
+        &self.faults
+    }
+
+    fn faults_mut(&mut self) -> &mut FaultInjector {
+        &mut self.faults
+    }
+}
+
+#[command_handlers(commands::BatteryModuleCommands)]
+impl DummyBatteryModule {
+    fn set_output(&mut self, state: bool) {
+        self.data.output_enabled = state;
+    }
+
+    fn get_voltage(&self) -> ElectricPotential {
+        ElectricPotential::new::<volt>(0.0)
+    }
+
+    fn get_cell_readings(&self) -> Vec<CellReading> {
+        self.cell_readings.clone()
+    }
+
+    fn get_config(&self) -> BatteryConfig {
+        self.config.clone()
+    }
+
+    fn set_config(&mut self, config: BatteryConfig) -> Result<(), SettingsError> {
+        self.apply_config(config)
+    }
+
+    fn set_config_value(&mut self, key: String, value: String) -> Result<(), SettingsError> {
+        settings::with_field(&self.config, &key, &value)
+            .and_then(|config| self.apply_config(config))
+    }
+
+    fn reset_config(&mut self) -> Result<(), SettingsError> {
+        if let Some(controller) = &self.system_controller {
+            controller.settings().reset::<BatteryConfig>(self.id)?;
+        }
+        self.config = BatteryConfig::default();
+        self.set_threshold_profile(self.config.thresholds.clone());
+        Ok(())
+    }
+
+    fn load_threshold_profile(&mut self, name: String) -> Result<(), ThresholdError> {
+        let thresholds = ThresholdProfile::builtin(&name)?;
+        self.apply_config(BatteryConfig {
+            thresholds,
+            ..self.config.clone()
+        })?;
+        Ok(())
+    }
+
+    fn get_balancing_decision(&self) -> Option<BalancingDecision> {
+        self.balancer.decision().cloned()
+    }
+
+    fn get_balancing_config(&self) -> BalancingConfig {
+        self.balancer.config().clone()
+    }
+
+    fn set_balancing_config(&mut self, config: BalancingConfig) -> Result<(), SettingsError> {
+        self.apply_balancing_config(config)
+    }
+
+    fn dummy(&mut self) {}
 }
 
 impl Module for DummyBatteryModule {
@@ -182,35 +657,326 @@ impl Module for DummyBatteryModule {
         }
     }
 
+    fn identify(&mut self) -> Result<ModuleIdentity, ModuleCommandExecutionError> {
+        let payload = self.identity.to_payload();
+        self.faults.transmit_reply(&I2CMessage::new(
+            self.identity.address(),
+            IDENTIFY_COMMAND_ID,
+            &payload,
+        ))?;
+
+        Ok(self.identity)
+    }
+
     fn process_command(&mut self, command: Self::ModuleCommand) -> ModuleCommandExecutionResponse {
-        command_match!(command, BatteryModuleCommands,
-            SetOutput { state } => {
-                dbg!(state);
-            },
-            GetVoltage {} => ElectricPotential::new::<volt>(0.0),
-            Dummy => (),
-        )
+        // Route an (empty) acknowledgement frame through the simulated bus so that dropped or
+        // corrupted replies surface exactly as they would on hardware. Local commands never
+        // reach the module.
+        if !command.is_local() {
+            self.faults.transmit_reply(&I2CMessage::new(
+                self.identity.address(),
+                command.command_id(),
+                &[],
+            ))?;
+        }
+
+        CommandHandlers::process_command(self, command)
     }
 
     fn status(&self) -> Self::ModuleStatus {
         let (warnings, errors) = self.detect_warnings_and_errors();
 
         BatteryModuleStatus {
-            charge: self.data.charge,
-            voltage: self.data.voltage,
-            current: self.data.current,
-            temperature: self.data.temperature,
+            charge: self.readings.charge,
+            voltage: self.readings.voltage,
+            current: self.readings.current,
+            temperature: self.readings.temperature,
             warnings,
             errors,
             last_updated: self.last_update,
         }
     }
 
+    fn telemetry_schema(&self) -> TelemetrySchema {
+        TelemetrySchema {
+            module_kind: ModuleKind::Battery,
+            fields: vec![
+                TelemetryField::new("charge", "Charge", QuantityKind::Ratio),
+                TelemetryField::new("voltage", "Voltage", QuantityKind::Voltage),
+                TelemetryField::new("current", "Current", QuantityKind::Current),
+                TelemetryField::new("temperature", "Temperature", QuantityKind::Temperature),
+                TelemetryField::new("output_enabled", "Output", QuantityKind::Boolean),
+                TelemetryField::new(
+                    "max_discharge_current",
+                    "Max Discharge Current",
+                    QuantityKind::Current,
+                ),
+                TelemetryField::new(
+                    "remaining_capacity",
+                    "Remaining Capacity",
+                    QuantityKind::Charge,
+                ),
+                TelemetryField::new("average_current", "Average Current", QuantityKind::Current),
+                TelemetryField::new("time_to_empty", "Time to Empty", QuantityKind::Duration),
+                TelemetryField::new("time_to_full", "Time to Full", QuantityKind::Duration),
+                TelemetryField::new(
+                    "runtime_confidence",
+                    "Estimate Confidence",
+                    QuantityKind::Ratio,
+                ),
+                TelemetryField::new(
+                    "discharge_derating",
+                    "Discharge Derating",
+                    QuantityKind::Ratio,
+                ),
+                TelemetryField::new("cell_spread", "Cell Spread", QuantityKind::Voltage),
+                TelemetryField::new("balancing_cells", "Balancing Cells", QuantityKind::Count),
+            ]
+            .into_iter()
+            .chain((1..=CELL_COUNT).flat_map(|cell| {
+                [
+                    TelemetryField::new(
+                        format!("cell_{cell}_voltage"),
+                        format!("Cell {cell} Voltage"),
+                        QuantityKind::Voltage,
+                    ),
+                    TelemetryField::new(
+                        format!("cell_{cell}_temperature"),
+                        format!("Cell {cell} Temperature"),
+                        QuantityKind::Temperature,
+                    ),
+                    TelemetryField::new(
+                        format!("cell_{cell}_balancing"),
+                        format!("Cell {cell} Balancing"),
+                        QuantityKind::Boolean,
+                    ),
+                ]
+            }))
+            .collect(),
+        }
+    }
+
+    fn telemetry(&self) -> TelemetryReport {
+        let mut report = TelemetryReport::new(self.id, self.last_update)
+            .with(
+                "charge",
+                TelemetryValue::Ratio(Ratio::new::<percent>(self.readings.charge as f64)),
+                self.thresholds.severity("charge"),
+            )
+            .with(
+                "voltage",
+                TelemetryValue::Voltage(self.readings.voltage),
+                self.thresholds.severity("voltage"),
+            )
+            .with(
+                "current",
+                TelemetryValue::Current(self.readings.current),
+                self.thresholds.severity("current"),
+            )
+            .with(
+                "temperature",
+                TelemetryValue::Temperature(self.readings.temperature),
+                self.thresholds.severity("temperature"),
+            )
+            .with(
+                "output_enabled",
+                TelemetryValue::Boolean(self.data.output_enabled),
+                Severity::Normal,
+            )
+            .with(
+                "max_discharge_current",
+                TelemetryValue::Current(self.max_discharge_current()),
+                Severity::Normal,
+            )
+            .with(
+                "remaining_capacity",
+                TelemetryValue::Charge(ElectricCharge::new::<milliampere_hour>(
+                    self.remaining_capacity_mah(),
+                )),
+                self.thresholds.severity("charge"),
+            )
+            .with(
+                "discharge_derating",
+                TelemetryValue::Ratio(Ratio::new::<percent>(
+                    self.discharge_derating.factor() * 100.0,
+                )),
+                if self.discharge_derating.is_active() {
+                    Severity::Warning
+                } else {
+                    Severity::Normal
+                },
+            );
+
+        self.runtime.estimate().report(&mut report);
+
+        if let Some(decision) = self.balancer.decision() {
+            report.push(
+                "cell_spread",
+                TelemetryValue::Voltage(decision.spread),
+                if decision.spread.get::<millivolt>() > self.balancer.config().start_delta_mv {
+                    Severity::Warning
+                } else {
+                    Severity::Normal
+                },
+            );
+            report.push(
+                "balancing_cells",
+                TelemetryValue::Count(decision.balancing_cells().count() as i64),
+                Severity::Normal,
+            );
+        }
+
+        for (cell, (reading, state)) in self.cell_readings.iter().zip(&self.cells).enumerate() {
+            let cell = cell + 1;
+            report.push(
+                format!("cell_{cell}_voltage"),
+                TelemetryValue::Voltage(reading.voltage),
+                Severity::Normal,
+            );
+            report.push(
+                format!("cell_{cell}_temperature"),
+                TelemetryValue::Temperature(reading.temperature),
+                if reading.temperature.get::<degree_celsius>()
+                    > self.balancer.config().max_cell_temperature_c
+                {
+                    Severity::Warning
+                } else {
+                    Severity::Normal
+                },
+            );
+            report.push(
+                format!("cell_{cell}_balancing"),
+                TelemetryValue::Boolean(state.balancing),
+                Severity::Normal,
+            );
+        }
+
+        report
+    }
+
+    fn update(&mut self) {
+        self.update_state();
+    }
+
+    fn reset(&mut self) -> Result<(), ModuleCommandExecutionError> {
+        self.faults.reset();
+        self.faults
+            .transmit_reply(&I2CMessage::new(self.identity.address(), 0, &[]))?;
+        Ok(())
+    }
+
+    fn enter_safe_state(&mut self) {
+        self.data.output_enabled = false;
+        for cell in &mut self.cells {
+            cell.balancing = false;
+        }
+    }
+
+    fn simulated(&mut self) -> Option<&mut dyn Simulated> {
+        Some(self)
+    }
+
     fn initialize(
         &mut self,
         system_controller: Arc<SystemController>,
     ) -> Result<(), ModuleCommandExecutionError> {
+        self.last_update = system_controller.now();
+
+        self.config = system_controller
+            .settings()
+            .load(self.id)
+            .unwrap_or_else(|err| {
+                error!(
+                    "Unable to load battery {} config, using defaults: {}",
+                    self.id, err
+                );
+                BatteryConfig::default()
+            });
+        self.set_threshold_profile(self.config.thresholds.clone());
+
+        let balancing = system_controller
+            .settings()
+            .load(self.id)
+            .unwrap_or_else(|err| {
+                error!(
+                    "Unable to load battery {} balancing config, using defaults: {}",
+                    self.id, err
+                );
+                BalancingConfig::default()
+            });
+        self.balancer.set_config(balancing);
+
         self.system_controller = Some(system_controller);
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        clock::ManualClock,
+        modules::{alarms::AlarmState, mock::testing, thresholds::Threshold},
+    };
+
+    /// A battery warm enough to cross a 20 °C temperature warning
+    fn warm_battery() -> (Arc<ManualClock>, Arc<SystemController>, DummyBatteryModule) {
+        let (clock, controller, _) = testing::setup();
+        let mut battery = DummyBatteryModule::new(1);
+        battery.initialize(controller.clone()).unwrap();
+
+        let mut config = BatteryConfig::default();
+        config
+            .thresholds
+            .thresholds
+            .insert("temperature".into(), Threshold::high(20.0, 60.0));
+        battery.set_config(config).unwrap();
+        clock.advance(Duration::from_secs(1));
+        battery.update_state();
+        assert_eq!(
+            temperature_alarm(&controller, &battery),
+            Some(AlarmState::Active)
+        );
+
+        (clock, controller, battery)
+    }
+
+    fn temperature_alarm(
+        controller: &SystemController,
+        battery: &DummyBatteryModule,
+    ) -> Option<AlarmState> {
+        controller
+            .alarms()
+            .into_iter()
+            .find(|alarm| alarm.key.module_id == battery.id && alarm.key.condition == "temperature")
+            .map(|alarm| alarm.state)
+    }
+
+    #[test]
+    fn new_profile_clears_the_alarms_it_no_longer_raises() {
+        let (clock, controller, mut battery) = warm_battery();
+        battery.load_threshold_profile("li-ion-5s".into()).unwrap();
+
+        // Cleared through the usual debounce
+        battery.update_state();
+        clock.advance(Duration::from_secs(6));
+        battery.update_state();
+        assert_eq!(
+            temperature_alarm(&controller, &battery),
+            Some(AlarmState::Cleared)
+        );
+    }
+
+    #[test]
+    fn new_profile_clears_the_alarms_of_values_it_drops() {
+        let (_, controller, mut battery) = warm_battery();
+        let mut config = BatteryConfig::default();
+        config.thresholds.thresholds.remove("temperature");
+        battery.set_config(config).unwrap();
+
+        assert_eq!(
+            temperature_alarm(&controller, &battery),
+            Some(AlarmState::Cleared)
+        );
+    }
+}
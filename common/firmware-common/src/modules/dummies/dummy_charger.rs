@@ -0,0 +1,171 @@
+use uom::si::electric_current::ampere;
+use uom::si::electric_potential::volt;
+use uom::si::f64::{ElectricCurrent, ElectricPotential, ThermodynamicTemperature};
+use uom::si::thermodynamic_temperature::degree_celsius;
+
+use crate::{
+    comms::i2c_protocol::I2CMessage,
+    modules::{
+        charger::{ChargeMeasurements, ChargeSetpoint},
+        charger_module::{ChargerHardware, ChargerModule},
+        identity::{HardwareUid, ModuleIdentity, IDENTIFY_COMMAND_ID},
+        module::{ModuleCommandExecutionError, ModuleKind},
+    },
+    simulation::{
+        faults::{FaultInjector, Sensor},
+        PackState, SimEnvironment, Simulated,
+    },
+};
+
+/// A charge input module backed by a simulated power stage
+pub type DummyChargerModule = ChargerModule<SimulatedChargerHardware>;
+
+impl DummyChargerModule {
+    /// A simulated charger plugged into `slot`, using the slot number as its serial number
+    pub fn new(slot: u8) -> Self {
+        let identity = ModuleIdentity::new(
+            HardwareUid::simulated(ModuleKind::Charger, slot.into()),
+            slot,
+        );
+
+        ChargerModule::with_hardware(identity, SimulatedChargerHardware::new(identity))
+    }
+}
+
+/// Simulated CC/CV power stage.
+///
+/// It measures the pack through `SimEnvironment::pack` and hands its setpoint, capped by what
+/// the input source can supply, to the battery through `SimEnvironment::charge_supply`.
+pub struct SimulatedChargerHardware {
+    identity: ModuleIdentity,
+    /// What the source on the charge input can supply, `None` if nothing is plugged in
+    input_current: Option<ElectricCurrent>,
+    pack: Option<PackState>,
+    setpoint: Option<ChargeSetpoint>,
+    faults: FaultInjector,
+}
+
+impl SimulatedChargerHardware {
+    pub fn new(identity: ModuleIdentity) -> Self {
+        Self {
+            identity,
+            input_current: None,
+            pack: None,
+            setpoint: None,
+            faults: FaultInjector::default(),
+        }
+    }
+
+    /// **Routes an (empty) reply through the simulated bus**
+    fn acknowledge(&self, command_id: u8) -> Result<(), ModuleCommandExecutionError> {
+        self.faults
+            .transmit_reply(&I2CMessage::new(self.identity.address(), command_id, &[]))?;
+        Ok(())
+    }
+}
+
+impl ChargerHardware for SimulatedChargerHardware {
+    fn identify(&mut self) -> Result<ModuleIdentity, ModuleCommandExecutionError> {
+        let payload = self.identity.to_payload();
+        self.faults.transmit_reply(&I2CMessage::new(
+            self.identity.address(),
+            IDENTIFY_COMMAND_ID,
+            &payload,
+        ))?;
+
+        Ok(self.identity)
+    }
+
+    fn measure(&mut self) -> Result<ChargeMeasurements, ModuleCommandExecutionError> {
+        self.acknowledge(0)?;
+
+        let input_present = self
+            .input_current
+            .is_some_and(|current| current.get::<ampere>() > 0.0);
+
+        let Some(pack) = self.pack else {
+            return Ok(ChargeMeasurements {
+                input_present,
+                pack_present: false,
+                pack_voltage: ElectricPotential::new::<volt>(0.0),
+                pack_current: ElectricCurrent::new::<ampere>(0.0),
+                pack_temperature: ThermodynamicTemperature::new::<degree_celsius>(0.0),
+            });
+        };
+
+        let temperature = self
+            .faults
+            .forced_temperature()
+            .unwrap_or(pack.temperature)
+            .get::<degree_celsius>();
+
+        Ok(ChargeMeasurements {
+            input_present,
+            pack_present: true,
+            pack_voltage: ElectricPotential::new::<volt>(
+                self.faults
+                    .read_sensor(Sensor::Voltage, pack.voltage.get::<volt>()),
+            ),
+            pack_current: ElectricCurrent::new::<ampere>(
+                self.faults
+                    .read_sensor(Sensor::Current, pack.current.get::<ampere>()),
+            ),
+            pack_temperature: ThermodynamicTemperature::new::<degree_celsius>(
+                self.faults.read_sensor(Sensor::Temperature, temperature),
+            ),
+        })
+    }
+
+    fn apply(
+        &mut self,
+        setpoint: Option<ChargeSetpoint>,
+    ) -> Result<(), ModuleCommandExecutionError> {
+        self.acknowledge(1)?;
+        self.setpoint = setpoint;
+        Ok(())
+    }
+
+    fn reset(&mut self) -> Result<(), ModuleCommandExecutionError> {
+        self.faults.reset();
+        self.setpoint = None;
+        self.acknowledge(0)
+    }
+
+    fn simulated(&mut self) -> Option<&mut dyn Simulated> {
+        Some(self)
+    }
+}
+
+impl Simulated for SimulatedChargerHardware {
+    fn apply_environment(&mut self, environment: &SimEnvironment) {
+        self.input_current = environment.input_current;
+        self.pack = environment.pack;
+    }
+
+    fn charge_supply(&self) -> Option<ChargeSetpoint> {
+        // A hung controller stops refreshing the power stage, which times out on its own
+        if self.faults.is_removed() || self.faults.is_hung() {
+            return None;
+        }
+
+        let input = self.input_current?;
+        let setpoint = self.setpoint?;
+
+        Some(ChargeSetpoint {
+            current_limit: if input < setpoint.current_limit {
+                input
+            } else {
+                setpoint.current_limit
+            },
+            ..setpoint
+        })
+    }
+
+    fn faults(&self) -> &FaultInjector {
+        &self.faults
+    }
+
+    fn faults_mut(&mut self) -> &mut FaultInjector {
+        &mut self.faults
+    }
+}
@@ -0,0 +1,333 @@
+use crate::{
+    comms::i2c_protocol::I2CMessage,
+    modules::{
+        commands::{self, PortModuleCommands},
+        identity::{HardwareUid, ModuleIdentity, IDENTIFY_COMMAND_ID},
+        module::{
+            command_handlers, CommandHandlers, Module, ModuleCommandExecutionError,
+            ModuleCommandExecutionResponse, ModuleKind, ModuleMetadata,
+        },
+        port::PortConfig,
+        system_controller::SystemController,
+        telemetry::{
+            QuantityKind, Severity, TelemetryField, TelemetryReport, TelemetrySchema,
+            TelemetryValue,
+        },
+    },
+    settings::{self, SettingsError},
+    simulation::{
+        faults::{FaultInjector, Sensor},
+        SimEnvironment, Simulated,
+    },
+};
+use log::error;
+use std::{sync::Arc, time::Duration};
+use uom::si::electric_current::ampere;
+use uom::si::f64::{ElectricCurrent, Power};
+use uom::si::power::watt;
+
+/// A simulated output port (USB-C, USB-A, DC barrel, ...).
+///
+/// The port passes through whatever the load plugged into it demands, up to the smaller of
+/// its hardware maximum and the limit set with `SetCurrentLimit`.
+pub struct DummyPortModule {
+    id: u16,
+    identity: ModuleIdentity,
+    /// Physical port number the module is plugged into
+    port: u8,
+    config: PortConfig,
+    output_enabled: bool,
+    /// Limit requested by the power budget (or a user), on top of `config.max_current_a`
+    current_limit: ElectricCurrent,
+    /// Current the plugged-in load wants
+    demand: ElectricCurrent,
+    /// What the current sensor reported on the last update
+    measured_current: ElectricCurrent,
+    faults: FaultInjector,
+    last_update: Duration,
+    system_controller: Option<Arc<SystemController>>,
+}
+
+impl DummyPortModule {
+    /// A simulated port module plugged into `slot`, using the slot number as its serial number
+    pub fn new(slot: u8, port: u8) -> Self {
+        Self::with_identity(
+            ModuleIdentity::new(
+                HardwareUid::simulated(ModuleKind::OutputPort, slot.into()),
+                slot,
+            ),
+            port,
+        )
+    }
+
+    pub fn with_identity(identity: ModuleIdentity, port: u8) -> Self {
+        let config = PortConfig::default();
+
+        Self {
+            id: identity.module_id(),
+            identity,
+            port,
+            current_limit: ElectricCurrent::new::<ampere>(config.max_current_a),
+            config,
+            output_enabled: true,
+            demand: ElectricCurrent::new::<ampere>(0.0),
+            measured_current: ElectricCurrent::new::<ampere>(0.0),
+            faults: FaultInjector::default(),
+            last_update: Duration::ZERO,
+            system_controller: None,
+        }
+    }
+
+    /// **The current the port actually lets through**
+    fn effective_limit(&self) -> ElectricCurrent {
+        if !self.output_enabled || self.faults.is_removed() {
+            return ElectricCurrent::new::<ampere>(0.0);
+        }
+
+        let hardware_max = ElectricCurrent::new::<ampere>(self.config.max_current_a);
+        if self.current_limit < hardware_max {
+            self.current_limit
+        } else {
+            hardware_max
+        }
+    }
+
+    /// **Current flowing out of the port**
+    fn draw(&self) -> ElectricCurrent {
+        if let Some(forced) = self.faults.forced_current() {
+            return forced.abs();
+        }
+
+        let limit = self.effective_limit();
+        if self.demand < limit {
+            self.demand
+        } else {
+            limit
+        }
+    }
+
+    /// **Persists and applies a new configuration**
+    fn apply_config(&mut self, config: PortConfig) -> Result<(), SettingsError> {
+        if let Some(controller) = &self.system_controller {
+            controller.settings().save(self.id, &config)?;
+        }
+
+        self.config = config;
+        Ok(())
+    }
+}
+
+impl Simulated for DummyPortModule {
+    fn apply_environment(&mut self, environment: &SimEnvironment) {
+        self.demand = environment
+            .port_loads
+            .get(&self.port)
+            .copied()
+            .unwrap_or(ElectricCurrent::new::<ampere>(0.0));
+    }
+
+    fn port_limit(&self) -> Option<(u8, ElectricCurrent)> {
+        Some((self.port, self.effective_limit()))
+    }
+
+    fn faults(&self) -> &FaultInjector {
+        &self.faults
+    }
+
+    fn faults_mut(&mut self) -> &mut FaultInjector {
+        &mut self.faults
+    }
+}
+
+#[command_handlers(commands::PortModuleCommands)]
+impl DummyPortModule {
+    fn set_output(&mut self, state: bool) {
+        self.output_enabled = state;
+    }
+
+    fn set_current_limit(&mut self, limit: ElectricCurrent) {
+        self.current_limit = limit;
+    }
+
+    fn get_demand(&self) -> ElectricCurrent {
+        self.demand
+    }
+
+    fn get_config(&self) -> PortConfig {
+        self.config.clone()
+    }
+
+    fn set_config(&mut self, config: PortConfig) -> Result<(), SettingsError> {
+        self.apply_config(config)
+    }
+
+    fn set_config_value(&mut self, key: String, value: String) -> Result<(), SettingsError> {
+        settings::with_field(&self.config, &key, &value)
+            .and_then(|config| self.apply_config(config))
+    }
+
+    fn reset_config(&mut self) -> Result<(), SettingsError> {
+        if let Some(controller) = &self.system_controller {
+            controller.settings().reset::<PortConfig>(self.id)?;
+        }
+        self.config = PortConfig::default();
+        Ok(())
+    }
+}
+
+impl Module for DummyPortModule {
+    type ModuleCommand = PortModuleCommands;
+    type ModuleStatus = ();
+
+    fn metadata(&self) -> ModuleMetadata {
+        ModuleMetadata {
+            id: self.id,
+            module_kind: ModuleKind::OutputPort,
+            name: "Dummy Port Module".into(),
+            version: "1".into(),
+        }
+    }
+
+    fn identify(&mut self) -> Result<ModuleIdentity, ModuleCommandExecutionError> {
+        let payload = self.identity.to_payload();
+        self.faults.transmit_reply(&I2CMessage::new(
+            self.identity.address(),
+            IDENTIFY_COMMAND_ID,
+            &payload,
+        ))?;
+
+        Ok(self.identity)
+    }
+
+    fn process_command(&mut self, command: Self::ModuleCommand) -> ModuleCommandExecutionResponse {
+        // Local commands never reach the module
+        if !command.is_local() {
+            self.faults.transmit_reply(&I2CMessage::new(
+                self.identity.address(),
+                command.command_id(),
+                &[],
+            ))?;
+        }
+
+        CommandHandlers::process_command(self, command)
+    }
+
+    fn status(&self) -> Self::ModuleStatus {}
+
+    fn telemetry_schema(&self) -> TelemetrySchema {
+        TelemetrySchema {
+            module_kind: ModuleKind::OutputPort,
+            fields: vec![
+                TelemetryField::new("port", "Port", QuantityKind::Count),
+                TelemetryField::new("priority", "Priority", QuantityKind::Count),
+                TelemetryField::new("output_enabled", "Output", QuantityKind::Boolean),
+                TelemetryField::new("demand", "Demand", QuantityKind::Current),
+                TelemetryField::new("current", "Current", QuantityKind::Current),
+                TelemetryField::new("current_limit", "Current Limit", QuantityKind::Current),
+                TelemetryField::new("max_current", "Max Current", QuantityKind::Current),
+                TelemetryField::new("power", "Power", QuantityKind::Power),
+            ],
+        }
+    }
+
+    fn telemetry(&self) -> TelemetryReport {
+        let current = self.measured_current.get::<ampere>();
+        let derated = self.demand > self.effective_limit();
+
+        TelemetryReport::new(self.id, self.last_update)
+            .with(
+                "port",
+                TelemetryValue::Count(self.port.into()),
+                Severity::Normal,
+            )
+            .with(
+                "priority",
+                TelemetryValue::Count(self.config.priority.into()),
+                Severity::Normal,
+            )
+            .with(
+                "output_enabled",
+                TelemetryValue::Boolean(self.output_enabled),
+                Severity::Normal,
+            )
+            .with(
+                "demand",
+                TelemetryValue::Current(self.demand),
+                Severity::Normal,
+            )
+            .with(
+                "current",
+                TelemetryValue::Current(ElectricCurrent::new::<ampere>(current)),
+                if derated {
+                    Severity::Warning
+                } else {
+                    Severity::Normal
+                },
+            )
+            .with(
+                "current_limit",
+                TelemetryValue::Current(self.effective_limit()),
+                Severity::Normal,
+            )
+            .with(
+                "max_current",
+                TelemetryValue::Current(ElectricCurrent::new::<ampere>(self.config.max_current_a)),
+                Severity::Normal,
+            )
+            .with(
+                "power",
+                TelemetryValue::Power(Power::new::<watt>(current * self.config.voltage_v)),
+                Severity::Normal,
+            )
+    }
+
+    fn update(&mut self) {
+        if self.faults.is_hung() {
+            return;
+        }
+        if let Some(controller) = &self.system_controller {
+            self.last_update = controller.now();
+        }
+
+        let draw = self.draw().get::<ampere>();
+        self.measured_current =
+            ElectricCurrent::new::<ampere>(self.faults.read_sensor(Sensor::Current, draw));
+    }
+
+    fn reset(&mut self) -> Result<(), ModuleCommandExecutionError> {
+        self.faults.reset();
+        self.faults
+            .transmit_reply(&I2CMessage::new(self.identity.address(), 0, &[]))?;
+        Ok(())
+    }
+
+    fn enter_safe_state(&mut self) {
+        self.output_enabled = false;
+    }
+
+    fn simulated(&mut self) -> Option<&mut dyn Simulated> {
+        Some(self)
+    }
+
+    fn initialize(
+        &mut self,
+        system_controller: Arc<SystemController>,
+    ) -> Result<(), ModuleCommandExecutionError> {
+        self.last_update = system_controller.now();
+
+        self.config = system_controller
+            .settings()
+            .load(self.id)
+            .unwrap_or_else(|err| {
+                error!(
+                    "Unable to load port {} config, using defaults: {}",
+                    self.id, err
+                );
+                PortConfig::default()
+            });
+        self.current_limit = ElectricCurrent::new::<ampere>(self.config.max_current_a);
+
+        self.system_controller = Some(system_controller);
+        Ok(())
+    }
+}
@@ -1 +1,3 @@
 pub mod dummy_battery;
+pub mod dummy_charger;
+pub mod dummy_port;
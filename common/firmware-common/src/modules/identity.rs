@@ -0,0 +1,266 @@
+use crc::{Crc, CRC_16_IBM_3740};
+use std::{fmt, str::FromStr};
+use thiserror::Error;
+
+use super::module::ModuleKind;
+
+/// Command ID of the identification request every module answers on the bus.
+/// The reply payload is the module's `HardwareUid` followed by the slot it sits in.
+pub const IDENTIFY_COMMAND_ID: u8 = 0x01;
+
+/// Bus address of the first backplane slot; slot `n` answers on `SLOT_BASE_ADDRESS + n`
+pub const SLOT_BASE_ADDRESS: u8 = 0x10;
+
+#[derive(Debug, Error, PartialEq)]
+pub enum IdentityError {
+    #[error("Identification reply has {0} bytes, expected {expected}", expected = HardwareUid::LEN + 1)]
+    InvalidReplyLength(usize),
+
+    #[error("Invalid hardware UID '{0}', expected {len} hex digits", len = HardwareUid::LEN * 2)]
+    InvalidUid(String),
+}
+
+/// Factory-programmed unique ID of a module, read from its MCU over the bus.
+///
+/// Laid out like a 1-Wire ROM code: the first byte is a family code telling the module kind
+/// apart, the remaining seven bytes are the serial number.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct HardwareUid(pub [u8; HardwareUid::LEN]);
+
+impl HardwareUid {
+    pub const LEN: usize = 8;
+
+    /// UID of a simulated module, derived from its kind and a serial number so that a dummy
+    /// module re-created with the same serial keeps its identity (and its settings).
+    pub fn simulated(kind: ModuleKind, serial: u32) -> Self {
+        let mut bytes = [0u8; Self::LEN];
+        bytes[0] = kind.family_code();
+        // Marks the UID as not belonging to real hardware
+        bytes[1] = 0x5A;
+        bytes[4..].copy_from_slice(&serial.to_be_bytes());
+        Self(bytes)
+    }
+
+    pub fn family_code(&self) -> u8 {
+        self.0[0]
+    }
+
+    /// **Module ID derived from the UID**
+    ///
+    /// The ID is a CRC-16 of the UID, so it is the same on every boot. 0 is never returned
+    /// as it is reserved for "no module".
+    pub fn module_id(&self) -> u16 {
+        match Crc::<u16>::new(&CRC_16_IBM_3740).checksum(&self.0) {
+            0 => 1,
+            id => id,
+        }
+    }
+}
+
+impl fmt::Display for HardwareUid {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for byte in self.0 {
+            write!(f, "{byte:02X}")?;
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for HardwareUid {
+    type Err = IdentityError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || IdentityError::InvalidUid(s.to_string());
+        let digits = s.trim();
+
+        if digits.len() != Self::LEN * 2 || !digits.is_ascii() {
+            return Err(invalid());
+        }
+
+        let mut bytes = [0u8; Self::LEN];
+        for (index, byte) in bytes.iter_mut().enumerate() {
+            *byte =
+                u8::from_str_radix(&digits[index * 2..index * 2 + 2], 16).map_err(|_| invalid())?;
+        }
+
+        Ok(Self(bytes))
+    }
+}
+
+/// What a module reports when asked to identify itself
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ModuleIdentity {
+    pub uid: HardwareUid,
+    /// Backplane slot the module is plugged into, as strapped on the connector
+    pub slot: u8,
+}
+
+impl ModuleIdentity {
+    pub fn new(uid: HardwareUid, slot: u8) -> Self {
+        Self { uid, slot }
+    }
+
+    pub fn module_id(&self) -> u16 {
+        self.uid.module_id()
+    }
+
+    /// Bus address the module answers on
+    pub fn address(&self) -> u8 {
+        SLOT_BASE_ADDRESS.wrapping_add(self.slot)
+    }
+
+    /// Payload of the reply to `IDENTIFY_COMMAND_ID`
+    pub fn to_payload(&self) -> [u8; HardwareUid::LEN + 1] {
+        let mut payload = [0u8; HardwareUid::LEN + 1];
+        payload[..HardwareUid::LEN].copy_from_slice(&self.uid.0);
+        payload[HardwareUid::LEN] = self.slot;
+        payload
+    }
+
+    pub fn from_payload(payload: &[u8]) -> Result<Self, IdentityError> {
+        if payload.len() != HardwareUid::LEN + 1 {
+            return Err(IdentityError::InvalidReplyLength(payload.len()));
+        }
+
+        let mut uid = [0u8; HardwareUid::LEN];
+        uid.copy_from_slice(&payload[..HardwareUid::LEN]);
+
+        Ok(Self {
+            uid: HardwareUid(uid),
+            slot: payload[HardwareUid::LEN],
+        })
+    }
+}
+
+/// A physical slot of the backplane, positioned for drawing the pack layout
+#[derive(Debug, Clone, PartialEq)]
+pub struct SlotDefinition {
+    pub slot: u8,
+    pub label: String,
+    pub row: u8,
+    pub column: u8,
+}
+
+/// The slots a pack has and where they sit
+#[derive(Debug, Clone, PartialEq)]
+pub struct SlotLayout {
+    pub rows: u8,
+    pub columns: u8,
+    pub slots: Vec<SlotDefinition>,
+}
+
+impl SlotLayout {
+    /// A `rows` x `columns` grid of slots, numbered from 0 row by row. Slot numbers are a
+    /// `u8`, so a grid of more than 256 slots stops at slot 255.
+    pub fn grid(rows: u8, columns: u8) -> Self {
+        let slots = (0..rows)
+            .flat_map(|row| (0..columns).map(move |column| (row, column)))
+            .map_while(|(row, column)| {
+                let slot =
+                    u8::try_from(u16::from(row) * u16::from(columns) + u16::from(column)).ok()?;
+                Some(SlotDefinition {
+                    slot,
+                    label: format!("Slot {}", u16::from(slot) + 1),
+                    row,
+                    column,
+                })
+            })
+            .collect();
+
+        Self {
+            rows,
+            columns,
+            slots,
+        }
+    }
+
+    pub fn slot(&self, slot: u8) -> Option<&SlotDefinition> {
+        self.slots.iter().find(|definition| definition.slot == slot)
+    }
+}
+
+impl Default for SlotLayout {
+    fn default() -> Self {
+        Self::grid(2, 4)
+    }
+}
+
+/// The module sitting in a slot
+#[derive(Debug, Clone)]
+pub struct SlotOccupant {
+    pub module_id: u16,
+    pub uid: HardwareUid,
+    pub module_kind: ModuleKind,
+    pub name: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct SlotState {
+    pub definition: SlotDefinition,
+    pub occupant: Option<SlotOccupant>,
+}
+
+/// Snapshot of which module sits in which slot
+#[derive(Debug, Clone)]
+pub struct Topology {
+    pub rows: u8,
+    pub columns: u8,
+    pub slots: Vec<SlotState>,
+    /// Modules reporting a slot the layout does not have
+    pub unplaced: Vec<(u8, SlotOccupant)>,
+}
+
+impl Topology {
+    pub fn slot(&self, slot: u8) -> Option<&SlotState> {
+        self.slots
+            .iter()
+            .find(|state| state.definition.slot == slot)
+    }
+
+    /// First slot with nothing plugged in
+    pub fn first_free_slot(&self) -> Option<u8> {
+        self.slots
+            .iter()
+            .find(|state| state.occupant.is_none())
+            .map(|state| state.definition.slot)
+    }
+
+    pub fn slot_of(&self, module_id: u16) -> Option<u8> {
+        self.slots
+            .iter()
+            .find(|state| {
+                state
+                    .occupant
+                    .as_ref()
+                    .is_some_and(|occupant| occupant.module_id == module_id)
+            })
+            .map(|state| state.definition.slot)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn grid_numbers_slots_row_by_row() {
+        let layout = SlotLayout::grid(2, 4);
+        assert_eq!(layout.slots.len(), 8);
+        let slot = layout.slot(5).unwrap();
+        assert_eq!(
+            (slot.row, slot.column, slot.label.as_str()),
+            (1, 1, "Slot 6")
+        );
+    }
+
+    #[test]
+    fn large_grid_stops_at_the_last_slot_number() {
+        let layout = SlotLayout::grid(16, 16);
+        assert_eq!(layout.slots.len(), 256);
+        assert_eq!(layout.slot(255).unwrap().label, "Slot 256");
+
+        let layout = SlotLayout::grid(20, 20);
+        assert_eq!(layout.slots.len(), 256);
+        assert_eq!((layout.rows, layout.columns), (20, 20));
+    }
+}
@@ -0,0 +1,177 @@
+use std::{collections::HashMap, fmt, time::Duration};
+
+use thiserror::Error;
+
+use super::{
+    alarms::{Alarm, AlarmKey, AlarmState},
+    telemetry::Severity,
+};
+
+/// How long a confirmation token can be used after it was issued
+pub const CONFIRMATION_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// How dangerous a command is, as declared with `#[safety(...)]` in `def_module_commands!`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord)]
+pub enum SafetyClass {
+    #[default]
+    Normal,
+    /// Can defeat a protection or leave the hardware in an unsafe state. Only dispatched
+    /// with a `ConfirmationToken` issued for it.
+    Critical,
+}
+
+impl fmt::Display for SafetyClass {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SafetyClass::Normal => write!(f, "Normal"),
+            SafetyClass::Critical => write!(f, "Critical"),
+        }
+    }
+}
+
+/// A condition under which a command is refused, as declared with `#[interlock(...)]`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Interlock {
+    /// The module has the alarm `condition` raised, e.g. `"temperature"`
+    NoAlarm(&'static str),
+    /// Any module has a critical alarm raised
+    NoCriticalAlarm,
+}
+
+impl Interlock {
+    /// The raised alarm that trips the interlock for a command to `module_id`, if any
+    pub fn check<'a>(&self, module_id: u16, alarms: &'a [Alarm]) -> Option<&'a Alarm> {
+        alarms
+            .iter()
+            .filter(|alarm| alarm.state != AlarmState::Cleared)
+            .find(|alarm| match self {
+                Interlock::NoAlarm(condition) => {
+                    alarm.key.module_id == module_id && alarm.key.condition == *condition
+                }
+                Interlock::NoCriticalAlarm => alarm.severity == Severity::Critical,
+            })
+    }
+}
+
+impl fmt::Display for Interlock {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Interlock::NoAlarm(condition) => write!(f, "no `{condition}` alarm"),
+            Interlock::NoCriticalAlarm => write!(f, "no critical alarm"),
+        }
+    }
+}
+
+/// Safety class of a command, and the interlocks that apply to it with its arguments
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CommandSafety {
+    pub class: SafetyClass,
+    pub interlocks: Vec<Interlock>,
+}
+
+#[derive(Debug, Error)]
+pub enum InterlockError {
+    #[error("{command} is interlocked by alarm {alarm}")]
+    Interlocked {
+        command: &'static str,
+        interlock: Interlock,
+        alarm: AlarmKey,
+    },
+
+    #[error("{0} is a critical command and must be confirmed")]
+    ConfirmationRequired(&'static str),
+
+    #[error("Confirmation of {0} is unknown, expired or for another command")]
+    InvalidConfirmation(&'static str),
+}
+
+/// Proof that a critical command was confirmed, issued by `Confirmations::issue`.
+///
+/// A token is only good for the command and module it was issued for, once, and for
+/// `CONFIRMATION_TIMEOUT`.
+#[derive(Debug, PartialEq, Eq)]
+pub struct ConfirmationToken(u64);
+
+#[derive(Debug)]
+struct PendingConfirmation {
+    module_id: u16,
+    command: String,
+    expires_at: Duration,
+}
+
+/// Confirmation tokens issued and not used yet
+#[derive(Debug, Default)]
+pub struct Confirmations {
+    pending: HashMap<u64, PendingConfirmation>,
+    next_token: u64,
+}
+
+impl Confirmations {
+    /// Issue a token for the command named `command` to `module_id`
+    pub fn issue(&mut self, module_id: u16, command: &str, now: Duration) -> ConfirmationToken {
+        self.pending.retain(|_, pending| pending.expires_at > now);
+
+        self.next_token += 1;
+        self.pending.insert(
+            self.next_token,
+            PendingConfirmation {
+                module_id,
+                command: command.to_string(),
+                expires_at: now + CONFIRMATION_TIMEOUT,
+            },
+        );
+        ConfirmationToken(self.next_token)
+    }
+
+    /// Use up `token`, checking that it was issued for this command and has not expired
+    pub fn redeem(
+        &mut self,
+        token: ConfirmationToken,
+        module_id: u16,
+        command: &'static str,
+        now: Duration,
+    ) -> Result<(), InterlockError> {
+        match self.pending.remove(&token.0) {
+            Some(pending)
+                if pending.module_id == module_id
+                    && pending.command == command
+                    && pending.expires_at > now =>
+            {
+                Ok(())
+            }
+            _ => Err(InterlockError::InvalidConfirmation(command)),
+        }
+    }
+}
+
+/// Whether `command` may be dispatched to `module_id` given the raised `alarms`, using up
+/// `confirmation` if it is critical
+pub fn authorize(
+    command: &'static str,
+    safety: &CommandSafety,
+    module_id: u16,
+    alarms: &[Alarm],
+    confirmations: &mut Confirmations,
+    confirmation: Option<ConfirmationToken>,
+    now: Duration,
+) -> Result<(), InterlockError> {
+    if let Some((interlock, alarm)) = safety
+        .interlocks
+        .iter()
+        .find_map(|interlock| Some((*interlock, interlock.check(module_id, alarms)?)))
+    {
+        return Err(InterlockError::Interlocked {
+            command,
+            interlock,
+            alarm: alarm.key.clone(),
+        });
+    }
+
+    match (safety.class, confirmation) {
+        (SafetyClass::Normal, _) => Ok(()),
+        (SafetyClass::Critical, None) => Err(InterlockError::ConfirmationRequired(command)),
+        (SafetyClass::Critical, Some(token)) => {
+            confirmations.redeem(token, module_id, command, now)
+        }
+    }
+}
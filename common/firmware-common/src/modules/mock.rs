@@ -0,0 +1,385 @@
+use std::{any::Any, marker::PhantomData, sync::Arc};
+
+use super::{
+    descriptor::CommandSet,
+    identity::ModuleIdentity,
+    module::{
+        Module, ModuleCommand, ModuleCommandExecutionError, ModuleCommandExecutionResponse,
+        ModuleKind, ModuleMetadata,
+    },
+    system_controller::SystemController,
+    telemetry::TelemetryReport,
+};
+
+// Checks the arguments of a command
+type Matcher<C> = Box<dyn Fn(&C) -> bool>;
+
+// A programmed answer to a command
+struct Expectation<C> {
+    command: &'static str,
+    matcher: Option<Matcher<C>>,
+    /// How many times the command is expected, any number if `None`
+    times: Option<usize>,
+    calls: usize,
+    respond: Box<dyn FnMut(&C) -> ModuleCommandExecutionResponse>,
+}
+
+/// A `Module` answering commands of `C` the way a test programs it, for `ModuleManager` and
+/// safety logic tests that should not depend on a simulated module.
+///
+/// `def_module_commands!` generates a wrapper with one `expect_*` method per command for enums
+/// marked `#[mock(Name)]`.
+///
+/// ```
+/// # use stratum_firmware_common::modules::{
+/// #     commands::{MockPort, PortModuleCommands},
+/// #     identity::{HardwareUid, ModuleIdentity},
+/// #     module::{ModuleCommandExecutionError, ModuleKind},
+/// # };
+/// # use uom::si::{electric_current::ampere, f64::ElectricCurrent};
+/// # let identity = ModuleIdentity::new(HardwareUid::simulated(ModuleKind::OutputPort, 1), 1);
+/// let mut port = MockPort::new(identity, ModuleKind::OutputPort);
+/// port.expect_get_demand().returning(ElectricCurrent::new::<ampere>(1.5));
+/// port.expect_set_output()
+///     .matching(|command| matches!(command, PortModuleCommands::SetOutput { state: false }))
+///     .times(1)
+///     .returning(());
+/// port.expect_set_current_limit()
+///     .failing(|| ModuleCommandExecutionError::HardwareFailure("DAC not responding".into()));
+/// ```
+pub struct MockModule<C> {
+    identity: ModuleIdentity,
+    kind: ModuleKind,
+    expectations: Vec<Expectation<C>>,
+    calls: Vec<C>,
+    unexpected: Vec<&'static str>,
+    responsive: bool,
+    safe_state: bool,
+    telemetry: Option<Box<dyn Fn() -> TelemetryReport>>,
+}
+
+impl<C: CommandSet> MockModule<C> {
+    pub fn new(identity: ModuleIdentity, kind: ModuleKind) -> Self {
+        Self {
+            identity,
+            kind,
+            expectations: Vec::new(),
+            calls: Vec::new(),
+            unexpected: Vec::new(),
+            responsive: true,
+            safe_state: false,
+            telemetry: None,
+        }
+    }
+
+    /// Program an answer to the command `S`
+    pub fn expect<S: ModuleCommand>(&mut self) -> MockExpectation<'_, C, S> {
+        MockExpectation {
+            mock: self,
+            matcher: None,
+            times: None,
+            command: PhantomData,
+        }
+    }
+
+    /// Commands received so far, expected or not, in order
+    pub fn calls(&self) -> &[C] {
+        &self.calls
+    }
+
+    /// How many `S` commands were received
+    pub fn call_count<S: ModuleCommand>(&self) -> usize {
+        self.calls
+            .iter()
+            .filter(|command| command.command_name() == S::NAME)
+            .count()
+    }
+
+    /// Commands that no expectation matched, and that were answered with `InvalidCommand`
+    pub fn unexpected(&self) -> &[&'static str] {
+        &self.unexpected
+    }
+
+    pub fn clear_calls(&mut self) {
+        self.calls.clear();
+        self.unexpected.clear();
+    }
+
+    /// Make `identify`, and therefore heartbeats, fail, as a module that hangs would
+    pub fn set_responsive(&mut self, responsive: bool) {
+        self.responsive = responsive;
+    }
+
+    /// Report what `telemetry` returns, e.g. an output state shared with an expectation
+    pub fn set_telemetry(&mut self, telemetry: impl Fn() -> TelemetryReport + 'static) {
+        self.telemetry = Some(Box::new(telemetry));
+    }
+
+    /// Whether the module was put in its safe state
+    pub fn entered_safe_state(&self) -> bool {
+        self.safe_state
+    }
+
+    /// Panic if a command was not expected, or if an expectation with `times` was not met
+    pub fn verify(&self) {
+        let mut failures: Vec<String> = self
+            .unexpected
+            .iter()
+            .map(|command| format!("unexpected {command}"))
+            .collect();
+        failures.extend(self.expectations.iter().filter_map(|expectation| {
+            let times = expectation.times?;
+            (expectation.calls != times).then(|| {
+                format!(
+                    "{} expected {} times, received {} times",
+                    expectation.command, times, expectation.calls
+                )
+            })
+        }));
+
+        if !failures.is_empty() {
+            panic!(
+                "Mock module {} failed verification: {}",
+                self.identity.module_id(),
+                failures.join(", ")
+            );
+        }
+    }
+}
+
+/// An answer being programmed with `MockModule::expect`, added by `returning`,
+/// `returning_with` or `failing`
+pub struct MockExpectation<'a, C, S> {
+    mock: &'a mut MockModule<C>,
+    matcher: Option<Matcher<C>>,
+    times: Option<usize>,
+    command: PhantomData<S>,
+}
+
+impl<C: CommandSet, S: ModuleCommand> MockExpectation<'_, C, S> {
+    /// Only answer commands `matcher` accepts, e.g. to check the arguments
+    pub fn matching(mut self, matcher: impl Fn(&C) -> bool + 'static) -> Self {
+        self.matcher = Some(Box::new(matcher));
+        self
+    }
+
+    /// Answer `times` commands, and expect exactly as many in `verify`
+    pub fn times(mut self, times: usize) -> Self {
+        self.times = Some(times);
+        self
+    }
+
+    pub fn returning(self, response: S::Response)
+    where
+        S::Response: Clone + 'static,
+    {
+        self.returning_with(move |_| response.clone());
+    }
+
+    /// Answer with what `respond` makes of the command
+    pub fn returning_with(self, mut respond: impl FnMut(&C) -> S::Response + 'static)
+    where
+        S::Response: 'static,
+    {
+        self.respond(move |command| Ok(Box::new(respond(command)) as Box<dyn Any>));
+    }
+
+    /// Fail the command with the error `error` makes
+    pub fn failing(self, error: impl Fn() -> ModuleCommandExecutionError + 'static) {
+        self.respond(move |_| Err(error()));
+    }
+
+    fn respond(self, respond: impl FnMut(&C) -> ModuleCommandExecutionResponse + 'static) {
+        self.mock.expectations.push(Expectation {
+            command: S::NAME,
+            matcher: self.matcher,
+            times: self.times,
+            calls: 0,
+            respond: Box::new(respond),
+        });
+    }
+}
+
+impl<C: CommandSet> Module for MockModule<C> {
+    type ModuleCommand = C;
+    type ModuleStatus = ();
+
+    fn metadata(&self) -> ModuleMetadata {
+        ModuleMetadata {
+            id: self.identity.module_id(),
+            name: format!("Mock {}", self.kind),
+            module_kind: self.kind,
+            version: "mock".into(),
+        }
+    }
+
+    fn identify(&mut self) -> Result<ModuleIdentity, ModuleCommandExecutionError> {
+        if self.responsive {
+            Ok(self.identity)
+        } else {
+            Err(ModuleCommandExecutionError::HardwareFailure(
+                "Mock module is unresponsive".into(),
+            ))
+        }
+    }
+
+    /// Answered by the first expectation for the command that matches it and is not used up
+    fn process_command(&mut self, command: C) -> ModuleCommandExecutionResponse {
+        let name = command.command_name();
+        let expectation = self.expectations.iter_mut().find(|expectation| {
+            expectation.command == name
+                && expectation
+                    .times
+                    .is_none_or(|times| expectation.calls < times)
+                && expectation
+                    .matcher
+                    .as_ref()
+                    .is_none_or(|matcher| matcher(&command))
+        });
+
+        let response = match expectation {
+            Some(expectation) => {
+                expectation.calls += 1;
+                (expectation.respond)(&command)
+            }
+            None => {
+                self.unexpected.push(name);
+                Err(ModuleCommandExecutionError::InvalidCommand(format!(
+                    "{name} was not expected by the mock"
+                )))
+            }
+        };
+
+        self.calls.push(command);
+        response
+    }
+
+    fn status(&self) -> Self::ModuleStatus {}
+
+    fn telemetry(&self) -> TelemetryReport {
+        match &self.telemetry {
+            Some(telemetry) => telemetry(),
+            None => TelemetryReport::new(self.identity.module_id(), Default::default()),
+        }
+    }
+
+    fn enter_safe_state(&mut self) {
+        self.safe_state = true;
+    }
+
+    fn initialize(
+        &mut self,
+        _system_controller: Arc<SystemController>,
+    ) -> Result<(), ModuleCommandExecutionError> {
+        Ok(())
+    }
+}
+
+/// Fixtures shared by the tests driving `ModuleManager` through mock modules
+#[cfg(test)]
+pub(crate) mod testing {
+    use std::{sync::Arc, time::Duration};
+
+    use crate::{
+        clock::ManualClock,
+        modules::{
+            commands::MockPort,
+            identity::{HardwareUid, ModuleIdentity},
+            module::ModuleKind,
+            module_manager::ModuleManager,
+            system_controller::SystemController,
+        },
+    };
+
+    /// A controller on a manual clock starting at zero, with in-memory settings, and a
+    /// manager without modules
+    pub(crate) fn setup() -> (Arc<ManualClock>, Arc<SystemController>, ModuleManager) {
+        let clock = Arc::new(ManualClock::new(Duration::ZERO));
+        let controller = SystemController::with_clock(clock.clone());
+        (clock, controller, ModuleManager::new())
+    }
+
+    /// The identity of a simulated module of `kind` in `slot`, its serial being the slot
+    pub(crate) fn identity(kind: ModuleKind, slot: u8) -> ModuleIdentity {
+        ModuleIdentity::new(HardwareUid::simulated(kind, slot.into()), slot)
+    }
+
+    /// A port in `slot` without expectations
+    pub(crate) fn mock_port(slot: u8) -> MockPort {
+        MockPort::new(
+            identity(ModuleKind::OutputPort, slot),
+            ModuleKind::OutputPort,
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use uom::si::{electric_current::ampere, f64::ElectricCurrent};
+
+    use super::{testing::*, *};
+    use crate::modules::{
+        commands::{port_module_commands, MockPort, PortModuleCommands},
+        module_manager::ModuleManager,
+    };
+
+    #[test]
+    fn expectations_answer_in_order_until_used_up() {
+        let (_, controller, mut modules) = setup();
+        let mut port = mock_port(1);
+        port.expect_get_demand()
+            .times(1)
+            .returning(ElectricCurrent::new::<ampere>(1.5));
+        port.expect_get_demand()
+            .returning(ElectricCurrent::new::<ampere>(0.5));
+        let id = modules.register_module(port, controller).unwrap();
+
+        let demand = |modules: &mut ModuleManager| {
+            let response = modules.send_command(id, PortModuleCommands::GetDemand);
+            *response.unwrap().downcast::<ElectricCurrent>().unwrap()
+        };
+        assert_eq!(demand(&mut modules).get::<ampere>(), 1.5);
+        assert_eq!(demand(&mut modules).get::<ampere>(), 0.5);
+        assert_eq!(demand(&mut modules).get::<ampere>(), 0.5);
+
+        let port = modules.get_module::<MockPort>(id).unwrap();
+        assert_eq!(port.call_count::<port_module_commands::GetDemand>(), 3);
+        port.verify();
+    }
+
+    #[test]
+    fn unmatched_command_is_invalid() {
+        let (_, controller, mut modules) = setup();
+        let mut port = mock_port(1);
+        port.expect_set_output()
+            .matching(|command| matches!(command, PortModuleCommands::SetOutput { state: false }))
+            .returning(());
+        port.expect_set_current_limit()
+            .failing(|| ModuleCommandExecutionError::HardwareFailure("DAC".into()));
+        let id = modules.register_module(port, controller).unwrap();
+
+        let on = modules.send_command(id, PortModuleCommands::SetOutput { state: true });
+        assert!(matches!(
+            on,
+            Err(ModuleCommandExecutionError::InvalidCommand(_))
+        ));
+        let limit = ElectricCurrent::new::<ampere>(2.0);
+        let limit = modules.send_command(id, PortModuleCommands::SetCurrentLimit { limit });
+        assert!(matches!(
+            limit,
+            Err(ModuleCommandExecutionError::HardwareFailure(_))
+        ));
+
+        let port = modules.get_module::<MockPort>(id).unwrap();
+        assert_eq!(port.calls().len(), 2);
+        assert_eq!(port.unexpected(), ["SetOutput"]);
+    }
+
+    #[test]
+    #[should_panic(expected = "SetOutput expected 1 times, received 0 times")]
+    fn verify_reports_missing_calls() {
+        let mut port = mock_port(1);
+        port.expect_set_output().times(1).returning(());
+        port.verify();
+    }
+}
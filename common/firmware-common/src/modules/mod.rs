@@ -1,6 +1,24 @@
+pub mod alarms;
+pub mod automations;
+pub mod balancing;
 pub mod battery;
+pub mod charger;
+pub mod charger_module;
+pub mod command_queue;
 pub mod commands;
+pub mod derating;
+pub mod descriptor;
 pub mod dummies;
+pub mod identity;
+pub mod interlock;
+pub mod mock;
 pub mod module;
 pub mod module_manager;
+pub mod port;
+pub mod power_budget;
+pub mod runtime;
+pub mod safety_policy;
 pub mod system_controller;
+pub mod telemetry;
+pub mod thresholds;
+pub mod watchdog;
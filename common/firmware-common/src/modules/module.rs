@@ -1,4 +1,14 @@
-use super::system_controller::SystemController;
+use super::{
+    descriptor::{CommandSet, ValueError},
+    identity::{IdentityError, ModuleIdentity},
+    interlock::InterlockError,
+    system_controller::SystemController,
+    telemetry::{TelemetryReport, TelemetrySchema},
+};
+use crate::{
+    comms::{codec::CodecError, i2c_protocol::I2CError},
+    simulation::Simulated,
+};
 use anyhow::Result;
 use std::{
     fmt::{self, Debug},
@@ -14,18 +24,36 @@ pub struct ModuleMetadata {
     pub version: String,         // Firmware version
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum ModuleKind {
     Battery,
+    OutputPort,
+    Charger,
     WaveformGenerator,
     SolderingUnit,
     Unknown,
 }
 
+impl ModuleKind {
+    /// First byte of the `HardwareUid` of modules of this kind
+    pub fn family_code(&self) -> u8 {
+        match self {
+            ModuleKind::Battery => 0x01,
+            ModuleKind::OutputPort => 0x02,
+            ModuleKind::Charger => 0x05,
+            ModuleKind::WaveformGenerator => 0x03,
+            ModuleKind::SolderingUnit => 0x04,
+            ModuleKind::Unknown => 0xFF,
+        }
+    }
+}
+
 impl fmt::Display for ModuleKind {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match self {
             ModuleKind::Battery => write!(f, "Battery"),
+            ModuleKind::OutputPort => write!(f, "Output Port"),
+            ModuleKind::Charger => write!(f, "Charge Input"),
             ModuleKind::SolderingUnit => write!(f, "Soldering Unit"),
             ModuleKind::WaveformGenerator => write!(f, "Waveform Generator"),
             ModuleKind::Unknown => write!(f, "Unknown"),
@@ -36,9 +64,20 @@ impl fmt::Display for ModuleKind {
 pub trait ModuleCommand {
     type Response;
 
+    /// Stable ID of the command on the bus, as assigned in `def_module_commands!`
+    const ID: u8;
+    const NAME: &'static str;
+
     fn as_any(&self) -> &dyn std::any::Any;
 }
 
+/// A command that is sent to the module over the bus, rather than handled by the host
+pub trait WireCommand: ModuleCommand {
+    fn encode_response(response: &Self::Response) -> Result<Vec<u8>, CodecError>;
+
+    fn decode_response(payload: &[u8]) -> Result<Self::Response, CodecError>;
+}
+
 #[derive(Debug, Error)]
 pub enum ModuleCommandExecutionError {
     #[error("Invalid command: {0}")]
@@ -50,9 +89,33 @@ pub enum ModuleCommandExecutionError {
     #[error("Hardware failure: {0}")]
     HardwareFailure(String),
 
+    #[error("Bus error: {0}")]
+    BusError(#[from] I2CError),
+
+    #[error("Codec error: {0}")]
+    Codec(#[from] CodecError),
+
+    #[error("Refused: {0}")]
+    Refused(#[from] InterlockError),
+
+    #[error("Invalid arguments: {0}")]
+    InvalidArguments(#[from] ValueError),
+
+    #[error("Identification failed: {0}")]
+    IdentificationError(#[from] IdentityError),
+
     #[error("Initialization error")]
     InitializationError,
 
+    #[error("Command was cancelled before it was sent")]
+    Cancelled,
+
+    #[error("Command was still queued when its queue timeout ran out")]
+    QueueTimeout,
+
+    #[error("Command queue of module {0} is full")]
+    QueueFull(u16),
+
     #[error("Unknown error")]
     Unknown,
 }
@@ -60,59 +123,79 @@ pub enum ModuleCommandExecutionError {
 pub type ModuleCommandExecutionResponse =
     Result<Box<dyn std::any::Any>, ModuleCommandExecutionError>;
 
+/// Dispatch of a command enum to one handler method per command, implemented with
+/// `#[command_handlers]`
+pub trait CommandHandlers {
+    type Commands;
+
+    fn process_command(&mut self, command: Self::Commands) -> ModuleCommandExecutionResponse;
+}
+
 pub trait Module {
-    type ModuleCommand;
+    /// The command enum generated by `def_module_commands!`
+    type ModuleCommand: CommandSet;
     type ModuleStatus;
 
     fn metadata(&self) -> ModuleMetadata;
 
+    /// Ask the module for its hardware UID and slot over the bus.
+    /// `metadata().id` must be `ModuleIdentity::module_id` of the returned identity.
+    fn identify(&mut self) -> Result<ModuleIdentity, ModuleCommandExecutionError>;
+
     fn process_command(&mut self, command: Self::ModuleCommand) -> ModuleCommandExecutionResponse;
 
     fn status(&self) -> Self::ModuleStatus;
 
+    /// Describe the values reported by `telemetry`
+    fn telemetry_schema(&self) -> TelemetrySchema {
+        TelemetrySchema {
+            module_kind: self.metadata().module_kind,
+            fields: Vec::new(),
+        }
+    }
+
+    /// A type-erased snapshot of the module's state. Unlike `status`, this is reachable
+    /// through `DynModule`, so generic UIs and exporters can display any module.
+    fn telemetry(&self) -> TelemetryReport {
+        TelemetryReport::new(self.metadata().id, Default::default())
+    }
+
+    /// Advance the module's internal state. Called periodically by `ModuleManager::update_modules`.
+    fn update(&mut self) {}
+
+    /// Check that the module is alive. Called periodically by the `ModuleManager`'s watchdog;
+    /// by default the module is asked to identify itself over the bus.
+    fn heartbeat(&mut self) -> Result<(), ModuleCommandExecutionError> {
+        self.identify().map(|_| ())
+    }
+
+    /// Reset the module's hardware to get it out of a hang. `initialize` is called again
+    /// afterwards.
+    fn reset(&mut self) -> Result<(), ModuleCommandExecutionError> {
+        Ok(())
+    }
+
+    /// Leave the module in a state that is safe without supervision (outputs off, no
+    /// charging), once it stopped responding and could not be recovered. Best effort, as the
+    /// module is likely not listening.
+    fn enter_safe_state(&mut self) {}
+
+    /// Access to the module's simulation inputs, if it is a simulated module
+    fn simulated(&mut self) -> Option<&mut dyn Simulated> {
+        None
+    }
+
     fn initialize(
         &mut self,
         system_controller: Arc<SystemController>,
     ) -> Result<(), ModuleCommandExecutionError>;
 }
 
-/// A macro that performs a type-enforced match for a module command enum.
-///
-/// This macro ensures that the response type of each command variant is correctly inferred.
-/// It immediately evaluates the provided closure body and returns the result as a boxed `Any` type.
-///
-/// # Example
-/// ```
-/// command_match!(
-///     command_enum,
-///     parent_module,
-///     my_module,
-///     Foo { arg1, arg2 } => some_function(arg1, arg2),
-///     Bar { arg } => another_function(arg),
-/// )
-/// ```
-#[macro_export]
-macro_rules! command_match {
-    ($cmd_enum:expr, $module_name:ident,
-        $(
-            $variant:ident $( { $( $arg:ident ),* } )? => $body:expr
-        ),* $(,)?
-    ) => {{
-        paste::paste! {
-            match $cmd_enum {
-                $(
-                    $module_name::$variant $( { $( $arg ),* } )? => {
-                        let result = (|| -> <crate::modules::commands::[<$module_name:snake>]::$variant as crate::modules::module::ModuleCommand>::Response {
-                            $body
-                        })(); // Invoke the closure immediately (this is to allow early returns inside the body)
-
-                        Ok(Box::new(result) as Box<dyn std::any::Any>)
-                    }
-                ),*
-            }
-        }
-    }};
-}
-
+pub use amnio_macros::command_handlers;
 pub use amnio_macros::def_module_commands;
 pub use amnio_macros::execute_command;
+pub use amnio_macros::submit_command;
+
+// Used by the code the macros above generate
+#[doc(hidden)]
+pub use amnio_macros::__command_handlers;
@@ -1,14 +1,72 @@
-use log::error;
-use rand::Rng;
+use log::{error, info};
+use thiserror::Error;
 
-use super::module::ModuleMetadata;
+use super::command_queue::{CommandHandle, CommandQueue};
+use super::descriptor::{CommandDescriptor, CommandSet, Value, ValueError};
+use super::identity::{HardwareUid, ModuleIdentity, SlotLayout, SlotOccupant, SlotState, Topology};
+use super::interlock::{self, CommandSafety, ConfirmationToken, Confirmations};
+use super::module::{
+    ModuleCommand, ModuleCommandExecutionError, ModuleCommandExecutionResponse, ModuleMetadata,
+};
+use super::system_controller::{CriticalEvent, ModuleEvent};
+use super::telemetry::{TelemetryReport, TelemetrySchema};
+use super::watchdog::{ModuleHealth, Watchdog, WatchdogConfig, WatchdogEvent};
 use super::{module::Module, system_controller::SystemController};
 use std::any::Any;
 use std::collections::HashMap;
 use std::sync::Arc;
+use std::time::Duration;
+
+use crate::{
+    clock::Interval,
+    simulation::{SimEnvironment, Simulated},
+};
+
+/// How often modules are updated when driven by `ModuleManager::tick`
+pub const DEFAULT_UPDATE_PERIOD: Duration = Duration::from_millis(100);
+
+#[derive(Debug, Error)]
+pub enum ModuleRegistrationError {
+    #[error("Module did not identify itself: {0}")]
+    IdentificationFailed(#[from] ModuleCommandExecutionError),
+
+    #[error("Module reports ID {reported} but its UID maps to {expected}")]
+    IdMismatch { reported: u16, expected: u16 },
+
+    #[error("Module ID {id} of UID {uid} is already taken by UID {existing}")]
+    IdCollision {
+        id: u16,
+        uid: HardwareUid,
+        existing: HardwareUid,
+    },
+
+    #[error("Slot {slot} is already occupied by module {occupant}")]
+    SlotOccupied { slot: u8, occupant: u16 },
+}
 
 pub trait DynModule: Any {
     fn metadata(&self) -> ModuleMetadata;
+    fn telemetry_schema(&self) -> TelemetrySchema;
+    fn telemetry(&self) -> TelemetryReport;
+    fn update(&mut self);
+    fn heartbeat(&mut self) -> Result<(), ModuleCommandExecutionError>;
+    fn reset(&mut self) -> Result<(), ModuleCommandExecutionError>;
+    fn enter_safe_state(&mut self);
+    fn initialize(
+        &mut self,
+        system_controller: Arc<SystemController>,
+    ) -> Result<(), ModuleCommandExecutionError>;
+    fn process_dyn_command(&mut self, command: Box<dyn Any>) -> ModuleCommandExecutionResponse;
+    fn command_descriptors(&self) -> Vec<CommandDescriptor>;
+    /// Name and safety of `command`, if it is of the module's command type
+    fn command_safety(&self, command: &dyn Any) -> Option<(&'static str, CommandSafety)>;
+    fn command_from_values(
+        &self,
+        command: &str,
+        args: Vec<Value>,
+    ) -> Result<Box<dyn Any>, ValueError>;
+    fn response_value(&self, command: &str, response: &dyn Any) -> Option<Value>;
+    fn simulated(&mut self) -> Option<&mut dyn Simulated>;
     fn as_any(&self) -> &dyn Any;
     fn as_any_mut(&mut self) -> &mut dyn Any;
 }
@@ -18,6 +76,74 @@ impl<M: Module + 'static> DynModule for M {
         self.metadata()
     }
 
+    fn telemetry_schema(&self) -> TelemetrySchema {
+        Module::telemetry_schema(self)
+    }
+
+    fn telemetry(&self) -> TelemetryReport {
+        Module::telemetry(self)
+    }
+
+    fn update(&mut self) {
+        Module::update(self)
+    }
+
+    fn heartbeat(&mut self) -> Result<(), ModuleCommandExecutionError> {
+        Module::heartbeat(self)
+    }
+
+    fn reset(&mut self) -> Result<(), ModuleCommandExecutionError> {
+        Module::reset(self)
+    }
+
+    fn enter_safe_state(&mut self) {
+        Module::enter_safe_state(self)
+    }
+
+    fn initialize(
+        &mut self,
+        system_controller: Arc<SystemController>,
+    ) -> Result<(), ModuleCommandExecutionError> {
+        Module::initialize(self, system_controller)
+    }
+
+    fn process_dyn_command(&mut self, command: Box<dyn Any>) -> ModuleCommandExecutionResponse {
+        match command.downcast::<M::ModuleCommand>() {
+            Ok(command) => self.process_command(*command),
+            Err(_) => Err(ModuleCommandExecutionError::InvalidCommand(format!(
+                "{} does not accept this command type",
+                self.metadata().name
+            ))),
+        }
+    }
+
+    fn command_descriptors(&self) -> Vec<CommandDescriptor> {
+        M::ModuleCommand::descriptors()
+    }
+
+    fn command_safety(&self, command: &dyn Any) -> Option<(&'static str, CommandSafety)> {
+        command
+            .downcast_ref::<M::ModuleCommand>()
+            .map(|command| (command.command_name(), command.safety()))
+    }
+
+    fn command_from_values(
+        &self,
+        command: &str,
+        args: Vec<Value>,
+    ) -> Result<Box<dyn Any>, ValueError> {
+        M::ModuleCommand::from_values(command, args)
+            .map(|command| Box::new(command) as Box<dyn Any>)
+    }
+
+    fn response_value(&self, command: &str, response: &dyn Any) -> Option<Value> {
+        M::ModuleCommand::response_value(command, response)
+    }
+
+    fn simulated(&mut self) -> Option<&mut dyn Simulated> {
+        Module::simulated(self)
+    }
+
     fn as_any(&self) -> &dyn Any {
         self
     }
@@ -29,39 +155,149 @@ impl<M: Module + 'static> DynModule for M {
 
 pub struct ModuleManager {
     modules: HashMap<u16, Box<dyn DynModule>>,
+    /// What each registered module reported when it was identified
+    identities: HashMap<u16, ModuleIdentity>,
+    layout: SlotLayout,
+    update_interval: Option<Interval>,
+    watchdog: Watchdog,
+    /// Controller the modules were registered with, whose alarms gate commands
+    system_controller: Option<Arc<SystemController>>,
+    confirmations: Confirmations,
+    /// Commands submitted and not run yet
+    queue: CommandQueue,
 }
 
 impl ModuleManager {
     pub fn new() -> Self {
+        Self::with_layout(SlotLayout::default())
+    }
+
+    pub fn with_layout(layout: SlotLayout) -> Self {
         Self {
             modules: HashMap::new(),
+            identities: HashMap::new(),
+            layout,
+            update_interval: None,
+            watchdog: Watchdog::default(),
+            system_controller: None,
+            confirmations: Confirmations::default(),
+            queue: CommandQueue::default(),
         }
     }
 
-    /// Generate a **random unique module ID**
-    pub fn generate_unique_id(&self) -> u16 {
-        let mut rng = rand::rng();
-
-        loop {
-            let id = rng.random_range(1..=9999);
-            if !self.modules.contains_key(&id) {
-                return id;
-            }
-        }
+    /// Supervise modules with other watchdog timings than the defaults
+    pub fn with_watchdog(mut self, config: WatchdogConfig) -> Self {
+        self.watchdog = Watchdog::new(config);
+        self
     }
 
+    /// Identify a newly detected module over the bus, then initialize and register it.
+    ///
+    /// The module ID is derived from the module's hardware UID, so a module keeps its ID
+    /// (and therefore its settings and log history) across reboots and slot changes.
     pub fn register_module<M: Module + 'static>(
         &mut self,
         mut module: M,
         system_controller: Arc<SystemController>,
-    ) {
-        let id = module.metadata().id;
+    ) -> Result<u16, ModuleRegistrationError> {
+        let identity = module.identify()?;
+        let id = identity.module_id();
+
+        let reported = module.metadata().id;
+        if reported != id {
+            return Err(ModuleRegistrationError::IdMismatch {
+                reported,
+                expected: id,
+            });
+        }
+
+        if let Some(existing) = self.identities.get(&id) {
+            if existing.uid != identity.uid {
+                return Err(ModuleRegistrationError::IdCollision {
+                    id,
+                    uid: identity.uid,
+                    existing: existing.uid,
+                });
+            }
+        }
+
+        if let Some((occupant, _)) = self
+            .identities
+            .iter()
+            .find(|(other, existing)| **other != id && existing.slot == identity.slot)
+        {
+            return Err(ModuleRegistrationError::SlotOccupied {
+                slot: identity.slot,
+                occupant: *occupant,
+            });
+        }
 
         if let Err(err) = module.initialize(system_controller.clone()) {
             error!("Unable to initialize module {}", err);
         }
 
+        info!(
+            "Module {} ({}) registered in slot {}",
+            id, identity.uid, identity.slot
+        );
+
+        self.identities.insert(id, identity);
         self.modules.insert(id, Box::new(module));
+        self.watchdog.watch(id, system_controller.now());
+        self.system_controller = Some(system_controller);
+        Ok(id)
+    }
+
+    /// Identity reported by a registered module
+    pub fn identity(&self, id: u16) -> Option<ModuleIdentity> {
+        self.identities.get(&id).copied()
+    }
+
+    pub fn layout(&self) -> &SlotLayout {
+        &self.layout
+    }
+
+    /// Which module sits in which slot of the layout
+    pub fn topology(&self) -> Topology {
+        let mut occupants: Vec<(u8, SlotOccupant)> = self
+            .modules
+            .iter()
+            .filter_map(|(id, module)| {
+                let identity = self.identities.get(id)?;
+                let metadata = module.metadata();
+
+                Some((
+                    identity.slot,
+                    SlotOccupant {
+                        module_id: *id,
+                        uid: identity.uid,
+                        module_kind: metadata.module_kind,
+                        name: metadata.name,
+                    },
+                ))
+            })
+            .collect();
+        occupants.sort_by_key(|(slot, _)| *slot);
+
+        let slots = self
+            .layout
+            .slots
+            .iter()
+            .map(|definition| SlotState {
+                definition: definition.clone(),
+                occupant: occupants
+                    .iter()
+                    .position(|(slot, _)| *slot == definition.slot)
+                    .map(|index| occupants.remove(index).1),
+            })
+            .collect();
+
+        Topology {
+            rows: self.layout.rows,
+            columns: self.layout.columns,
+            slots,
+            unplaced: occupants,
+        }
     }
 
     pub fn get_module<M: Module + 'static>(&self, id: u16) -> Option<&M> {
@@ -76,11 +312,421 @@ impl ModuleManager {
             .and_then(|module| module.as_any_mut().downcast_mut::<M>())
     }
 
+    /// Send a command to a module without knowing its concrete type.
+    ///
+    /// Fails with `InvalidCommand` if `command` is not of the module's command type, and is
+    /// `Refused` if an interlock of the command trips or if it is critical.
+    pub fn send_command<C: 'static>(
+        &mut self,
+        id: u16,
+        command: C,
+    ) -> ModuleCommandExecutionResponse {
+        self.dispatch(id, Box::new(command), None)
+    }
+
+    /// Send a critical command, confirmed with a token from `request_confirmation`
+    pub fn send_confirmed_command<C: 'static>(
+        &mut self,
+        id: u16,
+        command: C,
+        confirmation: ConfirmationToken,
+    ) -> ModuleCommandExecutionResponse {
+        self.dispatch(id, Box::new(command), Some(confirmation))
+    }
+
+    /// A token to send the critical command named `command` to a module with, once the user
+    /// confirmed it. The token expires after `interlock::CONFIRMATION_TIMEOUT`.
+    pub fn request_confirmation(&mut self, id: u16, command: &str) -> ConfirmationToken {
+        let now = self.now();
+        self.confirmations.issue(id, command, now)
+    }
+
+    /// Queue a command for a module, returning a handle to its response of command `S`.
+    ///
+    /// Commands are run in the order they were submitted to each module, the next time
+    /// `process_queue` (or `tick`) is called. `submit_command!` derives `S` from the command.
+    ///
+    /// `queue_timeout` only bounds how long the command may wait in the queue: one that has
+    /// not been sent by then completes with `QueueTimeout`. Once sent, a command runs to
+    /// completion however long the module takes.
+    pub fn submit<S: ModuleCommand, C: 'static>(
+        &mut self,
+        id: u16,
+        command: C,
+        queue_timeout: Option<Duration>,
+    ) -> CommandHandle<S::Response>
+    where
+        S::Response: 'static,
+    {
+        let send_by = queue_timeout.map(|timeout| self.now() + timeout);
+        self.queue.push(id, Box::new(command), None, send_by)
+    }
+
+    /// Queue a critical command, confirmed with a token from `request_confirmation`
+    pub fn submit_confirmed<S: ModuleCommand, C: 'static>(
+        &mut self,
+        id: u16,
+        command: C,
+        confirmation: ConfirmationToken,
+        queue_timeout: Option<Duration>,
+    ) -> CommandHandle<S::Response>
+    where
+        S::Response: 'static,
+    {
+        let send_by = queue_timeout.map(|timeout| self.now() + timeout);
+        self.queue
+            .push(id, Box::new(command), Some(confirmation), send_by)
+    }
+
+    /// How many commands are waiting for a module
+    pub fn queued_commands(&self, id: u16) -> usize {
+        self.queue.len(id)
+    }
+
+    /// Run the submitted commands, completing their handles. Cancelled commands and those past
+    /// their queue timeout are completed without being sent.
+    pub fn process_queue(&mut self) {
+        for (id, queued) in self.queue.drain() {
+            if !queued.is_live(self.now()) {
+                continue;
+            }
+
+            queued.run(|command, confirmation| self.dispatch(id, command, confirmation));
+        }
+    }
+
+    // Every command sent through the manager goes through here, so that interlocks and
+    // confirmations cannot be bypassed
+    fn dispatch(
+        &mut self,
+        id: u16,
+        command: Box<dyn Any>,
+        confirmation: Option<ConfirmationToken>,
+    ) -> ModuleCommandExecutionResponse {
+        let now = self.now();
+        let Some(module) = self.modules.get_mut(&id) else {
+            return Err(ModuleCommandExecutionError::InvalidCommand(format!(
+                "No module with ID {id}"
+            )));
+        };
+
+        if let Some((name, safety)) = module.command_safety(command.as_ref()) {
+            let alarms = self
+                .system_controller
+                .as_ref()
+                .map(|controller| controller.alarms())
+                .unwrap_or_default();
+            interlock::authorize(
+                name,
+                &safety,
+                id,
+                &alarms,
+                &mut self.confirmations,
+                confirmation,
+                now,
+            )?;
+        }
+
+        module.process_dyn_command(command)
+    }
+
+    fn now(&self) -> Duration {
+        self.system_controller
+            .as_ref()
+            .map_or(Duration::ZERO, |controller| controller.now())
+    }
+
+    /// Commands of a module, for UIs that do not know its command type
+    pub fn command_descriptors(&self, id: u16) -> Option<Vec<CommandDescriptor>> {
+        self.modules
+            .get(&id)
+            .map(|module| module.command_descriptors())
+    }
+
+    /// Send the command named `command` to a module, with arguments and response as `Value`s.
+    /// Critical commands need a `confirmation`.
+    ///
+    /// Fails with `InvalidArguments` if the arguments do not fit the command's descriptor.
+    pub fn send_values(
+        &mut self,
+        id: u16,
+        command: &str,
+        args: Vec<Value>,
+        confirmation: Option<ConfirmationToken>,
+    ) -> Result<Value, ModuleCommandExecutionError> {
+        let Some(module) = self.modules.get(&id) else {
+            return Err(ModuleCommandExecutionError::InvalidCommand(format!(
+                "No module with ID {id}"
+            )));
+        };
+        let built = module.command_from_values(command, args)?;
+
+        let response = self.dispatch(id, built, confirmation)?;
+        self.modules
+            .get(&id)
+            .and_then(|module| module.response_value(command, response.as_ref()))
+            .ok_or(ModuleCommandExecutionError::DowncastFailure)
+    }
+
     pub fn remove_module(&mut self, id: u16) -> bool {
+        self.identities.remove(&id);
+        self.watchdog.forget(id);
         self.modules.remove(&id).is_some()
     }
 
     pub fn list_modules(&self) -> Vec<ModuleMetadata> {
         self.modules.values().map(|m| m.metadata()).collect()
     }
+
+    pub fn telemetry_schema(&self, id: u16) -> Option<TelemetrySchema> {
+        self.modules
+            .get(&id)
+            .map(|module| module.telemetry_schema())
+    }
+
+    pub fn telemetry(&self, id: u16) -> Option<TelemetryReport> {
+        self.modules.get(&id).map(|module| module.telemetry())
+    }
+
+    /// Telemetry of every registered module
+    pub fn all_telemetry(&self) -> Vec<TelemetryReport> {
+        self.modules
+            .values()
+            .map(|module| module.telemetry())
+            .collect()
+    }
+
+    /// Update every registered module once. Modules the watchdog gave up on are left alone.
+    pub fn update_modules(&mut self) {
+        for (id, module) in self.modules.iter_mut() {
+            if !self.watchdog.is_faulted(*id) {
+                module.update();
+            }
+        }
+    }
+
+    pub fn watchdog(&self) -> &Watchdog {
+        &self.watchdog
+    }
+
+    /// Health of a registered module, as seen by the watchdog
+    pub fn health(&self, id: u16) -> Option<ModuleHealth> {
+        self.watchdog.health(id)
+    }
+
+    /// Try again to recover a module the watchdog gave up on, e.g. once it has been replaced
+    pub fn recover_module(&mut self, id: u16) {
+        self.watchdog.retry(id);
+    }
+
+    /// Poll the modules whose heartbeat is due, and handle the ones that stopped responding.
+    ///
+    /// A module that misses `WatchdogConfig::missed_heartbeats` heartbeats in a row is reported
+    /// as failed, then reset and re-initialized before each following heartbeat. If that does
+    /// not bring it back within `WatchdogConfig::recovery_attempts`, it is put in its safe state
+    /// and no longer updated.
+    pub fn supervise(&mut self, system_controller: &Arc<SystemController>) {
+        let now = system_controller.now();
+
+        for id in self.watchdog.due(now) {
+            let Some(module) = self.modules.get_mut(&id) else {
+                continue;
+            };
+
+            if let Some(ModuleHealth::Recovering { .. }) = self.watchdog.health(id) {
+                self.watchdog.begin_recovery(id);
+                info!("Resetting unresponsive module {}", id);
+
+                let recovered = module
+                    .reset()
+                    .and_then(|_| module.initialize(system_controller.clone()));
+                if let Err(err) = recovered {
+                    error!("Unable to reset module {}: {}", id, err);
+                }
+            }
+
+            let result = module.heartbeat().map_err(|err| err.to_string());
+            let Some(event) = self.watchdog.record(id, result, now) else {
+                continue;
+            };
+
+            match event {
+                WatchdogEvent::Unresponsive { error } => {
+                    error!("Module {} stopped responding: {}", id, error);
+                    system_controller.raise_alarm(
+                        id,
+                        "unresponsive",
+                        ModuleEvent::Critical(CriticalEvent::ModuleFailure(format!(
+                            "Module {id} stopped responding: {error}"
+                        ))),
+                    );
+                }
+                WatchdogEvent::Recovered => {
+                    info!("Module {} is responding again", id);
+                    system_controller.clear_alarm(id, "unresponsive");
+                    system_controller.clear_alarm(id, "unrecoverable");
+                    system_controller.emit_event(ModuleEvent::Info(format!(
+                        "Module {id} is responding again"
+                    )));
+                }
+                WatchdogEvent::GaveUp { error } => {
+                    error!("Module {} could not be recovered: {}", id, error);
+                    module.enter_safe_state();
+                    system_controller.raise_alarm(
+                        id,
+                        "unrecoverable",
+                        ModuleEvent::Critical(CriticalEvent::ModuleFailure(format!(
+                            "Module {id} could not be recovered and was put in its safe state: {error}"
+                        ))),
+                    );
+                }
+            }
+        }
+    }
+
+    /// Iterate over simulated modules, either all of them or only the one with id `target`
+    pub fn simulated_modules_mut(
+        &mut self,
+        target: Option<u16>,
+    ) -> impl Iterator<Item = (u16, &mut dyn Simulated)> {
+        self.modules
+            .iter_mut()
+            .filter(move |(id, _)| target.is_none_or(|target| target == **id))
+            .filter_map(|(id, module)| module.simulated().map(|simulated| (*id, simulated)))
+    }
+
+    /// Feed the simulated environment to every simulated module
+    pub fn apply_environment(&mut self, environment: &SimEnvironment) {
+        let mut environment = environment.clone();
+
+        // Ports limit what the loads plugged into them can draw, which in turn
+        // determines the load seen by the batteries. Likewise the charger and the
+        // batteries see each other through the environment.
+        for (_, simulated) in self.simulated_modules_mut(None) {
+            if let Some((port, limit)) = simulated.port_limit() {
+                environment.port_limits.insert(port, limit);
+            }
+            if let Some(supply) = simulated.charge_supply() {
+                environment.charge_supply = Some(supply);
+            }
+            if let Some(pack) = simulated.pack_state() {
+                environment.pack = Some(pack);
+            }
+        }
+
+        for (_, simulated) in self.simulated_modules_mut(None) {
+            simulated.apply_environment(&environment);
+        }
+    }
+
+    /// Run the submitted commands, then update all modules if the update period has elapsed on
+    /// the system clock, and run the watchdog. Meant to be called from the main loop as often
+    /// as convenient.
+    pub fn tick(&mut self, system_controller: &Arc<SystemController>) -> bool {
+        self.process_queue();
+
+        let now = system_controller.now();
+        let interval = self
+            .update_interval
+            .get_or_insert_with(|| Interval::new(DEFAULT_UPDATE_PERIOD, now));
+
+        if !interval.poll(now) {
+            return false;
+        }
+
+        self.update_modules();
+        self.supervise(system_controller);
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::modules::{
+        commands::{
+            port_module_commands, BatteryModuleCommands, MockBattery, MockPort, PortModuleCommands,
+        },
+        interlock::{InterlockError, CONFIRMATION_TIMEOUT},
+        mock::testing::{identity, mock_port, setup},
+        module::ModuleKind,
+    };
+
+    #[test]
+    fn interlock_refuses_only_what_it_covers() {
+        let (_, controller, mut modules) = setup();
+        let mut port = mock_port(1);
+        port.expect_set_output().returning(());
+        let id = modules.register_module(port, controller.clone()).unwrap();
+
+        controller.raise_alarm(
+            id,
+            "current",
+            ModuleEvent::Critical(CriticalEvent::ModuleFailure("shorted".into())),
+        );
+
+        let on = modules.send_command(id, PortModuleCommands::SetOutput { state: true });
+        assert!(matches!(
+            on,
+            Err(ModuleCommandExecutionError::Refused(
+                InterlockError::Interlocked { .. }
+            ))
+        ));
+        // Turning the output off stays possible whatever the alarms
+        let off = modules.send_command(id, PortModuleCommands::SetOutput { state: false });
+        assert!(off.is_ok());
+
+        controller.clear_alarm(id, "current");
+        let on = modules.send_command(id, PortModuleCommands::SetOutput { state: true });
+        assert!(on.is_ok());
+
+        let port = modules.get_module::<MockPort>(id).unwrap();
+        assert_eq!(port.call_count::<port_module_commands::SetOutput>(), 2);
+    }
+
+    #[test]
+    fn critical_command_needs_its_own_fresh_token() {
+        let (clock, controller, mut modules) = setup();
+        let mut battery = MockBattery::new(identity(ModuleKind::Battery, 1), ModuleKind::Battery);
+        battery
+            .expect_reset_config()
+            .times(1)
+            .returning_with(|_| Ok(()));
+        let id = modules.register_module(battery, controller).unwrap();
+
+        let unconfirmed = modules.send_command(id, BatteryModuleCommands::ResetConfig);
+        assert!(matches!(
+            unconfirmed,
+            Err(ModuleCommandExecutionError::Refused(
+                InterlockError::ConfirmationRequired("ResetConfig")
+            ))
+        ));
+
+        let other_command = modules.request_confirmation(id, "SetConfig");
+        let result =
+            modules.send_confirmed_command(id, BatteryModuleCommands::ResetConfig, other_command);
+        assert!(matches!(
+            result,
+            Err(ModuleCommandExecutionError::Refused(
+                InterlockError::InvalidConfirmation("ResetConfig")
+            ))
+        ));
+
+        let expired = modules.request_confirmation(id, "ResetConfig");
+        clock.advance(CONFIRMATION_TIMEOUT);
+        let result =
+            modules.send_confirmed_command(id, BatteryModuleCommands::ResetConfig, expired);
+        assert!(matches!(
+            result,
+            Err(ModuleCommandExecutionError::Refused(
+                InterlockError::InvalidConfirmation("ResetConfig")
+            ))
+        ));
+
+        let token = modules.request_confirmation(id, "ResetConfig");
+        let result = modules.send_confirmed_command(id, BatteryModuleCommands::ResetConfig, token);
+        assert!(result.is_ok());
+
+        modules.get_module::<MockBattery>(id).unwrap().verify();
+    }
 }
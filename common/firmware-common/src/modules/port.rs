@@ -0,0 +1,37 @@
+use serde::{Deserialize, Serialize};
+
+use crate::settings::ModuleConfig;
+
+/// User-tunable configuration of an output port module, persisted through the settings store.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PortConfig {
+    /// User-facing name (e.g. "USB-C 1"), empty if unnamed
+    pub name: String,
+
+    /// Power budget priority: when the pack cannot supply every port, higher priority
+    /// ports keep their current and lower priority ports are derated first
+    pub priority: u8,
+
+    /// Most current the port hardware can deliver, in amperes
+    pub max_current_a: f64,
+
+    /// Output voltage, in volts
+    pub voltage_v: f64,
+}
+
+impl Default for PortConfig {
+    fn default() -> Self {
+        Self {
+            name: String::new(),
+            priority: 100,
+            max_current_a: 3.0,
+            voltage_v: 5.0,
+        }
+    }
+}
+
+impl ModuleConfig for PortConfig {
+    const NAME: &'static str = "port";
+    const VERSION: u32 = 1;
+}
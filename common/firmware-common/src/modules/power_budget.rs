@@ -0,0 +1,307 @@
+use log::error;
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    time::Duration,
+};
+use uom::si::electric_charge::milliampere_hour;
+use uom::si::electric_current::ampere;
+use uom::si::f64::{ElectricCharge, ElectricCurrent};
+
+use crate::clock::Interval;
+
+use super::{
+    commands::PortModuleCommands,
+    module::ModuleKind,
+    module_manager::ModuleManager,
+    system_controller::{ModuleEvent, SystemController},
+    telemetry::{Severity, TelemetryReport, TelemetryValue},
+};
+
+/// How often the budget is recomputed by default
+pub const DEFAULT_BUDGET_PERIOD: Duration = Duration::from_millis(500);
+
+/// Allocation changes smaller than this are not sent to the port modules, in amperes
+const ALLOCATION_DEADBAND_A: f64 = 0.05;
+
+/// What the power budget granted to a single output port
+#[derive(Debug, Clone)]
+pub struct PortAllocation {
+    pub module_id: u16,
+    pub port: u8,
+    pub priority: u8,
+    /// Current the load on the port asks for
+    pub demand: ElectricCurrent,
+    /// Most current the port hardware can deliver
+    pub max_current: ElectricCurrent,
+    /// Current limit handed to the port
+    pub allocated: ElectricCurrent,
+}
+
+impl PortAllocation {
+    /// Whether the port gets less than its load asks for (within what the hardware allows)
+    pub fn is_derated(&self) -> bool {
+        let wanted = if self.demand < self.max_current {
+            self.demand
+        } else {
+            self.max_current
+        };
+
+        (wanted - self.allocated).get::<ampere>() > ALLOCATION_DEADBAND_A
+    }
+}
+
+/// Result of the last budget computation, for display
+#[derive(Debug, Clone)]
+pub struct BudgetSnapshot {
+    pub timestamp: Duration,
+    /// Combined continuous discharge limit of every battery
+    pub available_current: ElectricCurrent,
+    /// Combined charge left in every battery
+    pub remaining_capacity: ElectricCharge,
+    pub total_demand: ElectricCurrent,
+    pub total_allocated: ElectricCurrent,
+    /// Whether a battery currently derates its discharge current for temperature
+    pub thermal_derating: bool,
+    /// Allocations in priority order
+    pub ports: Vec<PortAllocation>,
+}
+
+impl Default for BudgetSnapshot {
+    fn default() -> Self {
+        Self {
+            timestamp: Duration::ZERO,
+            available_current: ElectricCurrent::new::<ampere>(0.0),
+            remaining_capacity: ElectricCharge::new::<milliampere_hour>(0.0),
+            total_demand: ElectricCurrent::new::<ampere>(0.0),
+            total_allocated: ElectricCurrent::new::<ampere>(0.0),
+            thermal_derating: false,
+            ports: Vec::new(),
+        }
+    }
+}
+
+impl BudgetSnapshot {
+    pub fn is_derating(&self) -> bool {
+        self.ports.iter().any(PortAllocation::is_derated)
+    }
+}
+
+/// Shares the current the batteries can supply between the output ports.
+///
+/// Every period the budget reads the telemetry of all modules, grants each port its demand in
+/// priority order (higher `PortConfig::priority` first, lower module ID on ties) until the
+/// battery limit is reached, hands out any leftover headroom in the same order, and pushes the
+/// resulting limits to the ports with `PortModuleCommands::SetCurrentLimit`.
+pub struct PowerBudget {
+    interval: Interval,
+    snapshot: BudgetSnapshot,
+    /// Last limit sent to each port module
+    applied: BTreeMap<u16, ElectricCurrent>,
+    /// Port modules currently derated, to only report changes
+    derated: BTreeSet<u16>,
+}
+
+impl PowerBudget {
+    pub fn new(period: Duration) -> Self {
+        Self {
+            interval: Interval::new(period, Duration::ZERO),
+            snapshot: BudgetSnapshot::default(),
+            applied: BTreeMap::new(),
+            derated: BTreeSet::new(),
+        }
+    }
+
+    pub fn snapshot(&self) -> &BudgetSnapshot {
+        &self.snapshot
+    }
+
+    /// Rebalance if the budget period has elapsed on the system clock.
+    /// Meant to be called from the main loop right after `ModuleManager::tick`.
+    pub fn tick(
+        &mut self,
+        modules: &mut ModuleManager,
+        system_controller: &SystemController,
+    ) -> bool {
+        if !self.interval.poll(system_controller.now()) {
+            return false;
+        }
+
+        self.rebalance(modules, system_controller);
+        true
+    }
+
+    /// Recompute the budget and apply it to the port modules
+    pub fn rebalance(&mut self, modules: &mut ModuleManager, system_controller: &SystemController) {
+        let kinds: BTreeMap<u16, ModuleKind> = modules
+            .list_modules()
+            .into_iter()
+            .map(|metadata| (metadata.id, metadata.module_kind))
+            .collect();
+
+        let mut snapshot = BudgetSnapshot {
+            timestamp: system_controller.now(),
+            ..Default::default()
+        };
+
+        for report in modules.all_telemetry() {
+            match kinds.get(&report.module_id) {
+                Some(ModuleKind::Battery) => {
+                    if let Some(limit) = current(&report, "max_discharge_current") {
+                        snapshot.available_current += limit;
+                    }
+                    if let Some(TelemetryValue::Charge(charge)) =
+                        report.get("remaining_capacity").map(|entry| &entry.value)
+                    {
+                        snapshot.remaining_capacity += *charge;
+                    }
+                    if report
+                        .get("discharge_derating")
+                        .is_some_and(|entry| entry.severity != Severity::Normal)
+                    {
+                        snapshot.thermal_derating = true;
+                    }
+                }
+                Some(ModuleKind::OutputPort) => {
+                    let enabled = !matches!(
+                        report.get("output_enabled").map(|entry| &entry.value),
+                        Some(TelemetryValue::Boolean(false))
+                    );
+                    let zero = ElectricCurrent::new::<ampere>(0.0);
+
+                    snapshot.ports.push(PortAllocation {
+                        module_id: report.module_id,
+                        port: count(&report, "port").unwrap_or_default() as u8,
+                        priority: count(&report, "priority").unwrap_or_default() as u8,
+                        demand: enabled
+                            .then(|| current(&report, "demand"))
+                            .flatten()
+                            .unwrap_or(zero),
+                        max_current: current(&report, "max_current").unwrap_or(zero),
+                        allocated: zero,
+                    });
+                }
+                _ => {}
+            }
+        }
+
+        allocate(snapshot.available_current, &mut snapshot.ports);
+
+        for port in &snapshot.ports {
+            snapshot.total_demand += port.demand;
+            snapshot.total_allocated += port.allocated;
+        }
+
+        self.apply(&snapshot, modules, system_controller);
+        self.snapshot = snapshot;
+    }
+
+    /// Send changed limits to the ports and report ports entering or leaving derating
+    fn apply(
+        &mut self,
+        snapshot: &BudgetSnapshot,
+        modules: &mut ModuleManager,
+        system_controller: &SystemController,
+    ) {
+        self.applied
+            .retain(|id, _| snapshot.ports.iter().any(|port| port.module_id == *id));
+        self.derated
+            .retain(|id| snapshot.ports.iter().any(|port| port.module_id == *id));
+
+        for port in &snapshot.ports {
+            let changed = self.applied.get(&port.module_id).is_none_or(|applied| {
+                (*applied - port.allocated).abs().get::<ampere>() > ALLOCATION_DEADBAND_A
+            });
+
+            if changed {
+                match modules.send_command(
+                    port.module_id,
+                    PortModuleCommands::SetCurrentLimit {
+                        limit: port.allocated,
+                    },
+                ) {
+                    Ok(_) => {
+                        self.applied.insert(port.module_id, port.allocated);
+                    }
+                    Err(err) => error!(
+                        "Unable to set current limit of port module {}: {}",
+                        port.module_id, err
+                    ),
+                }
+            }
+
+            if port.is_derated() {
+                if self.derated.insert(port.module_id) {
+                    system_controller.emit_event(ModuleEvent::Warning(format!(
+                        "Port {} derated to {:.2} A (demand {:.2} A): {}",
+                        port.port,
+                        port.allocated.get::<ampere>(),
+                        port.demand.get::<ampere>(),
+                        if snapshot.thermal_derating {
+                            "battery derated for temperature"
+                        } else {
+                            "battery limit reached"
+                        }
+                    )));
+                }
+            } else if self.derated.remove(&port.module_id) {
+                system_controller.emit_event(ModuleEvent::Info(format!(
+                    "Port {} back to full current",
+                    port.port
+                )));
+            }
+        }
+    }
+}
+
+impl Default for PowerBudget {
+    fn default() -> Self {
+        Self::new(DEFAULT_BUDGET_PERIOD)
+    }
+}
+
+/// Split `available` between `ports`, sorting them by priority in the process
+fn allocate(available: ElectricCurrent, ports: &mut [PortAllocation]) {
+    ports.sort_by(|a, b| {
+        b.priority
+            .cmp(&a.priority)
+            .then(a.module_id.cmp(&b.module_id))
+    });
+
+    let mut remaining = available.get::<ampere>().max(0.0);
+
+    // First serve the demand, highest priority first
+    for port in ports.iter_mut() {
+        let wanted = port
+            .demand
+            .get::<ampere>()
+            .min(port.max_current.get::<ampere>())
+            .max(0.0);
+        let granted = wanted.min(remaining);
+
+        port.allocated = ElectricCurrent::new::<ampere>(granted);
+        remaining -= granted;
+    }
+
+    // Then hand out the headroom so that loads can ramp up before the next rebalance
+    for port in ports.iter_mut() {
+        let headroom = (port.max_current - port.allocated).get::<ampere>().max(0.0);
+        let granted = headroom.min(remaining);
+
+        port.allocated += ElectricCurrent::new::<ampere>(granted);
+        remaining -= granted;
+    }
+}
+
+fn current(report: &TelemetryReport, key: &str) -> Option<ElectricCurrent> {
+    match report.get(key).map(|entry| &entry.value) {
+        Some(TelemetryValue::Current(current)) => Some(*current),
+        _ => None,
+    }
+}
+
+fn count(report: &TelemetryReport, key: &str) -> Option<i64> {
+    match report.get(key).map(|entry| &entry.value) {
+        Some(TelemetryValue::Count(count)) => Some(*count),
+        _ => None,
+    }
+}
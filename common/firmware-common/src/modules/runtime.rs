@@ -0,0 +1,280 @@
+use std::time::Duration;
+use uom::si::electric_current::ampere;
+use uom::si::f64::{ElectricCurrent, Ratio, Time};
+use uom::si::ratio::ratio;
+use uom::si::time::second;
+
+use super::telemetry::{Severity, TelemetryReport, TelemetryValue};
+
+/// Time constant of the fast current average, which follows load changes, in seconds
+const FAST_TIME_CONSTANT_S: f64 = 10.0;
+
+/// Time constant of the slow current average the estimate is based on, in seconds
+const SLOW_TIME_CONSTANT_S: f64 = 120.0;
+
+/// Relative difference between the fast and slow averages treated as a new load, which
+/// restarts the slow average instead of letting it drift over for minutes
+const LOAD_STEP_RATIO: f64 = 0.25;
+
+/// Below this the pack is considered idle and no estimate is made, in amperes
+const IDLE_CURRENT_A: f64 = 0.02;
+
+/// Estimates longer than this are not reported, the pack is effectively idle
+const MAX_ESTIMATE: Duration = Duration::from_secs(100 * 3600);
+
+/// How long the pack can run, or take to charge, at the current load
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RuntimeEstimate {
+    /// Smoothed pack current (negative = discharge, positive = charge)
+    pub average_current: ElectricCurrent,
+    /// `None` unless discharging
+    pub time_to_empty: Option<Duration>,
+    /// `None` unless charging
+    pub time_to_full: Option<Duration>,
+    /// How much the estimate can be trusted, from 0.0 (just changed) to 1.0 (steady load)
+    pub confidence: f64,
+}
+
+impl Default for RuntimeEstimate {
+    fn default() -> Self {
+        Self {
+            average_current: ElectricCurrent::new::<ampere>(0.0),
+            time_to_empty: None,
+            time_to_full: None,
+            confidence: 0.0,
+        }
+    }
+}
+
+impl RuntimeEstimate {
+    /// Combine the estimates of several packs feeding the same load: the system runs until the
+    /// first pack is empty and is charged once the last one is full
+    pub fn combine(estimates: impl IntoIterator<Item = RuntimeEstimate>) -> Option<Self> {
+        estimates.into_iter().reduce(|a, b| Self {
+            average_current: a.average_current + b.average_current,
+            time_to_empty: match (a.time_to_empty, b.time_to_empty) {
+                (Some(a), Some(b)) => Some(a.min(b)),
+                (a, b) => a.or(b),
+            },
+            time_to_full: a.time_to_full.max(b.time_to_full),
+            confidence: a.confidence.min(b.confidence),
+        })
+    }
+
+    /// Read back an estimate a battery reported through telemetry
+    pub fn from_telemetry(report: &TelemetryReport) -> Option<Self> {
+        let duration = |key: &str| match report.get(key).map(|entry| &entry.value) {
+            Some(TelemetryValue::Duration(time)) => {
+                Duration::try_from_secs_f64(time.get::<second>().max(0.0)).ok()
+            }
+            _ => None,
+        };
+
+        let confidence = match report.get("runtime_confidence").map(|entry| &entry.value) {
+            Some(TelemetryValue::Ratio(confidence)) => confidence.get::<ratio>(),
+            _ => return None,
+        };
+        let average_current = match report.get("average_current").map(|entry| &entry.value) {
+            Some(TelemetryValue::Current(current)) => *current,
+            _ => ElectricCurrent::new::<ampere>(0.0),
+        };
+
+        Some(Self {
+            average_current,
+            time_to_empty: duration("time_to_empty"),
+            time_to_full: duration("time_to_full"),
+            confidence,
+        })
+    }
+
+    /// Add the estimate to a telemetry report, under the keys `from_telemetry` reads
+    pub fn report(&self, report: &mut TelemetryReport) {
+        report.push(
+            "average_current",
+            TelemetryValue::Current(self.average_current),
+            Severity::Normal,
+        );
+        if let Some(time) = self.time_to_empty {
+            report.push(
+                "time_to_empty",
+                TelemetryValue::Duration(Time::new::<second>(time.as_secs_f64())),
+                Severity::Normal,
+            );
+        }
+        if let Some(time) = self.time_to_full {
+            report.push(
+                "time_to_full",
+                TelemetryValue::Duration(Time::new::<second>(time.as_secs_f64())),
+                Severity::Normal,
+            );
+        }
+        report.push(
+            "runtime_confidence",
+            TelemetryValue::Ratio(Ratio::new::<ratio>(self.confidence)),
+            Severity::Normal,
+        );
+    }
+}
+
+/// Predicts time to empty and time to full from the pack current and charge.
+///
+/// The current is smoothed by two exponential averages: the estimate uses the slow one so
+/// that short bursts do not make it jump around, and the fast one detects a new load, in
+/// which case the slow average restarts from it. Confidence grows as the load stays steady
+/// and drops with how much the current fluctuates.
+pub struct RuntimeEstimator {
+    fast: f64,
+    slow: f64,
+    /// Exponential average of the squared deviation from `slow`
+    variance: f64,
+    /// When the current load started
+    load_since: Option<Duration>,
+    last_update: Option<Duration>,
+    estimate: RuntimeEstimate,
+}
+
+impl RuntimeEstimator {
+    pub fn new() -> Self {
+        Self {
+            fast: 0.0,
+            slow: 0.0,
+            variance: 0.0,
+            load_since: None,
+            last_update: None,
+            estimate: RuntimeEstimate::default(),
+        }
+    }
+
+    pub fn estimate(&self) -> &RuntimeEstimate {
+        &self.estimate
+    }
+
+    /// Feed a new sample.
+    ///
+    /// `current` is the pack current (negative = discharge), `remaining_mah` the charge left and
+    /// `capacity_mah` the charge of a full pack.
+    pub fn update(
+        &mut self,
+        current: ElectricCurrent,
+        remaining_mah: f64,
+        capacity_mah: f64,
+        now: Duration,
+    ) -> &RuntimeEstimate {
+        let current = current.get::<ampere>();
+
+        let Some(last) = self.last_update.replace(now) else {
+            self.restart(current, now);
+            return self.finish(remaining_mah, capacity_mah, now);
+        };
+
+        let dt = now.saturating_sub(last).as_secs_f64();
+        let fast_weight = 1.0 - (-dt / FAST_TIME_CONSTANT_S).exp();
+        let slow_weight = 1.0 - (-dt / SLOW_TIME_CONSTANT_S).exp();
+
+        self.fast += (current - self.fast) * fast_weight;
+
+        let step = (self.fast - self.slow).abs();
+        let reference = self.slow.abs().max(IDLE_CURRENT_A);
+        if step / reference > LOAD_STEP_RATIO && step > IDLE_CURRENT_A {
+            self.restart(self.fast, now);
+        } else {
+            self.slow += (current - self.slow) * slow_weight;
+            self.variance += ((current - self.slow).powi(2) - self.variance) * slow_weight;
+        }
+
+        self.finish(remaining_mah, capacity_mah, now)
+    }
+
+    fn restart(&mut self, current: f64, now: Duration) {
+        self.fast = current;
+        self.slow = current;
+        self.variance = 0.0;
+        self.load_since = Some(now);
+    }
+
+    fn finish(&mut self, remaining_mah: f64, capacity_mah: f64, now: Duration) -> &RuntimeEstimate {
+        let current = self.slow;
+        let time_for = |charge_mah: f64| {
+            let seconds = charge_mah.max(0.0) / (current.abs() * 1000.0) * 3600.0;
+            // Checked before the filter, as a tiny current makes the time too long for a Duration
+            Duration::try_from_secs_f64(seconds)
+                .ok()
+                .filter(|time| *time <= MAX_ESTIMATE)
+        };
+
+        let (time_to_empty, time_to_full) = if current < -IDLE_CURRENT_A {
+            (time_for(remaining_mah), None)
+        } else if current > IDLE_CURRENT_A {
+            (None, time_for(capacity_mah - remaining_mah))
+        } else {
+            (None, None)
+        };
+
+        // Confidence builds up over the slow time constant after a load change...
+        let steady_for = self
+            .load_since
+            .map_or(0.0, |since| now.saturating_sub(since).as_secs_f64());
+        let settled = 1.0 - (-steady_for / SLOW_TIME_CONSTANT_S).exp();
+        // ...and is lower for a fluctuating load
+        let fluctuation = self.variance.sqrt() / current.abs().max(IDLE_CURRENT_A);
+        let stability = 1.0 / (1.0 + fluctuation);
+
+        self.estimate = RuntimeEstimate {
+            average_current: ElectricCurrent::new::<ampere>(current),
+            time_to_empty,
+            time_to_full,
+            confidence: (settled * stability).clamp(0.0, 1.0),
+        };
+        &self.estimate
+    }
+}
+
+impl Default for RuntimeEstimator {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn estimates_time_to_empty() {
+        let mut estimator = RuntimeEstimator::new();
+        let estimate = estimator.update(
+            ElectricCurrent::new::<ampere>(-1.0),
+            500.0,
+            1000.0,
+            Duration::ZERO,
+        );
+        assert_eq!(estimate.time_to_empty, Some(Duration::from_secs(1800)));
+        assert_eq!(estimate.time_to_full, None);
+    }
+
+    #[test]
+    fn drops_estimates_too_long_for_a_duration() {
+        let mut estimator = RuntimeEstimator::new();
+        let estimate = estimator.update(
+            ElectricCurrent::new::<ampere>(IDLE_CURRENT_A * 1.01),
+            0.0,
+            f64::MAX,
+            Duration::ZERO,
+        );
+        assert_eq!(estimate.time_to_full, None);
+
+        let mut report = TelemetryReport::new(1, Duration::ZERO);
+        report.push(
+            "time_to_empty",
+            TelemetryValue::Duration(Time::new::<second>(f64::INFINITY)),
+            Severity::Normal,
+        );
+        report.push(
+            "runtime_confidence",
+            TelemetryValue::Ratio(Ratio::new::<ratio>(1.0)),
+            Severity::Normal,
+        );
+        let estimate = RuntimeEstimate::from_telemetry(&report).unwrap();
+        assert_eq!(estimate.time_to_empty, None);
+    }
+}
@@ -0,0 +1,941 @@
+use crossbeam::channel::Receiver;
+use log::{info, warn};
+use std::{
+    cmp::Reverse,
+    collections::{BTreeMap, BTreeSet, VecDeque},
+    fmt,
+    time::Duration,
+};
+
+use super::{
+    alarms::{AlarmKey, AlarmState},
+    commands::{BatteryModuleCommands, ChargerModuleCommands, PortModuleCommands},
+    module::ModuleKind,
+    module_manager::ModuleManager,
+    system_controller::{CriticalEvent, ModuleEvent, SystemController, TimestampedEvent},
+    telemetry::{Severity, TelemetryValue},
+};
+
+/// How many actions `SafetyPolicy::audit_log` keeps
+const AUDIT_LOG_LEN: usize = 200;
+
+/// Alarm condition raised on a charger while charging is locked out. The interlock of
+/// `ChargerModuleCommands::SetChargingEnabled` refuses to enable charging while it is raised.
+pub const CHARGE_LOCKOUT_CONDITION: &str = "charge_lockout";
+
+/// Alarm condition raised on a module whose output a rule turned off, until the alarm that
+/// made the rule act clears. The interlocks of the output commands refuse to turn it back on
+/// while it is raised, whoever asks.
+pub const SAFETY_HOLD_CONDITION: &str = "safety_hold";
+
+/// A `CriticalEvent` without its reading, to match events in rules
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CriticalEventKind {
+    OverVoltage,
+    UnderVoltage,
+    OverCurrent,
+    OverTemperature,
+    ModuleFailure,
+}
+
+impl CriticalEventKind {
+    pub fn of(event: &CriticalEvent) -> Self {
+        match event {
+            CriticalEvent::OverVoltage(_) => CriticalEventKind::OverVoltage,
+            CriticalEvent::UnderVoltage(_) => CriticalEventKind::UnderVoltage,
+            CriticalEvent::OverCurrent(_) => CriticalEventKind::OverCurrent,
+            CriticalEvent::OverTemperature(_) => CriticalEventKind::OverTemperature,
+            CriticalEvent::ModuleFailure(_) => CriticalEventKind::ModuleFailure,
+        }
+    }
+}
+
+impl fmt::Display for CriticalEventKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            CriticalEventKind::OverVoltage => write!(f, "Overvoltage"),
+            CriticalEventKind::UnderVoltage => write!(f, "Undervoltage"),
+            CriticalEventKind::OverCurrent => write!(f, "Overcurrent"),
+            CriticalEventKind::OverTemperature => write!(f, "Overtemperature"),
+            CriticalEventKind::ModuleFailure => write!(f, "Module failure"),
+        }
+    }
+}
+
+/// What makes a rule act
+#[derive(Debug, Clone, PartialEq)]
+pub enum PolicyTrigger {
+    /// The alarm `condition` is raised with at least `severity`, on a module of `module_kind`
+    /// or on any module. The rule acts once per alarm, and again if it is raised again after
+    /// clearing.
+    Alarm {
+        condition: String,
+        module_kind: Option<ModuleKind>,
+        severity: Severity,
+    },
+    /// A critical event of this kind is emitted. Events do not say which module emitted them,
+    /// so `PolicyTarget::Source` resolves to nothing.
+    Event(CriticalEventKind),
+}
+
+impl PolicyTrigger {
+    pub fn alarm(condition: impl Into<String>, severity: Severity) -> Self {
+        PolicyTrigger::Alarm {
+            condition: condition.into(),
+            module_kind: None,
+            severity,
+        }
+    }
+
+    /// Only match the alarm on modules of `kind`
+    pub fn on(self, kind: ModuleKind) -> Self {
+        match self {
+            PolicyTrigger::Alarm {
+                condition,
+                severity,
+                ..
+            } => PolicyTrigger::Alarm {
+                condition,
+                module_kind: Some(kind),
+                severity,
+            },
+            event => event,
+        }
+    }
+}
+
+impl fmt::Display for PolicyTrigger {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PolicyTrigger::Alarm {
+                condition,
+                module_kind: Some(kind),
+                severity,
+            } => write!(f, "{severity:?} `{condition}` alarm on a {kind} module"),
+            PolicyTrigger::Alarm {
+                condition,
+                module_kind: None,
+                severity,
+            } => write!(f, "{severity:?} `{condition}` alarm"),
+            PolicyTrigger::Event(kind) => write!(f, "{kind} event"),
+        }
+    }
+}
+
+/// Modules an action applies to
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PolicyTarget {
+    /// The module that raised the alarm
+    Source,
+    Module(u16),
+    /// Every module of this kind
+    Kind(ModuleKind),
+}
+
+impl fmt::Display for PolicyTarget {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PolicyTarget::Source => write!(f, "source module"),
+            PolicyTarget::Module(id) => write!(f, "module {id}"),
+            PolicyTarget::Kind(kind) => write!(f, "every {kind} module"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PolicyAction {
+    /// Turn off the output of batteries and output ports, or stop chargers
+    DisableOutput(PolicyTarget),
+    /// Turn off the enabled output port with the lowest power budget priority (the highest
+    /// module ID on ties, the reverse of the order the budget serves ports in)
+    ShedLowestPriorityPort,
+    /// Stop every charger and keep charging from being enabled until
+    /// `SafetyPolicy::release_charge_lockout`
+    LockOutCharging,
+}
+
+impl fmt::Display for PolicyAction {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PolicyAction::DisableOutput(target) => write!(f, "Disable output of {target}"),
+            PolicyAction::ShedLowestPriorityPort => write!(f, "Shed lowest priority port"),
+            PolicyAction::LockOutCharging => write!(f, "Lock out charging"),
+        }
+    }
+}
+
+/// Actions to take when a trigger fires
+#[derive(Debug, Clone, PartialEq)]
+pub struct PolicyRule {
+    pub name: String,
+    /// Rules with a higher priority act first. Rules of the same priority act in the order
+    /// they were added.
+    pub priority: u8,
+    pub trigger: PolicyTrigger,
+    /// Taken in order
+    pub actions: Vec<PolicyAction>,
+}
+
+impl PolicyRule {
+    pub fn new(name: impl Into<String>, trigger: PolicyTrigger) -> Self {
+        Self {
+            name: name.into(),
+            priority: 100,
+            trigger,
+            actions: Vec::new(),
+        }
+    }
+
+    pub fn priority(mut self, priority: u8) -> Self {
+        self.priority = priority;
+        self
+    }
+
+    /// Add an action (builder style)
+    pub fn then(mut self, action: PolicyAction) -> Self {
+        self.actions.push(action);
+        self
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum AuditOutcome {
+    Applied,
+    /// Not applied because the policy is in dry-run mode
+    DryRun,
+    Failed(String),
+    /// Nothing to apply the action to, or a module that does not report whether it did
+    Skipped(String),
+}
+
+impl fmt::Display for AuditOutcome {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            AuditOutcome::Applied => write!(f, "applied"),
+            AuditOutcome::DryRun => write!(f, "dry run"),
+            AuditOutcome::Failed(error) => write!(f, "failed: {error}"),
+            AuditOutcome::Skipped(reason) => write!(f, "skipped: {reason}"),
+        }
+    }
+}
+
+/// An action a rule took, or would have taken in dry-run mode
+#[derive(Debug, Clone, PartialEq)]
+pub struct AuditEntry {
+    pub timestamp: Duration,
+    pub rule: String,
+    /// What fired the rule, e.g. the alarm and its message
+    pub cause: String,
+    pub action: PolicyAction,
+    /// Module the action was applied to, if it got that far
+    pub module_id: Option<u16>,
+    pub outcome: AuditOutcome,
+}
+
+impl fmt::Display for AuditEntry {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "[{:.3}s] {} ({}): {}",
+            self.timestamp.as_secs_f64(),
+            self.rule,
+            self.cause,
+            self.action
+        )?;
+        if let Some(id) = self.module_id {
+            write!(f, " on module {id}")?;
+        }
+        write!(f, ", {}", self.outcome)
+    }
+}
+
+// A rule firing, with what fired it
+struct Activation {
+    rule: usize,
+    /// Alarm that fired the rule, `None` for events
+    alarm: Option<AlarmKey>,
+    cause: String,
+}
+
+/// Protective actions taken automatically on alarms and critical events.
+///
+/// Rules are evaluated every `tick`, highest priority first, and act through
+/// `ModuleManager::send_command` like any other command. Each action is recorded in the audit
+/// log; in dry-run mode it is only recorded, so that a policy can be tried against a
+/// simulation without touching the modules.
+///
+/// What the policy turns off stays off for as long as the reason for it: outputs are held
+/// with `SAFETY_HOLD_CONDITION` until the alarm that fired the rule clears (or until
+/// `release_holds` for rules fired by events), and chargers with `CHARGE_LOCKOUT_CONDITION`
+/// until `release_charge_lockout`.
+///
+/// ```
+/// # use stratum_firmware_common::modules::{
+/// #     safety_policy::{PolicyAction, PolicyRule, PolicyTrigger, SafetyPolicy},
+/// #     telemetry::Severity,
+/// # };
+/// let policy = SafetyPolicy::new(vec![
+///     PolicyRule::new("Port overload", PolicyTrigger::alarm("current", Severity::Warning))
+///         .then(PolicyAction::ShedLowestPriorityPort),
+/// ]);
+/// ```
+pub struct SafetyPolicy {
+    /// Sorted by priority
+    rules: Vec<PolicyRule>,
+    dry_run: bool,
+    /// Critical events since the last tick, subscribed to on the first one
+    events: Option<Receiver<TimestampedEvent>>,
+    /// Alarms each rule already acted on, by rule name, until they go away
+    fired: BTreeSet<(String, AlarmKey)>,
+    /// Chargers locked out
+    lockouts: BTreeSet<u16>,
+    /// Modules whose output is held off, with the alarms holding them
+    holds: BTreeMap<u16, BTreeSet<Option<AlarmKey>>>,
+    audit: VecDeque<AuditEntry>,
+}
+
+impl SafetyPolicy {
+    pub fn new(rules: Vec<PolicyRule>) -> Self {
+        let mut policy = Self {
+            rules: Vec::new(),
+            dry_run: false,
+            events: None,
+            fired: BTreeSet::new(),
+            lockouts: BTreeSet::new(),
+            holds: BTreeMap::new(),
+            audit: VecDeque::with_capacity(AUDIT_LOG_LEN),
+        };
+        for rule in rules {
+            policy.add_rule(rule);
+        }
+        policy
+    }
+
+    /// Rules for the modules of a standard Stratum: shed load and then disconnect the battery
+    /// on over-current, stop everything on battery over-temperature, and lock out charging on
+    /// charge faults and over-voltage.
+    pub fn default_rules() -> Vec<PolicyRule> {
+        vec![
+            PolicyRule::new(
+                "Shed load on battery over-current",
+                PolicyTrigger::alarm("current", Severity::Warning).on(ModuleKind::Battery),
+            )
+            .priority(200)
+            .then(PolicyAction::ShedLowestPriorityPort),
+            PolicyRule::new(
+                "Disconnect battery on over-current",
+                PolicyTrigger::alarm("current", Severity::Critical).on(ModuleKind::Battery),
+            )
+            .priority(150)
+            .then(PolicyAction::DisableOutput(PolicyTarget::Source)),
+            PolicyRule::new(
+                "Stop on battery over-temperature",
+                PolicyTrigger::alarm("temperature", Severity::Critical).on(ModuleKind::Battery),
+            )
+            .priority(150)
+            .then(PolicyAction::LockOutCharging)
+            .then(PolicyAction::DisableOutput(PolicyTarget::Source)),
+            PolicyRule::new(
+                "Lock out charging on charge fault",
+                PolicyTrigger::alarm("charge_fault", Severity::Critical).on(ModuleKind::Charger),
+            )
+            .then(PolicyAction::LockOutCharging),
+            PolicyRule::new(
+                "Lock out charging on over-voltage",
+                PolicyTrigger::Event(CriticalEventKind::OverVoltage),
+            )
+            .then(PolicyAction::LockOutCharging),
+        ]
+    }
+
+    /// Insert `rule` after the rules of the same or a higher priority
+    pub fn add_rule(&mut self, rule: PolicyRule) {
+        let index = self
+            .rules
+            .partition_point(|existing| existing.priority >= rule.priority);
+        self.rules.insert(index, rule);
+    }
+
+    /// Rules in the order they act
+    pub fn rules(&self) -> &[PolicyRule] {
+        &self.rules
+    }
+
+    /// Only record what the rules would do. Switching modes lets rules act again on the
+    /// alarms that are still raised.
+    pub fn set_dry_run(&mut self, dry_run: bool) {
+        if self.dry_run != dry_run {
+            self.fired.clear();
+        }
+        self.dry_run = dry_run;
+    }
+
+    pub fn is_dry_run(&self) -> bool {
+        self.dry_run
+    }
+
+    /// Actions taken, oldest first
+    pub fn audit_log(&self) -> impl DoubleEndedIterator<Item = &AuditEntry> {
+        self.audit.iter()
+    }
+
+    /// Chargers currently locked out
+    pub fn charge_lockouts(&self) -> impl Iterator<Item = u16> + '_ {
+        self.lockouts.iter().copied()
+    }
+
+    /// Allow charging again after a lockout, once its cause has been dealt with. Chargers stay
+    /// stopped until they are enabled.
+    pub fn release_charge_lockout(&mut self, system_controller: &SystemController) {
+        for id in std::mem::take(&mut self.lockouts) {
+            system_controller.clear_alarm(id, CHARGE_LOCKOUT_CONDITION);
+            info!("Charging lockout of module {} released", id);
+        }
+    }
+
+    /// Modules whose output is held off
+    pub fn holds(&self) -> impl Iterator<Item = u16> + '_ {
+        self.holds.keys().copied()
+    }
+
+    /// Allow every held output to be turned on again, including the ones held because of an
+    /// event. Outputs stay off until they are turned on.
+    pub fn release_holds(&mut self, system_controller: &SystemController) {
+        for id in std::mem::take(&mut self.holds).into_keys() {
+            system_controller.clear_alarm(id, SAFETY_HOLD_CONDITION);
+            info!("Safety hold of module {} released", id);
+        }
+    }
+
+    /// Act on the alarms raised and the critical events emitted since the last tick. Meant to
+    /// be called from the main loop right after `ModuleManager::tick`.
+    pub fn tick(&mut self, modules: &mut ModuleManager, system_controller: &SystemController) {
+        let events = self
+            .events
+            .get_or_insert_with(|| system_controller.subscribe());
+        let events: Vec<CriticalEvent> = events
+            .try_iter()
+            .filter_map(|event| match event.event {
+                ModuleEvent::Critical(critical) => Some(critical),
+                _ => None,
+            })
+            .collect();
+
+        let kinds: BTreeMap<u16, ModuleKind> = modules
+            .list_modules()
+            .into_iter()
+            .map(|metadata| (metadata.id, metadata.module_kind))
+            .collect();
+
+        let mut activations = Vec::new();
+        let mut raised = BTreeSet::new();
+        for alarm in system_controller
+            .alarms()
+            .into_iter()
+            .filter(|alarm| alarm.state != AlarmState::Cleared)
+        {
+            for (index, rule) in self.rules.iter().enumerate() {
+                let PolicyTrigger::Alarm {
+                    condition,
+                    module_kind,
+                    severity,
+                } = &rule.trigger
+                else {
+                    continue;
+                };
+                let matches = alarm.key.condition == *condition
+                    && alarm.severity >= *severity
+                    && module_kind
+                        .is_none_or(|kind| kinds.get(&alarm.key.module_id) == Some(&kind));
+                if !matches {
+                    continue;
+                }
+
+                let fired = (rule.name.clone(), alarm.key.clone());
+                if !self.fired.contains(&fired) {
+                    activations.push(Activation {
+                        rule: index,
+                        alarm: Some(alarm.key.clone()),
+                        cause: format!("{}: {}", alarm.key, alarm.message),
+                    });
+                }
+                raised.insert(fired);
+            }
+        }
+        // Alarms that went away arm their rules again, and release what they held
+        let active: BTreeSet<&AlarmKey> = raised.iter().map(|(_, key)| key).collect();
+        self.holds.retain(|id, causes| {
+            causes.retain(|cause| cause.as_ref().is_none_or(|key| active.contains(key)));
+            if causes.is_empty() {
+                system_controller.clear_alarm(*id, SAFETY_HOLD_CONDITION);
+                info!("Safety hold of module {} released", id);
+            }
+            !causes.is_empty()
+        });
+        self.fired = raised;
+
+        for event in &events {
+            let kind = CriticalEventKind::of(event);
+            for (index, rule) in self.rules.iter().enumerate() {
+                if rule.trigger == PolicyTrigger::Event(kind) {
+                    activations.push(Activation {
+                        rule: index,
+                        alarm: None,
+                        cause: event.to_string(),
+                    });
+                }
+            }
+        }
+
+        activations.sort_by_key(|activation| activation.rule);
+        for activation in activations {
+            let rule = &self.rules[activation.rule];
+            let name = rule.name.clone();
+            for action in rule.actions.clone() {
+                self.apply(
+                    &name,
+                    &activation,
+                    action,
+                    &kinds,
+                    modules,
+                    system_controller,
+                );
+            }
+        }
+    }
+
+    fn apply(
+        &mut self,
+        rule: &str,
+        activation: &Activation,
+        action: PolicyAction,
+        kinds: &BTreeMap<u16, ModuleKind>,
+        modules: &mut ModuleManager,
+        system_controller: &SystemController,
+    ) {
+        let targets: Vec<u16> = match action {
+            PolicyAction::DisableOutput(PolicyTarget::Source) => {
+                activation.alarm.iter().map(|key| key.module_id).collect()
+            }
+            PolicyAction::DisableOutput(PolicyTarget::Module(id)) => vec![id],
+            PolicyAction::DisableOutput(PolicyTarget::Kind(kind)) => kinds
+                .iter()
+                .filter(|(_, module_kind)| **module_kind == kind)
+                .map(|(id, _)| *id)
+                .collect(),
+            PolicyAction::ShedLowestPriorityPort => {
+                lowest_priority_port(modules, kinds).into_iter().collect()
+            }
+            PolicyAction::LockOutCharging => kinds
+                .iter()
+                .filter(|(_, kind)| **kind == ModuleKind::Charger)
+                .map(|(id, _)| *id)
+                .collect(),
+        };
+
+        if targets.is_empty() {
+            let reason = match action {
+                PolicyAction::DisableOutput(target) => format!("no {target}"),
+                PolicyAction::ShedLowestPriorityPort => "no enabled port".to_string(),
+                PolicyAction::LockOutCharging => "no charger".to_string(),
+            };
+            self.record(
+                rule,
+                activation,
+                action,
+                None,
+                AuditOutcome::Skipped(reason),
+                system_controller,
+            );
+            return;
+        }
+
+        for id in targets {
+            let outcome = if self.dry_run {
+                AuditOutcome::DryRun
+            } else {
+                let kind = match action {
+                    PolicyAction::LockOutCharging => {
+                        self.lockouts.insert(id);
+                        system_controller.raise_alarm(
+                            id,
+                            CHARGE_LOCKOUT_CONDITION,
+                            ModuleEvent::Warning(format!(
+                                "Charging of module {id} locked out by safety rule `{rule}`"
+                            )),
+                        );
+                        Some(ModuleKind::Charger)
+                    }
+                    _ => kinds.get(&id).copied(),
+                };
+                let outcome = match kind {
+                    Some(kind) => disable_output(modules, id, kind),
+                    None => AuditOutcome::Skipped(format!("module {id} has no output")),
+                };
+                if outcome == AuditOutcome::Applied && action != PolicyAction::LockOutCharging {
+                    self.hold(id, rule, activation, system_controller);
+                }
+                outcome
+            };
+            self.record(
+                rule,
+                activation,
+                action,
+                Some(id),
+                outcome,
+                system_controller,
+            );
+        }
+    }
+
+    fn hold(
+        &mut self,
+        id: u16,
+        rule: &str,
+        activation: &Activation,
+        system_controller: &SystemController,
+    ) {
+        self.holds
+            .entry(id)
+            .or_default()
+            .insert(activation.alarm.clone());
+        system_controller.raise_alarm(
+            id,
+            SAFETY_HOLD_CONDITION,
+            ModuleEvent::Warning(format!(
+                "Output of module {id} held off by safety rule `{rule}`"
+            )),
+        );
+    }
+
+    fn record(
+        &mut self,
+        rule: &str,
+        activation: &Activation,
+        action: PolicyAction,
+        module_id: Option<u16>,
+        outcome: AuditOutcome,
+        system_controller: &SystemController,
+    ) {
+        let entry = AuditEntry {
+            timestamp: system_controller.now(),
+            rule: rule.to_string(),
+            cause: activation.cause.clone(),
+            action,
+            module_id,
+            outcome,
+        };
+
+        match entry.outcome {
+            AuditOutcome::Applied => {
+                warn!("Safety policy: {}", entry);
+                system_controller.emit_event(ModuleEvent::Warning(format!(
+                    "Safety rule `{}`: {}{}",
+                    entry.rule,
+                    entry.action,
+                    module_id.map_or(String::new(), |id| format!(" on module {id}"))
+                )));
+            }
+            _ => info!("Safety policy: {}", entry),
+        }
+
+        if self.audit.len() >= AUDIT_LOG_LEN {
+            self.audit.pop_front();
+        }
+        self.audit.push_back(entry);
+    }
+}
+
+impl Default for SafetyPolicy {
+    fn default() -> Self {
+        Self::new(Self::default_rules())
+    }
+}
+
+/// Turn off what module `id` outputs.
+///
+/// Only counts as applied once the module reports its output off: a module that accepts the
+/// command without reporting its output state has not shown that anything was switched.
+fn disable_output(modules: &mut ModuleManager, id: u16, kind: ModuleKind) -> AuditOutcome {
+    let (result, state_key) = match kind {
+        ModuleKind::Battery => (
+            modules.send_command(id, BatteryModuleCommands::SetOutput { state: false }),
+            "output_enabled",
+        ),
+        ModuleKind::OutputPort => (
+            modules.send_command(id, PortModuleCommands::SetOutput { state: false }),
+            "output_enabled",
+        ),
+        ModuleKind::Charger => (
+            modules.send_command(
+                id,
+                ChargerModuleCommands::SetChargingEnabled { enabled: false },
+            ),
+            "charge_enabled",
+        ),
+        _ => return AuditOutcome::Skipped(format!("module {id} has no output")),
+    };
+    if let Err(err) = result {
+        return AuditOutcome::Failed(err.to_string());
+    }
+
+    let report = modules.telemetry(id);
+    match report.as_ref().and_then(|report| report.get(state_key)) {
+        Some(entry) if matches!(entry.value, TelemetryValue::Boolean(false)) => {
+            AuditOutcome::Applied
+        }
+        Some(_) => AuditOutcome::Failed(format!("module {id} still reports its output on")),
+        None => AuditOutcome::Skipped(format!("module {id} does not report its output state")),
+    }
+}
+
+/// The enabled port the power budget would serve last
+fn lowest_priority_port(modules: &ModuleManager, kinds: &BTreeMap<u16, ModuleKind>) -> Option<u16> {
+    kinds
+        .iter()
+        .filter(|(_, kind)| **kind == ModuleKind::OutputPort)
+        .filter_map(|(id, _)| {
+            let report = modules.telemetry(*id)?;
+            let enabled = matches!(
+                report.get("output_enabled").map(|entry| &entry.value),
+                Some(TelemetryValue::Boolean(true))
+            );
+            let priority = match report.get("priority").map(|entry| &entry.value) {
+                Some(TelemetryValue::Count(priority)) => *priority,
+                _ => 0,
+            };
+            enabled.then_some((priority, Reverse(*id)))
+        })
+        .min()
+        .map(|(_, Reverse(id))| id)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{cell::Cell, rc::Rc, sync::Arc};
+
+    use super::*;
+    use crate::modules::{
+        commands::{MockBattery, MockCharger, MockPort},
+        interlock::InterlockError,
+        mock::testing::{identity, mock_port, setup},
+        module::{ModuleCommandExecutionError, ModuleCommandExecutionResponse},
+        telemetry::TelemetryReport,
+    };
+
+    /// A port that reports the output state it was last set to
+    fn add_port(
+        modules: &mut ModuleManager,
+        controller: &Arc<SystemController>,
+        slot: u8,
+        priority: i64,
+    ) -> u16 {
+        let output = Rc::new(Cell::new(true));
+        let mut port = mock_port(slot);
+
+        let state = output.clone();
+        port.expect_set_output().returning_with(move |command| {
+            if let PortModuleCommands::SetOutput { state: on } = command {
+                state.set(*on);
+            }
+        });
+        let id = identity(ModuleKind::OutputPort, slot).module_id();
+        port.set_telemetry(move || {
+            TelemetryReport::new(id, Duration::ZERO)
+                .with(
+                    "output_enabled",
+                    TelemetryValue::Boolean(output.get()),
+                    Severity::Normal,
+                )
+                .with(
+                    "priority",
+                    TelemetryValue::Count(priority),
+                    Severity::Normal,
+                )
+        });
+        modules.register_module(port, controller.clone()).unwrap()
+    }
+
+    fn warn(controller: &SystemController, id: u16, condition: &str) {
+        controller.raise_alarm(id, condition, ModuleEvent::Warning("test".into()));
+    }
+
+    fn outcomes(policy: &SafetyPolicy) -> Vec<(String, Option<u16>, AuditOutcome)> {
+        policy
+            .audit_log()
+            .map(|entry| (entry.rule.clone(), entry.module_id, entry.outcome.clone()))
+            .collect()
+    }
+
+    fn refused_by(result: ModuleCommandExecutionResponse) -> Option<String> {
+        match result {
+            Err(ModuleCommandExecutionError::Refused(InterlockError::Interlocked {
+                alarm,
+                ..
+            })) => Some(alarm.condition),
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn rules_act_in_priority_order() {
+        let (_, controller, mut modules) = setup();
+        let low = add_port(&mut modules, &controller, 1, 50);
+        let high = add_port(&mut modules, &controller, 2, 200);
+
+        let trigger = PolicyTrigger::alarm("current", Severity::Warning);
+        let mut policy = SafetyPolicy::new(vec![
+            PolicyRule::new("Second", trigger.clone())
+                .priority(10)
+                .then(PolicyAction::DisableOutput(PolicyTarget::Source)),
+            PolicyRule::new("First", trigger)
+                .priority(200)
+                .then(PolicyAction::ShedLowestPriorityPort),
+        ]);
+
+        warn(&controller, high, "current");
+        policy.tick(&mut modules, &controller);
+
+        assert_eq!(
+            outcomes(&policy),
+            vec![
+                ("First".into(), Some(low), AuditOutcome::Applied),
+                ("Second".into(), Some(high), AuditOutcome::Applied),
+            ]
+        );
+
+        // Edge-triggered: nothing more while the alarm stays raised
+        policy.tick(&mut modules, &controller);
+        assert_eq!(policy.audit_log().count(), 2);
+    }
+
+    #[test]
+    fn dry_run_only_records() {
+        let (_, controller, mut modules) = setup();
+        let port = add_port(&mut modules, &controller, 1, 100);
+        let mut policy = SafetyPolicy::new(vec![PolicyRule::new(
+            "Disconnect",
+            PolicyTrigger::alarm("current", Severity::Warning),
+        )
+        .then(PolicyAction::DisableOutput(PolicyTarget::Source))]);
+        policy.set_dry_run(true);
+
+        warn(&controller, port, "current");
+        policy.tick(&mut modules, &controller);
+        assert_eq!(
+            outcomes(&policy),
+            vec![("Disconnect".into(), Some(port), AuditOutcome::DryRun)]
+        );
+        assert!(modules
+            .get_module::<MockPort>(port)
+            .unwrap()
+            .calls()
+            .is_empty());
+        assert_eq!(policy.holds().count(), 0);
+
+        // Going live acts on the alarm that is still raised
+        policy.set_dry_run(false);
+        policy.tick(&mut modules, &controller);
+        assert_eq!(
+            outcomes(&policy).last(),
+            Some(&("Disconnect".into(), Some(port), AuditOutcome::Applied))
+        );
+    }
+
+    #[test]
+    fn hold_keeps_output_off_until_the_alarm_clears() {
+        let (_, controller, mut modules) = setup();
+        let port = add_port(&mut modules, &controller, 1, 100);
+        let mut policy = SafetyPolicy::new(vec![PolicyRule::new(
+            "Disconnect",
+            PolicyTrigger::alarm("current", Severity::Warning),
+        )
+        .then(PolicyAction::DisableOutput(PolicyTarget::Source))]);
+
+        warn(&controller, port, "current");
+        policy.tick(&mut modules, &controller);
+        assert_eq!(policy.holds().collect::<Vec<_>>(), vec![port]);
+
+        let on = modules.send_command(port, PortModuleCommands::SetOutput { state: true });
+        assert_eq!(refused_by(on).as_deref(), Some(SAFETY_HOLD_CONDITION));
+
+        controller.clear_alarm(port, "current");
+        policy.tick(&mut modules, &controller);
+        assert_eq!(policy.holds().count(), 0);
+        let on = modules.send_command(port, PortModuleCommands::SetOutput { state: true });
+        assert!(on.is_ok());
+    }
+
+    #[test]
+    fn output_the_module_does_not_report_is_skipped() {
+        let (_, controller, mut modules) = setup();
+        let mut battery = MockBattery::new(identity(ModuleKind::Battery, 1), ModuleKind::Battery);
+        battery.expect_set_output().times(1).returning(());
+        let battery = modules
+            .register_module(battery, controller.clone())
+            .unwrap();
+        let mut policy = SafetyPolicy::new(vec![PolicyRule::new(
+            "Disconnect",
+            PolicyTrigger::alarm("current", Severity::Warning),
+        )
+        .then(PolicyAction::DisableOutput(PolicyTarget::Source))]);
+
+        warn(&controller, battery, "current");
+        policy.tick(&mut modules, &controller);
+
+        let outcomes = outcomes(&policy);
+        assert!(matches!(outcomes[..], [(_, _, AuditOutcome::Skipped(_))]));
+        assert_eq!(policy.holds().count(), 0);
+        modules.get_module::<MockBattery>(battery).unwrap().verify();
+    }
+
+    #[test]
+    fn charge_lockout_lasts_until_released() {
+        let (_, controller, mut modules) = setup();
+        let identity = identity(ModuleKind::Charger, 1);
+        let enabled = Rc::new(Cell::new(true));
+        let mut charger = MockCharger::new(identity, ModuleKind::Charger);
+        let state = enabled.clone();
+        charger
+            .expect_set_charging_enabled()
+            .returning_with(move |command| {
+                if let ChargerModuleCommands::SetChargingEnabled { enabled } = command {
+                    state.set(*enabled);
+                }
+            });
+        let id = identity.module_id();
+        charger.set_telemetry(move || {
+            TelemetryReport::new(id, Duration::ZERO).with(
+                "charge_enabled",
+                TelemetryValue::Boolean(enabled.get()),
+                Severity::Normal,
+            )
+        });
+        let charger = modules
+            .register_module(charger, controller.clone())
+            .unwrap();
+
+        let mut policy = SafetyPolicy::new(vec![PolicyRule::new(
+            "Lock out",
+            PolicyTrigger::alarm("charge_fault", Severity::Warning),
+        )
+        .then(PolicyAction::LockOutCharging)]);
+        warn(&controller, charger, "charge_fault");
+        policy.tick(&mut modules, &controller);
+        assert_eq!(policy.charge_lockouts().collect::<Vec<_>>(), vec![charger]);
+
+        // The lockout outlives the fault
+        controller.clear_alarm(charger, "charge_fault");
+        policy.tick(&mut modules, &controller);
+        let enable = ChargerModuleCommands::SetChargingEnabled { enabled: true };
+        assert_eq!(
+            refused_by(modules.send_command(charger, enable)).as_deref(),
+            Some(CHARGE_LOCKOUT_CONDITION)
+        );
+
+        policy.release_charge_lockout(&controller);
+        assert_eq!(policy.charge_lockouts().count(), 0);
+        let enable = ChargerModuleCommands::SetChargingEnabled { enabled: true };
+        assert!(modules.send_command(charger, enable).is_ok());
+    }
+}
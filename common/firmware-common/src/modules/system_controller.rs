@@ -1,17 +1,32 @@
+use crossbeam::channel::{unbounded, Receiver, Sender};
 use stratum_ui_common::ui_logging::LogLevel;
 use log::error;
 use std::{
     any::Any,
     collections::{HashMap, VecDeque},
+    fmt,
     sync::{Arc, Mutex},
+    time::Duration,
 };
+use uom::si::electric_current::ampere;
+use uom::si::electric_potential::volt;
 use uom::si::f64::{ElectricCurrent, ElectricPotential, ThermodynamicTemperature};
+use uom::si::thermodynamic_temperature::degree_celsius;
 
-use crate::events::{create_event_queue, start_event_loop, EventQueue};
+use crate::{
+    clock::{RealClock, SharedClock},
+    events::{create_event_queue, start_event_loop, EventQueue},
+    modules::{
+        alarms::{Alarm, AlarmKey, AlarmManager},
+        telemetry::Severity,
+    },
+    settings::SettingsStore,
+};
 
 /// Log entry struct
 #[derive(Debug, Clone)]
 pub struct LogEntry {
+    pub timestamp: Duration,
     pub level: LogLevel,
     pub message: String,
 }
@@ -25,6 +40,28 @@ pub enum CriticalEvent {
     ModuleFailure(String),           // e.g., Generic module failure with description
 }
 
+impl fmt::Display for CriticalEvent {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            CriticalEvent::OverVoltage(voltage) => {
+                write!(f, "Overvoltage ({:.2} V)", voltage.get::<volt>())
+            }
+            CriticalEvent::UnderVoltage(voltage) => {
+                write!(f, "Undervoltage ({:.2} V)", voltage.get::<volt>())
+            }
+            CriticalEvent::OverCurrent(current) => {
+                write!(f, "Overcurrent ({:.2} A)", current.get::<ampere>())
+            }
+            CriticalEvent::OverTemperature(temperature) => write!(
+                f,
+                "Overtemperature ({:.1} °C)",
+                temperature.get::<degree_celsius>()
+            ),
+            CriticalEvent::ModuleFailure(description) => write!(f, "{description}"),
+        }
+    }
+}
+
 #[derive(Debug, Clone)]
 pub enum ModuleEvent {
     Critical(CriticalEvent),
@@ -36,21 +73,65 @@ pub enum ModuleEvent {
     },
 }
 
+/// A `ModuleEvent` stamped with the system clock time at which it was emitted
+#[derive(Debug, Clone)]
+pub struct TimestampedEvent {
+    pub timestamp: Duration,
+    pub event: ModuleEvent,
+}
+
+/// Services a `SystemController` is built with
+pub struct SystemControllerOptions {
+    pub clock: SharedClock,
+    pub settings: SettingsStore,
+}
+
+impl Default for SystemControllerOptions {
+    fn default() -> Self {
+        Self {
+            clock: Arc::new(RealClock::new()),
+            settings: SettingsStore::in_memory(),
+        }
+    }
+}
+
 /// SystemController manages modules and logs
 pub struct SystemController {
     event_log: Arc<Mutex<VecDeque<String>>>,
     module_logs: Arc<Mutex<HashMap<u16, VecDeque<LogEntry>>>>,
     event_queue: Arc<dyn EventQueue>,
+    subscribers: Mutex<Vec<Sender<TimestampedEvent>>>,
+    alarms: Mutex<AlarmManager>,
+    clock: SharedClock,
+    settings: SettingsStore,
 }
 
 impl SystemController {
     pub fn new() -> Arc<Self> {
+        Self::with_options(SystemControllerOptions::default())
+    }
+
+    /// Create a controller whose timestamps (and those of every module attached to it)
+    /// come from `clock` instead of the wall clock
+    pub fn with_clock(clock: SharedClock) -> Arc<Self> {
+        Self::with_options(SystemControllerOptions {
+            clock,
+            ..Default::default()
+        })
+    }
+
+    pub fn with_options(options: SystemControllerOptions) -> Arc<Self> {
+        let SystemControllerOptions { clock, settings } = options;
         let queue: Arc<dyn EventQueue> = Arc::new(create_event_queue());
 
         let controller = Arc::new(Self {
             event_log: Arc::new(Mutex::new(VecDeque::with_capacity(10_000))),
             module_logs: Arc::new(Mutex::new(HashMap::new())),
             event_queue: Arc::clone(&queue),
+            subscribers: Mutex::new(Vec::new()),
+            alarms: Mutex::new(AlarmManager::new()),
+            clock,
+            settings,
         });
 
         let controller_clone = Arc::clone(&controller); // Clone controller for the closure
@@ -63,18 +144,128 @@ impl SystemController {
         controller
     }
 
-    pub fn handle_event(&self, event: ModuleEvent) {
+    pub fn clock(&self) -> SharedClock {
+        Arc::clone(&self.clock)
+    }
+
+    /// Persistent module configuration
+    pub fn settings(&self) -> &SettingsStore {
+        &self.settings
+    }
+
+    /// Current time according to the system clock
+    pub fn now(&self) -> Duration {
+        self.clock.now()
+    }
+
+    pub fn handle_event(&self, event: TimestampedEvent) {
         if let Ok(mut log) = self.event_log.lock() {
-            log.push_back(format!("{:?}", event));
+            log.push_back(format!(
+                "[{:.3}s] {:?}",
+                event.timestamp.as_secs_f64(),
+                event.event
+            ));
         } else {
             error!("Failed to acquire event_log lock (mutex poisoned)");
         }
     }
 
     pub fn emit_event(&self, event: ModuleEvent) {
+        let event = TimestampedEvent {
+            timestamp: self.clock.now(),
+            event,
+        };
+
+        if let Ok(mut subscribers) = self.subscribers.lock() {
+            // Drop subscribers whose receiving end has gone away
+            subscribers.retain(|subscriber| subscriber.send(event.clone()).is_ok());
+        } else {
+            error!("Failed to acquire subscribers lock (mutex poisoned)");
+        }
+
         self.event_queue.send(event);
     }
 
+    /// Receive a copy of every event emitted from now on. Delivery happens synchronously
+    /// in `emit_event`, so subscribers see events in emission order.
+    pub fn subscribe(&self) -> Receiver<TimestampedEvent> {
+        let (sender, receiver) = unbounded();
+
+        if let Ok(mut subscribers) = self.subscribers.lock() {
+            subscribers.push(sender);
+        } else {
+            error!("Failed to acquire subscribers lock (mutex poisoned)");
+        }
+
+        receiver
+    }
+
+    /// Raise the alarm `condition` of a module, described by `event`.
+    ///
+    /// `event` is only emitted when the alarm is news (see `AlarmManager::raise`), so a module
+    /// can keep raising a condition for as long as it persists without flooding the event log.
+    pub fn raise_alarm(&self, module_id: u16, condition: &str, event: ModuleEvent) {
+        let (severity, message) = match &event {
+            ModuleEvent::Critical(critical) => (Severity::Critical, critical.to_string()),
+            ModuleEvent::Warning(message) => (Severity::Warning, message.clone()),
+            ModuleEvent::Info(message) => (Severity::Normal, message.clone()),
+            ModuleEvent::ModuleEvent { .. } => (Severity::Normal, condition.to_string()),
+        };
+
+        // Better a duplicate than a lost alarm if the manager is unavailable
+        let news = self
+            .with_alarms(|alarms, now| {
+                alarms.raise(AlarmKey::new(module_id, condition), severity, message, now)
+            })
+            .unwrap_or(true);
+
+        if news {
+            self.emit_event(event);
+        }
+    }
+
+    /// The alarm `condition` of a module went away. Returns whether it was raised.
+    pub fn clear_alarm(&self, module_id: u16, condition: &str) -> bool {
+        self.with_alarms(|alarms, now| alarms.clear(&AlarmKey::new(module_id, condition), now))
+            .unwrap_or(false)
+    }
+
+    /// Clear every alarm of a module, e.g. once it is removed
+    pub fn clear_module_alarms(&self, module_id: u16) {
+        self.with_alarms(|alarms, now| alarms.clear_module(module_id, now));
+    }
+
+    pub fn acknowledge_alarm(&self, key: &AlarmKey) -> bool {
+        self.with_alarms(|alarms, now| alarms.acknowledge(key, now))
+            .unwrap_or(false)
+    }
+
+    pub fn acknowledge_all_alarms(&self) {
+        self.with_alarms(|alarms, now| alarms.acknowledge_all(now));
+    }
+
+    /// Alarms that are active, or cleared but not acknowledged yet, most severe first
+    pub fn alarms(&self) -> Vec<Alarm> {
+        self.with_alarms(|alarms, _| alarms.alarms())
+            .unwrap_or_default()
+    }
+
+    /// Alarms that were both cleared and acknowledged, most recent first
+    pub fn alarm_history(&self) -> Vec<Alarm> {
+        self.with_alarms(|alarms, _| alarms.history().rev().cloned().collect())
+            .unwrap_or_default()
+    }
+
+    fn with_alarms<T>(&self, f: impl FnOnce(&mut AlarmManager, Duration) -> T) -> Option<T> {
+        match self.alarms.lock() {
+            Ok(mut alarms) => Some(f(&mut alarms, self.clock.now())),
+            Err(_) => {
+                error!("Failed to acquire alarms lock (mutex poisoned)");
+                None
+            }
+        }
+    }
+
     pub fn log_module_event(&self, module_id: u16, entry: LogEntry) {
         if let Ok(mut logs) = self.module_logs.lock() {
             let module_log = logs
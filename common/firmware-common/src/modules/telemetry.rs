@@ -0,0 +1,232 @@
+use std::{borrow::Cow, fmt, time::Duration};
+
+use uom::si::{
+    electric_charge::milliampere_hour,
+    electric_current::ampere,
+    electric_potential::volt,
+    energy::watt_hour,
+    f64::{
+        ElectricCharge, ElectricCurrent, ElectricPotential, Energy, Power, Ratio,
+        ThermodynamicTemperature, Time,
+    },
+    power::watt,
+    ratio::percent,
+    thermodynamic_temperature::degree_celsius,
+    time::second,
+};
+
+use super::module::ModuleKind;
+
+/// Identifies a telemetry value within a module, e.g. `"voltage"` or `"cell_3_voltage"`
+pub type TelemetryKey = Cow<'static, str>;
+
+/// How concerning a telemetry value is
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Default)]
+pub enum Severity {
+    #[default]
+    Normal,
+    Warning,
+    Critical,
+}
+
+/// The physical quantity a telemetry field measures, and therefore the unit it is shown in
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum QuantityKind {
+    Voltage,
+    Current,
+    Temperature,
+    Power,
+    Energy,
+    Charge,
+    Duration,
+    Ratio,
+    Boolean,
+    Count,
+    /// A named state, e.g. the phase of a state machine
+    State,
+}
+
+impl QuantityKind {
+    /// Symbol of the unit `TelemetryValue::display_value` is expressed in
+    pub fn display_unit(&self) -> &'static str {
+        match self {
+            QuantityKind::Voltage => "V",
+            QuantityKind::Current => "A",
+            QuantityKind::Temperature => "°C",
+            QuantityKind::Power => "W",
+            QuantityKind::Energy => "Wh",
+            QuantityKind::Charge => "mAh",
+            QuantityKind::Duration => "s",
+            QuantityKind::Ratio => "%",
+            QuantityKind::Boolean | QuantityKind::Count | QuantityKind::State => "",
+        }
+    }
+}
+
+/// A single telemetry value, carrying its unit through `uom`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TelemetryValue {
+    Voltage(ElectricPotential),
+    Current(ElectricCurrent),
+    Temperature(ThermodynamicTemperature),
+    Power(Power),
+    Energy(Energy),
+    Charge(ElectricCharge),
+    Duration(Time),
+    Ratio(Ratio),
+    Boolean(bool),
+    Count(i64),
+    State(&'static str),
+}
+
+impl TelemetryValue {
+    pub fn kind(&self) -> QuantityKind {
+        match self {
+            TelemetryValue::Voltage(_) => QuantityKind::Voltage,
+            TelemetryValue::Current(_) => QuantityKind::Current,
+            TelemetryValue::Temperature(_) => QuantityKind::Temperature,
+            TelemetryValue::Power(_) => QuantityKind::Power,
+            TelemetryValue::Energy(_) => QuantityKind::Energy,
+            TelemetryValue::Charge(_) => QuantityKind::Charge,
+            TelemetryValue::Duration(_) => QuantityKind::Duration,
+            TelemetryValue::Ratio(_) => QuantityKind::Ratio,
+            TelemetryValue::Boolean(_) => QuantityKind::Boolean,
+            TelemetryValue::Count(_) => QuantityKind::Count,
+            TelemetryValue::State(_) => QuantityKind::State,
+        }
+    }
+
+    /// The value as a plain number in `QuantityKind::display_unit`, for charts and exporters.
+    /// States have no numeric value and return NaN.
+    pub fn display_value(&self) -> f64 {
+        match self {
+            TelemetryValue::Voltage(v) => v.get::<volt>(),
+            TelemetryValue::Current(v) => v.get::<ampere>(),
+            TelemetryValue::Temperature(v) => v.get::<degree_celsius>(),
+            TelemetryValue::Power(v) => v.get::<watt>(),
+            TelemetryValue::Energy(v) => v.get::<watt_hour>(),
+            TelemetryValue::Charge(v) => v.get::<milliampere_hour>(),
+            TelemetryValue::Duration(v) => v.get::<second>(),
+            TelemetryValue::Ratio(v) => v.get::<percent>(),
+            TelemetryValue::Boolean(v) => f64::from(u8::from(*v)),
+            TelemetryValue::Count(v) => *v as f64,
+            TelemetryValue::State(_) => f64::NAN,
+        }
+    }
+}
+
+impl fmt::Display for TelemetryValue {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TelemetryValue::Boolean(v) => write!(f, "{}", if *v { "on" } else { "off" }),
+            TelemetryValue::Count(v) => write!(f, "{v}"),
+            TelemetryValue::State(v) => write!(f, "{v}"),
+            _ => write!(
+                f,
+                "{:.2} {}",
+                self.display_value(),
+                self.kind().display_unit()
+            ),
+        }
+    }
+}
+
+/// Describes one value a module reports
+#[derive(Debug, Clone)]
+pub struct TelemetryField {
+    pub key: TelemetryKey,
+    /// Human-readable name
+    pub label: Cow<'static, str>,
+    pub kind: QuantityKind,
+}
+
+impl TelemetryField {
+    pub fn new(
+        key: impl Into<TelemetryKey>,
+        label: impl Into<Cow<'static, str>>,
+        kind: QuantityKind,
+    ) -> Self {
+        Self {
+            key: key.into(),
+            label: label.into(),
+            kind,
+        }
+    }
+}
+
+/// Everything a module can report, so that dashboards and exporters can be built
+/// without knowing the module's concrete type
+#[derive(Debug, Clone)]
+pub struct TelemetrySchema {
+    pub module_kind: ModuleKind,
+    pub fields: Vec<TelemetryField>,
+}
+
+impl TelemetrySchema {
+    pub fn field(&self, key: &str) -> Option<&TelemetryField> {
+        self.fields.iter().find(|field| field.key == key)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct TelemetryEntry {
+    pub key: TelemetryKey,
+    pub value: TelemetryValue,
+    pub severity: Severity,
+}
+
+/// A snapshot of a module's state, as a list of self-describing values
+#[derive(Debug, Clone)]
+pub struct TelemetryReport {
+    pub module_id: u16,
+    /// System clock time the values were sampled at
+    pub timestamp: Duration,
+    pub entries: Vec<TelemetryEntry>,
+}
+
+impl TelemetryReport {
+    pub fn new(module_id: u16, timestamp: Duration) -> Self {
+        Self {
+            module_id,
+            timestamp,
+            entries: Vec::new(),
+        }
+    }
+
+    /// Append a value (builder style)
+    pub fn with(
+        mut self,
+        key: impl Into<TelemetryKey>,
+        value: TelemetryValue,
+        severity: Severity,
+    ) -> Self {
+        self.push(key, value, severity);
+        self
+    }
+
+    pub fn push(
+        &mut self,
+        key: impl Into<TelemetryKey>,
+        value: TelemetryValue,
+        severity: Severity,
+    ) {
+        self.entries.push(TelemetryEntry {
+            key: key.into(),
+            value,
+            severity,
+        });
+    }
+
+    pub fn get(&self, key: &str) -> Option<&TelemetryEntry> {
+        self.entries.iter().find(|entry| entry.key == key)
+    }
+
+    /// The most severe level among all entries
+    pub fn severity(&self) -> Severity {
+        self.entries
+            .iter()
+            .map(|entry| entry.severity)
+            .max()
+            .unwrap_or_default()
+    }
+}
@@ -0,0 +1,480 @@
+use serde::{Deserialize, Serialize};
+use std::{collections::BTreeMap, fmt, time::Duration};
+use thiserror::Error;
+
+use super::telemetry::Severity;
+use crate::settings::SettingsError;
+
+#[derive(Debug, Error)]
+pub enum ThresholdError {
+    #[error("Unknown threshold profile `{0}`")]
+    UnknownProfile(String),
+
+    #[error(transparent)]
+    Settings(#[from] SettingsError),
+}
+
+/// One of the limits of a `Threshold`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Limit {
+    LowError,
+    LowWarning,
+    HighWarning,
+    HighError,
+}
+
+impl Limit {
+    /// Most severe first, which is the order limits are checked in
+    const ALL: [Limit; 4] = [
+        Limit::HighError,
+        Limit::LowError,
+        Limit::HighWarning,
+        Limit::LowWarning,
+    ];
+
+    pub fn severity(&self) -> Severity {
+        match self {
+            Limit::LowError | Limit::HighError => Severity::Critical,
+            Limit::LowWarning | Limit::HighWarning => Severity::Warning,
+        }
+    }
+
+    pub fn is_high(&self) -> bool {
+        matches!(self, Limit::HighWarning | Limit::HighError)
+    }
+
+    /// Whether being past `self` also means being past `other`, e.g. a high error is past
+    /// the high warning
+    fn implies(&self, other: Limit) -> bool {
+        self.is_high() == other.is_high() && self.severity() >= other.severity()
+    }
+}
+
+impl fmt::Display for Limit {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Limit::LowError => write!(f, "low error"),
+            Limit::LowWarning => write!(f, "low warning"),
+            Limit::HighWarning => write!(f, "high warning"),
+            Limit::HighError => write!(f, "high error"),
+        }
+    }
+}
+
+/// Warning and error limits of one measured value.
+///
+/// A high limit is crossed once the value rises above it and only clears once the value falls
+/// back below it by `hysteresis`; a low limit the other way round. Unset limits are never
+/// crossed.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Threshold {
+    pub low_error: Option<f64>,
+    pub low_warning: Option<f64>,
+    pub high_warning: Option<f64>,
+    pub high_error: Option<f64>,
+
+    /// How far back inside a crossed limit the value must come before it clears, in the unit
+    /// of the value
+    pub hysteresis: f64,
+    /// How long the value must stay past a limit, or back inside it, before the change is
+    /// reported, in seconds
+    pub debounce_s: f64,
+}
+
+impl Default for Threshold {
+    fn default() -> Self {
+        Self {
+            low_error: None,
+            low_warning: None,
+            high_warning: None,
+            high_error: None,
+            hysteresis: 0.0,
+            debounce_s: 0.0,
+        }
+    }
+}
+
+impl Threshold {
+    /// Warning and error limits above which the value is out of bounds
+    pub fn high(warning: f64, error: f64) -> Self {
+        Self {
+            high_warning: Some(warning),
+            high_error: Some(error),
+            ..Default::default()
+        }
+    }
+
+    /// Warning and error limits below which the value is out of bounds
+    pub fn low(warning: f64, error: f64) -> Self {
+        Self {
+            low_warning: Some(warning),
+            low_error: Some(error),
+            ..Default::default()
+        }
+    }
+
+    pub fn with_hysteresis(mut self, hysteresis: f64) -> Self {
+        self.hysteresis = hysteresis;
+        self
+    }
+
+    pub fn with_debounce(mut self, debounce: Duration) -> Self {
+        self.debounce_s = debounce.as_secs_f64();
+        self
+    }
+
+    pub fn limit(&self, limit: Limit) -> Option<f64> {
+        match limit {
+            Limit::LowError => self.low_error,
+            Limit::LowWarning => self.low_warning,
+            Limit::HighWarning => self.high_warning,
+            Limit::HighError => self.high_error,
+        }
+    }
+
+    pub fn limit_mut(&mut self, limit: Limit) -> &mut Option<f64> {
+        match limit {
+            Limit::LowError => &mut self.low_error,
+            Limit::LowWarning => &mut self.low_warning,
+            Limit::HighWarning => &mut self.high_warning,
+            Limit::HighError => &mut self.high_error,
+        }
+    }
+
+    /// `debounce_s` as a `Duration`, an infinite debounce never letting a change through
+    pub fn debounce(&self) -> Duration {
+        Duration::try_from_secs_f64(self.debounce_s.max(0.0)).unwrap_or(Duration::MAX)
+    }
+
+    /// The most severe limit `value` is past, given the limit currently `active`
+    pub fn evaluate(&self, value: f64, active: Option<Limit>) -> Option<Limit> {
+        Limit::ALL.into_iter().find(|limit| {
+            let Some(level) = self.limit(*limit) else {
+                return false;
+            };
+            // A limit that is already crossed clears with hysteresis
+            let held = active.is_some_and(|active| active.implies(*limit));
+            let margin = if held { self.hysteresis.abs() } else { 0.0 };
+
+            if limit.is_high() {
+                value > level - margin
+            } else {
+                value < level + margin
+            }
+        })
+    }
+}
+
+/// Named set of thresholds, keyed by the telemetry key of the value they apply to.
+///
+/// Everything that depends on the cells (chemistry, number in series, thermal limits) lives
+/// here, so that a different pack only needs a different profile.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ThresholdProfile {
+    /// e.g. the chemistry and configuration the profile is for
+    pub name: String,
+    pub thresholds: BTreeMap<String, Threshold>,
+}
+
+impl Default for ThresholdProfile {
+    fn default() -> Self {
+        Self::li_ion_5s()
+    }
+}
+
+impl ThresholdProfile {
+    /// Names accepted by `ThresholdProfile::builtin`
+    pub const BUILTIN: [&'static str; 2] = ["li-ion-5s", "lifepo4-6s"];
+
+    pub fn builtin(name: &str) -> Result<Self, ThresholdError> {
+        match name {
+            "li-ion-5s" => Ok(Self::li_ion_5s()),
+            "lifepo4-6s" => Ok(Self::lifepo4_6s()),
+            _ => Err(ThresholdError::UnknownProfile(name.into())),
+        }
+    }
+
+    /// 5S Li-ion (NMC) pack, 3.0 V to 4.2 V per cell
+    pub fn li_ion_5s() -> Self {
+        Self {
+            name: "li-ion-5s".into(),
+            thresholds: BTreeMap::from([
+                (
+                    "current".into(),
+                    Threshold::high(4.5, 5.0)
+                        .with_hysteresis(0.2)
+                        .with_debounce(Duration::from_secs(1)),
+                ),
+                (
+                    "temperature".into(),
+                    Threshold::high(40.0, 50.0)
+                        .with_hysteresis(2.0)
+                        .with_debounce(Duration::from_secs(5)),
+                ),
+                (
+                    "voltage".into(),
+                    Threshold {
+                        low_error: Some(15.0),
+                        high_error: Some(21.5),
+                        ..Default::default()
+                    }
+                    .with_hysteresis(0.2)
+                    .with_debounce(Duration::from_secs(1)),
+                ),
+                (
+                    "charge".into(),
+                    Threshold {
+                        low_warning: Some(15.0),
+                        ..Default::default()
+                    }
+                    .with_hysteresis(2.0),
+                ),
+            ]),
+        }
+    }
+
+    /// 6S LiFePO4 pack, 2.5 V to 3.65 V per cell. The flat discharge curve leaves little room
+    /// between the voltage limits, but the cells tolerate more heat.
+    pub fn lifepo4_6s() -> Self {
+        Self {
+            name: "lifepo4-6s".into(),
+            thresholds: BTreeMap::from([
+                (
+                    "current".into(),
+                    Threshold::high(4.5, 5.0)
+                        .with_hysteresis(0.2)
+                        .with_debounce(Duration::from_secs(1)),
+                ),
+                (
+                    "temperature".into(),
+                    Threshold::high(45.0, 55.0)
+                        .with_hysteresis(2.0)
+                        .with_debounce(Duration::from_secs(5)),
+                ),
+                (
+                    "voltage".into(),
+                    Threshold {
+                        low_error: Some(15.0),
+                        high_error: Some(21.9),
+                        ..Default::default()
+                    }
+                    .with_hysteresis(0.1)
+                    .with_debounce(Duration::from_secs(1)),
+                ),
+                (
+                    "charge".into(),
+                    Threshold {
+                        low_warning: Some(15.0),
+                        ..Default::default()
+                    }
+                    .with_hysteresis(2.0),
+                ),
+            ]),
+        }
+    }
+
+    pub fn get(&self, key: &str) -> Option<&Threshold> {
+        self.thresholds.get(key)
+    }
+}
+
+/// A limit crossed or cleared, as reported by `ThresholdMonitor::update`
+#[derive(Debug, Clone, PartialEq)]
+pub struct ThresholdChange {
+    pub key: String,
+    pub value: f64,
+    pub from: Option<Limit>,
+    pub to: Option<Limit>,
+}
+
+impl fmt::Display for ThresholdChange {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match (self.from, self.to) {
+            (_, Some(to)) => write!(f, "{} {} at {:.2}", self.key, to, self.value),
+            (Some(from), None) => write!(f, "{} {} cleared at {:.2}", self.key, from, self.value),
+            (None, None) => write!(f, "{} normal at {:.2}", self.key, self.value),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+struct ThresholdState {
+    active: Option<Limit>,
+    /// Level the value has been at since the given time, waiting for the debounce to elapse
+    pending: Option<(Option<Limit>, Duration)>,
+}
+
+/// Evaluates measured values against a `ThresholdProfile`, keeping track of which limits are
+/// crossed so that values hovering around a limit do not flap
+#[derive(Debug, Clone)]
+pub struct ThresholdMonitor {
+    profile: ThresholdProfile,
+    states: BTreeMap<String, ThresholdState>,
+}
+
+impl ThresholdMonitor {
+    pub fn new(profile: ThresholdProfile) -> Self {
+        Self {
+            profile,
+            states: BTreeMap::new(),
+        }
+    }
+
+    pub fn profile(&self) -> &ThresholdProfile {
+        &self.profile
+    }
+
+    /// Switch to another profile.
+    ///
+    /// Values keep the limit they crossed until their next update, which evaluates them
+    /// against the new limits and reports the change as usual. Returns the limits crossed by
+    /// values the new profile has no threshold for: those are no longer updated, so whoever
+    /// reported them must clear them.
+    pub fn set_profile(&mut self, profile: ThresholdProfile) -> Vec<(String, Limit)> {
+        if profile == self.profile {
+            return Vec::new();
+        }
+        self.profile = profile;
+
+        let mut dropped = Vec::new();
+        self.states.retain(|key, state| {
+            if self.profile.get(key).is_none() {
+                dropped.extend(state.active.map(|limit| (key.clone(), limit)));
+                return false;
+            }
+            state.pending = None;
+            true
+        });
+        dropped
+    }
+
+    /// Record a new measurement of `key`, returning the change worth reporting if any.
+    ///
+    /// Values without a threshold in the profile are ignored, and so are NaN readings: the
+    /// previous level is kept rather than clearing a limit on a broken sensor.
+    pub fn update(&mut self, key: &str, value: f64, now: Duration) -> Option<ThresholdChange> {
+        let threshold = self.profile.get(key)?;
+        if value.is_nan() {
+            return None;
+        }
+
+        let state = self.states.entry(key.into()).or_default();
+        let target = threshold.evaluate(value, state.active);
+
+        if target == state.active {
+            state.pending = None;
+            return None;
+        }
+
+        let since = match state.pending {
+            Some((pending, since)) if pending == target => since,
+            _ => {
+                state.pending = Some((target, now));
+                now
+            }
+        };
+        if now.saturating_sub(since) < threshold.debounce() {
+            return None;
+        }
+
+        let from = std::mem::replace(&mut state.active, target);
+        state.pending = None;
+
+        Some(ThresholdChange {
+            key: key.into(),
+            value,
+            from,
+            to: target,
+        })
+    }
+
+    /// Limit currently crossed by `key`
+    pub fn active(&self, key: &str) -> Option<Limit> {
+        self.states.get(key).and_then(|state| state.active)
+    }
+
+    pub fn severity(&self, key: &str) -> Severity {
+        self.active(key)
+            .map_or(Severity::Normal, |limit| limit.severity())
+    }
+
+    /// Every crossed limit, by key
+    pub fn active_limits(&self) -> impl Iterator<Item = (&str, Limit)> {
+        self.states
+            .iter()
+            .filter_map(|(key, state)| state.active.map(|limit| (key.as_str(), limit)))
+    }
+}
+
+impl Default for ThresholdMonitor {
+    fn default() -> Self {
+        Self::new(ThresholdProfile::default())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn profile(high_warning: f64) -> ThresholdProfile {
+        ThresholdProfile {
+            name: "test".into(),
+            thresholds: BTreeMap::from([
+                (
+                    "temperature".into(),
+                    Threshold::high(high_warning, 60.0).with_hysteresis(2.0),
+                ),
+                ("current".into(), Threshold::high(4.5, 5.0)),
+            ]),
+        }
+    }
+
+    #[test]
+    fn crossed_limit_clears_against_the_new_profile() {
+        let mut monitor = ThresholdMonitor::new(profile(40.0));
+        let change = monitor.update("temperature", 45.0, Duration::ZERO).unwrap();
+        assert_eq!(change.to, Some(Limit::HighWarning));
+
+        assert!(monitor.set_profile(profile(50.0)).is_empty());
+        assert_eq!(monitor.active("temperature"), Some(Limit::HighWarning));
+
+        let change = monitor.update("temperature", 45.0, Duration::ZERO).unwrap();
+        assert_eq!((change.from, change.to), (Some(Limit::HighWarning), None));
+    }
+
+    #[test]
+    fn limits_without_a_threshold_are_returned() {
+        let mut monitor = ThresholdMonitor::new(profile(40.0));
+        monitor.update("current", 4.8, Duration::ZERO);
+        monitor.update("temperature", 20.0, Duration::ZERO);
+
+        let mut without_current = profile(40.0);
+        without_current.thresholds.remove("current");
+        assert_eq!(
+            monitor.set_profile(without_current),
+            vec![("current".to_string(), Limit::HighWarning)]
+        );
+        assert_eq!(monitor.active_limits().count(), 0);
+    }
+
+    #[test]
+    fn infinite_debounce_holds_the_level() {
+        let mut monitor = ThresholdMonitor::new(ThresholdProfile {
+            name: "test".into(),
+            thresholds: BTreeMap::from([(
+                "current".into(),
+                Threshold {
+                    debounce_s: f64::INFINITY,
+                    ..Threshold::high(4.5, 5.0)
+                },
+            )]),
+        });
+        assert_eq!(monitor.update("current", 6.0, Duration::ZERO), None);
+        assert_eq!(
+            monitor.update("current", 6.0, Duration::from_secs(3600)),
+            None
+        );
+    }
+}
@@ -0,0 +1,208 @@
+use std::{collections::HashMap, fmt, time::Duration};
+
+/// Timing of the module watchdog
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct WatchdogConfig {
+    /// How often each module is polled
+    pub heartbeat_period: Duration,
+    /// Consecutive missed heartbeats after which a module is considered unresponsive
+    pub missed_heartbeats: u32,
+    /// Reset and re-initialize attempts before the module is put in its safe state
+    pub recovery_attempts: u32,
+}
+
+impl Default for WatchdogConfig {
+    fn default() -> Self {
+        Self {
+            heartbeat_period: Duration::from_secs(1),
+            missed_heartbeats: 3,
+            recovery_attempts: 3,
+        }
+    }
+}
+
+/// How a supervised module is doing
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ModuleHealth {
+    Healthy,
+    /// Missed some heartbeats, not yet considered unresponsive
+    Late {
+        missed: u32,
+    },
+    /// Stopped responding, `attempt` recoveries were made so far
+    Recovering {
+        attempt: u32,
+    },
+    /// Could not be recovered and was put in its safe state. Stays there until recovery is
+    /// requested again, e.g. once the module has been replaced.
+    Faulted,
+}
+
+impl ModuleHealth {
+    pub fn is_responding(&self) -> bool {
+        matches!(self, ModuleHealth::Healthy | ModuleHealth::Late { .. })
+    }
+}
+
+impl fmt::Display for ModuleHealth {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ModuleHealth::Healthy => write!(f, "Healthy"),
+            ModuleHealth::Late { missed } => write!(f, "Late ({missed} missed)"),
+            ModuleHealth::Recovering { attempt } => write!(f, "Recovering (attempt {attempt})"),
+            ModuleHealth::Faulted => write!(f, "Faulted"),
+        }
+    }
+}
+
+/// What a heartbeat changed, as reported by `Watchdog::record`
+#[derive(Debug, Clone, PartialEq)]
+pub enum WatchdogEvent {
+    /// The module missed too many heartbeats in a row
+    Unresponsive { error: String },
+    /// The module answers again
+    Recovered,
+    /// Every recovery attempt failed
+    GaveUp { error: String },
+}
+
+#[derive(Debug, Clone)]
+struct Supervision {
+    health: ModuleHealth,
+    last_seen: Duration,
+    next_poll: Duration,
+    last_error: Option<String>,
+}
+
+/// Tracks the heartbeats of every module and decides when a module has failed.
+///
+/// The watchdog itself only keeps state; `ModuleManager::supervise` polls the modules, resets
+/// the ones being recovered and reports what `record` returns.
+#[derive(Debug, Clone, Default)]
+pub struct Watchdog {
+    config: WatchdogConfig,
+    modules: HashMap<u16, Supervision>,
+}
+
+impl Watchdog {
+    pub fn new(config: WatchdogConfig) -> Self {
+        Self {
+            config,
+            modules: HashMap::new(),
+        }
+    }
+
+    pub fn config(&self) -> &WatchdogConfig {
+        &self.config
+    }
+
+    /// Start supervising a module that just answered, e.g. when it was identified
+    pub fn watch(&mut self, id: u16, now: Duration) {
+        self.modules.insert(
+            id,
+            Supervision {
+                health: ModuleHealth::Healthy,
+                last_seen: now,
+                next_poll: now + self.config.heartbeat_period,
+                last_error: None,
+            },
+        );
+    }
+
+    pub fn forget(&mut self, id: u16) {
+        self.modules.remove(&id);
+    }
+
+    pub fn health(&self, id: u16) -> Option<ModuleHealth> {
+        self.modules.get(&id).map(|module| module.health)
+    }
+
+    pub fn is_faulted(&self, id: u16) -> bool {
+        self.health(id) == Some(ModuleHealth::Faulted)
+    }
+
+    /// When the module last answered a heartbeat
+    pub fn last_seen(&self, id: u16) -> Option<Duration> {
+        self.modules.get(&id).map(|module| module.last_seen)
+    }
+
+    /// Why the last heartbeat of the module failed, if it did
+    pub fn last_error(&self, id: u16) -> Option<&str> {
+        self.modules.get(&id)?.last_error.as_deref()
+    }
+
+    /// Modules whose heartbeat is due. Faulted modules are no longer polled.
+    pub fn due(&self, now: Duration) -> Vec<u16> {
+        let mut due: Vec<u16> = self
+            .modules
+            .iter()
+            .filter(|(_, module)| module.health != ModuleHealth::Faulted && module.next_poll <= now)
+            .map(|(id, _)| *id)
+            .collect();
+        due.sort_unstable();
+        due
+    }
+
+    /// Count a recovery attempt of an unresponsive module
+    pub fn begin_recovery(&mut self, id: u16) {
+        if let Some(module) = self.modules.get_mut(&id) {
+            if let ModuleHealth::Recovering { attempt } = &mut module.health {
+                *attempt += 1;
+            }
+        }
+    }
+
+    /// Give a faulted module another round of recovery attempts
+    pub fn retry(&mut self, id: u16) {
+        if let Some(module) = self.modules.get_mut(&id) {
+            if module.health == ModuleHealth::Faulted {
+                module.health = ModuleHealth::Recovering { attempt: 0 };
+            }
+        }
+    }
+
+    /// Record the outcome of a heartbeat, returning the change worth reporting if any
+    pub fn record(
+        &mut self,
+        id: u16,
+        result: Result<(), String>,
+        now: Duration,
+    ) -> Option<WatchdogEvent> {
+        let config = self.config;
+        let module = self.modules.get_mut(&id)?;
+        module.next_poll = now + config.heartbeat_period;
+
+        let error = match result {
+            Ok(()) => {
+                module.last_seen = now;
+                module.last_error = None;
+                let was = std::mem::replace(&mut module.health, ModuleHealth::Healthy);
+                return (!was.is_responding()).then_some(WatchdogEvent::Recovered);
+            }
+            Err(error) => error,
+        };
+        module.last_error = Some(error.clone());
+
+        match module.health {
+            ModuleHealth::Healthy | ModuleHealth::Late { .. } => {
+                let missed = match module.health {
+                    ModuleHealth::Late { missed } => missed + 1,
+                    _ => 1,
+                };
+
+                if missed >= config.missed_heartbeats {
+                    module.health = ModuleHealth::Recovering { attempt: 0 };
+                    Some(WatchdogEvent::Unresponsive { error })
+                } else {
+                    module.health = ModuleHealth::Late { missed };
+                    None
+                }
+            }
+            ModuleHealth::Recovering { attempt } if attempt >= config.recovery_attempts => {
+                module.health = ModuleHealth::Faulted;
+                Some(WatchdogEvent::GaveUp { error })
+            }
+            ModuleHealth::Recovering { .. } | ModuleHealth::Faulted => None,
+        }
+    }
+}
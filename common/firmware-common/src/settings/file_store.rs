@@ -0,0 +1,64 @@
+use std::{
+    fs,
+    io::ErrorKind,
+    path::{Path, PathBuf},
+};
+
+use super::{KeyValueStore, SettingsError};
+
+/// Desktop settings storage: one `<key>.toml` file per key inside a directory,
+/// so that settings can also be inspected and edited by hand
+pub struct FileStore {
+    dir: PathBuf,
+}
+
+impl FileStore {
+    pub fn new(dir: impl AsRef<Path>) -> Result<Self, SettingsError> {
+        fs::create_dir_all(dir.as_ref())?;
+        Ok(Self {
+            dir: dir.as_ref().to_path_buf(),
+        })
+    }
+
+    fn path(&self, key: &str) -> PathBuf {
+        self.dir.join(format!("{key}.toml"))
+    }
+}
+
+impl KeyValueStore for FileStore {
+    fn get(&self, key: &str) -> Result<Option<Vec<u8>>, SettingsError> {
+        match fs::read(self.path(key)) {
+            Ok(bytes) => Ok(Some(bytes)),
+            Err(err) if err.kind() == ErrorKind::NotFound => Ok(None),
+            Err(err) => Err(err.into()),
+        }
+    }
+
+    fn set(&self, key: &str, value: &[u8]) -> Result<(), SettingsError> {
+        // Write then rename so that a crash never leaves a half-written file behind
+        let temp = self.dir.join(format!("{key}.toml.tmp"));
+        fs::write(&temp, value)?;
+        fs::rename(temp, self.path(key))?;
+        Ok(())
+    }
+
+    fn remove(&self, key: &str) -> Result<(), SettingsError> {
+        match fs::remove_file(self.path(key)) {
+            Err(err) if err.kind() != ErrorKind::NotFound => Err(err.into()),
+            _ => Ok(()),
+        }
+    }
+
+    fn keys(&self) -> Result<Vec<String>, SettingsError> {
+        Ok(fs::read_dir(&self.dir)?
+            .filter_map(Result::ok)
+            .filter_map(|entry| {
+                entry
+                    .file_name()
+                    .to_str()
+                    .and_then(|name| name.strip_suffix(".toml"))
+                    .map(String::from)
+            })
+            .collect())
+    }
+}
@@ -0,0 +1,32 @@
+use std::{collections::HashMap, sync::Mutex};
+
+use super::{KeyValueStore, SettingsError};
+
+/// Volatile settings storage, used in tests and simulations
+#[derive(Default)]
+pub struct MemoryStore {
+    entries: Mutex<HashMap<String, Vec<u8>>>,
+}
+
+impl KeyValueStore for MemoryStore {
+    fn get(&self, key: &str) -> Result<Option<Vec<u8>>, SettingsError> {
+        Ok(self.entries.lock().unwrap().get(key).cloned())
+    }
+
+    fn set(&self, key: &str, value: &[u8]) -> Result<(), SettingsError> {
+        self.entries
+            .lock()
+            .unwrap()
+            .insert(key.into(), value.to_vec());
+        Ok(())
+    }
+
+    fn remove(&self, key: &str) -> Result<(), SettingsError> {
+        self.entries.lock().unwrap().remove(key);
+        Ok(())
+    }
+
+    fn keys(&self) -> Result<Vec<String>, SettingsError> {
+        Ok(self.entries.lock().unwrap().keys().cloned().collect())
+    }
+}
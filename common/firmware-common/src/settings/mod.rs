@@ -0,0 +1,236 @@
+use std::sync::Arc;
+
+use serde::{de::DeserializeOwned, Serialize};
+use thiserror::Error;
+use toml::{Table, Value};
+
+mod file_store;
+mod memory_store;
+
+pub use file_store::FileStore;
+pub use memory_store::MemoryStore;
+
+#[derive(Debug, Error)]
+pub enum SettingsError {
+    #[error("Storage backend failure: {0}")]
+    Backend(String),
+
+    #[error("Stored settings are not valid UTF-8 TOML")]
+    InvalidEncoding,
+
+    #[error("Unable to serialize settings: {0}")]
+    Serialize(#[from] toml::ser::Error),
+
+    #[error("Unable to deserialize settings: {0}")]
+    Deserialize(#[from] toml::de::Error),
+
+    #[error(
+        "Settings were written by a newer schema (version {found}, supported up to {supported})"
+    )]
+    UnsupportedVersion { found: u32, supported: u32 },
+
+    #[error("Stored schema version {0} is not a valid version")]
+    InvalidVersion(i64),
+
+    #[error("No migration from schema version {0}")]
+    MissingMigration(u32),
+
+    #[error("Unknown setting `{0}`")]
+    UnknownField(String),
+}
+
+impl From<std::io::Error> for SettingsError {
+    fn from(err: std::io::Error) -> Self {
+        SettingsError::Backend(err.to_string())
+    }
+}
+
+/// Raw persistent storage for settings.
+///
+/// Implemented by `FileStore` on desktop and `MemoryStore` for tests and simulation. An
+/// NVS-backed store only has to implement this trait; keys are kept at most 15 characters
+/// long to fit within NVS key limits.
+pub trait KeyValueStore: Send + Sync {
+    fn get(&self, key: &str) -> Result<Option<Vec<u8>>, SettingsError>;
+
+    fn set(&self, key: &str, value: &[u8]) -> Result<(), SettingsError>;
+
+    fn remove(&self, key: &str) -> Result<(), SettingsError>;
+
+    fn keys(&self) -> Result<Vec<String>, SettingsError>;
+}
+
+/// Typed, versioned configuration of one kind of module.
+///
+/// Every stored configuration is tagged with `VERSION`. When the layout of the config changes,
+/// bump `VERSION` and teach `migrate` how to upgrade a document from the previous version;
+/// older documents are upgraded one version at a time on load.
+pub trait ModuleConfig: Serialize + DeserializeOwned + Default {
+    /// Short, stable name used to build storage keys (e.g. `"bat"`)
+    const NAME: &'static str;
+
+    /// Current schema version
+    const VERSION: u32;
+
+    /// Upgrade `config`, written with schema `from_version`, to `from_version + 1`
+    fn migrate(from_version: u32, config: Table) -> Result<Table, SettingsError> {
+        let _ = config;
+        Err(SettingsError::MissingMigration(from_version))
+    }
+}
+
+/// Typed access to module configurations on top of a `KeyValueStore`.
+///
+/// Configurations are stored as TOML documents of the form
+/// ```toml
+/// version = 1
+///
+/// [config]
+/// name = "Left pack"
+/// ```
+#[derive(Clone)]
+pub struct SettingsStore {
+    backend: Arc<dyn KeyValueStore>,
+}
+
+impl SettingsStore {
+    pub fn new(backend: Arc<dyn KeyValueStore>) -> Self {
+        Self { backend }
+    }
+
+    /// Settings that only live as long as the process
+    pub fn in_memory() -> Self {
+        Self::new(Arc::new(MemoryStore::default()))
+    }
+
+    fn key<T: ModuleConfig>(module_id: u16) -> String {
+        format!("{}.{}", T::NAME, module_id)
+    }
+
+    /// Load the configuration of a module, falling back to `T::default()` if none is stored
+    pub fn load<T: ModuleConfig>(&self, module_id: u16) -> Result<T, SettingsError> {
+        let Some(bytes) = self.backend.get(&Self::key::<T>(module_id))? else {
+            return Ok(T::default());
+        };
+
+        let text = String::from_utf8(bytes).map_err(|_| SettingsError::InvalidEncoding)?;
+        let mut document: Table = toml::from_str(&text)?;
+
+        let mut version = match document.remove("version") {
+            Some(Value::Integer(version)) => {
+                u32::try_from(version).map_err(|_| SettingsError::InvalidVersion(version))?
+            }
+            _ => 0,
+        };
+        let mut config = match document.remove("config") {
+            Some(Value::Table(config)) => config,
+            _ => Table::new(),
+        };
+
+        if version > T::VERSION {
+            return Err(SettingsError::UnsupportedVersion {
+                found: version,
+                supported: T::VERSION,
+            });
+        }
+
+        while version < T::VERSION {
+            config = T::migrate(version, config)?;
+            version += 1;
+        }
+
+        Ok(Value::Table(config).try_into()?)
+    }
+
+    pub fn save<T: ModuleConfig>(&self, module_id: u16, config: &T) -> Result<(), SettingsError> {
+        let mut document = Table::new();
+        document.insert("version".into(), Value::Integer(T::VERSION as i64));
+        document.insert("config".into(), Value::try_from(config)?);
+
+        self.backend.set(
+            &Self::key::<T>(module_id),
+            toml::to_string(&document)?.as_bytes(),
+        )
+    }
+
+    /// Forget a module's configuration so that it goes back to defaults
+    pub fn reset<T: ModuleConfig>(&self, module_id: u16) -> Result<(), SettingsError> {
+        self.backend.remove(&Self::key::<T>(module_id))
+    }
+}
+
+/// Return a copy of `config` with the field `key` set to `value`.
+///
+/// `value` is parsed as a TOML value (`4.5`, `true`, `"Left pack"`); anything that does not
+/// parse is taken as a plain string. This allows a single field to be tuned by name, e.g.
+/// from a command line or a generic settings UI.
+pub fn with_field<T: ModuleConfig>(config: &T, key: &str, value: &str) -> Result<T, SettingsError> {
+    let Value::Table(mut table) = Value::try_from(config)? else {
+        return Err(SettingsError::UnknownField(key.into()));
+    };
+
+    if !table.contains_key(key) {
+        return Err(SettingsError::UnknownField(key.into()));
+    }
+
+    let value = toml::from_str::<Table>(&format!("value = {value}"))
+        .ok()
+        .and_then(|mut parsed| parsed.remove("value"))
+        .unwrap_or_else(|| Value::String(value.into()));
+    table.insert(key.into(), value);
+
+    Ok(Value::Table(table).try_into()?)
+}
+
+#[cfg(test)]
+mod tests {
+    use serde::Deserialize;
+
+    use super::*;
+
+    #[derive(Debug, Default, Serialize, Deserialize)]
+    struct TestConfig {
+        name: String,
+    }
+
+    impl ModuleConfig for TestConfig {
+        const NAME: &'static str = "test";
+        const VERSION: u32 = 1;
+    }
+
+    fn store_with_version(version: i64) -> SettingsStore {
+        let settings = SettingsStore::in_memory();
+        let document = format!("version = {version}\n\n[config]\nname = \"Left pack\"\n");
+        settings
+            .backend
+            .set(&SettingsStore::key::<TestConfig>(1), document.as_bytes())
+            .unwrap();
+        settings
+    }
+
+    #[test]
+    fn rejects_versions_that_do_not_fit() {
+        for version in [-1, i64::from(u32::MAX) + 1] {
+            let loaded = store_with_version(version).load::<TestConfig>(1);
+            assert!(
+                matches!(loaded, Err(SettingsError::InvalidVersion(found)) if found == version)
+            );
+        }
+    }
+
+    #[test]
+    fn rejects_newer_versions() {
+        let loaded = store_with_version(2).load::<TestConfig>(1);
+        assert!(matches!(
+            loaded,
+            Err(SettingsError::UnsupportedVersion {
+                found: 2,
+                supported: 1
+            })
+        ));
+        assert_eq!(
+            store_with_version(1).load::<TestConfig>(1).unwrap().name,
+            "Left pack"
+        );
+    }
+}
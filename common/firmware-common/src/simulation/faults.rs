@@ -0,0 +1,261 @@
+use std::collections::HashMap;
+
+use rand::Rng;
+use serde::Deserialize;
+use uom::si::{
+    electric_current::ampere,
+    f64::{ElectricCurrent, ThermodynamicTemperature},
+    thermodynamic_temperature::degree_celsius,
+};
+
+use super::scenario::{de_amperes, de_celsius, de_finite};
+use crate::comms::i2c_protocol::{I2CError, I2CMessage};
+
+/// A measurement channel of a simulated module
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Sensor {
+    Voltage,
+    Current,
+    Temperature,
+}
+
+impl Sensor {
+    /// Unit that readings and noise amplitudes of this sensor are expressed in
+    pub fn unit(&self) -> &'static str {
+        match self {
+            Sensor::Voltage => "V",
+            Sensor::Current => "A",
+            Sensor::Temperature => "°C",
+        }
+    }
+}
+
+/// A fault that can be injected into a simulated module.
+///
+/// Faults can be scripted in scenario files:
+/// ```toml
+/// [[actions]]
+/// at = "5m"
+/// inject_fault = { kind = "noisy_sensor", sensor = "current", amplitude = 0.8 }
+/// ```
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum Fault {
+    /// The module draws `current` regardless of what is plugged in
+    Overcurrent {
+        #[serde(rename = "current_a", deserialize_with = "de_amperes")]
+        current: ElectricCurrent,
+    },
+
+    /// The module is held at `temperature` regardless of load and ambient
+    Overtemperature {
+        #[serde(rename = "temperature_c", deserialize_with = "de_celsius")]
+        temperature: ThermodynamicTemperature,
+    },
+
+    /// The sensor keeps reporting the value it had when the fault was injected
+    StuckSensor { sensor: Sensor },
+
+    /// Uniform noise of up to ±`amplitude` (in the sensor's unit) is added to every reading
+    NoisySensor {
+        sensor: Sensor,
+        #[serde(deserialize_with = "de_finite")]
+        amplitude: f64,
+    },
+
+    /// Each bus reply is lost with the given probability (0.0-1.0)
+    DroppedReplies {
+        #[serde(deserialize_with = "de_finite")]
+        probability: f64,
+    },
+
+    /// Each bus reply has a bit flipped with the given probability (0.0-1.0)
+    CorruptedReplies {
+        #[serde(deserialize_with = "de_finite")]
+        probability: f64,
+    },
+
+    /// The module is pulled out of its slot and stops responding altogether
+    Removed,
+
+    /// The module's firmware hangs: it stops updating and answering on the bus until it is
+    /// reset
+    Hung,
+}
+
+/// Identifies a fault independently of its parameters, used to clear it again
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum FaultKind {
+    Overcurrent,
+    Overtemperature,
+    StuckSensor { sensor: Sensor },
+    NoisySensor { sensor: Sensor },
+    DroppedReplies,
+    CorruptedReplies,
+    Removed,
+    Hung,
+}
+
+impl Fault {
+    pub fn kind(&self) -> FaultKind {
+        match self {
+            Fault::Overcurrent { .. } => FaultKind::Overcurrent,
+            Fault::Overtemperature { .. } => FaultKind::Overtemperature,
+            Fault::StuckSensor { sensor } => FaultKind::StuckSensor { sensor: *sensor },
+            Fault::NoisySensor { sensor, .. } => FaultKind::NoisySensor { sensor: *sensor },
+            Fault::DroppedReplies { .. } => FaultKind::DroppedReplies,
+            Fault::CorruptedReplies { .. } => FaultKind::CorruptedReplies,
+            Fault::Removed => FaultKind::Removed,
+            Fault::Hung => FaultKind::Hung,
+        }
+    }
+
+    /// Short human-readable description, for logs and UIs
+    pub fn describe(&self) -> String {
+        match self {
+            Fault::Overcurrent { current } => {
+                format!("Forced overcurrent ({:.1} A)", current.get::<ampere>())
+            }
+            Fault::Overtemperature { temperature } => format!(
+                "Forced overtemperature ({:.1} °C)",
+                temperature.get::<degree_celsius>()
+            ),
+            Fault::StuckSensor { sensor } => format!("Stuck {sensor:?} sensor"),
+            Fault::NoisySensor { sensor, amplitude } => {
+                format!("Noisy {sensor:?} sensor (±{amplitude} {})", sensor.unit())
+            }
+            Fault::DroppedReplies { probability } => {
+                format!("Dropped replies ({:.0}%)", probability * 100.0)
+            }
+            Fault::CorruptedReplies { probability } => {
+                format!("Corrupted replies ({:.0}%)", probability * 100.0)
+            }
+            Fault::Removed => "Removed".into(),
+            Fault::Hung => "Hung".into(),
+        }
+    }
+}
+
+/// Active faults of a simulated module, and the logic to apply them.
+///
+/// Modules embed a `FaultInjector` and route their physical model, sensor readings and
+/// bus replies through it.
+#[derive(Debug, Default)]
+pub struct FaultInjector {
+    active: Vec<Fault>,
+    stuck_readings: HashMap<Sensor, f64>,
+}
+
+impl FaultInjector {
+    /// Activate `fault`, replacing any active fault of the same kind
+    pub fn inject(&mut self, fault: Fault) {
+        self.clear(fault.kind());
+        self.active.push(fault);
+    }
+
+    pub fn clear(&mut self, kind: FaultKind) {
+        self.active.retain(|fault| fault.kind() != kind);
+
+        if let FaultKind::StuckSensor { sensor } = kind {
+            self.stuck_readings.remove(&sensor);
+        }
+    }
+
+    pub fn clear_all(&mut self) {
+        self.active.clear();
+        self.stuck_readings.clear();
+    }
+
+    pub fn active(&self) -> &[Fault] {
+        &self.active
+    }
+
+    pub fn is_active(&self, kind: FaultKind) -> bool {
+        self.active.iter().any(|fault| fault.kind() == kind)
+    }
+
+    pub fn is_removed(&self) -> bool {
+        self.is_active(FaultKind::Removed)
+    }
+
+    pub fn is_hung(&self) -> bool {
+        self.is_active(FaultKind::Hung)
+    }
+
+    /// The module was reset, which gets its firmware out of a hang
+    pub fn reset(&mut self) {
+        self.clear(FaultKind::Hung);
+    }
+
+    pub fn forced_current(&self) -> Option<ElectricCurrent> {
+        self.active.iter().find_map(|fault| match fault {
+            Fault::Overcurrent { current } => Some(*current),
+            _ => None,
+        })
+    }
+
+    pub fn forced_temperature(&self) -> Option<ThermodynamicTemperature> {
+        self.active.iter().find_map(|fault| match fault {
+            Fault::Overtemperature { temperature } => Some(*temperature),
+            _ => None,
+        })
+    }
+
+    /// Turn the true value of a sensor (in `Sensor::unit`) into what the module reports
+    pub fn read_sensor(&mut self, sensor: Sensor, actual: f64) -> f64 {
+        if self.is_active(FaultKind::StuckSensor { sensor }) {
+            return *self.stuck_readings.entry(sensor).or_insert(actual);
+        }
+
+        let noise = self.active.iter().find_map(|fault| match fault {
+            Fault::NoisySensor {
+                sensor: noisy,
+                amplitude,
+            } if *noisy == sensor && amplitude.is_finite() && *amplitude > 0.0 => {
+                Some(rand::rng().random_range(-amplitude..=*amplitude))
+            }
+            _ => None,
+        });
+
+        actual + noise.unwrap_or(0.0)
+    }
+
+    /// Pass a reply frame through the simulated bus, which may lose or corrupt it.
+    ///
+    /// The (possibly damaged) frame is decoded with the real protocol code, so callers
+    /// see the same `I2CError` the host would on hardware.
+    pub fn transmit_reply(&self, reply: &I2CMessage) -> Result<(), I2CError> {
+        let mut rng = rand::rng();
+        let mut bytes = reply.to_bytes();
+
+        for fault in &self.active {
+            match fault {
+                Fault::Removed | Fault::Hung => bytes.clear(),
+                Fault::DroppedReplies { probability } if rng.random_bool(chance(*probability)) => {
+                    bytes.clear()
+                }
+                Fault::CorruptedReplies { probability }
+                    if !bytes.is_empty() && rng.random_bool(chance(*probability)) =>
+                {
+                    // Leave the start byte alone so that the damage is caught by the CRC
+                    let index = rng.random_range(1..bytes.len());
+                    bytes[index] ^= 1 << rng.random_range(0..8);
+                }
+                _ => {}
+            }
+        }
+
+        I2CMessage::from_bytes(&bytes).map(|_| ())
+    }
+}
+
+/// Clamp a fault probability into what `random_bool` accepts, treating NaN as never
+fn chance(probability: f64) -> f64 {
+    if probability.is_nan() {
+        0.0
+    } else {
+        probability.clamp(0.0, 1.0)
+    }
+}
@@ -0,0 +1,108 @@
+use std::collections::BTreeMap;
+
+use faults::FaultInjector;
+
+use uom::si::{
+    electric_current::ampere,
+    f64::{ElectricCurrent, ElectricPotential, ThermodynamicTemperature},
+    thermodynamic_temperature::degree_celsius,
+};
+
+use crate::modules::charger::ChargeSetpoint;
+
+pub mod faults;
+pub mod scenario;
+pub mod scenario_runner;
+
+/// The outside world as seen by simulated modules: what is plugged in and how hot it is.
+#[derive(Debug, Clone)]
+pub struct SimEnvironment {
+    pub ambient_temperature: ThermodynamicTemperature,
+    /// Current drawn by whatever is plugged into each output port, keyed by port number
+    pub port_loads: BTreeMap<u8, ElectricCurrent>,
+    /// Current limit enforced by the module driving each port. Filled in from the simulated
+    /// port modules before the environment is handed out.
+    pub port_limits: BTreeMap<u8, ElectricCurrent>,
+    /// Current the power source plugged into the charge input can supply, `None` if
+    /// nothing is plugged in
+    pub input_current: Option<ElectricCurrent>,
+    /// What the charger is regulating to. Filled in from the simulated charger.
+    pub charge_supply: Option<ChargeSetpoint>,
+    /// State of the battery pack. Filled in from the simulated battery.
+    pub pack: Option<PackState>,
+}
+
+/// Terminal state of the battery pack, as seen by the charger connected to it
+#[derive(Debug, Clone, Copy)]
+pub struct PackState {
+    pub voltage: ElectricPotential,
+    /// Current into the pack, positive while charging
+    pub current: ElectricCurrent,
+    pub temperature: ThermodynamicTemperature,
+}
+
+impl Default for SimEnvironment {
+    fn default() -> Self {
+        Self {
+            ambient_temperature: ThermodynamicTemperature::new::<degree_celsius>(25.0),
+            port_loads: BTreeMap::new(),
+            port_limits: BTreeMap::new(),
+            input_current: None,
+            charge_supply: None,
+            pack: None,
+        }
+    }
+}
+
+impl SimEnvironment {
+    /// Current actually flowing out of `port`: the load's demand, capped by the port's limit
+    pub fn port_draw(&self, port: u8) -> ElectricCurrent {
+        let demand = self
+            .port_loads
+            .get(&port)
+            .copied()
+            .unwrap_or(ElectricCurrent::new::<ampere>(0.0));
+
+        match self.port_limits.get(&port) {
+            Some(limit) if *limit < demand => *limit,
+            _ => demand,
+        }
+    }
+
+    /// Combined current drawn across all ports
+    pub fn total_load(&self) -> ElectricCurrent {
+        self.port_loads
+            .keys()
+            .fold(ElectricCurrent::new::<ampere>(0.0), |total, port| {
+                total + self.port_draw(*port)
+            })
+    }
+}
+
+/// Implemented by modules that have a simulated counterpart to the real hardware.
+///
+/// Simulated modules are fed the environment before every update so that scenarios can
+/// drive them without knowing their concrete type. Faults injected through `faults_mut`
+/// let safety handling be exercised without touching the module's code.
+pub trait Simulated {
+    fn apply_environment(&mut self, environment: &SimEnvironment);
+
+    /// For modules that drive an output port: the port number and the current it lets through
+    fn port_limit(&self) -> Option<(u8, ElectricCurrent)> {
+        None
+    }
+
+    /// For chargers: the limits the power stage is currently regulating to
+    fn charge_supply(&self) -> Option<ChargeSetpoint> {
+        None
+    }
+
+    /// For batteries: the state of the pack
+    fn pack_state(&self) -> Option<PackState> {
+        None
+    }
+
+    fn faults(&self) -> &FaultInjector;
+
+    fn faults_mut(&mut self) -> &mut FaultInjector;
+}
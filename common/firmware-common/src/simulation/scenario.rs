@@ -0,0 +1,232 @@
+use std::{fs, path::Path, time::Duration};
+
+use serde::{Deserialize, Deserializer};
+use thiserror::Error;
+
+use super::faults::{Fault, FaultKind};
+use uom::si::{
+    electric_current::ampere,
+    f64::{ElectricCurrent, ThermodynamicTemperature},
+    thermodynamic_temperature::degree_celsius,
+};
+
+#[derive(Debug, Error)]
+pub enum ScenarioError {
+    #[error("Unable to read scenario file: {0}")]
+    Io(#[from] std::io::Error),
+
+    #[error("Invalid scenario: {0}")]
+    Parse(#[from] toml::de::Error),
+}
+
+/// A scripted test session, replayed against simulated modules by a `ScenarioRunner`.
+///
+/// Scenarios are written in TOML. Times and durations use a compact `1h30m`, `20m`, `45s`,
+/// `500ms` notation.
+///
+/// # Example
+/// ```toml
+/// name = "Heat soak under load"
+/// duration = "30m"
+///
+/// [[actions]]
+/// at = "0s"
+/// set_load = { port = 2, current_a = 3.0 }
+///
+/// [[actions]]
+/// at = "0s"
+/// ambient = { temperature_c = 45.0, over = "20m" }
+///
+/// [[actions]]
+/// at = "20m"
+/// unplug = { port = 2 }
+/// ```
+///
+/// See `Fault` for the faults that can be injected with `inject_fault` and `clear_fault`.
+#[derive(Debug, Clone, Deserialize)]
+pub struct Scenario {
+    pub name: String,
+
+    /// Total length of the scenario. Defaults to the time of the last action.
+    #[serde(default, deserialize_with = "de_opt_duration")]
+    pub duration: Option<Duration>,
+
+    #[serde(default)]
+    pub actions: Vec<ScheduledAction>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct ScheduledAction {
+    /// Offset from the start of the scenario
+    #[serde(deserialize_with = "de_duration")]
+    pub at: Duration,
+
+    #[serde(flatten)]
+    pub action: ScenarioAction,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ScenarioAction {
+    /// Plug a load into `port` (or change the load already on it)
+    SetLoad {
+        port: u8,
+        #[serde(rename = "current_a", deserialize_with = "de_amperes")]
+        current: ElectricCurrent,
+    },
+
+    /// Remove whatever is plugged into `port`
+    Unplug { port: u8 },
+
+    /// Plug a power source able to supply `current_a` into the charge input
+    PlugInput {
+        #[serde(rename = "current_a", deserialize_with = "de_amperes")]
+        current: ElectricCurrent,
+    },
+
+    /// Unplug the power source from the charge input
+    UnplugInput {},
+
+    /// Change the ambient temperature, either immediately or linearly `over` a period
+    Ambient {
+        #[serde(rename = "temperature_c", deserialize_with = "de_celsius")]
+        temperature: ThermodynamicTemperature,
+        #[serde(default, deserialize_with = "de_opt_duration")]
+        over: Option<Duration>,
+    },
+
+    /// Inject a fault into one module, or into every simulated module if `module` is omitted
+    InjectFault {
+        #[serde(default)]
+        module: Option<u16>,
+        #[serde(flatten)]
+        fault: Fault,
+    },
+
+    /// Clear a previously injected fault
+    ClearFault {
+        #[serde(default)]
+        module: Option<u16>,
+        #[serde(flatten)]
+        kind: FaultKind,
+    },
+}
+
+impl Scenario {
+    pub fn from_toml(source: &str) -> Result<Self, ScenarioError> {
+        let mut scenario: Scenario = toml::from_str(source)?;
+        scenario.actions.sort_by_key(|action| action.at);
+        Ok(scenario)
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, ScenarioError> {
+        Self::from_toml(&fs::read_to_string(path)?)
+    }
+
+    /// Length of the scenario, including the tail of any ramp started by the last action
+    pub fn length(&self) -> Duration {
+        let last_action = self
+            .actions
+            .iter()
+            .map(|scheduled| match scheduled.action {
+                ScenarioAction::Ambient {
+                    over: Some(over), ..
+                } => scheduled.at.saturating_add(over),
+                _ => scheduled.at,
+            })
+            .max()
+            .unwrap_or_default();
+
+        self.duration.unwrap_or(last_action)
+    }
+}
+
+/// Parse durations such as `1h30m`, `20m`, `45s`, `1.5h` or `500ms`
+pub fn parse_duration(text: &str) -> Option<Duration> {
+    let mut total = 0.0;
+    let mut rest = text.trim();
+
+    if rest.is_empty() {
+        return None;
+    }
+
+    while !rest.is_empty() {
+        let number_len = rest
+            .find(|c: char| !(c.is_ascii_digit() || c == '.'))
+            .unwrap_or(rest.len());
+        let value: f64 = rest[..number_len].parse().ok()?;
+        rest = &rest[number_len..];
+
+        let unit_len = rest
+            .find(|c: char| c.is_ascii_digit() || c == '.')
+            .unwrap_or(rest.len());
+        let seconds_per_unit = match rest[..unit_len].trim() {
+            "h" => 3600.0,
+            "m" => 60.0,
+            "s" => 1.0,
+            "ms" => 0.001,
+            _ => return None,
+        };
+        rest = &rest[unit_len..];
+
+        total += value * seconds_per_unit;
+    }
+
+    // Out of range for a `Duration` once the units add up, which would panic
+    Duration::try_from_secs_f64(total).ok()
+}
+
+fn de_duration<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Duration, D::Error> {
+    let text = String::deserialize(deserializer)?;
+    parse_duration(&text)
+        .ok_or_else(|| serde::de::Error::custom(format!("invalid duration `{text}`")))
+}
+
+fn de_opt_duration<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Option<Duration>, D::Error> {
+    de_duration(deserializer).map(Some)
+}
+
+/// A plain number that must be finite, as NaN or infinity would make the simulation panic
+pub(crate) fn de_finite<'de, D: Deserializer<'de>>(deserializer: D) -> Result<f64, D::Error> {
+    let value = f64::deserialize(deserializer)?;
+    if value.is_finite() {
+        Ok(value)
+    } else {
+        Err(serde::de::Error::custom(format!(
+            "invalid value `{value}`, expected a finite number"
+        )))
+    }
+}
+
+pub(crate) fn de_amperes<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<ElectricCurrent, D::Error> {
+    de_finite(deserializer).map(ElectricCurrent::new::<ampere>)
+}
+
+pub(crate) fn de_celsius<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<ThermodynamicTemperature, D::Error> {
+    de_finite(deserializer).map(ThermodynamicTemperature::new::<degree_celsius>)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_compound_durations() {
+        assert_eq!(parse_duration("1h30m"), Some(Duration::from_secs(5400)));
+        assert_eq!(parse_duration("1.5h"), Some(Duration::from_secs(5400)));
+        assert_eq!(parse_duration(" 500ms "), Some(Duration::from_millis(500)));
+        assert_eq!(parse_duration(""), None);
+        assert_eq!(parse_duration("5d"), None);
+    }
+
+    #[test]
+    fn rejects_durations_out_of_range() {
+        assert_eq!(parse_duration("99999999999999999999999h"), None);
+    }
+}
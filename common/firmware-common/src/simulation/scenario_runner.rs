@@ -0,0 +1,183 @@
+use std::{sync::Arc, time::Duration};
+
+use crossbeam::channel::Receiver;
+use uom::si::{f64::ThermodynamicTemperature, thermodynamic_temperature::kelvin};
+
+use super::{
+    scenario::{Scenario, ScenarioAction},
+    SimEnvironment,
+};
+use crate::{
+    clock::ManualClock,
+    modules::{
+        module_manager::ModuleManager,
+        system_controller::{SystemController, TimestampedEvent},
+    },
+};
+
+/// Linear ambient temperature change in progress
+#[derive(Debug, Clone)]
+struct AmbientRamp {
+    from: ThermodynamicTemperature,
+    to: ThermodynamicTemperature,
+    start: Duration,
+    end: Duration,
+}
+
+impl AmbientRamp {
+    fn temperature_at(&self, elapsed: Duration) -> ThermodynamicTemperature {
+        let span = (self.end - self.start).as_secs_f64();
+        let progress = if span > 0.0 {
+            ((elapsed.saturating_sub(self.start)).as_secs_f64() / span).clamp(0.0, 1.0)
+        } else {
+            1.0
+        };
+
+        let from = self.from.get::<kelvin>();
+        let to = self.to.get::<kelvin>();
+
+        ThermodynamicTemperature::new::<kelvin>(from + (to - from) * progress)
+    }
+}
+
+/// Replays a `Scenario` against the simulated modules of a `ModuleManager`.
+///
+/// The runner follows the system controller's clock, so the same scenario can be played
+/// live in LVScope (on a `ScaledClock`) or run to completion instantly with a `ManualClock`.
+/// Every event emitted while the scenario is running is recorded.
+pub struct ScenarioRunner {
+    scenario: Scenario,
+    system_controller: Arc<SystemController>,
+    start: Duration,
+    next_action: usize,
+    environment: SimEnvironment,
+    ambient_ramp: Option<AmbientRamp>,
+    events: Receiver<TimestampedEvent>,
+    recorded_events: Vec<TimestampedEvent>,
+}
+
+impl ScenarioRunner {
+    /// Start `scenario` at the system controller's current time
+    pub fn new(scenario: Scenario, system_controller: Arc<SystemController>) -> Self {
+        Self {
+            start: system_controller.now(),
+            events: system_controller.subscribe(),
+            scenario,
+            system_controller,
+            next_action: 0,
+            environment: SimEnvironment::default(),
+            ambient_ramp: None,
+            recorded_events: Vec::new(),
+        }
+    }
+
+    pub fn scenario(&self) -> &Scenario {
+        &self.scenario
+    }
+
+    pub fn environment(&self) -> &SimEnvironment {
+        &self.environment
+    }
+
+    pub fn recorded_events(&self) -> &[TimestampedEvent] {
+        &self.recorded_events
+    }
+
+    /// Time since the scenario started
+    pub fn elapsed(&self) -> Duration {
+        self.system_controller.now().saturating_sub(self.start)
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.elapsed() >= self.scenario.length()
+    }
+
+    /// Apply every action that is due, push the resulting environment to all simulated modules
+    /// and record any events emitted since the last step
+    pub fn step(&mut self, modules: &mut ModuleManager) {
+        let elapsed = self.elapsed().min(self.scenario.length());
+
+        while let Some(scheduled) = self.scenario.actions.get(self.next_action) {
+            if scheduled.at > elapsed {
+                break;
+            }
+
+            let (at, action) = (scheduled.at, scheduled.action.clone());
+            self.apply_action(at, action, modules);
+            self.next_action += 1;
+        }
+
+        if let Some(ramp) = &self.ambient_ramp {
+            self.environment.ambient_temperature = ramp.temperature_at(elapsed);
+            if elapsed >= ramp.end {
+                self.ambient_ramp = None;
+            }
+        }
+
+        modules.apply_environment(&self.environment);
+        self.recorded_events.extend(self.events.try_iter());
+    }
+
+    /// Run the whole scenario as fast as possible, updating modules every `step` of simulated time
+    ///
+    /// Panics if `step` is zero, as simulated time would never advance.
+    pub fn run_to_completion(
+        &mut self,
+        clock: &ManualClock,
+        modules: &mut ModuleManager,
+        step: Duration,
+    ) {
+        assert!(!step.is_zero(), "scenario step must be longer than zero");
+        self.step(modules);
+
+        while !self.is_finished() {
+            clock.advance(step);
+            self.step(modules);
+            modules.update_modules();
+            modules.supervise(&self.system_controller);
+        }
+
+        self.recorded_events.extend(self.events.try_iter());
+    }
+
+    fn apply_action(&mut self, at: Duration, action: ScenarioAction, modules: &mut ModuleManager) {
+        match action {
+            ScenarioAction::SetLoad { port, current } => {
+                self.environment.port_loads.insert(port, current);
+            }
+            ScenarioAction::Unplug { port } => {
+                self.environment.port_loads.remove(&port);
+            }
+            ScenarioAction::PlugInput { current } => {
+                self.environment.input_current = Some(current);
+            }
+            ScenarioAction::UnplugInput {} => {
+                self.environment.input_current = None;
+            }
+            ScenarioAction::Ambient { temperature, over } => match over {
+                Some(over) => {
+                    self.ambient_ramp = Some(AmbientRamp {
+                        from: self.environment.ambient_temperature,
+                        to: temperature,
+                        start: at,
+                        end: at + over,
+                    });
+                }
+                None => {
+                    self.ambient_ramp = None;
+                    self.environment.ambient_temperature = temperature;
+                }
+            },
+            ScenarioAction::InjectFault { module, fault } => {
+                for (_, simulated) in modules.simulated_modules_mut(module) {
+                    simulated.faults_mut().inject(fault.clone());
+                }
+            }
+            ScenarioAction::ClearFault { module, kind } => {
+                for (_, simulated) in modules.simulated_modules_mut(module) {
+                    simulated.faults_mut().clear(kind);
+                }
+            }
+        }
+    }
+}
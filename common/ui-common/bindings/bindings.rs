@@ -165,3 +165,13 @@ unsafe extern "C" {
 unsafe extern "C" {
     pub fn clear_flush_area_cb();
 }
+unsafe extern "C" {
+    pub fn ui_set_battery_runtime(time_to_empty_s: u32, time_to_full_s: u32, confidence_pct: u8);
+}
+unsafe extern "C" {
+    pub fn ui_set_alarm_banner(
+        message: *const ::std::os::raw::c_char,
+        severity: u8,
+        active_count: u8,
+    );
+}
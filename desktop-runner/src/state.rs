@@ -3,18 +3,44 @@ use crate::ui::{debug_panel::performance_page::LvglFpsLimit, lvgl_canvas::view::
 use crate::hot_reload_manager::SharedHotReloadManager;
 use crate::icon_manager::IconManager;
 use crate::lvgl_obj_tree::SharedTreeManager;
+use crate::ui::debug_panel::commands_page::CommandsPageState;
 use crate::ui::debug_panel::pages::DebugSidebarPages;
+use log::error;
 use std::path::PathBuf;
 use std::sync::Arc;
-use stratum_firmware_common::modules::{
-    module_manager::ModuleManager, system_controller::SystemController,
+use stratum_firmware_common::{
+    clock::{RealClock, ScaledClock},
+    history::{HistoryConfig, TelemetryHistory},
+    modules::{
+        automations::AutomationEngine,
+        module_manager::ModuleManager,
+        power_budget::PowerBudget,
+        safety_policy::SafetyPolicy,
+        system_controller::{SystemController, SystemControllerOptions},
+    },
+    settings::{FileStore, SettingsStore},
+    simulation::scenario_runner::ScenarioRunner,
 };
 use stratum_ui_common::ui_logging::UiLogger;
 
 /// Holds global UI state, including the LVGL renderer, modules, and logs.
 pub struct UiState {
     pub module_manager: ModuleManager,
+    /// Shares the battery current between the output port modules.
+    pub power_budget: PowerBudget,
+    /// Protective actions taken on alarms, e.g. shedding ports on battery over-current.
+    pub safety_policy: SafetyPolicy,
+    /// User automations, e.g. turning a port off after some time. Never override the policy.
+    pub automations: AutomationEngine,
+    /// Recorded telemetry of every module, for trend charts.
+    pub history: TelemetryHistory,
     pub system_controller: Arc<SystemController>,
+    /// Simulation clock shared by the system controller and all simulated modules.
+    pub sim_clock: Arc<ScaledClock>,
+    /// Scenario currently being played against the simulated modules, if any.
+    pub scenario_runner: Option<ScenarioRunner>,
+    pub selected_scenario: Option<PathBuf>,
+    pub scenario_error: Option<String>,
     /// Logger for UI messages (forwarded from C).
     pub ui_logger: Arc<UiLogger>,
     pub hot_reload_manager: SharedHotReloadManager,
@@ -27,6 +53,7 @@ pub struct UiState {
     pub element_select_active: bool,
     pub repaint_flash_active: bool,
     pub lvgl_fps_limit: LvglFpsLimit,
+    pub commands_page: CommandsPageState,
 }
 
 impl UiState {
@@ -37,9 +64,35 @@ impl UiState {
         tree_manager: SharedTreeManager,
         icon_manager: IconManager,
     ) -> Self {
+        let sim_clock = Arc::new(ScaledClock::new(Arc::new(RealClock::new()), 1.0));
+
+        let settings = match FileStore::new("./.settings") {
+            Ok(store) => SettingsStore::new(Arc::new(store)),
+            Err(err) => {
+                error!("Unable to open settings directory, settings won't persist: {err}");
+                SettingsStore::in_memory()
+            }
+        };
+
+        let automations = AutomationEngine::load(settings.clone()).unwrap_or_else(|err| {
+            error!("Unable to load automations, starting without any: {err}");
+            AutomationEngine::new(settings.clone())
+        });
+
         UiState {
             module_manager: ModuleManager::new(),
-            system_controller: SystemController::new(),
+            power_budget: PowerBudget::default(),
+            safety_policy: SafetyPolicy::default(),
+            automations,
+            history: TelemetryHistory::new(HistoryConfig::desktop()),
+            system_controller: SystemController::with_options(SystemControllerOptions {
+                clock: sim_clock.clone(),
+                settings,
+            }),
+            sim_clock,
+            scenario_runner: None,
+            selected_scenario: None,
+            scenario_error: None,
             ui_logger,
             hot_reload_manager,
             tree_manager,
@@ -51,6 +104,7 @@ impl UiState {
             element_select_active: false,
             repaint_flash_active: false,
             lvgl_fps_limit: LvglFpsLimit::Preset(30),
+            commands_page: CommandsPageState::default(),
         }
     }
 }
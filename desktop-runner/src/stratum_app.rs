@@ -9,12 +9,19 @@ use std::{
     path::PathBuf,
     sync::{atomic::Ordering, Arc, Mutex},
 };
+use stratum_firmware_common::modules::{
+    alarms::AlarmState, runtime::RuntimeEstimate, telemetry::Severity,
+};
 use stratum_ui_common::ui_logging::UiLogger;
 
 pub struct StratumApp {
     ui_state: UiState,
     lvgl_ui: StratumLvglUI,
     last_fps: LvglFpsLimit,
+    /// Runtime estimate last pushed to the dashboard, to only push changes
+    last_runtime: Option<(u32, u32, u8)>,
+    /// Alarm banner last pushed to the dashboard, to only push changes
+    last_alarm_banner: Option<(String, u8, u8)>,
 }
 
 impl<'ctx> StratumApp {
@@ -48,6 +55,8 @@ impl<'ctx> StratumApp {
             ui_state,
             lvgl_ui,
             last_fps: initial_fps,
+            last_runtime: None,
+            last_alarm_banner: None,
         }
     }
 
@@ -99,6 +108,59 @@ impl<'ctx> StratumApp {
             self.ui_state.ui_logger.clone().bind_ffi_callback();
             TreeManager::bind_ffi_callback(self.ui_state.tree_manager.clone());
             self.lvgl_ui.reload_ui();
+            // The reloaded UI starts without an estimate
+            self.last_runtime = None;
+            self.last_alarm_banner = None;
+        }
+    }
+
+    /// Push the pack runtime estimate to the dashboard when it changes
+    fn update_dashboard_runtime(&mut self) {
+        let reports = self.ui_state.module_manager.all_telemetry();
+        let estimate =
+            RuntimeEstimate::combine(reports.iter().filter_map(RuntimeEstimate::from_telemetry))
+                .unwrap_or_default();
+
+        // Whole minutes and 10% steps are all the dashboard shows
+        let minutes = |time: Option<std::time::Duration>| {
+            time.map_or(0, |time| (time.as_secs() as u32).div_ceil(60) * 60)
+        };
+        let runtime = (
+            minutes(estimate.time_to_empty),
+            minutes(estimate.time_to_full),
+            ((estimate.confidence * 10.0).round() * 10.0) as u8,
+        );
+
+        if self.last_runtime != Some(runtime) {
+            self.lvgl_ui
+                .set_battery_runtime(runtime.0, runtime.1, runtime.2);
+            self.last_runtime = Some(runtime);
+        }
+    }
+
+    /// Push the most severe active alarm to the dashboard when it changes
+    fn update_dashboard_alarms(&mut self) {
+        let alarms = self.ui_state.system_controller.alarms();
+        let mut active = alarms
+            .iter()
+            .filter(|alarm| alarm.state != AlarmState::Cleared);
+
+        let banner = match active.next() {
+            Some(alarm) => (
+                alarm.message.clone(),
+                match alarm.severity {
+                    Severity::Critical => 2,
+                    Severity::Warning => 1,
+                    Severity::Normal => 0,
+                },
+                (active.count() + 1).min(u8::MAX as usize) as u8,
+            ),
+            None => (String::new(), 0, 0),
+        };
+
+        if self.last_alarm_banner.as_ref() != Some(&banner) {
+            self.lvgl_ui.set_alarm_banner(&banner.0, banner.1, banner.2);
+            self.last_alarm_banner = Some(banner);
         }
     }
 }
@@ -119,6 +181,34 @@ impl eframe::App for StratumApp {
                 .set_enabled(self.ui_state.repaint_flash_active);
         }
 
+        if let Some(runner) = &mut self.ui_state.scenario_runner {
+            runner.step(&mut self.ui_state.module_manager);
+        }
+
+        if self
+            .ui_state
+            .module_manager
+            .tick(&self.ui_state.system_controller)
+        {
+            for report in self.ui_state.module_manager.all_telemetry() {
+                self.ui_state.history.record(&report);
+            }
+        }
+        self.ui_state.safety_policy.tick(
+            &mut self.ui_state.module_manager,
+            &self.ui_state.system_controller,
+        );
+        self.ui_state.automations.tick(
+            &mut self.ui_state.module_manager,
+            &self.ui_state.system_controller,
+        );
+        self.ui_state.power_budget.tick(
+            &mut self.ui_state.module_manager,
+            &self.ui_state.system_controller,
+        );
+        self.update_dashboard_runtime();
+        self.update_dashboard_alarms();
+
         crate::ui::draw_ui(ctx, &mut self.ui_state, &mut self.lvgl_ui);
     }
 }
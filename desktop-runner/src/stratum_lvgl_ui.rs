@@ -4,6 +4,7 @@ use crate::{
 };
 use egui::{ColorImage, Context, TextureFilter, TextureHandle, TextureOptions};
 use std::{
+    ffi::CString,
     pin::Pin,
     sync::mpsc::{channel, Receiver},
     sync::{Arc, LazyLock, Mutex},
@@ -166,6 +167,31 @@ impl StratumLvglUI {
         *self.render_interval.lock().unwrap() = new_interval;
     }
 
+    /// Push the pack runtime estimate to the dashboard. Times are in seconds, 0 when unknown.
+    pub fn set_battery_runtime(
+        &self,
+        time_to_empty_s: u32,
+        time_to_full_s: u32,
+        confidence_pct: u8,
+    ) {
+        // Holding the backend keeps the LVGL thread out while the label is updated
+        let _backend = self.backend.lock().unwrap();
+        unsafe {
+            stratum_ui_ffi::ui_set_battery_runtime(time_to_empty_s, time_to_full_s, confidence_pct);
+        }
+    }
+
+    /// Show the most severe active alarm on the dashboard. Severity is 1 for a warning and 2
+    /// for a critical alarm; an `active_count` of 0 hides the banner.
+    pub fn set_alarm_banner(&self, message: &str, severity: u8, active_count: u8) {
+        // Interior NULs would truncate the message on the C side anyway
+        let message = CString::new(message.replace('\0', "")).unwrap_or_default();
+        let _backend = self.backend.lock().unwrap();
+        unsafe {
+            stratum_ui_ffi::ui_set_alarm_banner(message.as_ptr(), severity, active_count);
+        }
+    }
+
     pub fn current_fps(&self) -> f64 {
         self.fps_tracker.lock().unwrap().fps()
     }
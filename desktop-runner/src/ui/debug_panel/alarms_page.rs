@@ -0,0 +1,89 @@
+use egui::{Color32, RichText};
+use stratum_firmware_common::modules::alarms::{Alarm, AlarmState};
+
+use super::modules_page::severity_color;
+use crate::state::UiState;
+
+fn alarm_row(ui: &mut egui::Ui, alarm: &Alarm) {
+    let color = match alarm.state {
+        AlarmState::Cleared => Some(Color32::GRAY),
+        _ => severity_color(alarm.severity),
+    };
+    let text = RichText::new(format!("● {}", alarm.key));
+    ui.label(match color {
+        Some(color) => text.color(color),
+        None => text,
+    });
+    ui.label(&alarm.message);
+    ui.label(format!(
+        "{:.1}s / {:.1}s",
+        alarm.first_seen.as_secs_f64(),
+        alarm.last_seen.as_secs_f64()
+    ));
+    ui.label(alarm.occurrences.to_string());
+    ui.label(alarm.state.to_string());
+}
+
+pub fn draw(ui: &mut egui::Ui, ui_state: &mut UiState) {
+    let controller = &ui_state.system_controller;
+    let alarms = controller.alarms();
+
+    ui.horizontal(|ui| {
+        ui.heading(format!("🚨 Alarms ({})", alarms.len()));
+        let unacknowledged = alarms
+            .iter()
+            .any(|alarm| alarm.state != AlarmState::Acknowledged);
+        if ui
+            .add_enabled(unacknowledged, egui::Button::new("✔ Acknowledge all"))
+            .clicked()
+        {
+            controller.acknowledge_all_alarms();
+        }
+    });
+
+    if alarms.is_empty() {
+        ui.label("No alarms.");
+    } else {
+        egui::Grid::new("alarm_grid")
+            .num_columns(6)
+            .striped(true)
+            .show(ui, |ui| {
+                for header in [
+                    "Alarm",
+                    "Message",
+                    "First / last seen",
+                    "Count",
+                    "State",
+                    "",
+                ] {
+                    ui.strong(header);
+                }
+                ui.end_row();
+
+                for alarm in &alarms {
+                    alarm_row(ui, alarm);
+                    if alarm.state != AlarmState::Acknowledged && ui.button("Acknowledge").clicked()
+                    {
+                        controller.acknowledge_alarm(&alarm.key);
+                    }
+                    ui.end_row();
+                }
+            });
+    }
+
+    ui.separator();
+    let history = controller.alarm_history();
+    egui::CollapsingHeader::new(format!("History ({})", history.len()))
+        .id_salt("alarm_history")
+        .show(ui, |ui| {
+            egui::Grid::new("alarm_history_grid")
+                .num_columns(5)
+                .striped(true)
+                .show(ui, |ui| {
+                    for alarm in &history {
+                        alarm_row(ui, alarm);
+                        ui.end_row();
+                    }
+                });
+        });
+}
@@ -0,0 +1,227 @@
+use egui::{Color32, ComboBox, RichText};
+use std::collections::HashMap;
+use stratum_firmware_common::modules::{
+    descriptor::{CommandDescriptor, Value, ValueKind},
+    interlock::{ConfirmationToken, SafetyClass},
+};
+
+use crate::state::UiState;
+
+/// Module picked on the commands page, with what was entered and returned for its commands
+#[derive(Debug, Default)]
+pub struct CommandsPageState {
+    pub selected_module: Option<u16>,
+    /// Argument values by command name
+    args: HashMap<&'static str, Vec<Value>>,
+    /// Response of the last invocation by command name
+    results: HashMap<&'static str, Result<Value, String>>,
+    /// Critical commands waiting for the user to confirm them
+    confirming: HashMap<&'static str, ConfirmationToken>,
+}
+
+/// Input for a value of `kind`, within `range` if there is one
+fn value_editor(ui: &mut egui::Ui, kind: &ValueKind, range: Option<(f64, f64)>, value: &mut Value) {
+    // A value left from another command or module is started over
+    let fits = matches!(
+        (kind, &*value),
+        (ValueKind::Bool, Value::Bool(_))
+            | (ValueKind::Integer { .. }, Value::Integer(_))
+            | (ValueKind::Float | ValueKind::Quantity(_), Value::Number(_))
+            | (ValueKind::Text | ValueKind::Config(_), Value::Text(_))
+            | (ValueKind::Optional(_), Value::Optional(_))
+    );
+    if !fits {
+        *value = kind.default_value(range);
+    }
+
+    match (kind, value) {
+        (ValueKind::Bool, Value::Bool(state)) => {
+            ui.checkbox(state, "");
+        }
+        (ValueKind::Integer { min, max }, Value::Integer(number)) => {
+            let (min, max) = range.map_or((*min, *max), |(low, high)| (low as i64, high as i64));
+            ui.add(egui::DragValue::new(number).range(min..=max));
+        }
+        (ValueKind::Float | ValueKind::Quantity(_), Value::Number(number)) => {
+            let mut input = egui::DragValue::new(number).speed(0.01).max_decimals(3);
+            if let Some((min, max)) = range {
+                input = input.range(min..=max);
+            }
+            if let Some(unit) = kind.unit() {
+                input = input.suffix(format!(" {unit}"));
+            }
+            ui.add(input);
+        }
+        (ValueKind::Text, Value::Text(text)) => {
+            ui.text_edit_singleline(text);
+        }
+        (ValueKind::Config(name), Value::Text(toml)) => {
+            ui.add(
+                egui::TextEdit::multiline(toml)
+                    .code_editor()
+                    .desired_rows(4)
+                    .hint_text(format!("{name} as TOML")),
+            );
+        }
+        (ValueKind::Optional(item), Value::Optional(inner)) => {
+            ui.horizontal(|ui| {
+                let mut set = inner.is_some();
+                ui.checkbox(&mut set, "");
+                match (set, inner.as_mut()) {
+                    (true, Some(value)) => value_editor(ui, item, range, value),
+                    (true, None) => *inner = Some(Box::new(item.default_value(range))),
+                    (false, _) => {
+                        *inner = None;
+                        ui.weak("none");
+                    }
+                }
+            });
+        }
+        _ => {
+            ui.weak("cannot be entered");
+        }
+    }
+}
+
+fn draw_command(
+    ui: &mut egui::Ui,
+    ui_state: &mut UiState,
+    module_id: u16,
+    command: &CommandDescriptor,
+) {
+    let title = if command.local {
+        format!("{} (host)", command.name)
+    } else {
+        format!("{} ({:#04x})", command.name, command.id)
+    };
+
+    egui::CollapsingHeader::new(title)
+        .id_salt((module_id, command.name))
+        .show(ui, |ui| {
+            if !command.doc.is_empty() {
+                ui.label(command.doc.trim());
+            }
+            if command.safety == SafetyClass::Critical {
+                ui.colored_label(Color32::ORANGE, "⚠ Critical: asks for confirmation");
+            }
+
+            let modules = &mut ui_state.module_manager;
+            let page = &mut ui_state.commands_page;
+            let args = page
+                .args
+                .entry(command.name)
+                .or_insert_with(|| command.args.iter().map(|arg| arg.default_value()).collect());
+
+            if !command.args.is_empty() {
+                egui::Grid::new(("command_args", module_id, command.name))
+                    .num_columns(2)
+                    .show(ui, |ui| {
+                        for (arg, value) in command.args.iter().zip(args.iter_mut()) {
+                            ui.label(arg.name).on_hover_text(if arg.doc.is_empty() {
+                                arg.rust_type.to_string()
+                            } else {
+                                format!("{}\n{}", arg.doc.trim(), arg.rust_type)
+                            });
+                            value_editor(ui, &arg.kind, arg.range, value);
+                            ui.end_row();
+                        }
+                    });
+            }
+
+            let mut send = None;
+            ui.horizontal(|ui| {
+                if page.confirming.contains_key(command.name) {
+                    ui.colored_label(Color32::ORANGE, format!("Send {}?", command.name));
+                    if ui.button("✔ Confirm").clicked() {
+                        send = page.confirming.remove(command.name).map(Some);
+                    }
+                    if ui.button("✖ Cancel").clicked() {
+                        page.confirming.remove(command.name);
+                    }
+                    return;
+                }
+
+                let invoke = ui.add_enabled(command.is_invocable(), egui::Button::new("▶ Invoke"));
+                if invoke.clicked() {
+                    match command.safety {
+                        SafetyClass::Normal => send = Some(None),
+                        SafetyClass::Critical => {
+                            let token = modules.request_confirmation(module_id, command.name);
+                            page.confirming.insert(command.name, token);
+                        }
+                    }
+                }
+                ui.weak(format!("→ {}", command.response_type));
+            });
+
+            if let Some(confirmation) = send {
+                let result = modules
+                    .send_values(module_id, command.name, args.clone(), confirmation)
+                    .map_err(|err| err.to_string());
+                page.results.insert(command.name, result);
+            }
+
+            match page.results.get(command.name) {
+                Some(Ok(Value::Error(err))) => {
+                    ui.colored_label(Color32::RED, format!("Error: {err}"));
+                }
+                Some(Ok(value)) => {
+                    ui.label(RichText::new(command.response.format(value)).monospace());
+                }
+                Some(Err(err)) => {
+                    ui.colored_label(Color32::RED, err);
+                }
+                None => {}
+            }
+        });
+}
+
+/// Invoke any command of any module, with a form built from its descriptor
+pub(super) fn draw(ui: &mut egui::Ui, ui_state: &mut UiState) {
+    ui.heading("⌨ Commands");
+
+    let mut modules = ui_state.module_manager.list_modules();
+    modules.sort_by_key(|module| module.id);
+
+    let selected = ui_state
+        .commands_page
+        .selected_module
+        .and_then(|id| modules.iter().find(|module| module.id == id));
+    let mut selected_module = selected.map(|module| module.id);
+    ComboBox::from_label("Module")
+        .selected_text(selected.map_or("Select a module".to_string(), |module| {
+            format!("{} ({})", module.name, module.id)
+        }))
+        .show_ui(ui, |ui| {
+            for module in &modules {
+                ui.selectable_value(
+                    &mut selected_module,
+                    Some(module.id),
+                    format!("{} ({})", module.name, module.id),
+                );
+            }
+        });
+
+    let page = &mut ui_state.commands_page;
+    if selected_module != page.selected_module {
+        page.selected_module = selected_module;
+        page.args.clear();
+        page.results.clear();
+        page.confirming.clear();
+    }
+
+    let Some(module_id) = selected_module else {
+        ui.label("No module selected.");
+        return;
+    };
+    let Some(commands) = ui_state.module_manager.command_descriptors(module_id) else {
+        return;
+    };
+
+    ui.separator();
+    egui::ScrollArea::vertical().show(ui, |ui| {
+        for command in &commands {
+            draw_command(ui, ui_state, module_id, command);
+        }
+    });
+}
@@ -1,8 +1,12 @@
+mod alarms_page;
+pub mod commands_page;
 mod elements_page;
 mod index;
 mod logs_page;
+mod modules_page;
 pub mod pages;
 pub mod performance_page;
+mod simulation_page;
 mod ui_build_page;
 
 pub use index::draw;
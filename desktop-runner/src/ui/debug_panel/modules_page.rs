@@ -0,0 +1,620 @@
+use egui::{Color32, RichText, Stroke};
+use log::error;
+use std::time::Duration;
+use stratum_firmware_common::history::TelemetryHistory;
+use stratum_firmware_common::modules::{
+    balancing::BalancingConfig,
+    commands::BatteryModuleCommands,
+    dummies::dummy_battery::DummyBatteryModule,
+    identity::Topology,
+    module::ModuleKind,
+    module_manager::ModuleManager,
+    power_budget::BudgetSnapshot,
+    runtime::RuntimeEstimate,
+    telemetry::{Severity, TelemetryReport, TelemetrySchema},
+    thresholds::{Limit, ThresholdProfile},
+    watchdog::ModuleHealth,
+};
+use uom::si::{
+    electric_charge::milliampere_hour, electric_current::ampere, electric_potential::volt,
+    thermodynamic_temperature::degree_celsius,
+};
+
+use crate::state::UiState;
+
+pub(super) fn severity_color(severity: Severity) -> Option<Color32> {
+    match severity {
+        Severity::Normal => None,
+        Severity::Warning => Some(Color32::ORANGE),
+        Severity::Critical => Some(Color32::RED),
+    }
+}
+
+fn colored(text: String, severity: Severity) -> RichText {
+    let text = RichText::new(text);
+    match severity_color(severity) {
+        Some(color) => text.color(color),
+        None => text,
+    }
+}
+
+/// Render a module's telemetry using nothing but its schema, so that any module kind works
+fn draw_telemetry(ui: &mut egui::Ui, schema: &TelemetrySchema, report: &TelemetryReport) {
+    egui::Grid::new(("telemetry_grid", report.module_id))
+        .num_columns(2)
+        .striped(true)
+        .show(ui, |ui| {
+            for field in &schema.fields {
+                ui.label(field.label.as_ref());
+                match report.get(&field.key) {
+                    Some(entry) => ui.label(colored(entry.value.to_string(), entry.severity)),
+                    None => ui.weak("—"),
+                };
+                ui.end_row();
+            }
+        });
+}
+
+/// Draw the backplane as a grid of slots with whatever module sits in each
+fn draw_pack_layout(ui: &mut egui::Ui, topology: &Topology, modules: &ModuleManager) {
+    ui.group(|ui| {
+        ui.label(RichText::new("🔋 Pack Layout").strong());
+
+        egui::Grid::new("pack_layout_grid")
+            .num_columns(topology.columns.into())
+            .spacing([6.0, 6.0])
+            .show(ui, |ui| {
+                for row in 0..topology.rows {
+                    for column in 0..topology.columns {
+                        let Some(state) = topology.slots.iter().find(|state| {
+                            state.definition.row == row && state.definition.column == column
+                        }) else {
+                            ui.label("");
+                            continue;
+                        };
+
+                        egui::Frame::group(ui.style()).show(ui, |ui| {
+                            ui.set_min_width(90.0);
+                            ui.vertical(|ui| {
+                                ui.weak(&state.definition.label);
+                                match &state.occupant {
+                                    Some(occupant) => {
+                                        let severity = modules
+                                            .telemetry(occupant.module_id)
+                                            .map(|report| report.severity())
+                                            .unwrap_or_default();
+                                        ui.label(colored(
+                                            occupant.module_kind.to_string(),
+                                            severity,
+                                        ))
+                                        .on_hover_text(
+                                            format!(
+                                                "{}\nID {}\nUID {}",
+                                                occupant.name, occupant.module_id, occupant.uid
+                                            ),
+                                        );
+                                    }
+                                    None => {
+                                        ui.weak("Empty");
+                                    }
+                                }
+                            });
+                        });
+                    }
+                    ui.end_row();
+                }
+            });
+
+        for (slot, occupant) in &topology.unplaced {
+            ui.colored_label(
+                Color32::ORANGE,
+                format!(
+                    "Module {} reports slot {}, which this pack does not have",
+                    occupant.module_id, slot
+                ),
+            );
+        }
+    });
+}
+
+/// Summary of how the battery current is shared between the output ports
+fn draw_power_budget(ui: &mut egui::Ui, budget: &BudgetSnapshot) {
+    ui.group(|ui| {
+        ui.label(RichText::new("⚡ Power Budget").strong());
+        ui.label(format!(
+            "Available: {:.2} A · Demand: {:.2} A · Allocated: {:.2} A · Remaining: {:.0} mAh",
+            budget.available_current.get::<ampere>(),
+            budget.total_demand.get::<ampere>(),
+            budget.total_allocated.get::<ampere>(),
+            budget.remaining_capacity.get::<milliampere_hour>(),
+        ));
+
+        if budget.thermal_derating {
+            ui.colored_label(
+                Color32::ORANGE,
+                "🌡 Battery discharge current derated for temperature",
+            );
+        }
+
+        if budget.ports.is_empty() {
+            ui.weak("No output ports connected.");
+            return;
+        }
+
+        egui::Grid::new("power_budget_grid")
+            .num_columns(4)
+            .striped(true)
+            .show(ui, |ui| {
+                ui.strong("Port");
+                ui.strong("Priority");
+                ui.strong("Demand");
+                ui.strong("Allocated");
+                ui.end_row();
+
+                for port in &budget.ports {
+                    let severity = if port.is_derated() {
+                        Severity::Warning
+                    } else {
+                        Severity::Normal
+                    };
+
+                    ui.label(format!("{} (ID {})", port.port, port.module_id));
+                    ui.label(port.priority.to_string());
+                    ui.label(format!("{:.2} A", port.demand.get::<ampere>()));
+                    ui.label(colored(
+                        format!(
+                            "{:.2} / {:.2} A",
+                            port.allocated.get::<ampere>(),
+                            port.max_current.get::<ampere>()
+                        ),
+                        severity,
+                    ));
+                    ui.end_row();
+                }
+            });
+    });
+}
+
+fn format_duration(time: std::time::Duration) -> String {
+    let minutes = time.as_secs().div_ceil(60);
+    format!("{}h {:02}m", minutes / 60, minutes % 60)
+}
+
+/// Time to empty or full of the whole pack, from the estimates the batteries report
+fn draw_runtime(ui: &mut egui::Ui, modules: &ModuleManager) {
+    let reports = modules.all_telemetry();
+    let Some(estimate) =
+        RuntimeEstimate::combine(reports.iter().filter_map(RuntimeEstimate::from_telemetry))
+    else {
+        return;
+    };
+
+    let text = match (estimate.time_to_empty, estimate.time_to_full) {
+        (Some(time), _) => format!("⏳ {} to empty", format_duration(time)),
+        (None, Some(time)) => format!("⏳ {} to full", format_duration(time)),
+        (None, None) => "⏳ Idle".to_string(),
+    };
+    let severity = if estimate.confidence < 0.5 {
+        Severity::Warning
+    } else {
+        Severity::Normal
+    };
+
+    ui.horizontal(|ui| {
+        ui.label(RichText::new(text).strong());
+        ui.label(colored(
+            format!(
+                "at {:.2} A · confidence {:.0}%",
+                estimate.average_current.get::<ampere>(),
+                estimate.confidence * 100.0
+            ),
+            severity,
+        ));
+    });
+}
+
+/// Time windows offered by the trend charts
+const HISTORY_WINDOWS: [(&str, Duration); 4] = [
+    ("1 min", Duration::from_secs(60)),
+    ("10 min", Duration::from_secs(10 * 60)),
+    ("1 h", Duration::from_secs(3600)),
+    ("1 day", Duration::from_secs(24 * 3600)),
+];
+
+/// Trend chart of one telemetry value: min/max band with the average on top
+fn draw_history(
+    ui: &mut egui::Ui,
+    history: &TelemetryHistory,
+    schema: &TelemetrySchema,
+    module_id: u16,
+    now: Duration,
+) {
+    egui::CollapsingHeader::new("📈 History")
+        .id_salt(("history", module_id))
+        .show(ui, |ui| {
+            let key_id = ui.id().with("key");
+            let window_id = ui.id().with("window");
+            let mut key: String = ui
+                .data_mut(|data| data.get_temp(key_id))
+                .unwrap_or_default();
+            let mut window: usize = ui
+                .data_mut(|data| data.get_temp(window_id))
+                .unwrap_or_default();
+
+            let recorded: Vec<_> = schema
+                .fields
+                .iter()
+                .filter(|field| history.series(module_id, &field.key).is_some())
+                .collect();
+            let Some(first) = recorded.first() else {
+                ui.weak("Nothing recorded yet.");
+                return;
+            };
+            let field = recorded
+                .iter()
+                .find(|field| field.key == key)
+                .unwrap_or(first);
+            key = field.key.to_string();
+
+            ui.horizontal(|ui| {
+                egui::ComboBox::from_id_salt(("history_key", module_id))
+                    .selected_text(field.label.as_ref())
+                    .show_ui(ui, |ui| {
+                        for field in &recorded {
+                            ui.selectable_value(
+                                &mut key,
+                                field.key.to_string(),
+                                field.label.as_ref(),
+                            );
+                        }
+                    });
+                for (index, (label, _)) in HISTORY_WINDOWS.iter().enumerate() {
+                    ui.selectable_value(&mut window, index, *label);
+                }
+            });
+            ui.data_mut(|data| {
+                data.insert_temp(key_id, key.clone());
+                data.insert_temp(window_id, window);
+            });
+
+            let span = HISTORY_WINDOWS[window.min(HISTORY_WINDOWS.len() - 1)].1;
+            let start = now.saturating_sub(span);
+            let buckets = history.query(module_id, &key, start..now + Duration::from_secs(1));
+            if buckets.is_empty() {
+                ui.weak("No data in this window.");
+                return;
+            }
+
+            let low = buckets
+                .iter()
+                .map(|bucket| bucket.min())
+                .fold(f64::INFINITY, f64::min);
+            let high = buckets
+                .iter()
+                .map(|bucket| bucket.max())
+                .fold(f64::NEG_INFINITY, f64::max);
+            let unit = field.kind.display_unit();
+            ui.weak(format!(
+                "{:.2} – {:.2} {unit} over {} buckets",
+                low,
+                high,
+                buckets.len()
+            ));
+
+            let (response, painter) =
+                ui.allocate_painter(egui::vec2(ui.available_width(), 80.0), egui::Sense::hover());
+            let rect = response.rect;
+            painter.rect_filled(rect, 2.0, ui.visuals().extreme_bg_color);
+
+            // Flat series still get a visible line in the middle
+            let (low, high) = if high - low < 1e-9 {
+                (low - 1.0, high + 1.0)
+            } else {
+                (low, high)
+            };
+            let x = |time: Duration| {
+                let t = (time.saturating_sub(start)).as_secs_f64() / span.as_secs_f64();
+                rect.left() + rect.width() * t.clamp(0.0, 1.0) as f32
+            };
+            let y = |value: f64| {
+                let t = (value - low) / (high - low);
+                rect.bottom() - rect.height() * t.clamp(0.0, 1.0) as f32
+            };
+
+            let band = ui.visuals().selection.bg_fill.gamma_multiply(0.5);
+            for bucket in &buckets {
+                let px = x(bucket.start());
+                painter.line_segment(
+                    [
+                        egui::pos2(px, y(bucket.min())),
+                        egui::pos2(px, y(bucket.max())),
+                    ],
+                    Stroke::new(2.0, band),
+                );
+            }
+
+            let line: Vec<egui::Pos2> = buckets
+                .iter()
+                .map(|bucket| egui::pos2(x(bucket.start()), y(bucket.avg())))
+                .collect();
+            painter.add(egui::Shape::line(
+                line,
+                Stroke::new(1.5, ui.visuals().selection.stroke.color),
+            ));
+        });
+}
+
+/// Per-cell voltages, the balancing decisions and the balancing thresholds of a battery
+fn draw_balancing(ui: &mut egui::Ui, modules: &mut ModuleManager, id: u16) {
+    let Some(battery) = modules.get_module::<DummyBatteryModule>(id) else {
+        return;
+    };
+    let cells = battery.cell_readings().to_vec();
+    let decision = battery.balancer().decision().cloned();
+    let history: Vec<String> = battery
+        .balancer()
+        .history()
+        .rev()
+        .take(10)
+        .map(|decision| format!("{:.0}s {}", decision.timestamp.as_secs_f64(), decision))
+        .collect();
+    let applied = battery.balancer().config().clone();
+
+    egui::CollapsingHeader::new("⚖ Cell Balancing")
+        .id_salt(("balancing", id))
+        .show(ui, |ui| {
+            let balancing = |cell: usize| {
+                decision
+                    .as_ref()
+                    .is_some_and(|decision| decision.balance.get(cell) == Some(&true))
+            };
+
+            for (cell, reading) in cells.iter().enumerate() {
+                // Cells span 3.0-4.2 V, which is all the bars need to show
+                let fill = ((reading.voltage.get::<volt>() - 3.0) / 1.2).clamp(0.0, 1.0) as f32;
+                ui.horizontal(|ui| {
+                    ui.label(format!("Cell {}", cell + 1));
+                    ui.add(
+                        egui::ProgressBar::new(fill)
+                            .desired_width(160.0)
+                            .text(format!(
+                                "{:.3} V · {:.1} °C",
+                                reading.voltage.get::<volt>(),
+                                reading.temperature.get::<degree_celsius>()
+                            )),
+                    );
+                    if balancing(cell) {
+                        ui.colored_label(Color32::LIGHT_BLUE, "balancing");
+                    }
+                });
+            }
+
+            if let Some(decision) = &decision {
+                ui.label(decision.to_string());
+            }
+
+            // The balancing config includes the pack thermal limit, so edits are confirmed
+            // before they are applied
+            let pending_id = ui.id().with("pending_balancing");
+            let mut config: BalancingConfig = ui
+                .data_mut(|data| data.get_temp(pending_id))
+                .unwrap_or_else(|| applied.clone());
+
+            ui.checkbox(&mut config.enabled, "Enabled");
+            ui.add(egui::Slider::new(&mut config.start_delta_mv, 5.0..=100.0).text("Start Δ (mV)"));
+            ui.add(
+                egui::Slider::new(&mut config.stop_delta_mv, 0.0..=config.start_delta_mv)
+                    .text("Stop Δ (mV)"),
+            );
+            ui.add(
+                egui::Slider::new(&mut config.max_balancing_cells, 1..=cells.len().max(1))
+                    .text("Max cells"),
+            );
+            ui.add(
+                egui::Slider::new(&mut config.pack_thermal_limit_c, 30.0..=70.0)
+                    .text("Thermal limit (°C)"),
+            );
+
+            if config != applied {
+                ui.horizontal(|ui| {
+                    ui.colored_label(Color32::ORANGE, "Apply the new balancing config?");
+                    if ui.button("✔ Confirm").clicked() {
+                        let token = modules.request_confirmation(id, "SetBalancingConfig");
+                        let command = BatteryModuleCommands::SetBalancingConfig {
+                            config: config.clone(),
+                        };
+                        if let Err(err) = modules.send_confirmed_command(id, command, token) {
+                            error!("Unable to apply balancing config to battery {id}: {err}");
+                        }
+                    }
+                    if ui.button("✖ Revert").clicked() {
+                        config = applied.clone();
+                    }
+                });
+            }
+            // Kept until the battery reports it, or the edit is reverted
+            ui.data_mut(|data| {
+                if config == applied {
+                    data.remove::<BalancingConfig>(pending_id);
+                } else {
+                    data.insert_temp(pending_id, config);
+                }
+            });
+
+            ui.collapsing("Decision log", |ui| {
+                if history.is_empty() {
+                    ui.weak("No decisions yet.");
+                }
+                for entry in &history {
+                    ui.label(entry);
+                }
+            });
+        });
+}
+
+fn draw_thresholds(ui: &mut egui::Ui, modules: &mut ModuleManager, id: u16) {
+    let Some(battery) = modules.get_module::<DummyBatteryModule>(id) else {
+        return;
+    };
+    let monitor = battery.thresholds().clone();
+    let profile = monitor.profile();
+
+    egui::CollapsingHeader::new("🚦 Thresholds")
+        .id_salt(("thresholds", id))
+        .show(ui, |ui| {
+            // Loading a profile replaces the protection thresholds, so it is confirmed first
+            let pending_id = ui.id().with("pending_profile");
+            let mut pending: Option<String> = ui.data_mut(|data| data.get_temp(pending_id));
+
+            ui.horizontal(|ui| {
+                egui::ComboBox::from_id_salt(("threshold_profile", id))
+                    .selected_text(&profile.name)
+                    .show_ui(ui, |ui| {
+                        for name in ThresholdProfile::BUILTIN {
+                            if ui.selectable_label(profile.name == name, name).clicked()
+                                && profile.name != name
+                            {
+                                pending = Some(name.into());
+                            }
+                        }
+                    });
+
+                let Some(name) = pending.clone() else {
+                    return;
+                };
+                ui.colored_label(Color32::ORANGE, format!("Load {name}?"));
+                if ui.button("✔ Confirm").clicked() {
+                    pending = None;
+                    let token = modules.request_confirmation(id, "LoadThresholdProfile");
+                    let command =
+                        BatteryModuleCommands::LoadThresholdProfile { name: name.clone() };
+                    if let Err(err) = modules.send_confirmed_command(id, command, token) {
+                        error!("Unable to load threshold profile {name} on battery {id}: {err}");
+                    }
+                }
+                if ui.button("✖ Cancel").clicked() {
+                    pending = None;
+                }
+            });
+            ui.data_mut(|data| match pending {
+                Some(name) => data.insert_temp(pending_id, name),
+                None => data.remove::<String>(pending_id),
+            });
+
+            let limit = |value: Option<f64>| value.map_or("–".to_string(), |v| format!("{v:.1}"));
+            egui::Grid::new(("threshold_grid", id))
+                .striped(true)
+                .show(ui, |ui| {
+                    for header in ["", "Low err", "Low warn", "High warn", "High err", "Hyst."] {
+                        ui.strong(header);
+                    }
+                    ui.end_row();
+
+                    for (key, threshold) in &profile.thresholds {
+                        ui.label(colored(key.clone(), monitor.severity(key)));
+                        for bound in [
+                            Limit::LowError,
+                            Limit::LowWarning,
+                            Limit::HighWarning,
+                            Limit::HighError,
+                        ] {
+                            let text = limit(threshold.limit(bound));
+                            if monitor.active(key) == Some(bound) {
+                                ui.label(colored(text, bound.severity()));
+                            } else {
+                                ui.label(text);
+                            }
+                        }
+                        ui.label(format!("{:.1}", threshold.hysteresis));
+                        ui.end_row();
+                    }
+                });
+        });
+}
+
+/// **Watchdog status of a module**, with a way to retry recovering it once given up on
+fn draw_health(ui: &mut egui::Ui, modules: &mut ModuleManager, id: u16) {
+    let Some(health) = modules.health(id) else {
+        return;
+    };
+
+    let severity = match health {
+        ModuleHealth::Healthy => Severity::Normal,
+        ModuleHealth::Late { .. } => Severity::Warning,
+        ModuleHealth::Recovering { .. } | ModuleHealth::Faulted => Severity::Critical,
+    };
+
+    ui.horizontal(|ui| {
+        ui.label(colored(format!("● {health}"), severity));
+        if let Some(error) = modules.watchdog().last_error(id) {
+            ui.weak(error);
+        }
+        if health == ModuleHealth::Faulted && ui.button("Retry recovery").clicked() {
+            modules.recover_module(id);
+        }
+    });
+}
+
+pub(super) fn draw(ui: &mut egui::Ui, ui_state: &mut UiState) {
+    ui.heading("📟 Module Telemetry");
+
+    draw_pack_layout(
+        ui,
+        &ui_state.module_manager.topology(),
+        &ui_state.module_manager,
+    );
+
+    let mut modules = ui_state.module_manager.list_modules();
+    if modules.is_empty() {
+        ui.label("No modules connected.");
+        return;
+    }
+
+    draw_runtime(ui, &ui_state.module_manager);
+    draw_power_budget(ui, ui_state.power_budget.snapshot());
+
+    modules.sort_by_key(|metadata| metadata.id);
+
+    for metadata in modules {
+        let (Some(schema), Some(report)) = (
+            ui_state.module_manager.telemetry_schema(metadata.id),
+            ui_state.module_manager.telemetry(metadata.id),
+        ) else {
+            continue;
+        };
+
+        ui.group(|ui| {
+            ui.horizontal(|ui| {
+                ui.label(RichText::new(&metadata.name).strong());
+                ui.weak(format!(
+                    "{} · ID {} · v{}",
+                    metadata.module_kind, metadata.id, metadata.version
+                ));
+            });
+            draw_health(ui, &mut ui_state.module_manager, metadata.id);
+
+            ui.label(colored(
+                format!(
+                    "{:?} @ {:.1}s",
+                    report.severity(),
+                    report.timestamp.as_secs_f64()
+                ),
+                report.severity(),
+            ));
+
+            draw_telemetry(ui, &schema, &report);
+            draw_history(
+                ui,
+                &ui_state.history,
+                &schema,
+                metadata.id,
+                report.timestamp,
+            );
+
+            if metadata.module_kind == ModuleKind::Battery {
+                draw_thresholds(ui, &mut ui_state.module_manager, metadata.id);
+                draw_balancing(ui, &mut ui_state.module_manager, metadata.id);
+            }
+        });
+    }
+}
@@ -1,10 +1,13 @@
 use strum_macros::EnumIter;
 
-use crate::{state::UiState, ui::debug_panel::logs_page};
+use crate::{
+    state::UiState,
+    ui::debug_panel::{alarms_page, commands_page, logs_page, modules_page},
+};
 
 use super::{
     elements_page::{self, property_editor::PropertyEditorTabs},
-    performance_page, ui_build_page,
+    performance_page, simulation_page, ui_build_page,
 };
 
 #[derive(Debug, Clone, PartialEq, EnumIter)]
@@ -12,7 +15,11 @@ pub enum DebugSidebarPages {
     UiBuild,
     Elements(PropertyEditorTabs),
     Logs,
+    Modules,
+    Commands,
+    Alarms,
     Performance,
+    Simulation,
 }
 
 impl DebugSidebarPages {
@@ -21,7 +28,11 @@ impl DebugSidebarPages {
             Self::UiBuild => "UI Build",
             Self::Elements(_) => "Elements",
             Self::Logs => "Logs",
+            Self::Modules => "Modules",
+            Self::Commands => "Commands",
+            Self::Alarms => "Alarms",
             Self::Performance => "Performance",
+            Self::Simulation => "Simulation",
         }
     }
 
@@ -30,7 +41,11 @@ impl DebugSidebarPages {
             Self::UiBuild => ui_build_page::draw(ui, ui_state),
             Self::Elements(_) => elements_page::draw(ui, ui_state),
             Self::Logs => logs_page::draw(ui, ui_state),
+            Self::Modules => modules_page::draw(ui, ui_state),
+            Self::Commands => commands_page::draw(ui, ui_state),
+            Self::Alarms => alarms_page::draw(ui, ui_state),
             Self::Performance => performance_page::draw(ui, ui_state),
+            Self::Simulation => simulation_page::draw(ui, ui_state),
         }
     }
 }
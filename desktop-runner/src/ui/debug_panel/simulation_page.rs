@@ -0,0 +1,247 @@
+use std::path::{Path, PathBuf};
+
+use egui::{Id, ScrollArea};
+use stratum_firmware_common::simulation::{
+    faults::{Fault, Sensor},
+    scenario::Scenario,
+    scenario_runner::ScenarioRunner,
+};
+use uom::si::{
+    electric_current::ampere,
+    f64::{ElectricCurrent, ThermodynamicTemperature},
+    thermodynamic_temperature::degree_celsius,
+};
+
+use crate::state::UiState;
+
+fn scenario_dir() -> PathBuf {
+    PathBuf::from("../common/firmware-common/scenarios")
+}
+
+fn available_scenarios() -> Vec<PathBuf> {
+    let mut scenarios: Vec<_> = glob::glob(&format!("{}/*.toml", scenario_dir().display()))
+        .unwrap()
+        .filter_map(Result::ok)
+        .collect();
+    scenarios.sort();
+    scenarios
+}
+
+/// Faults offered as one-click buttons for every simulated module
+fn fault_presets() -> Vec<Fault> {
+    vec![
+        Fault::Overcurrent {
+            current: ElectricCurrent::new::<ampere>(8.0),
+        },
+        Fault::Overtemperature {
+            temperature: ThermodynamicTemperature::new::<degree_celsius>(65.0),
+        },
+        Fault::StuckSensor {
+            sensor: Sensor::Temperature,
+        },
+        Fault::NoisySensor {
+            sensor: Sensor::Current,
+            amplitude: 1.0,
+        },
+        Fault::DroppedReplies { probability: 0.5 },
+        Fault::CorruptedReplies { probability: 0.5 },
+        Fault::Removed,
+        Fault::Hung,
+    ]
+}
+
+fn file_name(path: &Path) -> String {
+    path.file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_default()
+}
+
+fn play_selected_scenario(ui_state: &mut UiState) {
+    let Some(path) = &ui_state.selected_scenario else {
+        return;
+    };
+
+    match Scenario::load(path) {
+        Ok(scenario) => {
+            ui_state.scenario_runner = Some(ScenarioRunner::new(
+                scenario,
+                ui_state.system_controller.clone(),
+            ));
+            ui_state.scenario_error = None;
+        }
+        Err(err) => {
+            ui_state.scenario_runner = None;
+            ui_state.scenario_error = Some(err.to_string());
+        }
+    }
+}
+
+fn draw_scenario_picker(ui: &mut egui::Ui, ui_state: &mut UiState) {
+    ui.heading("🎬 Scenarios");
+
+    egui::ComboBox::from_id_salt("scenario_selector")
+        .width(ui.available_width())
+        .selected_text(
+            ui_state
+                .selected_scenario
+                .as_deref()
+                .map(file_name)
+                .unwrap_or_else(|| "Select a scenario…".into()),
+        )
+        .show_ui(ui, |cb| {
+            for path in available_scenarios() {
+                let label = file_name(&path);
+                cb.selectable_value(&mut ui_state.selected_scenario, Some(path), label);
+            }
+        });
+
+    ui.horizontal(|ui| {
+        if ui
+            .add_enabled(
+                ui_state.selected_scenario.is_some(),
+                egui::Button::new("▶ Load & Play"),
+            )
+            .clicked()
+        {
+            play_selected_scenario(ui_state);
+        }
+
+        if ui
+            .add_enabled(
+                ui_state.scenario_runner.is_some(),
+                egui::Button::new("⏹ Stop"),
+            )
+            .clicked()
+        {
+            ui_state.scenario_runner = None;
+        }
+    });
+
+    if let Some(err) = &ui_state.scenario_error {
+        ui.colored_label(egui::Color32::RED, err);
+    }
+}
+
+fn draw_running_scenario(ui: &mut egui::Ui, runner: &ScenarioRunner) {
+    let scenario = runner.scenario();
+    let elapsed = runner.elapsed().min(scenario.length());
+    let length = scenario.length();
+
+    ui.group(|ui| {
+        ui.label(egui::RichText::new(&scenario.name).strong());
+
+        let progress = if length.is_zero() {
+            1.0
+        } else {
+            (elapsed.as_secs_f64() / length.as_secs_f64()) as f32
+        };
+        ui.add(egui::ProgressBar::new(progress).text(format!(
+            "{:.0}s / {:.0}s{}",
+            elapsed.as_secs_f64(),
+            length.as_secs_f64(),
+            if runner.is_finished() { " (done)" } else { "" }
+        )));
+
+        let environment = runner.environment();
+        ui.label(format!(
+            "Ambient: {:.1} °C",
+            environment.ambient_temperature.get::<degree_celsius>()
+        ));
+
+        if environment.port_loads.is_empty() {
+            ui.label("Ports: nothing plugged in");
+        }
+        for (port, load) in &environment.port_loads {
+            ui.label(format!("Port {port}: {:.2} A", load.get::<ampere>()));
+        }
+    });
+
+    ui.label(
+        egui::RichText::new(format!(
+            "📜 Recorded Events ({})",
+            runner.recorded_events().len()
+        ))
+        .strong(),
+    );
+
+    ScrollArea::vertical()
+        .id_salt(Id::new("scenario_events_scroll"))
+        .stick_to_bottom(true)
+        .max_height(300.0)
+        .show(ui, |ui| {
+            for event in runner.recorded_events().iter().rev().take(200).rev() {
+                ui.monospace(format!(
+                    "[{:>9.1}s] {:?}",
+                    event.timestamp.as_secs_f64(),
+                    event.event
+                ));
+            }
+        });
+}
+
+fn draw_fault_injection(ui: &mut egui::Ui, ui_state: &mut UiState) {
+    ui.heading("💥 Fault Injection");
+
+    let mut simulated_modules: Vec<_> = ui_state
+        .module_manager
+        .simulated_modules_mut(None)
+        .collect();
+
+    if simulated_modules.is_empty() {
+        ui.label("No simulated modules connected.");
+        return;
+    }
+
+    simulated_modules.sort_by_key(|(id, _)| *id);
+
+    for (id, module) in simulated_modules {
+        ui.group(|ui| {
+            ui.horizontal(|ui| {
+                ui.label(egui::RichText::new(format!("Module {id}")).strong());
+                if ui
+                    .add_enabled(
+                        !module.faults().active().is_empty(),
+                        egui::Button::new("Clear All"),
+                    )
+                    .clicked()
+                {
+                    module.faults_mut().clear_all();
+                }
+            });
+
+            ui.horizontal_wrapped(|ui| {
+                for fault in fault_presets() {
+                    let is_active = module.faults().active().contains(&fault);
+                    if ui.selectable_label(is_active, fault.describe()).clicked() {
+                        if is_active {
+                            module.faults_mut().clear(fault.kind());
+                        } else {
+                            module.faults_mut().inject(fault);
+                        }
+                    }
+                }
+            });
+
+            for fault in module.faults().active() {
+                ui.colored_label(egui::Color32::ORANGE, format!("⚠ {}", fault.describe()));
+            }
+        });
+    }
+}
+
+pub(super) fn draw(ui: &mut egui::Ui, ui_state: &mut UiState) {
+    draw_fault_injection(ui, ui_state);
+
+    ui.separator();
+
+    draw_scenario_picker(ui, ui_state);
+
+    ui.separator();
+
+    match &ui_state.scenario_runner {
+        Some(runner) => draw_running_scenario(ui, runner),
+        None => {
+            ui.label("No scenario running.");
+        }
+    }
+}
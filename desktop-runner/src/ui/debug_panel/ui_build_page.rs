@@ -1,8 +1,25 @@
 use egui::{Id, ScrollArea};
-use stratum_firmware_common::modules::dummies::dummy_battery::DummyBatteryModule;
+use log::error;
+use stratum_firmware_common::modules::{
+    dummies::{
+        dummy_battery::DummyBatteryModule, dummy_charger::DummyChargerModule,
+        dummy_port::DummyPortModule,
+    },
+    module::Module,
+    telemetry::TelemetryValue,
+};
 
 use crate::state::UiState;
 
+fn register<M: Module + 'static>(ui_state: &mut UiState, module: M) {
+    if let Err(err) = ui_state
+        .module_manager
+        .register_module(module, ui_state.system_controller.clone())
+    {
+        error!("Unable to register module: {err}");
+    }
+}
+
 pub fn draw(ui: &mut egui::Ui, ui_state: &mut UiState) {
     ui.heading("🔌 Connected Modules");
     let connected_modules = ui_state.module_manager.list_modules();
@@ -12,19 +29,71 @@ pub fn draw(ui: &mut egui::Ui, ui_state: &mut UiState) {
         for module_metadata in connected_modules.iter() {
             ui.horizontal(|ui| {
                 ui.label(format!("Module ID: {}", module_metadata.id));
+                if let Some(identity) = ui_state.module_manager.identity(module_metadata.id) {
+                    ui.weak(format!("Slot {} · UID {}", identity.slot + 1, identity.uid));
+                }
                 if ui.button("🗑 Remove").clicked() {
                     ui_state.module_manager.remove_module(module_metadata.id);
+                    ui_state.history.remove_module(module_metadata.id);
+                    ui_state
+                        .system_controller
+                        .clear_module_alarms(module_metadata.id);
                 }
             });
         }
     }
 
     ui.separator();
-    if ui.button("➕ Add Battery Module").clicked() {
-        let dummy_module = DummyBatteryModule::new(ui_state.module_manager.generate_unique_id());
-        ui_state
+    let free_slot = ui_state.module_manager.topology().first_free_slot();
+    if free_slot.is_none() {
+        ui.label("All slots are occupied.");
+    }
+
+    if ui
+        .add_enabled(
+            free_slot.is_some(),
+            egui::Button::new("➕ Add Battery Module"),
+        )
+        .clicked()
+    {
+        if let Some(slot) = free_slot {
+            register(ui_state, DummyBatteryModule::new(slot));
+        }
+    }
+    if ui
+        .add_enabled(
+            free_slot.is_some(),
+            egui::Button::new("➕ Add Charger Module"),
+        )
+        .clicked()
+    {
+        if let Some(slot) = free_slot {
+            register(ui_state, DummyChargerModule::new(slot));
+        }
+    }
+    if ui
+        .add_enabled(free_slot.is_some(), egui::Button::new("➕ Add Port Module"))
+        .clicked()
+    {
+        // Port numbers start at 1, take the first one no port module is plugged into
+        let used_ports: Vec<i64> = ui_state
             .module_manager
-            .register_module(dummy_module, ui_state.system_controller.clone());
+            .all_telemetry()
+            .iter()
+            .filter_map(
+                |report| match report.get("port").map(|entry| &entry.value) {
+                    Some(TelemetryValue::Count(port)) => Some(*port),
+                    _ => None,
+                },
+            )
+            .collect();
+        let port = (1..=u8::MAX)
+            .find(|port| !used_ports.contains(&i64::from(*port)))
+            .unwrap_or(u8::MAX);
+
+        if let Some(slot) = free_slot {
+            register(ui_state, DummyPortModule::new(slot, port));
+        }
     }
 
     // ─── 🔥 Hot Reload Debugger Panel ───────────────────────────────
@@ -3,7 +3,50 @@ use crate::{
     stratum_lvgl_ui::StratumLvglUI,
     ui::lvgl_canvas::view::{ZOOM_MAX, ZOOM_MIN},
 };
-use egui::DragValue;
+use egui::{ComboBox, DragValue};
+use stratum_firmware_common::clock::Clock;
+
+const SIM_SPEED_PRESETS: [f64; 6] = [1.0, 10.0, 60.0, 600.0, 3600.0, 36_000.0];
+
+fn format_sim_speed(speed: f64) -> String {
+    if speed == 0.0 {
+        "Paused".into()
+    } else {
+        format!("{speed}x")
+    }
+}
+
+/// Simulation speed control: lets a multi-hour discharge play out in seconds
+fn draw_sim_speed(ui: &mut egui::Ui, ui_state: &UiState) {
+    let clock = &ui_state.sim_clock;
+    let speed = clock.speed();
+
+    ui.label("Sim:");
+
+    let pause_label = if clock.is_paused() { "▶" } else { "⏸" };
+    if ui
+        .button(pause_label)
+        .on_hover_text("Pause/resume simulated time")
+        .clicked()
+    {
+        clock.set_speed(if clock.is_paused() { 1.0 } else { 0.0 });
+    }
+
+    ComboBox::from_id_salt("sim_speed")
+        .selected_text(format_sim_speed(speed))
+        .show_ui(ui, |ui| {
+            for preset in SIM_SPEED_PRESETS {
+                if ui
+                    .selectable_label(speed == preset, format_sim_speed(preset))
+                    .clicked()
+                {
+                    clock.set_speed(preset);
+                }
+            }
+        });
+
+    ui.label(format!("t = {:.1}s", clock.now().as_secs_f64()));
+}
 
 pub fn draw(ctx: &egui::Context, ui_state: &mut UiState, lvgl_ui: &StratumLvglUI) {
     egui::TopBottomPanel::bottom("status_bar").show(ctx, |ui| {
@@ -11,6 +54,9 @@ pub fn draw(ctx: &egui::Context, ui_state: &mut UiState, lvgl_ui: &StratumLvglUI
             ui.label(format!("FPS: {:.2}", lvgl_ui.current_fps()));
             ui.separator();
 
+            draw_sim_speed(ui, ui_state);
+            ui.separator();
+
             ui.label("Zoom:");
 
             let initial_zoom = (ui_state.canvas_view.zoom * 100.0).round() / 100.0;
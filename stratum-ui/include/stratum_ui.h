@@ -6,4 +6,6 @@
 #include "lvgl_exports.h"
 #include "lvlens_registry.h"
 #include "lv_click_cache.h"
-#include "lvlens_flush_area.h"
\ No newline at end of file
+#include "lvlens_flush_area.h"
+#include "ui_runtime.h"
+#include "ui_alarms.h"
\ No newline at end of file
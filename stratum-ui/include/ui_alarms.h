@@ -0,0 +1,12 @@
+#pragma once
+
+#include <stdint.h>
+#include "lvgl.h"
+#include "ui_export_marker.h"
+
+// Most severe active alarm pushed by the firmware. Severity is 1 for a warning and 2 for a
+// critical alarm; an active_count of 0 hides the banner.
+UI_EXPORT void ui_set_alarm_banner(const char *message, uint8_t severity, uint8_t active_count);
+
+// Create a banner showing the latest alarm, kept up to date by ui_set_alarm_banner()
+lv_obj_t *ui_alarm_banner_create(lv_obj_t *parent);
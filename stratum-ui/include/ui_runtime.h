@@ -0,0 +1,12 @@
+#pragma once
+
+#include <stdint.h>
+#include "lvgl.h"
+#include "ui_export_marker.h"
+
+// Pack runtime estimate pushed by the firmware. Times are in seconds, 0 when unknown
+// (e.g. no time to empty while charging). Confidence is in percent.
+UI_EXPORT void ui_set_battery_runtime(uint32_t time_to_empty_s, uint32_t time_to_full_s, uint8_t confidence_pct);
+
+// Create a label showing the latest runtime estimate, kept up to date by ui_set_battery_runtime()
+lv_obj_t *ui_runtime_label_create(lv_obj_t *parent);
@@ -1,5 +1,7 @@
 #include "screens/screen_dashboard.h"
 #include "components/component_output_card.h"
+#include "ui_runtime.h"
+#include "ui_alarms.h"
 #include "lvgl.h"
 
 static lv_obj_t *screen;
@@ -65,6 +67,14 @@ void screen_dashboard_create(void)
     // build the tile row
     create_outut_card_row(screen);
 
+    // runtime estimate along the bottom edge
+    lv_obj_t *runtime = ui_runtime_label_create(screen);
+    lv_obj_align(runtime, LV_ALIGN_BOTTOM_MID, 0, -8);
+
+    // most severe alarm across the top, hidden while there is none
+    lv_obj_t *alarms = ui_alarm_banner_create(screen);
+    lv_obj_align(alarms, LV_ALIGN_TOP_MID, 0, 8);
+
     // finally, load it
     lv_scr_load(screen);
-}
\ No newline at end of file
+}
\ No newline at end of file
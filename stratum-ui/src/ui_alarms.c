@@ -0,0 +1,69 @@
+#include "ui_alarms.h"
+
+#include <string.h>
+
+// Latest alarm, kept so that a banner created later starts with it
+static char g_message[96] = "";
+static uint8_t g_severity = 0;
+static uint8_t g_active_count = 0;
+
+static lv_obj_t *g_alarm_banner = NULL;
+
+static void alarm_banner_refresh(void)
+{
+    if (!g_alarm_banner)
+    {
+        return;
+    }
+
+    if (g_active_count == 0)
+    {
+        lv_obj_add_flag(g_alarm_banner, LV_OBJ_FLAG_HIDDEN);
+        return;
+    }
+
+    // amber for warnings, red for critical alarms
+    uint32_t color = g_severity >= 2 ? 0xD32F2F : 0xFFA000;
+    lv_obj_set_style_bg_color(g_alarm_banner, lv_color_hex(color), LV_PART_MAIN);
+
+    if (g_active_count > 1)
+    {
+        lv_label_set_text_fmt(g_alarm_banner, "%s (+%u)", g_message, (unsigned)(g_active_count - 1));
+    }
+    else
+    {
+        lv_label_set_text(g_alarm_banner, g_message);
+    }
+    lv_obj_clear_flag(g_alarm_banner, LV_OBJ_FLAG_HIDDEN);
+}
+
+static void alarm_banner_deleted_cb(lv_event_t *e)
+{
+    (void)e;
+    g_alarm_banner = NULL;
+}
+
+UI_EXPORT void ui_set_alarm_banner(const char *message, uint8_t severity, uint8_t active_count)
+{
+    strncpy(g_message, message ? message : "", sizeof(g_message) - 1);
+    g_message[sizeof(g_message) - 1] = '\0';
+    g_severity = severity;
+    g_active_count = active_count;
+
+    alarm_banner_refresh();
+}
+
+lv_obj_t *ui_alarm_banner_create(lv_obj_t *parent)
+{
+    g_alarm_banner = lv_label_create(parent);
+    lv_obj_set_style_bg_opa(g_alarm_banner, LV_OPA_COVER, LV_PART_MAIN);
+    lv_obj_set_style_text_color(g_alarm_banner, lv_color_hex(0x000000), LV_PART_MAIN);
+    lv_obj_set_style_pad_hor(g_alarm_banner, 6, LV_PART_MAIN);
+    lv_obj_set_style_radius(g_alarm_banner, 4, LV_PART_MAIN);
+    lv_label_set_long_mode(g_alarm_banner, LV_LABEL_LONG_DOT);
+    lv_obj_set_width(g_alarm_banner, LV_PCT(90));
+    lv_obj_add_event_cb(g_alarm_banner, alarm_banner_deleted_cb, LV_EVENT_DELETE, NULL);
+
+    alarm_banner_refresh();
+    return g_alarm_banner;
+}
@@ -0,0 +1,63 @@
+#include "ui_runtime.h"
+
+// Latest estimate, kept so that a label created later starts with it
+static uint32_t g_time_to_empty_s = 0;
+static uint32_t g_time_to_full_s = 0;
+static uint8_t g_confidence_pct = 0;
+
+static lv_obj_t *g_runtime_label = NULL;
+
+static void runtime_label_refresh(void)
+{
+    if (!g_runtime_label)
+    {
+        return;
+    }
+
+    // A low confidence estimate is shown as approximate
+    const char *approx = g_confidence_pct < 50 ? "~" : "";
+
+    if (g_time_to_empty_s > 0)
+    {
+        lv_label_set_text_fmt(g_runtime_label, "%s%luh %02lum LEFT",
+                              approx,
+                              (unsigned long)(g_time_to_empty_s / 3600),
+                              (unsigned long)(g_time_to_empty_s / 60 % 60));
+    }
+    else if (g_time_to_full_s > 0)
+    {
+        lv_label_set_text_fmt(g_runtime_label, "FULL IN %s%luh %02lum",
+                              approx,
+                              (unsigned long)(g_time_to_full_s / 3600),
+                              (unsigned long)(g_time_to_full_s / 60 % 60));
+    }
+    else
+    {
+        lv_label_set_text(g_runtime_label, "--");
+    }
+}
+
+static void runtime_label_deleted_cb(lv_event_t *e)
+{
+    (void)e;
+    g_runtime_label = NULL;
+}
+
+UI_EXPORT void ui_set_battery_runtime(uint32_t time_to_empty_s, uint32_t time_to_full_s, uint8_t confidence_pct)
+{
+    g_time_to_empty_s = time_to_empty_s;
+    g_time_to_full_s = time_to_full_s;
+    g_confidence_pct = confidence_pct;
+
+    runtime_label_refresh();
+}
+
+lv_obj_t *ui_runtime_label_create(lv_obj_t *parent)
+{
+    g_runtime_label = lv_label_create(parent);
+    lv_obj_set_style_text_color(g_runtime_label, lv_color_hex(0xFFFFFF), LV_PART_MAIN);
+    lv_obj_add_event_cb(g_runtime_label, runtime_label_deleted_cb, LV_EVENT_DELETE, NULL);
+
+    runtime_label_refresh();
+    return g_runtime_label;
+}